@@ -76,6 +76,54 @@ fn decode_json(c: &mut Criterion) {
     });
 }
 
+fn from_value_struct(c: &mut Criterion) {
+    #[derive(serde::Deserialize)]
+    #[allow(unused)]
+    struct Server {
+        host: String,
+        port: u16,
+        tags: Vec<String>,
+        enabled: bool,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(unused)]
+    struct Config {
+        name: String,
+        servers: Vec<Server>,
+    }
+
+    let lua = Lua::new();
+
+    let table = lua
+        .load(
+            r#"
+        local servers = {}
+        for i = 1, 100 do
+            servers[i] = {
+                host = "server" .. i .. ".example.com",
+                port = 8000 + i,
+                tags = {"web", "prod", "region" .. (i % 4)},
+                enabled = i % 2 == 0,
+            }
+        end
+        return { name = "cluster", servers = servers }
+    "#,
+        )
+        .eval::<LuaValue>()
+        .unwrap();
+
+    c.bench_function("deserialize struct from value", |b| {
+        b.iter_batched(
+            || collect_gc_twice(&lua),
+            |_| {
+                lua.from_value::<Config>(table.clone()).unwrap();
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default()
@@ -85,6 +133,7 @@ criterion_group! {
     targets =
         encode_json,
         decode_json,
+        from_value_struct,
 }
 
 criterion_main!(benches);
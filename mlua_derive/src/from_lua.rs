@@ -2,8 +2,23 @@ use proc_macro::TokenStream;
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
 
+use crate::table::{self, ContainerAttrs};
+
 pub fn from_lua(input: TokenStream) -> TokenStream {
-    let DeriveInput { ident, generics, .. } = parse_macro_input!(input as DeriveInput);
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let attrs = match ContainerAttrs::parse(&input.attrs) {
+        Ok(attrs) => attrs,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    if attrs.table {
+        return match table::from_lua(&input, &attrs) {
+            Ok(expanded) => expanded.into(),
+            Err(err) => err.to_compile_error().into(),
+        };
+    }
+
+    let DeriveInput { ident, generics, .. } = input;
 
     let ident_str = ident.to_string();
     let (impl_generics, ty_generics, _) = generics.split_for_impl();
@@ -25,6 +40,11 @@ pub fn from_lua(input: TokenStream) -> TokenStream {
             }),
           }
         }
+
+        #[inline]
+        fn lua_type() -> ::mlua::TypeDesc {
+          ::mlua::TypeDesc::userdata::<Self>()
+        }
       }
     }
     .into()
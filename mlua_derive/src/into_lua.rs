@@ -0,0 +1,26 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, Error};
+
+use crate::table::{self, ContainerAttrs};
+
+pub fn into_lua(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let attrs = match ContainerAttrs::parse(&input.attrs) {
+        Ok(attrs) => attrs,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    if !attrs.table {
+        // `UserData` types already implement `IntoLua`
+        let err = Error::new_spanned(
+            &input.ident,
+            "`IntoLua` derive requires `#[lua(table)]` attribute",
+        );
+        return err.to_compile_error().into();
+    }
+
+    match table::into_lua(&input, &attrs) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
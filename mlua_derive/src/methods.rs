@@ -0,0 +1,239 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Group, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Attribute, Error, FnArg, ImplItem, ImplItemFn, ItemImpl, LitStr, Result, ReturnType,
+    Type,
+};
+
+/// Method attributes, e.g. `#[lua(rename = "name", meta = "Add")]`.
+#[derive(Default)]
+struct MethodAttrs {
+    rename: Option<String>,
+    meta: Option<LitStr>,
+    skip: bool,
+}
+
+impl MethodAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut this = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("lua")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    this.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("meta") {
+                    this.meta = Some(meta.value()?.parse::<LitStr>()?);
+                } else if meta.path.is_ident("skip") {
+                    this.skip = true;
+                } else {
+                    return Err(meta.error("unsupported `lua` method attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if this.rename.is_some() && this.meta.is_some() {
+            return Err(Error::new(
+                Span::call_site(),
+                "`rename` cannot be combined with `meta`",
+            ));
+        }
+        Ok(this)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Receiver {
+    None,
+    Ref,
+    RefMut,
+}
+
+pub fn methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let err = Error::new(Span::call_site(), "`methods` attribute does not accept arguments");
+        return err.to_compile_error().into();
+    }
+    let mut item = parse_macro_input!(item as ItemImpl);
+    match expand(&mut item) {
+        Ok(expanded) => quote!(#item #expanded).into(),
+        Err(err) => {
+            let err = err.to_compile_error();
+            quote!(#item #err).into()
+        }
+    }
+}
+
+fn expand(item: &mut ItemImpl) -> Result<TokenStream2> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new_spanned(
+            path,
+            "`methods` attribute must be used on inherent impl",
+        ));
+    }
+
+    let mut registrations = Vec::new();
+    for impl_item in &mut item.items {
+        if let ImplItem::Fn(func) = impl_item {
+            let attrs = MethodAttrs::parse(&func.attrs);
+            // `lua` is not a registered attribute outside derive macros
+            func.attrs.retain(|attr| !attr.path().is_ident("lua"));
+            let attrs = attrs?;
+            if !attrs.skip {
+                registrations.push(register_method(&item.self_ty, func, attrs)?);
+            }
+        }
+    }
+
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mlua::HasUserDataMethods<#self_ty> for ::mlua::UserDataMethodsProbe<#self_ty>
+        #where_clause
+        {
+            fn add_methods<M: ::mlua::UserDataMethods<#self_ty>>(&self, methods: &mut M) {
+                #(#registrations)*
+            }
+        }
+    })
+}
+
+/// Checks if the type is `Lua` (or `&Lua` when `by_ref` is set).
+fn is_lua_type(ty: &Type, by_ref: bool) -> bool {
+    let ty = match (ty, by_ref) {
+        (Type::Reference(ty), true) if ty.mutability.is_none() => &*ty.elem,
+        (Type::Reference(_), false) | (_, true) => return false,
+        (ty, false) => ty,
+    };
+    match ty {
+        Type::Path(path) => path.qself.is_none() && path.path.segments.last().unwrap().ident == "Lua",
+        _ => false,
+    }
+}
+
+/// Checks if the return type looks like a `Result`, errors of which should be raised.
+fn is_result_type(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Path(path) => path.path.segments.last().unwrap().ident == "Result",
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
+
+/// Replaces `Self` in the tokens with the concrete type (the registration code is not a part of
+/// the original `impl` block).
+fn replace_self(tokens: TokenStream2, self_ty: &Type) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|tt| match tt {
+            TokenTree::Ident(ident) if ident == "Self" => quote!(#self_ty),
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(group.delimiter(), replace_self(group.stream(), self_ty));
+                new_group.set_span(group.span());
+                quote!(#new_group)
+            }
+            tt => quote!(#tt),
+        })
+        .collect()
+}
+
+fn register_method(self_ty: &Type, func: &ImplItemFn, attrs: MethodAttrs) -> Result<TokenStream2> {
+    let sig = &func.sig;
+    let fn_ident = &sig.ident;
+    let is_async = sig.asyncness.is_some();
+
+    let mut inputs = sig.inputs.iter().peekable();
+    let receiver = match inputs.peek() {
+        Some(FnArg::Receiver(receiver)) => {
+            if receiver.reference.is_none() || receiver.colon_token.is_some() {
+                let msg = "only `&self` and `&mut self` receivers are supported";
+                return Err(Error::new_spanned(receiver, msg));
+            }
+            inputs.next();
+            match receiver.mutability {
+                Some(_) => Receiver::RefMut,
+                None => Receiver::Ref,
+            }
+        }
+        _ => Receiver::None,
+    };
+
+    // Optional `lua: &Lua` (or `lua: Lua` for async functions) argument
+    let mut call_args = Vec::new();
+    if let Some(FnArg::Typed(arg)) = inputs.peek() {
+        if is_lua_type(&arg.ty, !is_async) {
+            call_args.push(quote!(lua));
+            inputs.next();
+        }
+    }
+
+    let mut arg_pats = Vec::new();
+    let mut arg_types = Vec::new();
+    for (i, arg) in inputs.enumerate() {
+        let FnArg::Typed(arg) = arg else { unreachable!() };
+        let arg = &arg.ty;
+        let binding = format_ident!("__arg{}", i);
+        call_args.push(quote!(#binding));
+        arg_pats.push(binding);
+        arg_types.push(replace_self(quote!(#arg), self_ty));
+    }
+
+    let this = match receiver {
+        Receiver::None => quote!(),
+        Receiver::Ref => quote!(&*this,),
+        Receiver::RefMut => quote!(&mut *this,),
+    };
+    let call = quote! { <#self_ty>::#fn_ident(#this #(#call_args),*) };
+    let call = if is_async { quote!(#call.await) } else { call };
+    let result = match &sig.output {
+        ReturnType::Default => quote! {{ #call; Ok(()) }},
+        output if is_result_type(output) => quote! { #call.map_err(::std::convert::Into::into) },
+        _ => quote! { Ok(#call) },
+    };
+
+    let name = match (&attrs.meta, attrs.rename) {
+        (Some(meta), _) if meta.value().starts_with("__") => quote!(#meta),
+        (Some(meta), _) => {
+            let variant = syn::Ident::new(&meta.value(), meta.span());
+            quote!(::mlua::MetaMethod::#variant)
+        }
+        (None, Some(rename)) => quote!(#rename),
+        (None, None) => {
+            let name = fn_ident.to_string();
+            let name = name.strip_prefix("r#").unwrap_or(&name).to_string();
+            quote!(#name)
+        }
+    };
+
+    let is_meta = attrs.meta.is_some();
+    let register = match (receiver, is_async, is_meta) {
+        (Receiver::None, false, false) => quote!(add_function),
+        (Receiver::None, false, true) => quote!(add_meta_function),
+        (Receiver::None, true, false) => quote!(add_async_function),
+        (Receiver::None, true, true) => quote!(add_async_meta_function),
+        (Receiver::Ref, false, false) => quote!(add_method),
+        (Receiver::Ref, false, true) => quote!(add_meta_method),
+        (Receiver::Ref, true, false) => quote!(add_async_method),
+        (Receiver::Ref, true, true) => quote!(add_async_meta_method),
+        (Receiver::RefMut, false, false) => quote!(add_method_mut),
+        (Receiver::RefMut, false, true) => quote!(add_meta_method_mut),
+        (Receiver::RefMut, true, false) => quote!(add_async_method_mut),
+        (Receiver::RefMut, true, true) => quote!(add_async_meta_method_mut),
+    };
+
+    let args = quote! { (#(#arg_pats,)*): (#(#arg_types,)*) };
+    let closure = match (receiver, is_async) {
+        (Receiver::None, false) => quote! { |lua, #args| #result },
+        (Receiver::None, true) => quote! { |lua, #args| async move { #result } },
+        (Receiver::Ref, false) => quote! { |lua, this, #args| #result },
+        (Receiver::RefMut, false) => quote! { |lua, this, #args| #result },
+        (Receiver::Ref, true) => quote! { |lua, this, #args| async move { #result } },
+        (Receiver::RefMut, true) => quote! { |lua, mut this, #args| async move { #result } },
+    };
+
+    Ok(quote! {
+        #[allow(unused_variables, unused_mut)]
+        methods.#register(#name, #closure);
+    })
+}
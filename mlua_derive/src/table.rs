@@ -0,0 +1,798 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    Attribute, Data, DataEnum, DeriveInput, Error, ExprPath, Fields, GenericParam, Generics, Ident, LitStr,
+    Result, Variant,
+};
+
+/// Container attributes, e.g. `#[lua(table, rename_all = "camelCase")]`.
+#[derive(Default)]
+pub(crate) struct ContainerAttrs {
+    pub(crate) table: bool,
+    rename_all: Option<RenameRule>,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+}
+
+impl ContainerAttrs {
+    pub(crate) fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut this = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("lua")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("table") {
+                    this.table = true;
+                } else if meta.path.is_ident("rename_all") {
+                    let value = meta.value()?.parse::<LitStr>()?;
+                    let rule = RenameRule::from_str(&value.value())
+                        .ok_or_else(|| Error::new(value.span(), "unknown `rename_all` rule"))?;
+                    this.rename_all = Some(rule);
+                } else if meta.path.is_ident("tag") {
+                    this.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("content") {
+                    this.content = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("untagged") {
+                    this.untagged = true;
+                } else {
+                    return Err(meta.error("unsupported `lua` attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(this)
+    }
+
+    fn tagging(&self, span: Span) -> Result<Tagging> {
+        match (&self.tag, &self.content, self.untagged) {
+            (None, None, false) => Ok(Tagging::External),
+            (Some(tag), None, false) => Ok(Tagging::Internal(tag.clone())),
+            (Some(tag), Some(content), false) => Ok(Tagging::Adjacent(tag.clone(), content.clone())),
+            (None, None, true) => Ok(Tagging::Untagged),
+            (None, Some(_), _) => Err(Error::new(span, "`content` attribute requires `tag`")),
+            _ => Err(Error::new(span, "`untagged` cannot be combined with `tag`")),
+        }
+    }
+}
+
+/// Field attributes, e.g. `#[lua(rename = "type", default)]`.
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    default: Option<Option<ExprPath>>,
+    skip: bool,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut this = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("lua")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    this.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    match meta.value() {
+                        Ok(value) => this.default = Some(Some(value.parse::<LitStr>()?.parse()?)),
+                        Err(_) => this.default = Some(None),
+                    }
+                } else if meta.path.is_ident("skip") {
+                    this.skip = true;
+                } else {
+                    return Err(meta.error("unsupported `lua` field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(this)
+    }
+
+    fn default_expr(&self) -> Option<TokenStream2> {
+        match &self.default {
+            Some(Some(path)) => Some(quote! { #path() }),
+            Some(None) => Some(quote! { ::std::default::Default::default() }),
+            None if self.skip => Some(quote! { ::std::default::Default::default() }),
+            None => None,
+        }
+    }
+}
+
+/// Variant attributes, e.g. `#[lua(rename = "circle")]`.
+#[derive(Default)]
+struct VariantAttrs {
+    rename: Option<String>,
+}
+
+impl VariantAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut this = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("lua")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    this.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else {
+                    return Err(meta.error("unsupported `lua` variant attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(this)
+    }
+}
+
+/// How enum variants are represented in Lua (follows serde conventions).
+enum Tagging {
+    /// `"Unit"` or `{ Variant = content }`
+    External,
+    /// `{ [tag] = "Variant", ...fields }`
+    Internal(String),
+    /// `{ [tag] = "Variant", [content] = content }`
+    Adjacent(String, String),
+    /// `content`
+    Untagged,
+}
+
+#[derive(Clone, Copy)]
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn from_str(rule: &str) -> Option<Self> {
+        Some(match rule {
+            "lowercase" => RenameRule::Lower,
+            "UPPERCASE" => RenameRule::Upper,
+            "PascalCase" => RenameRule::Pascal,
+            "camelCase" => RenameRule::Camel,
+            "snake_case" => RenameRule::Snake,
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+            "kebab-case" => RenameRule::Kebab,
+            "SCREAMING-KEBAB-CASE" => RenameRule::ScreamingKebab,
+            _ => return None,
+        })
+    }
+
+    /// Applies the rule to a field name (expected to be in `snake_case`).
+    fn apply_to_field(self, name: &str) -> String {
+        match self {
+            RenameRule::Lower | RenameRule::Snake => name.to_string(),
+            RenameRule::Upper | RenameRule::ScreamingSnake => name.to_ascii_uppercase(),
+            RenameRule::Pascal | RenameRule::Camel => {
+                let mut result = String::with_capacity(name.len());
+                let mut capitalize = matches!(self, RenameRule::Pascal);
+                for ch in name.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        result.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        result.push(ch);
+                    }
+                }
+                result
+            }
+            RenameRule::Kebab => name.replace('_', "-"),
+            RenameRule::ScreamingKebab => name.replace('_', "-").to_ascii_uppercase(),
+        }
+    }
+
+    /// Applies the rule to a variant name (expected to be in `PascalCase`).
+    fn apply_to_variant(self, name: &str) -> String {
+        match self {
+            RenameRule::Pascal => name.to_string(),
+            RenameRule::Lower => name.to_ascii_lowercase(),
+            RenameRule::Upper => name.to_ascii_uppercase(),
+            RenameRule::Camel => {
+                let mut chars = name.chars();
+                (chars.next())
+                    .map(|first| first.to_lowercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+            _ => {
+                let mut snake = String::with_capacity(name.len() + 4);
+                for (i, ch) in name.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                RenameRule::apply_to_field(self, &snake)
+            }
+        }
+    }
+}
+
+/// A parsed struct (or variant) field.
+struct Field {
+    /// Rust member used to access the field (`name` or `0`)
+    member: TokenStream2,
+    /// Binding used when destructuring enum variants
+    binding: Ident,
+    ty: syn::Type,
+    /// Lua key (string for named fields, integer for tuple fields)
+    key: TokenStream2,
+    /// Human readable key used in error paths
+    path: String,
+    attrs: FieldAttrs,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Named,
+    Tuple,
+    Unit,
+}
+
+fn parse_fields(fields: &Fields, rename_all: Option<RenameRule>) -> Result<(Style, Vec<Field>)> {
+    let style = match fields {
+        Fields::Named(_) => Style::Named,
+        Fields::Unnamed(_) => Style::Tuple,
+        Fields::Unit => Style::Unit,
+    };
+    let mut index = 0;
+    let mut result = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        let (member, binding, key, path) = match &field.ident {
+            Some(ident) => {
+                let name = ident.to_string();
+                let name = name.strip_prefix("r#").unwrap_or(&name).to_string();
+                let name = match (&attrs.rename, rename_all) {
+                    (Some(rename), _) => rename.clone(),
+                    (None, Some(rule)) => rule.apply_to_field(&name),
+                    (None, None) => name,
+                };
+                let binding = format_ident!("__field_{}", i);
+                (quote!(#ident), binding, quote!(#name), name)
+            }
+            None => {
+                if attrs.rename.is_some() {
+                    return Err(Error::new_spanned(field, "tuple fields cannot be renamed"));
+                }
+                let member = syn::Index::from(i);
+                let binding = format_ident!("__field_{}", i);
+                // Skipped fields do not occupy a slot in the sequence
+                if !attrs.skip {
+                    index += 1;
+                }
+                let key = index as i64;
+                (quote!(#member), binding, quote!(#key), format!("[{key}]"))
+            }
+        };
+        result.push(Field {
+            member,
+            binding,
+            ty: field.ty.clone(),
+            key,
+            path,
+            attrs,
+        });
+    }
+    Ok((style, result))
+}
+
+fn variant_name(variant: &Variant, rename_all: Option<RenameRule>) -> Result<String> {
+    let attrs = VariantAttrs::parse(&variant.attrs)?;
+    Ok(match (attrs.rename, rename_all) {
+        (Some(rename), _) => rename,
+        (None, Some(rule)) => rule.apply_to_variant(&variant.ident.to_string()),
+        (None, None) => variant.ident.to_string(),
+    })
+}
+
+fn join_path(prefix: &str, path: &str) -> String {
+    match (prefix, path) {
+        ("", path) => path.to_string(),
+        (prefix, path) if path.starts_with('[') => format!("{prefix}{path}"),
+        (prefix, path) => format!("{prefix}.{path}"),
+    }
+}
+
+fn add_bounds(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    let params = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => Some(param.ident.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let where_clause = generics.make_where_clause();
+    for ident in params {
+        where_clause.predicates.push(syn::parse_quote!(#ident: #bound));
+    }
+    generics
+}
+
+fn expect_table(value: &Ident, type_name: &str, prefix: &str) -> TokenStream2 {
+    let conversion_error = quote! {
+        ::mlua::Error::FromLuaConversionError {
+            from: #value.type_name(),
+            to: #type_name.to_string(),
+            message: Some("expected table".to_string()),
+        }
+    };
+    let conversion_error = if prefix.is_empty() {
+        conversion_error
+    } else {
+        quote! { #conversion_error.from_lua_field(#type_name, #prefix) }
+    };
+    quote! {
+        match #value {
+            ::mlua::Value::Table(table) => table,
+            _ => return Err(#conversion_error),
+        }
+    }
+}
+
+/// Generates an expression that builds `ctor` from the fields read from `table`.
+fn fields_from_table(
+    style: Style,
+    fields: &[Field],
+    ctor: TokenStream2,
+    type_name: &str,
+    prefix: &str,
+) -> TokenStream2 {
+    let values = fields.iter().map(|field| {
+        let Field { ty, key, attrs, .. } = field;
+        let path = join_path(prefix, &field.path);
+        let default = attrs.default_expr();
+        if attrs.skip {
+            return quote! { #default };
+        }
+        let convert = quote! {
+            <#ty as ::mlua::FromLua>::from_lua(value, lua)
+                .map_err(|err| err.from_lua_field(#type_name, #path))?
+        };
+        let value = quote! {
+            let value = table
+                .get::<::mlua::Value>(#key)
+                .map_err(|err| err.from_lua_field(#type_name, #path))?;
+        };
+        match default {
+            Some(default) => quote! {{
+                #value
+                if value.is_nil() { #default } else { #convert }
+            }},
+            None => quote! {{
+                #value
+                #convert
+            }},
+        }
+    });
+    match style {
+        Style::Named => {
+            let members = fields.iter().map(|field| &field.member);
+            quote! { #ctor { #(#members: #values),* } }
+        }
+        Style::Tuple => quote! { #ctor(#(#values),*) },
+        Style::Unit => quote! { #ctor },
+    }
+}
+
+/// Generates statements that write the (already bound) fields into `table`.
+fn fields_into_table(fields: &[Field]) -> TokenStream2 {
+    let sets = fields.iter().filter(|field| !field.attrs.skip).map(|field| {
+        let Field { binding, key, .. } = field;
+        quote! { table.raw_set(#key, #binding)?; }
+    });
+    quote! { #(#sets)* }
+}
+
+fn table_capacity(style: Style, fields: &[Field]) -> (usize, usize) {
+    let len = fields.iter().filter(|field| !field.attrs.skip).count();
+    match style {
+        Style::Named => (0, len),
+        _ => (len, 0),
+    }
+}
+
+/// Pattern that destructures `ctor` into field bindings.
+fn destructure(style: Style, fields: &[Field], ctor: TokenStream2) -> TokenStream2 {
+    let bindings = fields.iter().map(|field| {
+        let Field { member, binding, .. } = field;
+        match style {
+            Style::Named => quote! { #member: #binding },
+            _ => quote! { #binding },
+        }
+    });
+    match style {
+        Style::Named => quote! { #ctor { #(#bindings),* } },
+        Style::Tuple => quote! { #ctor(#(#bindings),*) },
+        Style::Unit => quote! { #ctor },
+    }
+}
+
+/// Returns type description of the converted value (enums can be strings or tables).
+fn lua_type(input: &DeriveInput) -> TokenStream2 {
+    match &input.data {
+        Data::Struct(_) => quote!(::mlua::TypeDesc::Table),
+        _ => quote!(::mlua::TypeDesc::Any),
+    }
+}
+
+pub(crate) fn from_lua(input: &DeriveInput, attrs: &ContainerAttrs) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let type_name = ident.to_string();
+    let generics = add_bounds(&input.generics, quote!(::mlua::FromLua));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (style, fields) = parse_fields(&data.fields, attrs.rename_all)?;
+            let value = format_ident!("value");
+            let table = expect_table(&value, &type_name, "");
+            let ctor = fields_from_table(style, &fields, quote!(Self), &type_name, "");
+            quote! {
+                let table = #table;
+                Ok(#ctor)
+            }
+        }
+        Data::Enum(data) => enum_from_lua(data, attrs, &type_name, ident.span())?,
+        Data::Union(_) => return Err(Error::new_spanned(input, "unions are not supported")),
+    };
+    let lua_type = lua_type(input);
+
+    Ok(quote! {
+        impl #impl_generics ::mlua::FromLua for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn from_lua(value: ::mlua::Value, lua: &::mlua::Lua) -> ::mlua::Result<Self> {
+                #body
+            }
+
+            fn lua_type() -> ::mlua::TypeDesc {
+                #lua_type
+            }
+        }
+    })
+}
+
+/// Generates a closure `|value: Value| -> Result<Self>` that decodes the variant content.
+fn variant_content_from(
+    variant: &Variant,
+    rename_all: Option<RenameRule>,
+    type_name: &str,
+    prefix: &str,
+) -> Result<TokenStream2> {
+    let var_ident = &variant.ident;
+    let (style, fields) = parse_fields(&variant.fields, rename_all)?;
+    let value = format_ident!("value");
+    let body = match style {
+        Style::Unit => quote! { Ok(Self::#var_ident) },
+        Style::Tuple if fields.len() == 1 => {
+            let ty = &fields[0].ty;
+            let convert = quote! { <#ty as ::mlua::FromLua>::from_lua(value, lua) };
+            if prefix.is_empty() {
+                quote! { #convert.map(Self::#var_ident) }
+            } else {
+                quote! { #convert.map(Self::#var_ident).map_err(|err| err.from_lua_field(#type_name, #prefix)) }
+            }
+        }
+        _ => {
+            let table = expect_table(&value, type_name, prefix);
+            let ctor = fields_from_table(style, &fields, quote!(Self::#var_ident), type_name, prefix);
+            quote! {
+                let table = #table;
+                Ok(#ctor)
+            }
+        }
+    };
+    Ok(quote! {
+        (|#value: ::mlua::Value| -> ::mlua::Result<Self> { #body })
+    })
+}
+
+fn unknown_variant(
+    from: TokenStream2,
+    name: TokenStream2,
+    type_name: &str,
+    names: &[String],
+) -> TokenStream2 {
+    let expected = names
+        .iter()
+        .map(|name| format!("`{name}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    quote! {
+        ::mlua::Error::FromLuaConversionError {
+            from: #from,
+            to: #type_name.to_string(),
+            message: Some(format!("unknown variant `{}`, expected one of {}", #name, #expected)),
+        }
+    }
+}
+
+fn enum_from_lua(
+    data: &DataEnum,
+    attrs: &ContainerAttrs,
+    type_name: &str,
+    span: Span,
+) -> Result<TokenStream2> {
+    let rename_all = attrs.rename_all;
+    let names = (data.variants.iter())
+        .map(|variant| variant_name(variant, rename_all))
+        .collect::<Result<Vec<_>>>()?;
+    let value = format_ident!("value");
+
+    match attrs.tagging(span)? {
+        Tagging::External => {
+            let mut unit_arms = Vec::new();
+            let mut table_arms = Vec::new();
+            for (variant, name) in data.variants.iter().zip(&names) {
+                let var_ident = &variant.ident;
+                if let Fields::Unit = variant.fields {
+                    unit_arms.push(quote! { #name => Ok(Self::#var_ident), });
+                } else {
+                    let content = variant_content_from(variant, rename_all, type_name, name)?;
+                    table_arms.push(quote! {
+                        let content = table
+                            .get::<::mlua::Value>(#name)
+                            .map_err(|err| err.from_lua_field(#type_name, #name))?;
+                        match content {
+                            ::mlua::Value::Nil => {}
+                            content => return #content(content),
+                        }
+                    });
+                }
+            }
+            let unknown_str = unknown_variant(quote!("string"), quote!(name), type_name, &names);
+            let expected = names
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!("expected table with one of {expected} keys");
+            Ok(quote! {
+                match #value {
+                    ::mlua::Value::String(ref name) => {
+                        let name = name.to_str().map_err(|_| ::mlua::Error::FromLuaConversionError {
+                            from: "string",
+                            to: #type_name.to_string(),
+                            message: Some("variant name is not a valid UTF-8 string".to_string()),
+                        })?;
+                        match &*name {
+                            #(#unit_arms)*
+                            name => Err(#unknown_str),
+                        }
+                    }
+                    ::mlua::Value::Table(ref table) => {
+                        #(#table_arms)*
+                        Err(::mlua::Error::FromLuaConversionError {
+                            from: "table",
+                            to: #type_name.to_string(),
+                            message: Some(#message.to_string()),
+                        })
+                    }
+                    _ => Err(::mlua::Error::FromLuaConversionError {
+                        from: #value.type_name(),
+                        to: #type_name.to_string(),
+                        message: Some("expected string or table".to_string()),
+                    }),
+                }
+            })
+        }
+        Tagging::Internal(tag) => {
+            let mut arms = Vec::new();
+            for (variant, name) in data.variants.iter().zip(&names) {
+                if matches!(&variant.fields, Fields::Unnamed(fields) if fields.unnamed.len() > 1) {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "tuple variants are not supported by internally tagged enums",
+                    ));
+                }
+                let content = variant_content_from(variant, rename_all, type_name, "")?;
+                arms.push(quote! { #name => #content(#value), });
+            }
+            let table = expect_table(&value, type_name, "");
+            let unknown = unknown_variant(quote!("table"), quote!(name), type_name, &names);
+            Ok(quote! {
+                let table = #table;
+                let name = table
+                    .get::<::mlua::String>(#tag)
+                    .map_err(|err| err.from_lua_field(#type_name, #tag))?;
+                let name = (name.to_str()).map_err(|err| err.from_lua_field(#type_name, #tag))?;
+                let #value = ::mlua::Value::Table(table);
+                match &*name {
+                    #(#arms)*
+                    name => Err(#unknown),
+                }
+            })
+        }
+        Tagging::Adjacent(tag, content_key) => {
+            let mut arms = Vec::new();
+            for (variant, name) in data.variants.iter().zip(&names) {
+                let content = variant_content_from(variant, rename_all, type_name, &content_key)?;
+                arms.push(quote! { #name => #content(content), });
+            }
+            let table = expect_table(&value, type_name, "");
+            let unknown = unknown_variant(quote!("table"), quote!(name), type_name, &names);
+            Ok(quote! {
+                let table = #table;
+                let name = table
+                    .get::<::mlua::String>(#tag)
+                    .map_err(|err| err.from_lua_field(#type_name, #tag))?;
+                let name = (name.to_str()).map_err(|err| err.from_lua_field(#type_name, #tag))?;
+                let content = table
+                    .get::<::mlua::Value>(#content_key)
+                    .map_err(|err| err.from_lua_field(#type_name, #content_key))?;
+                match &*name {
+                    #(#arms)*
+                    name => Err(#unknown),
+                }
+            })
+        }
+        Tagging::Untagged => {
+            let mut attempts = Vec::new();
+            for variant in &data.variants {
+                let var_ident = &variant.ident;
+                if let Fields::Unit = variant.fields {
+                    attempts.push(quote! {
+                        if #value.is_nil() {
+                            return Ok(Self::#var_ident);
+                        }
+                    });
+                } else {
+                    let content = variant_content_from(variant, rename_all, type_name, "")?;
+                    attempts.push(quote! {
+                        if let Ok(this) = #content(#value.clone()) {
+                            return Ok(this);
+                        }
+                    });
+                }
+            }
+            let message = format!("data did not match any variant of untagged enum {type_name}");
+            Ok(quote! {
+                #(#attempts)*
+                Err(::mlua::Error::FromLuaConversionError {
+                    from: #value.type_name(),
+                    to: #type_name.to_string(),
+                    message: Some(#message.to_string()),
+                })
+            })
+        }
+    }
+}
+
+pub(crate) fn into_lua(input: &DeriveInput, attrs: &ContainerAttrs) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let generics = add_bounds(&input.generics, quote!(::mlua::IntoLua));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (style, fields) = parse_fields(&data.fields, attrs.rename_all)?;
+            let pattern = destructure(style, &fields, quote!(Self));
+            let (narr, nrec) = table_capacity(style, &fields);
+            let sets = fields_into_table(&fields);
+            quote! {
+                #[allow(unused_variables)]
+                let #pattern = self;
+                let table = lua.create_table_with_capacity(#narr, #nrec)?;
+                #sets
+                Ok(::mlua::Value::Table(table))
+            }
+        }
+        Data::Enum(data) => enum_into_lua(data, attrs, ident.span())?,
+        Data::Union(_) => return Err(Error::new_spanned(input, "unions are not supported")),
+    };
+    let lua_type = lua_type(input);
+
+    Ok(quote! {
+        impl #impl_generics ::mlua::IntoLua for #ident #ty_generics #where_clause {
+            fn into_lua(self, lua: &::mlua::Lua) -> ::mlua::Result<::mlua::Value> {
+                #body
+            }
+
+            fn lua_type() -> ::mlua::TypeDesc {
+                #lua_type
+            }
+        }
+    })
+}
+
+fn enum_into_lua(data: &DataEnum, attrs: &ContainerAttrs, span: Span) -> Result<TokenStream2> {
+    let rename_all = attrs.rename_all;
+    let tagging = attrs.tagging(span)?;
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let var_ident = &variant.ident;
+        let name = variant_name(variant, rename_all)?;
+        let (style, fields) = parse_fields(&variant.fields, rename_all)?;
+        let pattern = destructure(style, &fields, quote!(Self::#var_ident));
+        let newtype = style == Style::Tuple && fields.len() == 1;
+
+        // Expression that evaluates to the variant content
+        let content = match style {
+            Style::Unit => quote! { ::mlua::Value::Nil },
+            _ if newtype => {
+                let binding = &fields[0].binding;
+                quote! { ::mlua::IntoLua::into_lua(#binding, lua)? }
+            }
+            _ => {
+                let (narr, nrec) = table_capacity(style, &fields);
+                let sets = fields_into_table(&fields);
+                quote! {{
+                    let table = lua.create_table_with_capacity(#narr, #nrec)?;
+                    #sets
+                    ::mlua::Value::Table(table)
+                }}
+            }
+        };
+
+        let body = match &tagging {
+            Tagging::External if style == Style::Unit => quote! {
+                Ok(::mlua::Value::String(lua.create_string(#name)?))
+            },
+            Tagging::External => quote! {
+                let table = lua.create_table_with_capacity(0, 1)?;
+                table.raw_set(#name, #content)?;
+                Ok(::mlua::Value::Table(table))
+            },
+            Tagging::Internal(tag) => match style {
+                Style::Unit => quote! {
+                    let table = lua.create_table_with_capacity(0, 1)?;
+                    table.raw_set(#tag, #name)?;
+                    Ok(::mlua::Value::Table(table))
+                },
+                _ if newtype => {
+                    let message = format!("variant `{name}` content must be a table to be internally tagged");
+                    quote! {
+                        match #content {
+                            ::mlua::Value::Table(table) => {
+                                table.raw_set(#tag, #name)?;
+                                Ok(::mlua::Value::Table(table))
+                            }
+                            value => Err(::mlua::Error::ToLuaConversionError {
+                                from: value.type_name().to_string(),
+                                to: "table",
+                                message: Some(#message.to_string()),
+                            }),
+                        }
+                    }
+                }
+                Style::Named => {
+                    let (_, nrec) = table_capacity(style, &fields);
+                    let nrec = nrec + 1;
+                    let sets = fields_into_table(&fields);
+                    quote! {
+                        let table = lua.create_table_with_capacity(0, #nrec)?;
+                        table.raw_set(#tag, #name)?;
+                        #sets
+                        Ok(::mlua::Value::Table(table))
+                    }
+                }
+                Style::Tuple => {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "tuple variants are not supported by internally tagged enums",
+                    ))
+                }
+            },
+            Tagging::Adjacent(tag, content_key) => quote! {
+                let table = lua.create_table_with_capacity(0, 2)?;
+                table.raw_set(#tag, #name)?;
+                table.raw_set(#content_key, #content)?;
+                Ok(::mlua::Value::Table(table))
+            },
+            Tagging::Untagged => quote! { Ok(#content) },
+        };
+
+        arms.push(quote! {
+            #[allow(unused_variables)]
+            #pattern => { #body }
+        });
+    }
+
+    Ok(quote! {
+        match self {
+            #(#arms)*
+        }
+    })
+}
@@ -0,0 +1,91 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, LitStr, Result, Visibility};
+
+/// Field attributes, e.g. `#[lua(rename = "name")]`.
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    skip: bool,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut this = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("lua")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    this.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("skip") {
+                    this.skip = true;
+                } else {
+                    return Err(meta.error("unsupported `lua` field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(this)
+    }
+}
+
+pub fn userdata(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut fields = Vec::new();
+    match &input.data {
+        Data::Struct(data) => {
+            if let Fields::Named(named) = &data.fields {
+                for field in &named.named {
+                    let attrs = FieldAttrs::parse(&field.attrs)?;
+                    if attrs.skip || !matches!(field.vis, Visibility::Public(_)) {
+                        continue;
+                    }
+                    let field_ident = field.ident.as_ref().unwrap();
+                    let name = match attrs.rename {
+                        Some(rename) => rename,
+                        None => {
+                            let name = field_ident.to_string();
+                            name.strip_prefix("r#").unwrap_or(&name).to_string()
+                        }
+                    };
+                    fields.push(quote! {
+                        fields.add_field_method_get(#name, |_, this| {
+                            Ok(::std::clone::Clone::clone(&this.#field_ident))
+                        });
+                        fields.add_field_method_set(#name, |_, this, value| {
+                            this.#field_ident = value;
+                            Ok(())
+                        });
+                    });
+                }
+            }
+        }
+        Data::Enum(_) => {}
+        Data::Union(_) => return Err(syn::Error::new_spanned(input, "unions are not supported")),
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::mlua::UserData for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn add_fields<F: ::mlua::UserDataFields<Self>>(fields: &mut F) {
+                #(#fields)*
+            }
+
+            fn add_methods<M: ::mlua::UserDataMethods<Self>>(methods: &mut M) {
+                #[allow(unused_imports)]
+                use ::mlua::{HasUserDataMethods as _, NoUserDataMethods as _};
+                (&::mlua::UserDataMethodsProbe::<Self>::new()).add_methods(methods);
+            }
+        }
+    })
+}
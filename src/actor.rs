@@ -0,0 +1,263 @@
+//! Running a Lua state on a dedicated thread.
+
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{mpsc, Arc};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::traits::{FromLua, IntoLua};
+use crate::types::RegistryKey;
+
+type Job = Box<dyn FnOnce(&Lua) + Send>;
+
+/// A handle to a Lua state running on a dedicated thread.
+///
+/// The actor accepts closures that are executed on the Lua thread one by one, and returns their
+/// results as [`LuaTask`]s, that can be awaited or waited for. The handle is cheap to clone and
+/// can be shared between threads (even without the `send` feature), so it can be used from
+/// multithreaded async runtimes without additional locking.
+///
+/// Lua values should not leave the Lua thread (and cannot without the `send` feature), use
+/// [`LuaHandle`] to refer to them from other threads.
+///
+/// The Lua thread stops once all actor handles are dropped.
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{LuaActor, Result};
+/// # fn main() -> Result<()> {
+/// let actor = LuaActor::new()?;
+///
+/// assert!(actor.exec(|lua| lua.globals().set("counter", 0).is_ok()).wait()?);
+///
+/// let threads = (0..4)
+///     .map(|_| {
+///         let actor = actor.clone();
+///         std::thread::spawn(move || {
+///             let task = actor.exec(|lua| lua.load("counter = counter + 1").exec().is_ok());
+///             task.wait().unwrap()
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// for thread in threads {
+///     assert!(thread.join().unwrap());
+/// }
+///
+/// let counter = actor.exec(|lua| lua.globals().get::<i64>("counter").unwrap()).wait()?;
+/// assert_eq!(counter, 4);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct LuaActor {
+    sender: mpsc::Sender<Job>,
+}
+
+/// A result of a closure executed by [`LuaActor`].
+///
+/// The task can be awaited as a future or waited for using [`LuaTask::wait`].
+/// If the closure panics, the panic is propagated to the task owner.
+#[must_use = "tasks do nothing unless waited for or polled"]
+pub struct LuaTask<R> {
+    shared: Arc<TaskShared<R>>,
+}
+
+/// A thread-safe handle to a Lua value owned by a [`LuaActor`].
+///
+/// The value is kept in the Lua registry until the handle is dropped (which can happen on any
+/// thread). It can be resolved only in the Lua state where it was created.
+pub struct LuaHandle<T> {
+    key: RegistryKey,
+    _type: PhantomData<fn() -> T>,
+}
+
+struct TaskShared<R> {
+    state: Mutex<TaskState<R>>,
+    ready: Condvar,
+}
+
+enum TaskState<R> {
+    Pending(Option<Waker>),
+    Done(thread::Result<R>),
+    // The actor stopped before the closure was executed
+    Stopped,
+    Taken,
+}
+
+// Completes the task when dropped without a result
+struct TaskCompleter<R>(Arc<TaskShared<R>>);
+
+impl LuaActor {
+    /// Starts a new Lua state (with the safe subset of the standard libraries) on a dedicated
+    /// thread.
+    pub fn new() -> Result<LuaActor> {
+        Self::with_lua(Lua::new)
+    }
+
+    /// Starts a dedicated thread with the Lua state returned by `create`.
+    ///
+    /// The state is created on the Lua thread, so `create` can use any Lua API.
+    pub fn with_lua<F>(create: F) -> Result<LuaActor>
+    where
+        F: FnOnce() -> Lua + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        thread::Builder::new()
+            .name("mlua-actor".into())
+            .spawn(move || {
+                let lua = create();
+                for job in receiver {
+                    job(&lua);
+                }
+            })
+            .map_err(Error::external)?;
+        Ok(LuaActor { sender })
+    }
+
+    /// Executes the closure on the Lua thread.
+    ///
+    /// Waiting for the returned task inside another closure executed by the same actor
+    /// deadlocks.
+    pub fn exec<F, R>(&self, f: F) -> LuaTask<R>
+    where
+        F: FnOnce(&Lua) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let shared = Arc::new(TaskShared {
+            state: Mutex::new(TaskState::Pending(None)),
+            ready: Condvar::new(),
+        });
+        let completer = TaskCompleter(shared.clone());
+        let job: Job = Box::new(move |lua| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(lua)));
+            completer.complete(TaskState::Done(result));
+        });
+        // If the actor is stopped, the job is dropped and the task is completed
+        let _ = self.sender.send(job);
+        LuaTask { shared }
+    }
+}
+
+impl fmt::Debug for LuaActor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LuaActor").finish_non_exhaustive()
+    }
+}
+
+impl<R> LuaTask<R> {
+    /// Blocks the current thread until the closure is executed and returns its result.
+    ///
+    /// Returns an error if the actor stopped before executing the closure.
+    pub fn wait(self) -> Result<R> {
+        let mut state = self.shared.state.lock();
+        while let TaskState::Pending(_) = *state {
+            self.shared.ready.wait(&mut state);
+        }
+        take_result(&mut state)
+    }
+}
+
+impl<R> Future for LuaTask<R> {
+    type Output = Result<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock();
+        match &mut *state {
+            TaskState::Pending(waker) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            _ => Poll::Ready(take_result(&mut state)),
+        }
+    }
+}
+
+impl<R> fmt::Debug for LuaTask<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LuaTask").finish_non_exhaustive()
+    }
+}
+
+fn take_result<R>(state: &mut TaskState<R>) -> Result<R> {
+    match std::mem::replace(state, TaskState::Taken) {
+        TaskState::Done(Ok(value)) => Ok(value),
+        TaskState::Done(Err(payload)) => panic::resume_unwind(payload),
+        TaskState::Stopped => Err(Error::runtime("Lua actor is stopped")),
+        TaskState::Pending(_) | TaskState::Taken => unreachable!("task result is not available"),
+    }
+}
+
+impl<R> TaskCompleter<R> {
+    fn complete(&self, result: TaskState<R>) {
+        let mut state = self.0.state.lock();
+        let TaskState::Pending(waker) = &mut *state else {
+            return;
+        };
+        let waker = waker.take();
+        *state = result;
+        drop(state);
+
+        self.0.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<R> Drop for TaskCompleter<R> {
+    fn drop(&mut self) {
+        self.complete(TaskState::Stopped);
+    }
+}
+
+impl<T> LuaHandle<T> {
+    /// Stores the value in the Lua registry and returns a handle to it.
+    pub fn new(lua: &Lua, value: T) -> Result<Self>
+    where
+        T: IntoLua,
+    {
+        Ok(LuaHandle {
+            key: lua.create_registry_value(value)?,
+            _type: PhantomData,
+        })
+    }
+
+    /// Returns the value in the Lua state where the handle was created.
+    ///
+    /// Returns [`Error::MismatchedRegistryKey`] if the handle belongs to another Lua state.
+    pub fn get(&self, lua: &Lua) -> Result<T>
+    where
+        T: FromLua,
+    {
+        lua.registry_value(&self.key)
+    }
+
+    /// Converts the handle into the underlying registry key.
+    pub fn into_registry_key(self) -> RegistryKey {
+        self.key
+    }
+}
+
+impl<T> fmt::Debug for LuaHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("LuaHandle").field(&self.key).finish()
+    }
+}
+
+#[cfg(test)]
+mod assertions {
+    use super::*;
+
+    static_assertions::assert_impl_all!(LuaActor: Send, Sync);
+    static_assertions::assert_impl_all!(LuaTask<()>: Send, Sync);
+    static_assertions::assert_impl_all!(LuaHandle<crate::Table>: Send, Sync);
+}
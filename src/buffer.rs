@@ -1,27 +1,64 @@
+#[cfg(feature = "luau")]
+use std::cell::Cell;
+use std::io;
+use std::os::raw::c_void;
+
 #[cfg(feature = "serialize")]
-use serde::ser::{Serialize, Serializer};
+use serde::ser::{self, Serialize, Serializer};
 
+use crate::error::{Error, Result};
+use crate::state::LuaGuard;
 use crate::types::ValueRef;
 
-/// A Luau buffer type.
+#[cfg(not(feature = "luau"))]
+use {
+    crate::state::Lua,
+    crate::string::String as LuaString,
+    crate::types::{Integer, Number},
+    crate::userdata::{AnyUserData, MetaMethod, UserData, UserDataFields, UserDataMethods, UserDataStorage},
+    crate::util::get_userdata,
+    std::any::TypeId,
+    std::cell::UnsafeCell,
+    std::ptr,
+};
+
+/// A buffer type, a fixed-size mutable block of memory.
+///
+/// On Luau this is the native buffer type. On other Lua versions mlua provides a userdata-backed
+/// buffer with the same API (see [`StdLib::BUFFER`]). Its data is allocated outside of the Lua
+/// allocator, so it's not counted towards the memory limit.
 ///
 /// See the buffer [documentation] for more information.
 ///
+/// [`StdLib::BUFFER`]: crate::StdLib::BUFFER
 /// [documentation]: https://luau.org/library#buffer-library
-#[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
 #[derive(Clone, Debug, PartialEq)]
 pub struct Buffer(pub(crate) ValueRef);
 
-#[cfg_attr(not(feature = "luau"), allow(unused))]
+/// A cursor over the [`Buffer`] data, which implements [`Read`], [`Write`] and [`Seek`].
+///
+/// The buffer has a fixed size, so writing past its end writes nothing.
+///
+/// [`Read`]: std::io::Read
+/// [`Write`]: std::io::Write
+/// [`Seek`]: std::io::Seek
+#[derive(Clone, Debug)]
+pub struct BufferCursor {
+    buffer: Buffer,
+    pos: u64,
+}
+
 impl Buffer {
     /// Copies the buffer data into a new `Vec<u8>`.
-    pub fn to_vec(&self) -> Vec<u8> {
-        unsafe { self.as_slice().to_vec() }
+    ///
+    /// Returns an error if the buffer is mutably borrowed.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        self.with_slice(|data| data.to_vec())
     }
 
     /// Returns the length of the buffer.
     pub fn len(&self) -> usize {
-        unsafe { self.as_slice().len() }
+        unsafe { self.with_raw_parts(|_, size| size) }
     }
 
     /// Returns `true` if the buffer is empty.
@@ -30,53 +67,307 @@ impl Buffer {
         self.len() == 0
     }
 
+    /// Calls the closure with a view of the buffer data, without copying.
+    ///
+    /// The Lua state is locked while the closure runs, so Lua code in other threads cannot modify
+    /// the buffer. On Luau the builtin `buffer` library bypasses borrow checks, so calls into Lua
+    /// made from the closure fail with [`Error::BufferBorrowMutError`].
+    ///
+    /// Returns [`Error::BufferBorrowError`] if the buffer is mutably borrowed.
+    pub fn with_slice<R>(&self, f: impl FnOnce(&[u8]) -> R) -> Result<R> {
+        let _borrow = BufferBorrow::new(self, false)?;
+        Ok(unsafe { self.with_raw_parts(|buf, size| f(std::slice::from_raw_parts(buf, size))) })
+    }
+
+    /// Calls the closure with a mutable view of the buffer data, without copying.
+    ///
+    /// The Lua state is locked while the closure runs, so Lua code in other threads cannot access
+    /// the buffer. On Luau the builtin `buffer` library bypasses borrow checks, so calls into Lua
+    /// made from the closure fail with [`Error::BufferBorrowMutError`].
+    ///
+    /// Returns [`Error::BufferBorrowMutError`] if the buffer is already borrowed.
+    pub fn with_mut_slice<R>(&self, f: impl FnOnce(&mut [u8]) -> R) -> Result<R> {
+        let _borrow = BufferBorrow::new(self, true)?;
+        Ok(unsafe { self.with_raw_parts(|buf, size| f(std::slice::from_raw_parts_mut(buf, size))) })
+    }
+
+    /// Returns a cursor over the buffer data starting at the beginning.
+    pub fn cursor(&self) -> BufferCursor {
+        BufferCursor {
+            buffer: self.clone(),
+            pos: 0,
+        }
+    }
+
     /// Reads given number of bytes from the buffer at the given offset.
     ///
     /// Offset is 0-based.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or the buffer is mutably borrowed.
+    /// Use [`Buffer::try_read_bytes`] for a non-panicking version.
     #[track_caller]
     pub fn read_bytes<const N: usize>(&self, offset: usize) -> [u8; N] {
-        let data = unsafe { self.as_slice() };
-        let mut bytes = [0u8; N];
-        bytes.copy_from_slice(&data[offset..offset + N]);
-        bytes
+        let bytes = self.with_slice(|data| {
+            let mut bytes = [0u8; N];
+            bytes.copy_from_slice(&data[offset..offset + N]);
+            bytes
+        });
+        bytes.expect("cannot borrow buffer")
     }
 
     /// Writes given bytes to the buffer at the given offset.
     ///
     /// Offset is 0-based.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or the buffer is borrowed.
+    /// Use [`Buffer::try_write_bytes`] for a non-panicking version.
     #[track_caller]
     pub fn write_bytes(&self, offset: usize, bytes: &[u8]) {
-        let data = unsafe {
-            let (buf, size) = self.as_raw_parts();
-            std::slice::from_raw_parts_mut(buf, size)
-        };
-        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        let res = self.with_mut_slice(|data| data[offset..offset + bytes.len()].copy_from_slice(bytes));
+        res.expect("cannot borrow buffer")
     }
 
-    pub(crate) unsafe fn as_slice(&self) -> &[u8] {
-        let (buf, size) = self.as_raw_parts();
-        std::slice::from_raw_parts(buf, size)
+    /// Reads given number of bytes from the buffer at the given offset.
+    ///
+    /// Returns an error if the range is out of bounds or the buffer is mutably borrowed.
+    pub fn try_read_bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        self.with_slice(|data| {
+            let bytes = check_bounds(data.len(), offset, N).map(|range| &data[range])?;
+            Ok(bytes.try_into().unwrap())
+        })?
+    }
+
+    /// Writes given bytes to the buffer at the given offset.
+    ///
+    /// Returns an error if the range is out of bounds or the buffer is borrowed.
+    pub fn try_write_bytes(&self, offset: usize, bytes: &[u8]) -> Result<()> {
+        self.with_mut_slice(|data| {
+            let range = check_bounds(data.len(), offset, bytes.len())?;
+            data[range].copy_from_slice(bytes);
+            Ok(())
+        })?
     }
 
+    // Calls the closure with the buffer data pointer and size.
+    //
+    // The caller must make sure that the data is borrowed (see `BufferBorrow`) before accessing it.
     #[cfg(feature = "luau")]
-    unsafe fn as_raw_parts(&self) -> (*mut u8, usize) {
+    unsafe fn with_raw_parts<R>(&self, f: impl FnOnce(*mut u8, usize) -> R) -> R {
         let lua = self.0.lua.lock();
         let mut size = 0usize;
         let buf = ffi::lua_tobuffer(lua.ref_thread(), self.0.index, &mut size);
         mlua_assert!(!buf.is_null(), "invalid Luau buffer");
-        (buf as *mut u8, size)
+        f(buf as *mut u8, size)
     }
 
+    // Calls the closure with the buffer data pointer and size.
+    //
+    // The userdata is borrowed while the closure runs, so it cannot be destroyed.
+    // The caller must make sure that the data is borrowed (see `BufferBorrow`) before accessing it.
     #[cfg(not(feature = "luau"))]
-    unsafe fn as_raw_parts(&self) -> (*mut u8, usize) {
-        unreachable!()
+    unsafe fn with_raw_parts<R>(&self, f: impl FnOnce(*mut u8, usize) -> R) -> R {
+        let lua = self.0.lua.lock();
+        let type_id = lua.get_userdata_ref_type_id(&self.0);
+        mlua_assert!(
+            matches!(type_id, Ok(Some(id)) if id == TypeId::of::<BufferData>()),
+            "invalid buffer userdata"
+        );
+        let ud = &*get_userdata::<UserDataStorage<BufferData>>(lua.ref_thread(), self.0.index);
+        let res = ud.try_borrow_scoped(|ud| {
+            // Do not create references to the data, it can be borrowed elsewhere
+            let data = ptr::addr_of_mut!(**ud.0.get());
+            f(data as *mut u8, data.len())
+        });
+        mlua_expect!(res, "cannot access buffer userdata")
+    }
+}
+
+// A borrow of the buffer data registered in the Lua state.
+//
+// The Lua state is locked while the borrow is alive, the borrow is released on drop (even if the
+// caller panics).
+struct BufferBorrow {
+    lua: LuaGuard,
+    ptr: *const c_void,
+    mutable: bool,
+}
+
+#[cfg(feature = "luau")]
+std::thread_local! {
+    // Number of buffer borrows alive on the current thread
+    static ACTIVE_BORROWS: Cell<usize> = const { Cell::new(0) };
+}
+
+impl BufferBorrow {
+    fn new(buffer: &Buffer, mutable: bool) -> Result<Self> {
+        let lua = buffer.0.lua.lock();
+        let ptr = buffer.0.to_pointer();
+        if !unsafe { lua.borrow_buffer(ptr, mutable) } {
+            return Err(match mutable {
+                true => Error::BufferBorrowMutError,
+                false => Error::BufferBorrowError,
+            });
+        }
+        #[cfg(feature = "luau")]
+        ACTIVE_BORROWS.with(|n| n.set(n.get() + 1));
+        Ok(BufferBorrow { lua, ptr, mutable })
+    }
+}
+
+impl Drop for BufferBorrow {
+    fn drop(&mut self) {
+        #[cfg(feature = "luau")]
+        ACTIVE_BORROWS.with(|n| n.set(n.get() - 1));
+        unsafe { self.lua.release_buffer(self.ptr, self.mutable) };
+    }
+}
+
+// Returns an error if the buffer data is borrowed on the current thread.
+//
+// Luau builtin `buffer` functions access the data directly, so Lua code must not run while Rust
+// holds a view of it.
+#[cfg(feature = "luau")]
+#[inline]
+pub(crate) fn check_not_borrowed() -> Result<()> {
+    match ACTIVE_BORROWS.with(Cell::get) {
+        0 => Ok(()),
+        _ => Err(Error::BufferBorrowMutError),
+    }
+}
+
+fn check_bounds(len: usize, offset: usize, n: usize) -> Result<std::ops::Range<usize>> {
+    match offset.checked_add(n) {
+        Some(end) if end <= len => Ok(offset..end),
+        _ => Err(Error::runtime(format!(
+            "buffer access out of bounds (offset {offset}, size {n}, buffer length {len})"
+        ))),
+    }
+}
+
+macro_rules! impl_typed_access {
+    ($($ty:ty => $read:ident, $write:ident;)*) => {
+        impl Buffer {
+            $(
+                #[doc = concat!("Reads a little-endian `", stringify!($ty), "` at the given offset.")]
+                ///
+                /// Returns an error if the value is out of bounds.
+                pub fn $read(&self, offset: usize) -> Result<$ty> {
+                    Ok(<$ty>::from_le_bytes(self.try_read_bytes(offset)?))
+                }
+
+                #[doc = concat!("Writes a little-endian `", stringify!($ty), "` at the given offset.")]
+                ///
+                /// Returns an error if the value is out of bounds.
+                pub fn $write(&self, offset: usize, value: $ty) -> Result<()> {
+                    self.try_write_bytes(offset, &value.to_le_bytes())
+                }
+            )*
+        }
+    };
+}
+
+impl_typed_access! {
+    i8 => read_i8, write_i8;
+    u8 => read_u8, write_u8;
+    i16 => read_i16, write_i16;
+    u16 => read_u16, write_u16;
+    i32 => read_i32, write_i32;
+    u32 => read_u32, write_u32;
+    f32 => read_f32, write_f32;
+    f64 => read_f64, write_f64;
+}
+
+impl BufferCursor {
+    /// Returns the current position of the cursor.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Sets the position of the cursor.
+    pub fn set_position(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+
+    /// Returns a reference to the underlying buffer.
+    pub fn get_ref(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Consumes the cursor, returning the underlying buffer.
+    pub fn into_inner(self) -> Buffer {
+        self.buffer
+    }
+
+    // Returns the range of at most `n` bytes starting at the current position
+    fn range(&self, len: usize, n: usize) -> std::ops::Range<usize> {
+        let start = usize::try_from(self.pos).unwrap_or(usize::MAX).min(len);
+        start..start + n.min(len - start)
+    }
+}
+
+impl io::Read for BufferCursor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (self.buffer.with_slice(|data| {
+            let range = self.range(data.len(), buf.len());
+            let n = range.len();
+            buf[..n].copy_from_slice(&data[range]);
+            n
+        }))
+        .map_err(|err| io::Error::other(err.to_string()))?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl io::Write for BufferCursor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = (self.buffer.with_mut_slice(|data| {
+            let range = self.range(data.len(), buf.len());
+            let n = range.len();
+            data[range].copy_from_slice(&buf[..n]);
+            n
+        }))
+        .map_err(|err| io::Error::other(err.to_string()))?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Seek for BufferCursor {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(n) => {
+                self.pos = n;
+                return Ok(n);
+            }
+            io::SeekFrom::End(n) => (self.buffer.len() as u64, n),
+            io::SeekFrom::Current(n) => (self.pos, n),
+        };
+        match base.checked_add_signed(offset) {
+            Some(n) => {
+                self.pos = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
     }
 }
 
 #[cfg(feature = "serialize")]
 impl Serialize for Buffer {
     fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
-        serializer.serialize_bytes(unsafe { self.as_slice() })
+        (self.with_slice(|data| serializer.serialize_bytes(data))).map_err(ser::Error::custom)?
     }
 }
 
@@ -84,3 +375,217 @@ impl Serialize for Buffer {
 impl crate::types::LuaType for Buffer {
     const TYPE_ID: std::os::raw::c_int = ffi::LUA_TBUFFER;
 }
+
+// Maximum size of a buffer (the same as in Luau)
+#[cfg(not(feature = "luau"))]
+const MAX_BUFFER_SIZE: usize = 1 << 30;
+
+// Userdata that holds the buffer data on Lua versions without native buffers
+#[cfg(not(feature = "luau"))]
+pub(crate) struct BufferData(UnsafeCell<Box<[u8]>>);
+
+#[cfg(not(feature = "luau"))]
+impl UserData for BufferData {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_meta_field(MetaMethod::Type, "buffer");
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_function(MetaMethod::ToString, |_, ud: AnyUserData| {
+            Ok(format!("buffer: {:?}", ud.to_pointer()))
+        });
+    }
+}
+
+// Marks the buffer userdata as serializable, the data itself is serialized by `Buffer`
+// (see `AnyUserData::serialize`) as it must be borrowed first.
+#[cfg(all(feature = "serialize", not(feature = "luau")))]
+impl Serialize for BufferData {
+    fn serialize<S: Serializer>(&self, _: S) -> std::result::Result<S::Ok, S::Error> {
+        Err(ser::Error::custom("buffer data cannot be serialized directly"))
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+impl Buffer {
+    pub(crate) fn create_userdata(lua: &Lua, data: Box<[u8]>) -> Result<Buffer> {
+        if data.len() > MAX_BUFFER_SIZE {
+            return Err(Error::runtime("buffer size is too large"));
+        }
+        let data = BufferData(UnsafeCell::new(data));
+        #[cfg(feature = "serialize")]
+        let ud = lua.create_ser_userdata(data)?;
+        #[cfg(not(feature = "serialize"))]
+        let ud = lua.create_userdata(data)?;
+        Ok(Buffer(ud.0))
+    }
+
+    // Returns the buffer if the userdata holds buffer data
+    pub(crate) fn from_userdata(ud: &AnyUserData) -> Option<Buffer> {
+        ud.is::<BufferData>().then(|| Buffer(ud.0.clone()))
+    }
+
+    pub(crate) fn into_userdata(self) -> AnyUserData {
+        AnyUserData(self.0)
+    }
+}
+
+// Converts a Lua number to a buffer element, integers are wrapped around like in Luau
+#[cfg(not(feature = "luau"))]
+trait FromNumber {
+    fn from_number(n: Number) -> Self;
+}
+
+#[cfg(not(feature = "luau"))]
+macro_rules! impl_from_number {
+    ($($ty:ty),*) => {
+        $(
+            impl FromNumber for $ty {
+                #[inline]
+                fn from_number(n: Number) -> Self {
+                    n as i64 as $ty
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(not(feature = "luau"))]
+impl_from_number!(i8, u8, i16, u16, i32, u32);
+
+#[cfg(not(feature = "luau"))]
+impl FromNumber for f32 {
+    #[inline]
+    fn from_number(n: Number) -> Self {
+        n as f32
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+impl FromNumber for f64 {
+    #[inline]
+    fn from_number(n: Number) -> Self {
+        n
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+fn to_offset(n: Integer) -> Result<usize> {
+    usize::try_from(n).map_err(|_| Error::runtime(format!("buffer access out of bounds (offset {n})")))
+}
+
+/// Loads the `buffer` library into the globals table.
+#[cfg(not(feature = "luau"))]
+pub(crate) fn load_lib(lua: &Lua) -> Result<()> {
+    let lib = lua.create_table()?;
+
+    let create = lua.create_function(|lua, size: Integer| {
+        let size = usize::try_from(size).map_err(|_| Error::runtime("buffer size cannot be negative"))?;
+        if size > MAX_BUFFER_SIZE {
+            return Err(Error::runtime("buffer size is too large"));
+        }
+        Buffer::create_userdata(lua, vec![0; size].into_boxed_slice())
+    })?;
+    lib.raw_set("create", create)?;
+
+    let fromstring = lua.create_function(|lua, s: LuaString| lua.create_buffer(s.as_bytes()))?;
+    lib.raw_set("fromstring", fromstring)?;
+
+    let tostring =
+        lua.create_function(|lua, buf: Buffer| buf.with_slice(|data| lua.create_string(data))?)?;
+    lib.raw_set("tostring", tostring)?;
+
+    lib.raw_set("len", lua.create_function(|_, buf: Buffer| Ok(buf.len()))?)?;
+
+    macro_rules! register_typed_access {
+        ($($ty:ty => $name:literal, $read:ident, $write:ident;)*) => {
+            $(
+                let read = lua.create_function(|_, (buf, offset): (Buffer, Integer)| {
+                    buf.$read(to_offset(offset)?)
+                })?;
+                lib.raw_set(concat!("read", $name), read)?;
+
+                let write = lua.create_function(|_, (buf, offset, value): (Buffer, Integer, Number)| {
+                    buf.$write(to_offset(offset)?, <$ty>::from_number(value))
+                })?;
+                lib.raw_set(concat!("write", $name), write)?;
+            )*
+        };
+    }
+
+    register_typed_access! {
+        i8 => "i8", read_i8, write_i8;
+        u8 => "u8", read_u8, write_u8;
+        i16 => "i16", read_i16, write_i16;
+        u16 => "u16", read_u16, write_u16;
+        i32 => "i32", read_i32, write_i32;
+        u32 => "u32", read_u32, write_u32;
+        f32 => "f32", read_f32, write_f32;
+        f64 => "f64", read_f64, write_f64;
+    }
+
+    let readstring = lua.create_function(|lua, (buf, offset, count): (Buffer, Integer, Integer)| {
+        let (offset, count) = (to_offset(offset)?, to_offset(count)?);
+        buf.with_slice(|data| lua.create_string(&data[check_bounds(data.len(), offset, count)?]))?
+    })?;
+    lib.raw_set("readstring", readstring)?;
+
+    let writestring = lua.create_function(
+        |_, (buf, offset, s, count): (Buffer, Integer, LuaString, Option<Integer>)| {
+            let bytes = s.as_bytes();
+            let count = count.map(to_offset).transpose()?.unwrap_or(bytes.len());
+            if count > bytes.len() {
+                return Err(Error::runtime("string length overflow"));
+            }
+            buf.try_write_bytes(to_offset(offset)?, &bytes[..count])
+        },
+    )?;
+    lib.raw_set("writestring", writestring)?;
+
+    let copy = lua.create_function(
+        |_,
+         (target, target_offset, source, source_offset, count): (
+            Buffer,
+            Integer,
+            Buffer,
+            Option<Integer>,
+            Option<Integer>,
+        )| {
+            // Copy the source data first, as the target can be the same buffer
+            let offset = source_offset.map(to_offset).transpose()?.unwrap_or(0);
+            let count = count.map(to_offset).transpose()?;
+            let bytes = source.with_slice(|data| {
+                let count = count.unwrap_or(data.len().saturating_sub(offset));
+                check_bounds(data.len(), offset, count).map(|range| data[range].to_vec())
+            })??;
+            target.try_write_bytes(to_offset(target_offset)?, &bytes)
+        },
+    )?;
+    lib.raw_set("copy", copy)?;
+
+    let fill = lua.create_function(
+        |_, (buf, offset, value, count): (Buffer, Integer, Integer, Option<Integer>)| {
+            let offset = to_offset(offset)?;
+            let count = count.map(to_offset).transpose()?;
+            buf.with_mut_slice(|data| {
+                let count = count.unwrap_or(data.len().saturating_sub(offset));
+                let range = check_bounds(data.len(), offset, count)?;
+                data[range].fill(value as u8);
+                Ok(())
+            })?
+        },
+    )?;
+    lib.raw_set("fill", fill)?;
+
+    lua.globals().raw_set("buffer", lib)
+}
+
+#[cfg(test)]
+mod assertions {
+    use super::*;
+
+    #[cfg(not(feature = "send"))]
+    static_assertions::assert_not_impl_any!(BufferCursor: Send);
+    #[cfg(feature = "send")]
+    static_assertions::assert_impl_all!(BufferCursor: Send, Sync);
+}
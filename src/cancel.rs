@@ -0,0 +1,114 @@
+//! Cancellation of async Lua calls.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use parking_lot::Mutex;
+
+/// A token to cancel an [`AsyncThread`].
+///
+/// Each async thread has its own token, which can be obtained using
+/// [`AsyncThread::cancellation_token`] (before the thread is awaited) or [`Lua::cancellation_token`]
+/// (inside an async function called by the thread).
+///
+/// Once the token is cancelled, every pending or subsequent call to an async function in the
+/// thread raises [`Error::Cancelled`]. The error can be caught by the Lua code (for example, using
+/// `pcall`) to run cleanup code. If it's not caught, the thread finishes with the error and, on
+/// Lua 5.4, its pending to-be-closed variables are closed.
+///
+/// Async functions can use the token to stop their background work cooperatively.
+///
+/// Requires `feature = "async"`
+///
+/// [`AsyncThread`]: crate::AsyncThread
+/// [`AsyncThread::cancellation_token`]: crate::AsyncThread::cancellation_token
+/// [`Lua::cancellation_token`]: crate::Lua::cancellation_token
+/// [`Error::Cancelled`]: crate::Error::Cancelled
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+#[derive(Default)]
+struct TokenInner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl CancellationToken {
+    /// Creates a new token.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token and wakes up all tasks waiting for it.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        let wakers = std::mem::take(&mut *self.inner.wakers.lock());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Returns `true` if the token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns a future that completes when the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled { token: self.clone() }
+    }
+
+    // Registers the waker to be woken up on cancellation
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut wakers = self.inner.wakers.lock();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// A future returned by [`CancellationToken::cancelled`].
+///
+/// Requires `feature = "async"`
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Cancelled {
+    token: CancellationToken,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        self.token.register(cx.waker());
+        // Check again in case the token was cancelled while registering
+        match self.token.is_cancelled() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod assertions {
+    use super::*;
+
+    static_assertions::assert_impl_all!(CancellationToken: Send, Sync);
+}
@@ -0,0 +1,437 @@
+//! Passing Lua values between independent Lua states.
+
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::os::raw::c_void;
+use std::string::String as StdString;
+use std::sync::Arc;
+use std::task::Waker;
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::table::Table;
+use crate::traits::IntoLua;
+use crate::types::{Integer, Number};
+use crate::userdata::{AnyUserData, UserData, UserDataMethods};
+use crate::util::field_path;
+use crate::value::Value;
+
+#[cfg(feature = "async")]
+use std::{future::poll_fn, task::Poll};
+
+/// Trait for userdata types that can be sent through a [`Channel`].
+///
+/// When a userdata is sent, [`Transferable::transfer`] is called in the sending Lua state and the
+/// returned copy is moved to the receiving state, where a new userdata is created from it.
+/// The copy must not hold references to Lua values of the sending state.
+///
+/// The type must be registered using [`Lua::register_transferable`] in the sending state.
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{Lua, Result, Transferable, UserData};
+/// # fn main() -> Result<()> {
+/// #[derive(Clone)]
+/// struct Point(i64, i64);
+///
+/// impl UserData for Point {}
+///
+/// impl Transferable for Point {
+///     fn transfer(&self) -> Result<Self> {
+///         Ok(self.clone())
+///     }
+/// }
+///
+/// let lua = Lua::new();
+/// lua.register_transferable::<Point>();
+/// let channel = lua.create_channel()?;
+/// channel.send(&lua, Point(1, 2))?;
+///
+/// let lua2 = Lua::new();
+/// let point = channel.recv(&lua2)?.unwrap();
+/// assert_eq!(point.as_userdata().unwrap().borrow::<Point>()?.1, 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Lua::register_transferable`]: crate::Lua::register_transferable
+pub trait Transferable: UserData + Send + Sized + 'static {
+    /// Returns a copy of the userdata to be moved to another Lua state.
+    fn transfer(&self) -> Result<Self>;
+}
+
+// Type-erased `Transferable::transfer`
+pub(crate) type TransferFn = fn(&AnyUserData) -> Result<TransferredUserData>;
+
+type RestoreFn = fn(&Lua, Box<dyn Any + Send>) -> Result<AnyUserData>;
+
+pub(crate) struct TransferredUserData {
+    data: Box<dyn Any + Send>,
+    restore: RestoreFn,
+}
+
+pub(crate) fn transfer_userdata<T: Transferable>(ud: &AnyUserData) -> Result<TransferredUserData> {
+    Ok(TransferredUserData {
+        data: Box::new(T::transfer(&*ud.borrow::<T>()?)?),
+        restore: restore_userdata::<T>,
+    })
+}
+
+fn restore_userdata<T: Transferable>(lua: &Lua, data: Box<dyn Any + Send>) -> Result<AnyUserData> {
+    let data = data.downcast::<T>().expect("transferred userdata type mismatch");
+    lua.create_userdata(*data)
+}
+
+/// A multi-producer, multi-consumer queue for passing values between Lua states.
+///
+/// Values are deep-copied when sent, so the receiving state gets its own copy. Supported values
+/// are booleans, numbers, strings, tables (without metatables, preserving shared references and
+/// cycles) and registered userdata implementing [`Transferable`] (including channels, which are
+/// registered by [`Lua::create_channel`]). On Luau, vectors and buffers are supported as well. `nil` cannot be sent, as it marks a closed channel.
+///
+/// The channel is cheap to clone and can be moved between threads. It implements [`UserData`],
+/// so it can be passed to Lua scripts, which get the following methods:
+///
+/// - `send(value)` sends a value
+/// - `recv()` blocks until a value is received, returns `nil` if the channel is closed
+/// - `recv_async()` same as `recv` but yields instead of blocking (requires `feature = "async"`
+///   and running the script using async API)
+/// - `try_recv()` returns a value or `nil` if there are no values
+/// - `close()` closes the channel, `is_closed()` checks whether it's closed
+/// - `#channel` returns the number of pending values
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let channel = lua.create_channel()?;
+/// lua.globals().set("channel", channel.clone())?;
+///
+/// let worker = std::thread::spawn(move || {
+///     let lua = Lua::new();
+///     let mut sum = 0;
+///     while let Some(value) = channel.recv(&lua).unwrap() {
+///         sum += value.as_integer().unwrap();
+///     }
+///     sum
+/// });
+///
+/// lua.load("for i = 1, 10 do channel:send(i) end channel:close()").exec()?;
+/// assert_eq!(worker.join().unwrap(), 55);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Channel {
+    inner: Arc<ChannelInner>,
+}
+
+#[derive(Default)]
+struct ChannelInner {
+    state: Mutex<ChannelState>,
+    ready: Condvar,
+}
+
+#[derive(Default)]
+struct ChannelState {
+    queue: VecDeque<Message>,
+    wakers: Vec<Waker>,
+    closed: bool,
+}
+
+// A deep copy of a Lua value that can be moved to another Lua state
+struct Message {
+    tables: Vec<Vec<(Transfer, Transfer)>>,
+    value: Transfer,
+}
+
+enum Transfer {
+    Nil,
+    Boolean(bool),
+    Integer(Integer),
+    Number(Number),
+    String(Vec<u8>),
+    #[cfg(feature = "luau")]
+    Vector(crate::Vector),
+    Buffer(Vec<u8>),
+    // Index of the table in the message
+    Table(usize),
+    UserData(TransferredUserData),
+}
+
+impl Channel {
+    /// Creates a new channel.
+    ///
+    /// Unlike [`Lua::create_channel`], this does not register the channel type in any Lua state.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies the value and sends it through the channel.
+    ///
+    /// Returns an error if the channel is closed or the value cannot be sent.
+    pub fn send(&self, lua: &Lua, value: impl IntoLua) -> Result<()> {
+        let value = value.into_lua(lua)?;
+        if value.is_nil() {
+            return Err(Error::runtime("cannot send nil through a channel"));
+        }
+        let message = Encoder::default().encode(&value)?;
+
+        let mut state = self.inner.state.lock();
+        if state.closed {
+            return Err(Error::runtime("channel is closed"));
+        }
+        state.queue.push_back(message);
+        let wakers = std::mem::take(&mut state.wakers);
+        drop(state);
+
+        self.inner.ready.notify_one();
+        for waker in wakers {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// Receives a value, blocking the current thread until it's available.
+    ///
+    /// Returns `None` if the channel is closed and has no pending values.
+    pub fn recv(&self, lua: &Lua) -> Result<Option<Value>> {
+        let mut state = self.inner.state.lock();
+        loop {
+            if let Some(message) = state.queue.pop_front() {
+                drop(state);
+                return message.decode(lua).map(Some);
+            }
+            if state.closed {
+                return Ok(None);
+            }
+            self.inner.ready.wait(&mut state);
+        }
+    }
+
+    /// Receives a value without blocking.
+    ///
+    /// Returns `None` if there are no pending values.
+    pub fn try_recv(&self, lua: &Lua) -> Result<Option<Value>> {
+        let message = self.inner.state.lock().queue.pop_front();
+        message.map(|message| message.decode(lua)).transpose()
+    }
+
+    /// Receives a value asynchronously.
+    ///
+    /// Returns `None` if the channel is closed and has no pending values.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn recv_async(&self, lua: &Lua) -> Result<Option<Value>> {
+        let message = poll_fn(|cx| {
+            let mut state = self.inner.state.lock();
+            if let Some(message) = state.queue.pop_front() {
+                return Poll::Ready(Some(message));
+            }
+            if state.closed {
+                return Poll::Ready(None);
+            }
+            if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await;
+        message.map(|message| message.decode(lua)).transpose()
+    }
+
+    /// Closes the channel.
+    ///
+    /// Values that are already sent can still be received.
+    pub fn close(&self) {
+        let mut state = self.inner.state.lock();
+        state.closed = true;
+        let wakers = std::mem::take(&mut state.wakers);
+        drop(state);
+
+        self.inner.ready.notify_all();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.state.lock().closed
+    }
+
+    /// Returns the number of pending values.
+    pub fn len(&self) -> usize {
+        self.inner.state.lock().queue.len()
+    }
+
+    /// Returns `true` if there are no pending values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.inner.state.lock();
+        f.debug_struct("Channel")
+            .field("len", &state.queue.len())
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl Transferable for Channel {
+    fn transfer(&self) -> Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+impl UserData for Channel {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("send", |lua, this, value: Value| this.send(lua, value));
+        methods.add_method("recv", |lua, this, ()| this.recv(lua));
+        #[cfg(feature = "async")]
+        methods.add_async_method("recv_async", |lua, this, ()| async move {
+            this.recv_async(&lua).await
+        });
+        methods.add_method("try_recv", |lua, this, ()| this.try_recv(lua));
+        methods.add_method("close", |_, this, ()| {
+            this.close();
+            Ok(())
+        });
+        methods.add_method("is_closed", |_, this, ()| Ok(this.is_closed()));
+        methods.add_meta_method("__len", |_, this, ()| Ok(this.len()));
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    tables: Vec<Vec<(Transfer, Transfer)>>,
+    // Indices of the copied tables (by pointer)
+    refs: HashMap<*const c_void, usize>,
+    // Parent table index and key of each copied table (used in error messages)
+    parents: Vec<Option<(usize, Value)>>,
+    pending: Vec<(Table, usize)>,
+}
+
+impl Encoder {
+    fn encode(mut self, value: &Value) -> Result<Message> {
+        let value = self.encode_value(value, None)?;
+        while let Some((table, idx)) = self.pending.pop() {
+            let mut entries = Vec::new();
+            for pair in table.pairs::<Value, Value>() {
+                let (key, value) = pair?;
+                let key_transfer = self.encode_value(&key, Some((idx, Value::Nil)))?;
+                let value = self.encode_value(&value, Some((idx, key)))?;
+                entries.push((key_transfer, value));
+            }
+            self.tables[idx] = entries;
+        }
+        Ok(Message {
+            tables: self.tables,
+            value,
+        })
+    }
+
+    fn encode_value(&mut self, value: &Value, parent: Option<(usize, Value)>) -> Result<Transfer> {
+        Ok(match value {
+            Value::Nil => Transfer::Nil,
+            Value::Boolean(b) => Transfer::Boolean(*b),
+            Value::Integer(i) => Transfer::Integer(*i),
+            Value::Number(n) => Transfer::Number(*n),
+            Value::String(s) => Transfer::String(s.as_bytes().to_vec()),
+            #[cfg(feature = "luau")]
+            Value::Vector(v) => Transfer::Vector(*v),
+            #[cfg(feature = "luau")]
+            Value::Buffer(buf) => Transfer::Buffer(buf.to_vec()?),
+            Value::Table(t) => {
+                let ptr = t.to_pointer();
+                if let Some(&idx) = self.refs.get(&ptr) {
+                    return Ok(Transfer::Table(idx));
+                }
+                if t.metatable().is_some() {
+                    return Err(self.error("table with metatable", parent));
+                }
+                let idx = self.tables.len();
+                self.tables.push(Vec::new());
+                self.parents.push(parent);
+                self.refs.insert(ptr, idx);
+                self.pending.push((t.clone(), idx));
+                Transfer::Table(idx)
+            }
+            Value::UserData(ud) => {
+                #[cfg(not(feature = "luau"))]
+                if let Some(buf) = crate::Buffer::from_userdata(ud) {
+                    return Ok(Transfer::Buffer(buf.to_vec()?));
+                }
+                let transfer = unsafe { ud.0.lua.lock().get_userdata_ref_transfer(&ud.0) }.unwrap_or(None);
+                let Some(transfer) = transfer else {
+                    let type_name = ud.type_name().ok().flatten().unwrap_or_else(|| "userdata".into());
+                    return Err(self.error(&format!("userdata `{type_name}`"), parent));
+                };
+                Transfer::UserData(transfer(ud)?)
+            }
+            value => return Err(self.error(value.type_name(), parent)),
+        })
+    }
+
+    fn error(&self, what: &str, mut parent: Option<(usize, Value)>) -> Error {
+        let mut keys = Vec::new();
+        while let Some((idx, key)) = parent {
+            keys.push(key);
+            parent = self.parents[idx].clone();
+        }
+        let path = (keys.iter().rev()).fold(StdString::from("value"), |path, key| match key {
+            Value::Nil => format!("key of {path}"),
+            key => field_path(&path, key),
+        });
+        Error::runtime(format!("cannot send {what} at `{path}` through a channel"))
+    }
+}
+
+impl Message {
+    fn decode(self, lua: &Lua) -> Result<Value> {
+        let tables = (self.tables.iter())
+            .map(|entries| lua.create_table_with_capacity(0, entries.len()))
+            .collect::<Result<Vec<_>>>()?;
+        for (table, entries) in tables.iter().zip(self.tables) {
+            for (key, value) in entries {
+                let key = Self::decode_value(lua, &tables, key)?;
+                let value = Self::decode_value(lua, &tables, value)?;
+                table.raw_set(key, value)?;
+            }
+        }
+        Self::decode_value(lua, &tables, self.value)
+    }
+
+    fn decode_value(lua: &Lua, tables: &[Table], value: Transfer) -> Result<Value> {
+        Ok(match value {
+            Transfer::Nil => Value::Nil,
+            Transfer::Boolean(b) => Value::Boolean(b),
+            Transfer::Integer(i) => Value::Integer(i),
+            Transfer::Number(n) => Value::Number(n),
+            Transfer::String(s) => Value::String(lua.create_string(s)?),
+            #[cfg(feature = "luau")]
+            Transfer::Vector(v) => Value::Vector(v),
+            Transfer::Buffer(buf) => lua.create_buffer(buf)?.into_lua(lua)?,
+            Transfer::Table(idx) => Value::Table(tables[idx].clone()),
+            Transfer::UserData(ud) => Value::UserData((ud.restore)(lua, ud.data)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod assertions {
+    use super::*;
+
+    static_assertions::assert_impl_all!(Channel: Send, Sync);
+}
@@ -204,6 +204,11 @@ impl Compiler {
         self
     }
 
+    // Enables coverage support (for statements) if it's not enabled yet
+    pub(crate) fn enable_coverage(&mut self) {
+        self.coverage_level = self.coverage_level.max(1);
+    }
+
     #[doc(hidden)]
     #[must_use]
     pub fn set_vector_lib(mut self, lib: impl Into<String>) -> Self {
@@ -444,6 +449,16 @@ impl Chunk<'_> {
     /// This simply compiles the chunk without actually executing it.
     #[cfg_attr(not(feature = "luau"), allow(unused_mut))]
     pub fn into_function(mut self) -> Result<Function> {
+        #[cfg(feature = "luau")]
+        let coverage = self.lua.lock().coverage();
+        #[cfg(feature = "luau")]
+        if coverage.is_some() {
+            // Coverage collection requires chunks to be compiled with coverage support
+            self.compiler
+                .get_or_insert_with(Default::default)
+                .enable_coverage();
+        }
+
         #[cfg(feature = "luau")]
         if self.compiler.is_some() {
             // We don't need to compile source if no compiler set
@@ -451,9 +466,15 @@ impl Chunk<'_> {
         }
 
         let name = Self::convert_name(self.name)?;
-        self.lua
-            .lock()
-            .load_chunk(Some(&name), self.env?.as_ref(), self.mode, self.source?.as_ref())
+        let func =
+            self.lua
+                .lock()
+                .load_chunk(Some(&name), self.env?.as_ref(), self.mode, self.source?.as_ref())?;
+        #[cfg(feature = "luau")]
+        if let Some(coverage) = coverage {
+            coverage.lock().add_chunk(&name.to_string_lossy(), func.clone());
+        }
+        Ok(func)
     }
 
     /// Compiles the chunk and changes mode to binary.
@@ -524,10 +545,21 @@ impl Chunk<'_> {
         let source = self.source.as_ref();
         let source = source.map_err(Error::runtime)?;
         let source = Self::expression_source(source);
+        #[cfg(feature = "luau")]
+        let coverage = self.lua.lock().coverage();
+        #[cfg(feature = "luau")]
+        let mut compiler = Cow::Borrowed(&self.compiler);
+        #[cfg(feature = "luau")]
+        if coverage.is_some() {
+            compiler
+                .to_mut()
+                .get_or_insert_with(Default::default)
+                .enable_coverage();
+        }
         // We don't need to compile source if no compiler options set
         #[cfg(feature = "luau")]
-        let source = self
-            .compiler
+        let source = compiler
+            .as_ref()
             .as_ref()
             .map(|c| c.compile(&source))
             .transpose()?
@@ -539,7 +571,12 @@ impl Chunk<'_> {
             Ok(None) => None,
             Err(err) => return Err(err.clone()),
         };
-        self.lua.lock().load_chunk(Some(&name), env, None, &source)
+        let func = self.lua.lock().load_chunk(Some(&name), env, None, &source)?;
+        #[cfg(feature = "luau")]
+        if let Some(coverage) = coverage {
+            coverage.lock().add_chunk(&name.to_string_lossy(), func.clone());
+        }
+        Ok(func)
     }
 
     fn detect_mode(&self) -> ChunkMode {
@@ -17,6 +17,7 @@ use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
 use crate::traits::{FromLua, IntoLua, ShortTypeName as _};
+use crate::typedef::TypeDesc;
 use crate::types::{Either, LightUserData, MaybeSend, RegistryKey};
 use crate::userdata::{AnyUserData, UserData};
 use crate::value::{Nil, Value};
@@ -52,6 +53,11 @@ impl IntoLua for String {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::String(self))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for &String {
@@ -65,6 +71,11 @@ impl IntoLua for &String {
         lua.push_ref(&self.0);
         Ok(())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl FromLua for String {
@@ -89,6 +100,11 @@ impl FromLua for String {
         // Fallback to default
         Self::from_lua(lua.stack_value(idx, Some(type_id)), lua.lua())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for Table {
@@ -96,6 +112,11 @@ impl IntoLua for Table {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Table(self))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Table
+    }
 }
 
 impl IntoLua for &Table {
@@ -109,6 +130,11 @@ impl IntoLua for &Table {
         lua.push_ref(&self.0);
         Ok(())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Table
+    }
 }
 
 impl FromLua for Table {
@@ -123,6 +149,11 @@ impl FromLua for Table {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Table
+    }
 }
 
 impl IntoLua for Function {
@@ -130,6 +161,11 @@ impl IntoLua for Function {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Function(self))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Function
+    }
 }
 
 impl IntoLua for &Function {
@@ -143,6 +179,11 @@ impl IntoLua for &Function {
         lua.push_ref(&self.0);
         Ok(())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Function
+    }
 }
 
 impl FromLua for Function {
@@ -157,6 +198,11 @@ impl FromLua for Function {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Function
+    }
 }
 
 impl IntoLua for Thread {
@@ -164,6 +210,11 @@ impl IntoLua for Thread {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Thread(self))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Thread
+    }
 }
 
 impl IntoLua for &Thread {
@@ -177,6 +228,11 @@ impl IntoLua for &Thread {
         lua.push_ref(&self.0);
         Ok(())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Thread
+    }
 }
 
 impl FromLua for Thread {
@@ -191,6 +247,11 @@ impl FromLua for Thread {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Thread
+    }
 }
 
 impl IntoLua for AnyUserData {
@@ -232,6 +293,11 @@ impl<T: UserData + MaybeSend + 'static> IntoLua for T {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::UserData(lua.create_userdata(self)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::userdata::<T>()
+    }
 }
 
 impl IntoLua for Error {
@@ -310,6 +376,11 @@ impl IntoLua for bool {
         ffi::lua_pushboolean(lua.state(), self as c_int);
         Ok(())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Boolean
+    }
 }
 
 impl FromLua for bool {
@@ -326,6 +397,11 @@ impl FromLua for bool {
     unsafe fn from_stack(idx: c_int, lua: &RawLua) -> Result<Self> {
         Ok(ffi::lua_toboolean(lua.state(), idx) != 0)
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Boolean
+    }
 }
 
 impl IntoLua for LightUserData {
@@ -333,6 +409,11 @@ impl IntoLua for LightUserData {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::LightUserData(self))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::LightUserData
+    }
 }
 
 impl FromLua for LightUserData {
@@ -347,6 +428,11 @@ impl FromLua for LightUserData {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::LightUserData
+    }
 }
 
 #[cfg(feature = "luau")]
@@ -355,6 +441,11 @@ impl IntoLua for crate::Vector {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Vector(self))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Vector
+    }
 }
 
 #[cfg(feature = "luau")]
@@ -370,21 +461,33 @@ impl FromLua for crate::Vector {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Vector
+    }
 }
 
-#[cfg(feature = "luau")]
 impl IntoLua for crate::Buffer {
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
-        Ok(Value::Buffer(self))
+        #[cfg(feature = "luau")]
+        let value = Value::Buffer(self);
+        #[cfg(not(feature = "luau"))]
+        let value = Value::UserData(self.into_userdata());
+        Ok(value)
+    }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Buffer
     }
 }
 
-#[cfg(feature = "luau")]
 impl IntoLua for &crate::Buffer {
     #[inline]
-    fn into_lua(self, _: &Lua) -> Result<Value> {
-        Ok(Value::Buffer(self.clone()))
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        self.clone().into_lua(lua)
     }
 
     #[inline]
@@ -392,13 +495,22 @@ impl IntoLua for &crate::Buffer {
         lua.push_ref(&self.0);
         Ok(())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Buffer
+    }
 }
 
-#[cfg(feature = "luau")]
 impl FromLua for crate::Buffer {
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
+        #[cfg(not(feature = "luau"))]
+        if let Some(buf) = value.as_userdata().and_then(crate::Buffer::from_userdata) {
+            return Ok(buf);
+        }
         match value {
+            #[cfg(feature = "luau")]
             Value::Buffer(buf) => Ok(buf),
             _ => Err(Error::FromLuaConversionError {
                 from: value.type_name(),
@@ -407,6 +519,11 @@ impl FromLua for crate::Buffer {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Buffer
+    }
 }
 
 impl IntoLua for StdString {
@@ -419,6 +536,11 @@ impl IntoLua for StdString {
     unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
         push_bytes_into_stack(self, lua)
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl FromLua for StdString {
@@ -455,6 +577,11 @@ impl FromLua for StdString {
         // Fallback to default
         Self::from_lua(lua.stack_value(idx, Some(type_id)), lua.lua())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for &str {
@@ -467,6 +594,11 @@ impl IntoLua for &str {
     unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
         push_bytes_into_stack(self, lua)
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for Cow<'_, str> {
@@ -474,6 +606,11 @@ impl IntoLua for Cow<'_, str> {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.as_bytes())?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for Box<str> {
@@ -481,6 +618,11 @@ impl IntoLua for Box<str> {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(&*self)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl FromLua for Box<str> {
@@ -498,6 +640,11 @@ impl FromLua for Box<str> {
             .to_owned()
             .into_boxed_str())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for CString {
@@ -505,6 +652,11 @@ impl IntoLua for CString {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.as_bytes())?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl FromLua for CString {
@@ -528,6 +680,11 @@ impl FromLua for CString {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for &CStr {
@@ -535,6 +692,11 @@ impl IntoLua for &CStr {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.to_bytes())?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for Cow<'_, CStr> {
@@ -542,6 +704,11 @@ impl IntoLua for Cow<'_, CStr> {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.to_bytes())?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for BString {
@@ -549,6 +716,11 @@ impl IntoLua for BString {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl FromLua for BString {
@@ -557,7 +729,7 @@ impl FromLua for BString {
         match value {
             Value::String(s) => Ok((*s.as_bytes()).into()),
             #[cfg(feature = "luau")]
-            Value::Buffer(buf) => unsafe { Ok(buf.as_slice().into()) },
+            Value::Buffer(buf) => buf.with_slice(|data| data.into()),
             _ => Ok((*lua
                 .coerce_string(value)?
                 .ok_or_else(|| Error::FromLuaConversionError {
@@ -591,6 +763,11 @@ impl FromLua for BString {
             }
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for &BStr {
@@ -598,6 +775,11 @@ impl IntoLua for &BStr {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for OsString {
@@ -605,6 +787,11 @@ impl IntoLua for OsString {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         self.as_os_str().into_lua(lua)
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl FromLua for OsString {
@@ -620,6 +807,11 @@ impl FromLua for OsString {
                 message: Some(err.to_string()),
             })
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for &OsStr {
@@ -632,6 +824,11 @@ impl IntoLua for &OsStr {
         })?;
         Ok(Value::String(lua.create_string(s)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for PathBuf {
@@ -639,6 +836,11 @@ impl IntoLua for PathBuf {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         self.as_os_str().into_lua(lua)
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl FromLua for PathBuf {
@@ -646,6 +848,11 @@ impl FromLua for PathBuf {
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         OsString::from_lua(value, lua).map(PathBuf::from)
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 impl IntoLua for &Path {
@@ -653,6 +860,11 @@ impl IntoLua for &Path {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         self.as_os_str().into_lua(lua)
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::String
+    }
 }
 
 #[inline]
@@ -694,6 +906,11 @@ macro_rules! lua_convert_int {
                 }
                 Ok(())
             }
+
+            #[inline]
+            fn lua_type() -> TypeDesc {
+                TypeDesc::Integer
+            }
         }
 
         impl FromLua for $x {
@@ -744,6 +961,11 @@ macro_rules! lua_convert_int {
                 // Fallback to default
                 Self::from_lua(lua.stack_value(idx, Some(type_id)), lua.lua())
             }
+
+            #[inline]
+            fn lua_type() -> TypeDesc {
+                TypeDesc::Integer
+            }
         }
     };
 }
@@ -774,6 +996,11 @@ macro_rules! lua_convert_float {
                     })
                     .map(Value::Number)
             }
+
+            #[inline]
+            fn lua_type() -> TypeDesc {
+                TypeDesc::Number
+            }
         }
 
         impl FromLua for $x {
@@ -812,6 +1039,11 @@ macro_rules! lua_convert_float {
                 // Fallback to default
                 Self::from_lua(lua.stack_value(idx, Some(type_id)), lua.lua())
             }
+
+            #[inline]
+            fn lua_type() -> TypeDesc {
+                TypeDesc::Number
+            }
         }
     };
 }
@@ -827,6 +1059,11 @@ where
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_sequence_from(self.iter().cloned())?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Array(Box::new(T::lua_type()))
+    }
 }
 
 impl<T, const N: usize> IntoLua for [T; N]
@@ -837,6 +1074,11 @@ where
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_sequence_from(self)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Array(Box::new(T::lua_type()))
+    }
 }
 
 impl<T, const N: usize> FromLua for [T; N]
@@ -874,6 +1116,11 @@ where
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Array(Box::new(T::lua_type()))
+    }
 }
 
 impl<T: IntoLua> IntoLua for Box<[T]> {
@@ -881,6 +1128,11 @@ impl<T: IntoLua> IntoLua for Box<[T]> {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_sequence_from(self.into_vec())?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Array(Box::new(T::lua_type()))
+    }
 }
 
 impl<T: FromLua> FromLua for Box<[T]> {
@@ -888,6 +1140,11 @@ impl<T: FromLua> FromLua for Box<[T]> {
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         Ok(Vec::<T>::from_lua(value, lua)?.into_boxed_slice())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Array(Box::new(T::lua_type()))
+    }
 }
 
 impl<T: IntoLua> IntoLua for Vec<T> {
@@ -895,6 +1152,11 @@ impl<T: IntoLua> IntoLua for Vec<T> {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_sequence_from(self)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Array(Box::new(T::lua_type()))
+    }
 }
 
 impl<T: FromLua> FromLua for Vec<T> {
@@ -909,6 +1171,11 @@ impl<T: FromLua> FromLua for Vec<T> {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Array(Box::new(T::lua_type()))
+    }
 }
 
 impl<K: Eq + Hash + IntoLua, V: IntoLua, S: BuildHasher> IntoLua for HashMap<K, V, S> {
@@ -916,6 +1183,11 @@ impl<K: Eq + Hash + IntoLua, V: IntoLua, S: BuildHasher> IntoLua for HashMap<K,
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_table_from(self)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Map(Box::new(K::lua_type()), Box::new(V::lua_type()))
+    }
 }
 
 impl<K: Eq + Hash + FromLua, V: FromLua, S: BuildHasher + Default> FromLua for HashMap<K, V, S> {
@@ -931,6 +1203,11 @@ impl<K: Eq + Hash + FromLua, V: FromLua, S: BuildHasher + Default> FromLua for H
             })
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Map(Box::new(K::lua_type()), Box::new(V::lua_type()))
+    }
 }
 
 impl<K: Ord + IntoLua, V: IntoLua> IntoLua for BTreeMap<K, V> {
@@ -938,6 +1215,11 @@ impl<K: Ord + IntoLua, V: IntoLua> IntoLua for BTreeMap<K, V> {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_table_from(self)?))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Map(Box::new(K::lua_type()), Box::new(V::lua_type()))
+    }
 }
 
 impl<K: Ord + FromLua, V: FromLua> FromLua for BTreeMap<K, V> {
@@ -953,6 +1235,11 @@ impl<K: Ord + FromLua, V: FromLua> FromLua for BTreeMap<K, V> {
             })
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Map(Box::new(K::lua_type()), Box::new(V::lua_type()))
+    }
 }
 
 impl<T: Eq + Hash + IntoLua, S: BuildHasher> IntoLua for HashSet<T, S> {
@@ -962,6 +1249,11 @@ impl<T: Eq + Hash + IntoLua, S: BuildHasher> IntoLua for HashSet<T, S> {
             lua.create_table_from(self.into_iter().map(|val| (val, true)))?,
         ))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Map(Box::new(T::lua_type()), Box::new(TypeDesc::Boolean))
+    }
 }
 
 impl<T: Eq + Hash + FromLua, S: BuildHasher + Default> FromLua for HashSet<T, S> {
@@ -977,6 +1269,11 @@ impl<T: Eq + Hash + FromLua, S: BuildHasher + Default> FromLua for HashSet<T, S>
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Map(Box::new(T::lua_type()), Box::new(TypeDesc::Boolean))
+    }
 }
 
 impl<T: Ord + IntoLua> IntoLua for BTreeSet<T> {
@@ -986,6 +1283,11 @@ impl<T: Ord + IntoLua> IntoLua for BTreeSet<T> {
             lua.create_table_from(self.into_iter().map(|val| (val, true)))?,
         ))
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Map(Box::new(T::lua_type()), Box::new(TypeDesc::Boolean))
+    }
 }
 
 impl<T: Ord + FromLua> FromLua for BTreeSet<T> {
@@ -1001,6 +1303,11 @@ impl<T: Ord + FromLua> FromLua for BTreeSet<T> {
             }),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Map(Box::new(T::lua_type()), Box::new(TypeDesc::Boolean))
+    }
 }
 
 impl<T: IntoLua> IntoLua for Option<T> {
@@ -1020,6 +1327,11 @@ impl<T: IntoLua> IntoLua for Option<T> {
         }
         Ok(())
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        T::lua_type().optional()
+    }
 }
 
 impl<T: FromLua> FromLua for Option<T> {
@@ -1038,6 +1350,11 @@ impl<T: FromLua> FromLua for Option<T> {
             _ => Ok(Some(T::from_stack(idx, lua)?)),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        T::lua_type().optional()
+    }
 }
 
 impl<L: IntoLua, R: IntoLua> IntoLua for Either<L, R> {
@@ -1056,6 +1373,11 @@ impl<L: IntoLua, R: IntoLua> IntoLua for Either<L, R> {
             Either::Right(r) => r.push_into_stack(lua),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Union(vec![L::lua_type(), R::lua_type()])
+    }
 }
 
 impl<L: FromLua, R: FromLua> FromLua for Either<L, R> {
@@ -1094,4 +1416,9 @@ impl<L: FromLua, R: FromLua> FromLua for Either<L, R> {
             },
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Union(vec![L::lua_type(), R::lua_type()])
+    }
 }
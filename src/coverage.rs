@@ -0,0 +1,293 @@
+//! Line coverage collection for Lua code.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::string::String as StdString;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+
+use crate::state::WeakLua;
+use crate::types::XRc;
+use crate::util::xml_escape;
+
+#[cfg(not(feature = "luau"))]
+use {
+    crate::hook::{Debug, DebugEvent, HookHandle},
+    std::collections::HashSet,
+};
+
+#[cfg(feature = "luau")]
+use crate::function::Function;
+
+/// Line coverage collection started by [`Lua::start_coverage`].
+///
+/// The collection stops when [`Coverage::stop`] is called or the value is dropped.
+///
+/// [`Lua::start_coverage`]: crate::Lua::start_coverage
+pub struct Coverage {
+    #[cfg_attr(not(feature = "luau"), allow(unused))]
+    lua: WeakLua,
+    data: XRc<Mutex<CoverageData>>,
+    #[cfg(not(feature = "luau"))]
+    hook: HookHandle,
+    running: AtomicBool,
+}
+
+/// Line coverage of a single source file.
+#[derive(Clone, Debug, Default)]
+pub struct FileCoverage {
+    /// Path of the source file, as derived from the chunk name.
+    pub path: StdString,
+    /// Hit counts of the lines that contain code.
+    pub lines: BTreeMap<usize, u64>,
+}
+
+/// A line coverage report, which can be exported to the LCOV or Cobertura formats.
+#[derive(Clone, Debug, Default)]
+pub struct CoverageReport {
+    /// Covered files, sorted by path.
+    pub files: Vec<FileCoverage>,
+}
+
+impl Coverage {
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn new(lua: WeakLua, data: XRc<Mutex<CoverageData>>, hook: HookHandle) -> Self {
+        Coverage {
+            lua,
+            data,
+            hook,
+            running: AtomicBool::new(true),
+        }
+    }
+
+    #[cfg(feature = "luau")]
+    pub(crate) fn new(lua: WeakLua, data: XRc<Mutex<CoverageData>>) -> Self {
+        Coverage {
+            lua,
+            data,
+            running: AtomicBool::new(true),
+        }
+    }
+
+    /// Stops collecting coverage.
+    ///
+    /// The collected data stays available.
+    pub fn stop(&self) {
+        if !self.running.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        #[cfg(not(feature = "luau"))]
+        self.hook.remove();
+        #[cfg(feature = "luau")]
+        {
+            let chunks = std::mem::take(&mut self.data.lock().chunks);
+            if let Some(lua) = self.lua.try_lock() {
+                unsafe { lua.remove_coverage(&self.data) };
+                // Take a final snapshot of the counters
+                let lines = chunks_coverage(&chunks);
+                let mut data = self.data.lock();
+                for (path, lines) in lines {
+                    merge_lines(data.lines.entry(path).or_default(), lines);
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the coverage is being collected.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// Returns the line coverage collected so far.
+    pub fn report(&self) -> CoverageReport {
+        #[cfg(not(feature = "luau"))]
+        let lines = self.data.lock().lines.clone();
+        #[cfg(feature = "luau")]
+        let lines = {
+            let (mut lines, chunks) = {
+                let data = self.data.lock();
+                (data.lines.clone(), data.chunks.clone())
+            };
+            for (path, chunk_lines) in chunks_coverage(&chunks) {
+                merge_lines(lines.entry(path).or_default(), chunk_lines);
+            }
+            lines
+        };
+
+        let mut files = (lines.into_iter())
+            .map(|(path, lines)| FileCoverage { path, lines })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        CoverageReport { files }
+    }
+}
+
+impl Drop for Coverage {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl FileCoverage {
+    /// Returns the number of lines that were executed at least once.
+    pub fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|&&hits| hits > 0).count()
+    }
+}
+
+impl CoverageReport {
+    /// Returns the report in the [LCOV] tracefile format.
+    ///
+    /// [LCOV]: https://github.com/linux-test-project/lcov
+    pub fn to_lcov(&self) -> StdString {
+        let mut output = StdString::new();
+        for file in &self.files {
+            _ = writeln!(output, "TN:\nSF:{}", file.path);
+            for (line, hits) in &file.lines {
+                _ = writeln!(output, "DA:{line},{hits}");
+            }
+            _ = writeln!(output, "LF:{}\nLH:{}", file.lines.len(), file.lines_hit());
+            output.push_str("end_of_record\n");
+        }
+        output
+    }
+
+    /// Returns the report in the [Cobertura] XML format.
+    ///
+    /// Each file is represented as a class of a single package.
+    ///
+    /// [Cobertura]: https://cobertura.github.io/cobertura/
+    pub fn to_cobertura(&self) -> StdString {
+        let line_rate = |hit: usize, found: usize| match found {
+            0 => 1.0,
+            _ => hit as f64 / found as f64,
+        };
+        let found = self.files.iter().map(|f| f.lines.len()).sum::<usize>();
+        let hit = self.files.iter().map(|f| f.lines_hit()).sum::<usize>();
+        let rate = line_rate(hit, found);
+
+        let mut output = StdString::new();
+        output.push_str("<?xml version=\"1.0\" ?>\n");
+        output.push_str(
+            "<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">\n",
+        );
+        _ = writeln!(
+            output,
+            "<coverage line-rate=\"{rate:.4}\" branch-rate=\"0\" lines-covered=\"{hit}\" \
+             lines-valid=\"{found}\" branches-covered=\"0\" branches-valid=\"0\" complexity=\"0\" \
+             version=\"mlua\" timestamp=\"0\">"
+        );
+        output.push_str("  <sources>\n    <source>.</source>\n  </sources>\n");
+        output.push_str("  <packages>\n");
+        _ = writeln!(
+            output,
+            "    <package name=\"lua\" line-rate=\"{rate:.4}\" branch-rate=\"0\" complexity=\"0\">"
+        );
+        output.push_str("      <classes>\n");
+        for file in &self.files {
+            let path = xml_escape(&file.path);
+            let rate = line_rate(file.lines_hit(), file.lines.len());
+            _ = writeln!(
+                output,
+                "        <class name=\"{path}\" filename=\"{path}\" line-rate=\"{rate:.4}\" \
+                 branch-rate=\"0\" complexity=\"0\">"
+            );
+            output.push_str("          <methods/>\n          <lines>\n");
+            for (line, hits) in &file.lines {
+                _ = writeln!(output, "            <line number=\"{line}\" hits=\"{hits}\"/>");
+            }
+            output.push_str("          </lines>\n        </class>\n");
+        }
+        output.push_str("      </classes>\n    </package>\n  </packages>\n</coverage>\n");
+        output
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct CoverageData {
+    // Hit counts per file and line
+    lines: HashMap<StdString, BTreeMap<usize, u64>>,
+    // Functions which lines with code are already known (by source and line defined)
+    #[cfg(not(feature = "luau"))]
+    functions: HashSet<(StdString, usize)>,
+    // Chunks compiled with coverage support
+    #[cfg(feature = "luau")]
+    chunks: Vec<(StdString, Function)>,
+}
+
+impl CoverageData {
+    /// Handles a hook event (Lua 5.x).
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn on_hook(&mut self, debug: &Debug) {
+        let source = debug.source();
+        let Some(src) = source.source.as_deref() else {
+            return;
+        };
+        if source.what == "C" {
+            return;
+        }
+        match debug.event() {
+            DebugEvent::Call | DebugEvent::TailCall => {
+                let key = (src.to_string(), source.line_defined.unwrap_or(0));
+                if !self.functions.contains(&key) {
+                    // Record lines that were not executed yet
+                    let lines = self.lines.entry(file_path(src).to_string()).or_default();
+                    for line in debug.active_lines() {
+                        lines.entry(line).or_insert(0);
+                    }
+                    self.functions.insert(key);
+                }
+            }
+            DebugEvent::Line => {
+                let Ok(line) = usize::try_from(debug.curr_line()) else {
+                    return;
+                };
+                let path = file_path(src);
+                let lines = match self.lines.get_mut(path) {
+                    Some(lines) => lines,
+                    None => self.lines.entry(path.to_string()).or_default(),
+                };
+                *lines.entry(line).or_insert(0) += 1;
+            }
+            _ => {}
+        }
+    }
+
+    /// Registers a chunk compiled with coverage support (Luau).
+    #[cfg(feature = "luau")]
+    pub(crate) fn add_chunk(&mut self, name: &str, func: Function) {
+        self.chunks.push((file_path(name).to_string(), func));
+    }
+}
+
+// Collects hit counts of the chunks (Luau)
+#[cfg(feature = "luau")]
+fn chunks_coverage(chunks: &[(StdString, Function)]) -> HashMap<StdString, BTreeMap<usize, u64>> {
+    let mut files = HashMap::<StdString, BTreeMap<usize, u64>>::new();
+    for (path, func) in chunks {
+        let lines = files.entry(path.clone()).or_default();
+        func.coverage(|info| {
+            // Lines without code have negative hit counts
+            for (line, &hits) in info.hits.iter().enumerate() {
+                if let Ok(hits) = u64::try_from(hits) {
+                    *lines.entry(line).or_insert(0) += hits;
+                }
+            }
+        });
+    }
+    files
+}
+
+#[cfg(feature = "luau")]
+fn merge_lines(lines: &mut BTreeMap<usize, u64>, other: BTreeMap<usize, u64>) {
+    for (line, hits) in other {
+        *lines.entry(line).or_insert(0) += hits;
+    }
+}
+
+// Converts a chunk name to a file path
+fn file_path(source: &str) -> &str {
+    let path = source.strip_prefix(['@', '=']).unwrap_or(source);
+    path.lines().next().unwrap_or_default()
+}
@@ -0,0 +1,566 @@
+//! Debug Adapter Protocol server.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+use std::path::Path;
+use std::string::String as StdString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use parking_lot::Mutex;
+
+use super::{BreakpointId, DebugAction, DebugContext, Debugger, PauseReason};
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::XRc;
+use crate::util::json::{json_object, Json};
+use crate::value::Value;
+
+// The debugger is attached to a single Lua thread
+const THREAD_ID: i64 = 1;
+
+/// A [Debug Adapter Protocol] server for the [`Debugger`].
+///
+/// The server allows debugger frontends (eg. VS Code) to attach to a process embedding Lua, set
+/// breakpoints, step through the code, inspect variables and evaluate expressions in a stack frame.
+///
+/// Chunks loaded from files (eg. using `lua.load(Path::new("script.lua"))`) are named after the file
+/// path and mapped back to the source files. Relative paths are resolved against the current
+/// directory.
+///
+/// All requests are handled on the thread executing Lua code: while the code is paused, in the
+/// line hook while the code is running, or by calling [`DapServer::process_requests`] when Lua is
+/// idle.
+///
+/// Requires `feature = "dap"`
+///
+/// # Examples
+///
+/// ```no_run
+/// # use mlua::{Lua, Result};
+/// # use mlua::debugger::DapServer;
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let server = DapServer::listen("127.0.0.1:4711")?;
+/// server.attach(&lua);
+/// server.wait_for_configuration(&lua)?;
+///
+/// lua.load(std::path::Path::new("main.lua")).exec()?;
+/// server.terminate();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [Debug Adapter Protocol]: https://microsoft.github.io/debug-adapter-protocol/
+pub struct DapServer {
+    debugger: Debugger,
+    session: XRc<Session>,
+}
+
+struct Session {
+    requests: Mutex<Receiver<Json>>,
+    writer: Mutex<Writer>,
+    configured: AtomicBool,
+    disconnected: AtomicBool,
+    // Breakpoints set for each source path
+    breakpoints: Mutex<HashMap<StdString, Vec<BreakpointId>>>,
+    // Variable containers referenced by the client (valid while paused)
+    variables: Mutex<Vec<Variables>>,
+}
+
+struct Writer {
+    output: Box<dyn Write + Send>,
+    seq: i64,
+}
+
+#[derive(Clone)]
+enum Variables {
+    Locals(usize),
+    Upvalues(usize),
+    Table(Table),
+}
+
+impl DapServer {
+    /// Creates a new server communicating over the given reader and writer.
+    ///
+    /// Messages are read in a background thread.
+    pub fn new<R, W>(reader: R, writer: W) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let session = XRc::new(Session {
+            requests: Mutex::new(rx),
+            writer: Mutex::new(Writer {
+                output: Box::new(writer),
+                seq: 0,
+            }),
+            configured: AtomicBool::new(false),
+            disconnected: AtomicBool::new(false),
+            breakpoints: Mutex::new(HashMap::new()),
+            variables: Mutex::new(Vec::new()),
+        });
+
+        let debugger = Debugger::new({
+            let session = session.clone();
+            move |lua, ctx| Ok(session.on_pause(lua, ctx))
+        });
+        debugger.set_line_callback(Box::new({
+            let session = session.clone();
+            move |lua, debugger| {
+                session.process_pending(lua, debugger);
+                Ok(())
+            }
+        }));
+
+        DapServer { debugger, session }
+    }
+
+    /// Waits for a client to connect to the listener and creates a new server for the connection.
+    pub fn accept(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        let _ = stream.set_nodelay(true);
+        Ok(Self::new(stream.try_clone()?, stream))
+    }
+
+    /// Listens on the given address and waits for a client to connect.
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        Self::accept(&listener)
+    }
+
+    /// Creates a new server communicating over the standard input and output.
+    pub fn stdio() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+
+    /// Returns the underlying debugger.
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    /// Attaches the debugger to the main thread of the Lua instance.
+    ///
+    /// See [`Debugger::attach`] for details.
+    pub fn attach(&self, lua: &Lua) {
+        self.debugger.attach(lua);
+    }
+
+    /// Handles the client requests until the client finishes configuration (sets initial
+    /// breakpoints).
+    ///
+    /// Returns an error if the client disconnects.
+    pub fn wait_for_configuration(&self, lua: &Lua) -> Result<()> {
+        while !self.session.configured.load(Ordering::Acquire) {
+            let request = self.session.requests.lock().recv();
+            let request = request.map_err(|_| Error::runtime("debug adapter client disconnected"))?;
+            self.session.handle(lua, &self.debugger, None, &request);
+        }
+        Ok(())
+    }
+
+    /// Handles pending client requests without blocking.
+    ///
+    /// Requests are handled automatically while Lua code is running, this method should be called
+    /// periodically when Lua is idle.
+    pub fn process_requests(&self, lua: &Lua) {
+        self.session.process_pending(lua, &self.debugger);
+    }
+
+    /// Notifies the client that the debugging session has finished.
+    pub fn terminate(&self) {
+        self.session.send_event("terminated", json_object! {});
+    }
+}
+
+impl Session {
+    fn process_pending(&self, lua: &Lua, debugger: &Debugger) {
+        loop {
+            let request = self.requests.lock().try_recv();
+            match request {
+                Ok(request) => _ = self.handle(lua, debugger, None, &request),
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn on_pause(&self, lua: &Lua, ctx: &DebugContext) -> DebugAction {
+        if self.disconnected.load(Ordering::Acquire) {
+            return DebugAction::Continue;
+        }
+
+        let (reason, hit_breakpoints) = match ctx.reason() {
+            PauseReason::Breakpoint(id) => ("breakpoint", vec![id.0]),
+            PauseReason::Step => ("step", vec![]),
+            PauseReason::Pause => ("pause", vec![]),
+        };
+        self.send_event(
+            "stopped",
+            json_object! {
+                "reason": reason,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+                "hitBreakpointIds": hit_breakpoints,
+            },
+        );
+
+        let action = loop {
+            let request = self.requests.lock().recv();
+            let Ok(request) = request else {
+                // Client disconnected
+                self.disconnected.store(true, Ordering::Release);
+                break DebugAction::Continue;
+            };
+            if let Some(action) = self.handle(lua, ctx.debugger(), Some(ctx), &request) {
+                break action;
+            }
+        };
+        self.variables.lock().clear();
+        action
+    }
+
+    // Handles a client request and returns the action to resume execution (if requested)
+    fn handle(
+        &self,
+        lua: &Lua,
+        debugger: &Debugger,
+        ctx: Option<&DebugContext>,
+        request: &Json,
+    ) -> Option<DebugAction> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let paused = || ctx.ok_or_else(|| Error::runtime("execution is not paused"));
+
+        let mut action = None;
+        let result = match command {
+            "initialize" => Ok(json_object! {
+                "supportsConfigurationDoneRequest": true,
+                "supportsEvaluateForHovers": true,
+                "supportsSetVariable": true,
+            }),
+            "launch" | "attach" | "setExceptionBreakpoints" => Ok(json_object! {}),
+            "configurationDone" => {
+                self.configured.store(true, Ordering::Release);
+                Ok(json_object! {})
+            }
+            "setBreakpoints" => self.set_breakpoints(debugger, args),
+            "threads" => Ok(json_object! {
+                "threads": vec![json_object! { "id": THREAD_ID, "name": "main" }],
+            }),
+            "stackTrace" => paused().map(stack_trace),
+            "scopes" => paused().and_then(|ctx| self.scopes(ctx, args)),
+            "variables" => paused().and_then(|ctx| self.variables(ctx, args)),
+            "setVariable" => paused().and_then(|ctx| self.set_variable(lua, ctx, args)),
+            "evaluate" => paused().and_then(|ctx| {
+                let level = frame_level(&args["frameId"]).unwrap_or(0);
+                let expression = args["expression"].as_str().unwrap_or_default();
+                let value = evaluate(lua, ctx, level, expression)?;
+                let variable = self.variable(StdString::new(), value);
+                Ok(json_object! {
+                    "result": variable["value"].clone(),
+                    "type": variable["type"].clone(),
+                    "variablesReference": variable["variablesReference"].clone(),
+                })
+            }),
+            "continue" => {
+                action = Some(DebugAction::Continue);
+                Ok(json_object! { "allThreadsContinued": true })
+            }
+            "next" | "stepIn" | "stepOut" => {
+                action = Some(match command {
+                    "next" => DebugAction::StepOver,
+                    "stepIn" => DebugAction::StepIn,
+                    _ => DebugAction::StepOut,
+                });
+                Ok(json_object! {})
+            }
+            "pause" => {
+                debugger.pause();
+                Ok(json_object! {})
+            }
+            "disconnect" | "terminate" => {
+                for (_, ids) in self.breakpoints.lock().drain() {
+                    ids.into_iter().for_each(|id| _ = debugger.remove_breakpoint(id));
+                }
+                self.disconnected.store(true, Ordering::Release);
+                action = Some(DebugAction::Continue);
+                Ok(json_object! {})
+            }
+            _ => Err(Error::runtime(format!("unsupported request '{command}'"))),
+        };
+
+        self.respond(request, result);
+        if command == "initialize" {
+            self.send_event("initialized", json_object! {});
+        }
+        action
+    }
+
+    fn set_breakpoints(&self, debugger: &Debugger, args: &Json) -> Result<Json> {
+        let path = (args["source"]["path"].as_str())
+            .ok_or_else(|| Error::runtime("breakpoint source path is required"))?;
+        let lines = match args["breakpoints"].as_array() {
+            Some(breakpoints) => breakpoints.iter().map(|bp| &bp["line"]).collect::<Vec<_>>(),
+            None => (args["lines"].as_array())
+                .map(|lines| lines.iter().collect())
+                .unwrap_or_default(),
+        };
+
+        let mut breakpoints = self.breakpoints.lock();
+        for id in breakpoints.remove(path).unwrap_or_default() {
+            debugger.remove_breakpoint(id);
+        }
+        let mut ids = Vec::new();
+        let mut result = Vec::new();
+        for line in lines {
+            match line.as_u64() {
+                Some(line) => {
+                    let id = debugger.add_breakpoint(path, line as usize);
+                    ids.push(id);
+                    result.push(json_object! { "id": id.0, "verified": true, "line": line });
+                }
+                None => result.push(json_object! { "verified": false, "message": "invalid line" }),
+            }
+        }
+        breakpoints.insert(path.to_string(), ids);
+        Ok(json_object! { "breakpoints": result })
+    }
+
+    fn scopes(&self, ctx: &DebugContext, args: &Json) -> Result<Json> {
+        let level = frame_level(&args["frameId"])?;
+        ctx.locals(level)?; // Check that the frame exists
+        Ok(json_object! {
+            "scopes": vec![
+                json_object! {
+                    "name": "Locals",
+                    "presentationHint": "locals",
+                    "variablesReference": self.add_variables(Variables::Locals(level)),
+                    "expensive": false,
+                },
+                json_object! {
+                    "name": "Upvalues",
+                    "variablesReference": self.add_variables(Variables::Upvalues(level)),
+                    "expensive": false,
+                },
+            ],
+        })
+    }
+
+    fn variables(&self, ctx: &DebugContext, args: &Json) -> Result<Json> {
+        let variables = match self.get_variables(&args["variablesReference"])? {
+            Variables::Locals(level) => (ctx.locals(level)?.into_iter())
+                .map(|var| self.variable(var.name, var.value))
+                .collect(),
+            Variables::Upvalues(level) => (ctx.upvalues(level)?.into_iter())
+                .map(|var| self.variable(var.name, var.value))
+                .collect(),
+            Variables::Table(table) => {
+                let mut variables = Vec::new();
+                for pair in table.pairs::<Value, Value>() {
+                    let (key, value) = pair?;
+                    let name = match key {
+                        Value::String(s) => s.to_string_lossy(),
+                        key => format!("[{}]", display_value(&key)),
+                    };
+                    variables.push(self.variable(name, value));
+                }
+                variables
+            }
+        };
+        Ok(json_object! { "variables": variables })
+    }
+
+    fn set_variable(&self, lua: &Lua, ctx: &DebugContext, args: &Json) -> Result<Json> {
+        let variables = self.get_variables(&args["variablesReference"])?;
+        let name = args["name"].as_str().unwrap_or_default();
+        let expression = args["value"].as_str().unwrap_or_default();
+        let value = match &variables {
+            Variables::Locals(level) | Variables::Upvalues(level) => evaluate(lua, ctx, *level, expression)?,
+            Variables::Table(_) => evaluate(lua, ctx, 0, expression)?,
+        };
+        match &variables {
+            Variables::Locals(level) => ctx.set_local(*level, name, value.clone())?,
+            Variables::Upvalues(level) => ctx.set_upvalue(*level, name, value.clone())?,
+            Variables::Table(table) => match name.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                Some(key) => table.set(evaluate(lua, ctx, 0, key)?, value.clone())?,
+                None => table.set(name, value.clone())?,
+            },
+        }
+        let mut variable = self.variable(name.to_string(), value);
+        variable.remove("name");
+        Ok(variable)
+    }
+
+    fn variable(&self, name: StdString, value: Value) -> Json {
+        let reference = match &value {
+            Value::Table(table) => self.add_variables(Variables::Table(table.clone())),
+            _ => 0,
+        };
+        json_object! {
+            "name": name,
+            "value": display_value(&value),
+            "type": value.type_name(),
+            "variablesReference": reference,
+        }
+    }
+
+    fn add_variables(&self, variables: Variables) -> usize {
+        let mut vars = self.variables.lock();
+        vars.push(variables);
+        vars.len()
+    }
+
+    fn get_variables(&self, reference: &Json) -> Result<Variables> {
+        let index = (reference.as_u64().and_then(|r| r.checked_sub(1))).map(|i| i as usize);
+        let variables = index.and_then(|i| self.variables.lock().get(i).cloned());
+        variables.ok_or_else(|| Error::runtime("invalid variables reference"))
+    }
+
+    fn respond(&self, request: &Json, result: Result<Json>) {
+        let mut response = json_object! {
+            "type": "response",
+            "request_seq": request["seq"].clone(),
+            "command": request["command"].clone(),
+            "success": result.is_ok(),
+        };
+        match result {
+            Ok(body) => response.set("body", body),
+            Err(err) => response.set("message", err.to_string()),
+        }
+        self.send(response);
+    }
+
+    fn send_event(&self, event: &str, body: Json) {
+        self.send(json_object! { "type": "event", "event": event, "body": body });
+    }
+
+    fn send(&self, mut message: Json) {
+        let mut writer = self.writer.lock();
+        writer.seq += 1;
+        message.set("seq", writer.seq);
+        let body = message.to_string();
+        let output = &mut writer.output;
+        let result =
+            (write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())).and_then(|_| output.flush());
+        if result.is_err() {
+            // Client is gone, keep running the code
+            self.disconnected.store(true, Ordering::Release);
+        }
+    }
+}
+
+fn stack_trace(ctx: &DebugContext) -> Json {
+    let frames = ctx.frames();
+    let stack_frames = (frames.iter())
+        .map(|frame| {
+            let name = match (&frame.name, frame.what) {
+                (Some(name), _) => name.as_str(),
+                (None, "main") => "main chunk",
+                (None, _) => "?",
+            };
+            let mut stack_frame = json_object! {
+                "id": frame.level + 1,
+                "name": name,
+                "line": frame.line.unwrap_or(0),
+                "column": frame.line.map(|_| 1).unwrap_or(0),
+            };
+            if let Some(path) = frame.source.as_deref().and_then(|s| s.strip_prefix('@')) {
+                stack_frame.set("source", source(path));
+            }
+            stack_frame
+        })
+        .collect::<Vec<_>>();
+    json_object! { "stackFrames": stack_frames, "totalFrames": frames.len() }
+}
+
+// Maps chunk name to the source file
+fn source(path: &str) -> Json {
+    let path = Path::new(path);
+    let full_path = match std::env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    };
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    json_object! {
+        "name": name.to_string_lossy().into_owned(),
+        "path": full_path.to_string_lossy().into_owned(),
+    }
+}
+
+fn frame_level(frame_id: &Json) -> Result<usize> {
+    (frame_id.as_u64().and_then(|id| id.checked_sub(1)))
+        .map(|level| level as usize)
+        .ok_or_else(|| Error::runtime("invalid frame id"))
+}
+
+// Evaluates expression (or statement) with access to the frame variables
+fn evaluate(lua: &Lua, ctx: &DebugContext, level: usize, expression: &str) -> Result<Value> {
+    let env = lua.create_table()?;
+    // Locals shadow upvalues
+    for var in ctx.upvalues(level)?.into_iter().chain(ctx.locals(level)?) {
+        env.raw_set(var.name, var.value)?;
+    }
+    let mt = lua.create_table()?;
+    mt.raw_set("__index", lua.globals())?;
+    env.set_metatable(Some(mt));
+
+    let chunk = lua.load(format!("return {expression}"));
+    let func = match chunk
+        .set_name("=(eval)")
+        .set_environment(env.clone())
+        .into_function()
+    {
+        Ok(func) => func,
+        Err(Error::SyntaxError { .. }) => (lua.load(expression))
+            .set_name("=(eval)")
+            .set_environment(env)
+            .into_function()?,
+        Err(err) => return Err(err),
+    };
+    func.call(())
+}
+
+fn display_value(value: &Value) -> StdString {
+    match value {
+        Value::String(s) => format!("{:?}", s.to_string_lossy()),
+        value => {
+            (value.to_string()).unwrap_or_else(|_| format!("{}: {:?}", value.type_name(), value.to_pointer()))
+        }
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut content_length = None;
+    loop {
+        let mut line = StdString::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim_end() {
+            "" if content_length.is_some() => break,
+            "" => continue,
+            line => {
+                if let Some(len) = line.strip_prefix("Content-Length:") {
+                    content_length = len.trim().parse::<usize>().ok();
+                }
+            }
+        }
+    }
+    let mut body = vec![0; content_length.unwrap_or_default()];
+    reader.read_exact(&mut body)?;
+    let message = Json::parse(&body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(Some(message))
+}
@@ -0,0 +1,633 @@
+//! Interactive step debugger (Lua 5.x).
+//!
+//! The [`Debugger`] is built on top of [`Lua::set_hook`] and provides breakpoints, stepping
+//! (in/over/out) and access to the call stack frames of the paused code, including reading and
+//! writing of local variables and upvalues.
+//!
+//! When execution is paused (by a breakpoint, a finished step or a [`Debugger::pause`] request)
+//! the pause handler is called with a [`DebugContext`]. The handler returns a [`DebugAction`] that
+//! tells the debugger how to continue.
+//!
+//! # Synchronous and asynchronous execution
+//!
+//! A handler can block until the user decides how to continue (eg. waiting for a command from a
+//! debugger frontend) and then return the action. This works for any Lua code.
+//!
+//! Alternatively a handler can return [`DebugAction::Suspend`] to yield the running coroutine
+//! ([`VmState::Yield`]) and return control to the host. The thread stays paused until
+//! [`Debugger::resume`] is called, after which it can be resumed again. For [`AsyncThread`] this
+//! happens automatically: the future returns `Poll::Pending` and is woken up by
+//! [`Debugger::resume`]. Suspending requires Lua 5.3 or 5.4 and code running in a coroutine.
+//!
+//! [`Lua::set_hook`]: crate::Lua::set_hook
+//! [`VmState::Yield`]: crate::VmState::Yield
+//! [`AsyncThread`]: crate::AsyncThread
+
+use std::fmt;
+use std::mem;
+use std::os::raw::c_int;
+use std::string::String as StdString;
+
+#[cfg(feature = "async")]
+use std::task::Waker;
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::hook::{Debug, HookTriggers};
+use crate::state::Lua;
+use crate::thread::{Thread, ThreadStatus};
+use crate::types::{MaybeSend, VmState, XRc};
+use crate::util::{check_stack, linenumber_to_usize, ptr_to_lossy_str, ptr_to_str, StackGuard};
+use crate::value::Value;
+
+#[cfg(feature = "dap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dap")))]
+pub use dap::DapServer;
+
+#[cfg(feature = "dap")]
+mod dap;
+
+#[cfg(feature = "send")]
+type PauseHandler = Box<dyn Fn(&Lua, &DebugContext) -> Result<DebugAction> + Send>;
+
+#[cfg(not(feature = "send"))]
+type PauseHandler = Box<dyn Fn(&Lua, &DebugContext) -> Result<DebugAction>>;
+
+#[cfg(all(feature = "dap", feature = "send"))]
+type LineCallback = Box<dyn Fn(&Lua, &Debugger) -> Result<()> + Send>;
+
+#[cfg(all(feature = "dap", not(feature = "send")))]
+type LineCallback = Box<dyn Fn(&Lua, &Debugger) -> Result<()>>;
+
+/// Identifier of a breakpoint returned by [`Debugger::add_breakpoint`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BreakpointId(u64);
+
+/// A breakpoint set on a line of a chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// Breakpoint identifier.
+    pub id: BreakpointId,
+    /// Chunk name (without `@` or `=` prefix).
+    pub source: StdString,
+    /// Line number (starting from 1).
+    pub line: usize,
+}
+
+/// An action returned by the pause handler that determines how execution continues.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugAction {
+    /// Continue execution until the next breakpoint.
+    Continue,
+    /// Pause on the next executed line, entering called functions.
+    StepIn,
+    /// Pause on the next line of the current function (or its caller, if the function returns).
+    StepOver,
+    /// Pause on the next line after the current function returns.
+    StepOut,
+    /// Yield the running thread and keep it paused until [`Debugger::resume`] is called.
+    ///
+    /// Requires `feature = "lua54/lua53"`. The code must run inside a coroutine.
+    Suspend,
+}
+
+/// The reason why execution was paused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseReason {
+    /// A breakpoint was hit.
+    Breakpoint(BreakpointId),
+    /// A step was completed.
+    Step,
+    /// Pause was requested using [`Debugger::pause`].
+    Pause,
+}
+
+/// Information about a function on the call stack.
+#[derive(Clone, Debug)]
+pub struct DebugFrame {
+    /// Stack level of the frame, `0` is the paused function.
+    pub level: usize,
+    /// A (reasonable) name of the function (`None` if the name cannot be found).
+    pub name: Option<StdString>,
+    /// Source of the chunk that created the function.
+    pub source: Option<StdString>,
+    /// A "printable" version of `source`.
+    pub short_src: Option<StdString>,
+    /// Currently executing line (`None` if not available).
+    pub line: Option<usize>,
+    /// A string `Lua` if the function is a Lua function, `C` if it is a C function, `main` if it is
+    /// the main part of a chunk.
+    pub what: &'static str,
+}
+
+/// A named local variable or upvalue.
+#[derive(Clone, Debug)]
+pub struct DebugVariable {
+    /// Variable name.
+    pub name: StdString,
+    /// Current value.
+    pub value: Value,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum StepMode {
+    In,
+    Over(usize),
+    Out(usize),
+}
+
+#[derive(Default)]
+struct DebuggerState {
+    breakpoints: Vec<Breakpoint>,
+    next_id: u64,
+    step: Option<StepMode>,
+    pause_requested: bool,
+    suspended: Option<Suspended>,
+    #[cfg(feature = "async")]
+    waker: Option<Waker>,
+}
+
+#[derive(Clone, Copy)]
+struct Suspended {
+    reason: PauseReason,
+    // Stack depth of the suspended thread
+    depth: usize,
+    line: Option<usize>,
+}
+
+struct DebuggerInner {
+    state: Mutex<DebuggerState>,
+    handler: Mutex<PauseHandler>,
+    #[cfg(feature = "dap")]
+    line_callback: Mutex<Option<LineCallback>>,
+}
+
+/// An interactive step debugger.
+///
+/// Cloning the debugger returns a new handle to the same debugger.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result};
+/// # use mlua::debugger::{DebugAction, Debugger};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let debugger = Debugger::new(|_lua, ctx| {
+///     let locals = ctx.locals(0)?;
+///     println!("paused at line {:?}, locals: {locals:?}", ctx.frames()[0].line);
+///     Ok(DebugAction::StepOver)
+/// });
+/// debugger.add_breakpoint("main.lua", 2);
+/// debugger.attach(&lua);
+///
+/// lua.load("local a = 1\nlocal b = a + 1\nreturn b").set_name("@main.lua").exec()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Debugger(XRc<DebuggerInner>);
+
+impl fmt::Debug for Debugger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.0.state.lock();
+        f.debug_struct("Debugger")
+            .field("breakpoints", &state.breakpoints)
+            .field("suspended", &state.suspended.is_some())
+            .finish()
+    }
+}
+
+impl Debugger {
+    /// Creates a new debugger with the given pause handler.
+    ///
+    /// The handler is called every time execution is paused.
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(&Lua, &DebugContext) -> Result<DebugAction> + MaybeSend + 'static,
+    {
+        Debugger(XRc::new(DebuggerInner {
+            state: Mutex::new(DebuggerState::default()),
+            handler: Mutex::new(Box::new(handler)),
+            #[cfg(feature = "dap")]
+            line_callback: Mutex::new(None),
+        }))
+    }
+
+    /// Attaches the debugger to the main thread of the Lua instance.
+    ///
+    /// This replaces any hook previously set by [`Lua::set_hook`].
+    ///
+    /// [`Lua::set_hook`]: crate::Lua::set_hook
+    pub fn attach(&self, lua: &Lua) {
+        let this = self.clone();
+        lua.set_hook(HookTriggers::EVERY_LINE, move |lua, debug| {
+            this.on_line(lua, debug)
+        });
+    }
+
+    /// Attaches the debugger to the given thread (coroutine).
+    ///
+    /// This replaces any hook previously set by [`Lua::set_hook`] or [`Thread::set_hook`].
+    ///
+    /// [`Lua::set_hook`]: crate::Lua::set_hook
+    pub fn attach_thread(&self, thread: &Thread) {
+        let this = self.clone();
+        thread.set_hook(HookTriggers::EVERY_LINE, move |lua, debug| {
+            this.on_line(lua, debug)
+        });
+    }
+
+    /// Adds a breakpoint on the `line` of the chunk named `source`.
+    ///
+    /// The `@` and `=` prefixes of the chunk names are ignored. If the chunk name is a relative
+    /// path, the breakpoint `source` can also be any path ending with it (eg. an absolute path).
+    pub fn add_breakpoint(&self, source: impl AsRef<str>, line: usize) -> BreakpointId {
+        let mut state = self.0.state.lock();
+        state.next_id += 1;
+        let id = BreakpointId(state.next_id);
+        let source = normalize_source(source.as_ref()).to_string();
+        state.breakpoints.push(Breakpoint { id, source, line });
+        id
+    }
+
+    /// Removes a breakpoint.
+    ///
+    /// Returns `false` if the breakpoint does not exist.
+    pub fn remove_breakpoint(&self, id: BreakpointId) -> bool {
+        let mut state = self.0.state.lock();
+        let len = state.breakpoints.len();
+        state.breakpoints.retain(|bp| bp.id != id);
+        state.breakpoints.len() != len
+    }
+
+    /// Removes all breakpoints.
+    pub fn clear_breakpoints(&self) {
+        self.0.state.lock().breakpoints.clear();
+    }
+
+    /// Returns a list of the breakpoints.
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.0.state.lock().breakpoints.clone()
+    }
+
+    /// Requests execution to pause on the next executed line.
+    ///
+    /// Can be called from the other threads (with `send` feature) to interrupt running code.
+    pub fn pause(&self) {
+        self.0.state.lock().pause_requested = true;
+    }
+
+    /// Returns `true` if a thread is suspended by [`DebugAction::Suspend`].
+    pub fn is_suspended(&self) -> bool {
+        self.0.state.lock().suspended.is_some()
+    }
+
+    /// Returns the reason why the suspended thread was paused.
+    pub fn suspend_reason(&self) -> Option<PauseReason> {
+        self.0.state.lock().suspended.map(|s| s.reason)
+    }
+
+    /// Returns a context to inspect the suspended `thread`.
+    ///
+    /// Returns `None` if the debugger is not suspended or the thread is not resumable.
+    ///
+    /// In Lua 5.3 the state of the suspended function is reported as it was before the last
+    /// executed instruction, so variables declared on the previous line might not be visible yet.
+    pub fn suspended_context<'a>(&self, thread: &'a Thread) -> Option<DebugContext<'a>> {
+        let suspended = self.0.state.lock().suspended?;
+        if thread.status() != ThreadStatus::Resumable {
+            return None;
+        }
+        Some(DebugContext {
+            lua: thread.0.lua.upgrade(),
+            debugger: self.clone(),
+            state: thread.state(),
+            reason: suspended.reason,
+            line: suspended.line,
+            _lifetime: std::marker::PhantomData,
+        })
+    }
+
+    /// Resumes the suspended thread with the given action.
+    ///
+    /// For synchronous code the thread must then be resumed by the caller (eg. using
+    /// [`Thread::resume`]), whereas [`AsyncThread`] is woken up automatically.
+    ///
+    /// Returns `false` if the debugger is not suspended.
+    ///
+    /// [`AsyncThread`]: crate::AsyncThread
+    pub fn resume(&self, action: DebugAction) -> bool {
+        let mut state = self.0.state.lock();
+        let Some(suspended) = state.suspended.take() else {
+            return false;
+        };
+        state.apply(action, suspended.depth);
+        #[cfg(feature = "async")]
+        if let Some(waker) = state.waker.take() {
+            drop(state);
+            waker.wake();
+        }
+        true
+    }
+
+    /// Sets a function that is called on every executed line, before checking breakpoints.
+    #[cfg(feature = "dap")]
+    pub(crate) fn set_line_callback(&self, callback: LineCallback) {
+        *self.0.line_callback.lock() = Some(callback);
+    }
+
+    fn on_line(&self, lua: &Lua, debug: Debug) -> Result<VmState> {
+        #[cfg(feature = "dap")]
+        if let Some(callback) = &*self.0.line_callback.lock() {
+            callback(lua, self)?;
+        }
+
+        let (reason, depth, line) = {
+            let mut state = self.0.state.lock();
+            if state.suspended.is_some() {
+                // Resumed before `Debugger::resume` was called
+                return Ok(VmState::Yield);
+            }
+            if state.breakpoints.is_empty() && state.step.is_none() && !state.pause_requested {
+                return Ok(VmState::Continue);
+            }
+
+            match state.pause_reason(lua, debug) {
+                Some(pause) => pause,
+                None => return Ok(VmState::Continue),
+            }
+        };
+
+        let ctx = DebugContext {
+            lua: lua.clone(),
+            debugger: self.clone(),
+            state: lua.lock().state(),
+            reason,
+            line,
+            _lifetime: std::marker::PhantomData,
+        };
+        let action = (self.0.handler.lock())(lua, &ctx)?;
+
+        let mut state = self.0.state.lock();
+        if action == DebugAction::Suspend {
+            state.suspended = Some(Suspended { reason, depth, line });
+            #[cfg(feature = "async")]
+            unsafe {
+                let rawlua = lua.lock();
+                let waker = rawlua.waker();
+                if !waker.will_wake(futures_util::task::noop_waker_ref()) {
+                    state.waker = Some(waker.clone());
+                    rawlua.set_suspended_by_hook();
+                }
+            }
+            return Ok(VmState::Yield);
+        }
+        state.apply(action, depth);
+        Ok(VmState::Continue)
+    }
+}
+
+impl DebuggerState {
+    fn apply(&mut self, action: DebugAction, depth: usize) {
+        self.step = match action {
+            DebugAction::Continue | DebugAction::Suspend => None,
+            DebugAction::StepIn => Some(StepMode::In),
+            DebugAction::StepOver => Some(StepMode::Over(depth)),
+            DebugAction::StepOut => Some(StepMode::Out(depth)),
+        };
+    }
+
+    // Returns the reason to pause at the current line (with the stack depth and line number)
+    //
+    // Takes `debug` by value to release it before the debug handler is called.
+    fn pause_reason(&mut self, lua: &Lua, debug: Debug) -> Option<(PauseReason, usize, Option<usize>)> {
+        let depth = || unsafe { stack_depth(lua.lock().state()) };
+        let mut reason = None;
+        if mem::take(&mut self.pause_requested) {
+            reason = Some(PauseReason::Pause);
+        } else if let Some(step) = self.step {
+            let stop = match step {
+                StepMode::In => true,
+                StepMode::Over(start) => depth() <= start,
+                StepMode::Out(start) => depth() < start,
+            };
+            if stop {
+                reason = Some(PauseReason::Step);
+            }
+        }
+        let line = linenumber_to_usize(debug.curr_line());
+        if reason.is_none() && !self.breakpoints.is_empty() {
+            let source = debug.source().source.map(|s| normalize_source(&s).to_string());
+            let bp = (self.breakpoints.iter()).find(|bp| {
+                Some(bp.line) == line && source.as_deref().is_some_and(|s| source_matches(&bp.source, s))
+            });
+            reason = bp.map(|bp| PauseReason::Breakpoint(bp.id));
+        }
+        let reason = reason?;
+        self.step = None;
+        Some((reason, depth(), line))
+    }
+}
+
+/// Provides access to the call stack of the paused code.
+///
+/// Stack levels are counted from the paused function (level `0`) to the outermost caller.
+pub struct DebugContext<'a> {
+    lua: Lua,
+    debugger: Debugger,
+    state: *mut ffi::lua_State,
+    reason: PauseReason,
+    // Current line of the paused function (Lua 5.3 reports previous line for yielded threads)
+    line: Option<usize>,
+    _lifetime: std::marker::PhantomData<&'a ()>,
+}
+
+impl DebugContext<'_> {
+    /// Returns the reason why execution was paused.
+    pub fn reason(&self) -> PauseReason {
+        self.reason
+    }
+
+    /// Returns the debugger that paused execution.
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    /// Returns the call stack frames, starting from the paused function.
+    pub fn frames(&self) -> Vec<DebugFrame> {
+        let _lock = self.lua.lock();
+        let mut frames = Vec::new();
+        unsafe {
+            let mut ar: ffi::lua_Debug = mem::zeroed();
+            while ffi::lua_getstack(self.state, frames.len() as c_int, &mut ar) != 0 {
+                mlua_assert!(
+                    ffi::lua_getinfo(self.state, cstr!("nSl"), &mut ar) != 0,
+                    "lua_getinfo failed with `nSl`"
+                );
+                frames.push(DebugFrame {
+                    level: frames.len(),
+                    name: ptr_to_lossy_str(ar.name).map(|s| s.into_owned()),
+                    source: ptr_to_lossy_str(ar.source).map(|s| s.into_owned()),
+                    short_src: ptr_to_lossy_str(ar.short_src.as_ptr()).map(|s| s.into_owned()),
+                    line: match frames.len() {
+                        0 => self.line,
+                        _ => linenumber_to_usize(ar.currentline),
+                    },
+                    what: ptr_to_str(ar.what).unwrap_or("main"),
+                });
+            }
+        }
+        frames
+    }
+
+    /// Returns the local variables of the function at the stack `level`.
+    ///
+    /// Internal variables (with names starting from `(`) are skipped.
+    pub fn locals(&self, level: usize) -> Result<Vec<DebugVariable>> {
+        let lua = self.lua.lock();
+        let mut locals = Vec::new();
+        unsafe {
+            let _sg = StackGuard::new(lua.state());
+            check_stack(lua.state(), 2)?;
+            let ar = self.activation_record(level)?;
+            for n in 1.. {
+                check_stack(self.state, 1)?;
+                let name = ffi::lua_getlocal(self.state, &ar, n);
+                if name.is_null() {
+                    break;
+                }
+                ffi::lua_xmove(self.state, lua.state(), 1);
+                let value = lua.pop_value();
+                let name = ptr_to_lossy_str(name).unwrap_or_default();
+                if !name.starts_with('(') {
+                    locals.push(DebugVariable {
+                        name: name.into_owned(),
+                        value,
+                    });
+                }
+            }
+        }
+        Ok(locals)
+    }
+
+    /// Sets the value of the local variable `name` of the function at the stack `level`.
+    ///
+    /// If there are several visible variables with the same name, the innermost is used.
+    pub fn set_local(&self, level: usize, name: &str, value: impl crate::IntoLua) -> Result<()> {
+        let value = value.into_lua(&self.lua)?;
+        let lua = self.lua.lock();
+        unsafe {
+            let _sg = StackGuard::new(lua.state());
+            check_stack(lua.state(), 3)?;
+            let ar = self.activation_record(level)?;
+            let mut index = None;
+            for n in 1.. {
+                let local_name = ffi::lua_getlocal(self.state, &ar, n);
+                if local_name.is_null() {
+                    break;
+                }
+                ffi::lua_pop(self.state, 1);
+                if ptr_to_str(local_name) == Some(name) {
+                    index = Some(n);
+                }
+            }
+            let n = index.ok_or_else(|| Error::runtime(format!("local '{name}' not found")))?;
+            lua.push_value(&value)?;
+            ffi::lua_xmove(lua.state(), self.state, 1);
+            ffi::lua_setlocal(self.state, &ar, n);
+        }
+        Ok(())
+    }
+
+    /// Returns the upvalues of the function at the stack `level`.
+    pub fn upvalues(&self, level: usize) -> Result<Vec<DebugVariable>> {
+        let lua = self.lua.lock();
+        let mut upvalues = Vec::new();
+        unsafe {
+            let _sg = StackGuard::new(lua.state());
+            check_stack(lua.state(), 3)?;
+            self.push_function(&lua, level)?;
+            for n in 1.. {
+                let name = ffi::lua_getupvalue(lua.state(), -1, n);
+                if name.is_null() {
+                    break;
+                }
+                let value = lua.pop_value();
+                upvalues.push(DebugVariable {
+                    name: ptr_to_lossy_str(name).unwrap_or_default().into_owned(),
+                    value,
+                });
+            }
+        }
+        Ok(upvalues)
+    }
+
+    /// Sets the value of the upvalue `name` of the function at the stack `level`.
+    pub fn set_upvalue(&self, level: usize, name: &str, value: impl crate::IntoLua) -> Result<()> {
+        let value = value.into_lua(&self.lua)?;
+        let lua = self.lua.lock();
+        unsafe {
+            let _sg = StackGuard::new(lua.state());
+            check_stack(lua.state(), 4)?;
+            self.push_function(&lua, level)?;
+            for n in 1.. {
+                let upvalue_name = ffi::lua_getupvalue(lua.state(), -1, n);
+                if upvalue_name.is_null() {
+                    break;
+                }
+                ffi::lua_pop(lua.state(), 1);
+                if ptr_to_str(upvalue_name) == Some(name) {
+                    lua.push_value(&value)?;
+                    ffi::lua_setupvalue(lua.state(), -2, n);
+                    return Ok(());
+                }
+            }
+        }
+        Err(Error::runtime(format!("upvalue '{name}' not found")))
+    }
+
+    unsafe fn activation_record(&self, level: usize) -> Result<ffi::lua_Debug> {
+        let mut ar: ffi::lua_Debug = mem::zeroed();
+        if ffi::lua_getstack(self.state, level as c_int, &mut ar) == 0 {
+            return Err(Error::runtime(format!("invalid stack level {level}")));
+        }
+        Ok(ar)
+    }
+
+    // Pushes the function at the stack `level` to the `lua` stack
+    unsafe fn push_function(&self, lua: &crate::state::RawLua, level: usize) -> Result<()> {
+        let mut ar = self.activation_record(level)?;
+        check_stack(self.state, 1)?;
+        mlua_assert!(
+            ffi::lua_getinfo(self.state, cstr!("f"), &mut ar) != 0,
+            "lua_getinfo failed with `f`"
+        );
+        ffi::lua_xmove(self.state, lua.state(), 1);
+        Ok(())
+    }
+}
+
+// Returns number of the active functions on the thread stack
+unsafe fn stack_depth(state: *mut ffi::lua_State) -> usize {
+    let mut ar: ffi::lua_Debug = mem::zeroed();
+    let mut depth = 0;
+    while ffi::lua_getstack(state, depth as c_int, &mut ar) != 0 {
+        depth += 1;
+    }
+    depth
+}
+
+fn normalize_source(source: &str) -> &str {
+    source.strip_prefix(['@', '=']).unwrap_or(source)
+}
+
+fn source_matches(bp_source: &str, chunk_source: &str) -> bool {
+    let chunk_source = chunk_source.trim_start_matches("./");
+    match bp_source.strip_suffix(chunk_source) {
+        Some("") => true,
+        Some(prefix) => prefix.ends_with(['/', '\\']),
+        None => false,
+    }
+}
@@ -8,6 +8,7 @@ use std::string::String as StdString;
 use std::sync::Arc;
 
 use crate::private::Sealed;
+use crate::util::join_path;
 
 #[cfg(feature = "error-send")]
 type DynStdError = dyn StdError + Send + Sync;
@@ -40,6 +41,15 @@ pub enum Error {
     /// The Lua VM returns this error when the allocator does not return the requested memory, aka
     /// it is an out-of-memory error.
     MemoryError(StdString),
+    /// Execution limits set by [`Lua::set_execution_limits`] or [`Function::call_with_budget`]
+    /// were exceeded.
+    ///
+    /// The error is raised from within the running Lua code and cannot be suppressed by it: once a
+    /// limit is exceeded, the error is raised again at every subsequent check.
+    ///
+    /// [`Lua::set_execution_limits`]: crate::Lua::set_execution_limits
+    /// [`Function::call_with_budget`]: crate::Function::call_with_budget
+    ExecutionLimitExceeded,
     /// Lua garbage collector error, aka `LUA_ERRGCMM`.
     ///
     /// The Lua VM returns this error when there is an error running a `__gc` metamethod.
@@ -106,6 +116,19 @@ pub enum Error {
         /// A string containing more detailed error information.
         message: Option<StdString>,
     },
+    /// A field of a Lua table could not be converted to the corresponding field of a Rust type.
+    ///
+    /// Returned by the [`FromLua`] derive macro in table mode.
+    ///
+    /// [`FromLua`]: crate::FromLua
+    FromLuaFieldError {
+        /// Name of the Rust type that could not be created.
+        to: StdString,
+        /// Path to the field that could not be converted (eg. `pos[2]` or `inner.x`).
+        path: StdString,
+        /// Underlying error.
+        cause: Arc<Error>,
+    },
     /// [`Thread::resume`] was called on an unresumable coroutine.
     ///
     /// A coroutine is unresumable if its main function has returned or if an error has occurred
@@ -117,6 +140,14 @@ pub enum Error {
     /// [`Thread::resume`]: crate::Thread::resume
     /// [`Thread::status`]: crate::Thread::status
     CoroutineUnresumable,
+    /// An async call was cancelled using a [`CancellationToken`].
+    ///
+    /// This error is raised by async functions called from a cancelled [`AsyncThread`] and can be
+    /// caught by the Lua code. If it's not caught, the thread returns it as well.
+    ///
+    /// [`CancellationToken`]: crate::CancellationToken
+    /// [`AsyncThread`]: crate::AsyncThread
+    Cancelled,
     /// An [`AnyUserData`] is not the expected type in a borrow.
     ///
     /// This error can only happen when manually using [`AnyUserData`], or when implementing
@@ -151,6 +182,24 @@ pub enum Error {
     /// [`AnyUserData`]: crate::AnyUserData
     /// [`UserData`]: crate::UserData
     UserDataBorrowMutError,
+    /// A [`Buffer`] immutable borrow failed.
+    ///
+    /// This error can occur when the buffer data is accessed while it's mutably borrowed using
+    /// [`Buffer::with_mut_slice`].
+    ///
+    /// [`Buffer`]: crate::Buffer
+    /// [`Buffer::with_mut_slice`]: crate::Buffer::with_mut_slice
+    BufferBorrowError,
+    /// A [`Buffer`] mutable borrow failed.
+    ///
+    /// This error can occur when the buffer data is modified while it's borrowed using
+    /// [`Buffer::with_slice`] or [`Buffer::with_mut_slice`]. On Luau it's also returned when Lua
+    /// code is called while any buffer is borrowed.
+    ///
+    /// [`Buffer`]: crate::Buffer
+    /// [`Buffer::with_slice`]: crate::Buffer::with_slice
+    /// [`Buffer::with_mut_slice`]: crate::Buffer::with_mut_slice
+    BufferBorrowMutError,
     /// A [`MetaMethod`] operation is restricted (typically for `__gc` or `__metatable`).
     ///
     /// [`MetaMethod`]: crate::MetaMethod
@@ -189,7 +238,14 @@ pub enum Error {
     /// Deserialization error.
     #[cfg(feature = "serialize")]
     #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
-    DeserializeError(StdString),
+    DeserializeError {
+        /// Path to the nested value that could not be deserialized (eg. `servers[3].port`).
+        ///
+        /// Empty if the error is related to the top-level value.
+        path: StdString,
+        /// A string containing more detailed error information.
+        message: StdString,
+    },
     /// A custom error.
     ///
     /// This can be used for returning user-defined errors from callbacks.
@@ -219,6 +275,7 @@ impl fmt::Display for Error {
             Error::MemoryError(msg) => {
                 write!(fmt, "memory error: {msg}")
             }
+            Error::ExecutionLimitExceeded => write!(fmt, "execution limit exceeded"),
             #[cfg(any(feature = "lua53", feature = "lua52"))]
             Error::GarbageCollectorError(msg) => {
                 write!(fmt, "garbage collector error: {msg}")
@@ -267,11 +324,17 @@ impl fmt::Display for Error {
                     Some(message) => write!(fmt, " ({message})"),
                 }
             }
+            Error::FromLuaFieldError { to, path, cause } => {
+                write!(fmt, "error converting Lua table to {to} (field `{path}`: {cause})")
+            }
             Error::CoroutineUnresumable => write!(fmt, "coroutine is non-resumable"),
+            Error::Cancelled => write!(fmt, "async call was cancelled"),
             Error::UserDataTypeMismatch => write!(fmt, "userdata is not expected type"),
             Error::UserDataDestructed => write!(fmt, "userdata has been destructed"),
             Error::UserDataBorrowError => write!(fmt, "error borrowing userdata"),
             Error::UserDataBorrowMutError => write!(fmt, "error mutably borrowing userdata"),
+            Error::BufferBorrowError => write!(fmt, "error borrowing buffer"),
+            Error::BufferBorrowMutError => write!(fmt, "error mutably borrowing buffer"),
             Error::MetaMethodRestricted(method) => write!(fmt, "metamethod {method} is restricted"),
             Error::MetaMethodTypeError { method, type_name, message } => {
                 write!(fmt, "metamethod {method} has unsupported type {type_name}")?;
@@ -314,8 +377,12 @@ impl fmt::Display for Error {
                 write!(fmt, "serialize error: {err}")
             },
             #[cfg(feature = "serialize")]
-            Error::DeserializeError(err) => {
-                write!(fmt, "deserialize error: {err}")
+            Error::DeserializeError { path, message } => {
+                if path.is_empty() {
+                    write!(fmt, "deserialize error: {message}")
+                } else {
+                    write!(fmt, "deserialize error: {path}: {message}")
+                }
             },
             Error::ExternalError(err) => err.fmt(fmt),
             Error::WithContext { context, cause } => {
@@ -380,11 +447,35 @@ impl Error {
     pub fn parent(&self) -> Option<&Error> {
         match self {
             Error::CallbackError { cause, .. } => Some(cause.as_ref()),
+            Error::FromLuaFieldError { cause, .. } => Some(cause.as_ref()),
             Error::WithContext { cause, .. } => Some(cause.as_ref()),
             _ => None,
         }
     }
 
+    /// Wraps an error that occurred while converting the field at `path` of the Rust type `to`.
+    ///
+    /// The result is always a [`Error::FromLuaFieldError`]. If the error is already a field
+    /// error (of a nested type), `path` is prepended to its path.
+    ///
+    /// Used by the `FromLua` derive macro.
+    #[doc(hidden)]
+    pub fn from_lua_field(self, to: &str, path: &str) -> Self {
+        let (path, cause) = match self {
+            Error::FromLuaFieldError {
+                path: inner_path,
+                cause,
+                ..
+            } => (join_path(path, &inner_path), cause),
+            err => (path.to_string(), Arc::new(err)),
+        };
+        Error::FromLuaFieldError {
+            to: to.to_string(),
+            path,
+            cause,
+        }
+    }
+
     pub(crate) fn bad_self_argument(to: &str, cause: Error) -> Self {
         Error::BadArgument {
             to: Some(to.to_string()),
@@ -394,6 +485,14 @@ impl Error {
         }
     }
 
+    #[cfg(feature = "serialize")]
+    pub(crate) fn deserialize_error(message: impl ToString) -> Self {
+        Error::DeserializeError {
+            path: StdString::new(),
+            message: message.to_string(),
+        }
+    }
+
     pub(crate) fn from_lua_conversion(
         from: &'static str,
         to: impl ToString,
@@ -504,7 +603,7 @@ impl serde::ser::Error for Error {
 #[cfg(feature = "serialize")]
 impl serde::de::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Self {
-        Self::DeserializeError(msg.to_string())
+        Self::deserialize_error(msg)
     }
 }
 
@@ -536,6 +635,7 @@ impl<'a> Iterator for Chain<'a> {
                 Some(current) => match current.downcast_ref::<Error>()? {
                     Error::BadArgument { cause, .. }
                     | Error::CallbackError { cause, .. }
+                    | Error::FromLuaFieldError { cause, .. }
                     | Error::WithContext { cause, .. } => {
                         self.current = Some(&**cause);
                         self.current
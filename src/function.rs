@@ -3,6 +3,7 @@ use std::os::raw::{c_int, c_void};
 use std::{mem, ptr, slice};
 
 use crate::error::{Error, Result};
+use crate::limits::ExecutionLimits;
 use crate::state::Lua;
 use crate::table::Table;
 use crate::traits::{FromLuaMulti, IntoLua, IntoLuaMulti, LuaNativeFn, LuaNativeFnMut};
@@ -103,6 +104,8 @@ impl Function {
     /// ```
     pub fn call<R: FromLuaMulti>(&self, args: impl IntoLuaMulti) -> Result<R> {
         let lua = self.0.lua.lock();
+        #[cfg(feature = "luau")]
+        crate::buffer::check_not_borrowed()?;
         let state = lua.state();
         unsafe {
             let _sg = StackGuard::new(state);
@@ -125,6 +128,43 @@ impl Function {
         }
     }
 
+    /// Calls the function with the given execution limits.
+    ///
+    /// Works like [`Function::call`], but the call is interrupted with
+    /// [`Error::ExecutionLimitExceeded`] once any of the limits is exceeded. The limits cannot
+    /// extend the ones set by [`Lua::set_execution_limits`] (or an outer call), and are restored
+    /// when the call returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Error, ExecutionLimits, Function, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let spin: Function = lua.load("function(n) for i = 1, n do end end").eval()?;
+    ///
+    /// let budget = ExecutionLimits::new().instructions(10_000);
+    /// spin.call_with_budget::<()>(10, budget)?;
+    /// assert!(matches!(
+    ///     spin.call_with_budget::<()>(1_000_000, budget),
+    ///     Err(Error::ExecutionLimitExceeded)
+    /// ));
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::set_execution_limits`]: crate::Lua::set_execution_limits
+    pub fn call_with_budget<R: FromLuaMulti>(
+        &self,
+        args: impl IntoLuaMulti,
+        limits: ExecutionLimits,
+    ) -> Result<R> {
+        let lua = self.0.lua.lock();
+        let _guard = unsafe { lua.enter_execution_limits(limits) };
+        self.call(args)
+    }
+
     /// Returns a future that, when polled, calls `self`, passing `args` as function arguments,
     /// and drives the execution.
     ///
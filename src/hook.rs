@@ -10,6 +10,8 @@ use ffi::lua_Debug;
 use crate::state::RawLua;
 use crate::types::ReentrantMutexGuard;
 use crate::util::{linenumber_to_usize, ptr_to_lossy_str, ptr_to_str};
+#[cfg(not(feature = "luau"))]
+use {crate::state::WeakLua, crate::util::StackGuard, std::fmt};
 
 /// Contains information about currently executing Lua code.
 ///
@@ -205,6 +207,32 @@ impl<'a> Debug<'a> {
             stack
         }
     }
+
+    /// Corresponds to the `L` what mask. Returns the (sorted) lines of the function that contain
+    /// code.
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn active_lines(&self) -> Vec<usize> {
+        let state = self.lua.state();
+        let mut lines = Vec::new();
+        unsafe {
+            if ffi::lua_checkstack(state, 3) == 0 {
+                return lines;
+            }
+            let _sg = StackGuard::new(state);
+            if ffi::lua_getinfo(state, cstr!("L"), self.ar.get()) == 0 || ffi::lua_istable(state, -1) == 0 {
+                return lines;
+            }
+            ffi::lua_pushnil(state);
+            while ffi::lua_next(state, -2) != 0 {
+                ffi::lua_pop(state, 1);
+                if let Some(line) = linenumber_to_usize(ffi::lua_tointeger(state, -1) as c_int) {
+                    lines.push(line);
+                }
+            }
+        }
+        lines.sort_unstable();
+        lines
+    }
 }
 
 enum ActivationRecord {
@@ -398,3 +426,40 @@ impl BitOrAssign for HookTriggers {
         *self = *self | rhs;
     }
 }
+
+/// A handle to a hook function added by [`Lua::add_hook`].
+///
+/// The hook stays active until it's removed with [`HookHandle::remove`]. Dropping the handle does
+/// not remove the hook.
+///
+/// [`Lua::add_hook`]: crate::Lua::add_hook
+#[cfg(not(feature = "luau"))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+#[derive(Clone)]
+pub struct HookHandle {
+    lua: WeakLua,
+    id: u64,
+}
+
+#[cfg(not(feature = "luau"))]
+impl HookHandle {
+    pub(crate) fn new(lua: WeakLua, id: u64) -> Self {
+        HookHandle { lua, id }
+    }
+
+    /// Removes the hook.
+    ///
+    /// This function has no effect if the hook was already removed.
+    pub fn remove(&self) {
+        if let Some(lua) = self.lua.try_lock() {
+            unsafe { lua.remove_hook_by_id(self.id) };
+        }
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+impl fmt::Debug for HookHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HookHandle").field("id", &self.id).finish()
+    }
+}
@@ -70,23 +70,39 @@
 #[macro_use]
 mod macros;
 
+mod actor;
 mod buffer;
+#[cfg(feature = "async")]
+mod cancel;
+mod channel;
 mod chunk;
 mod conversion;
+mod coverage;
 mod error;
 mod function;
 mod hook;
+mod limits;
 #[cfg(feature = "luau")]
 mod luau;
 mod memory;
 mod multi;
+#[cfg(not(feature = "luau"))]
+mod persist;
+mod pool;
+mod profiler;
 mod scope;
+#[cfg(not(feature = "luau"))]
+mod searcher;
+#[cfg(not(feature = "luau"))]
+mod snapshot;
 mod state;
 mod stdlib;
 mod string;
 mod table;
+mod testing;
 mod thread;
 mod traits;
+mod typedef;
 mod types;
 mod userdata;
 mod util;
@@ -98,20 +114,29 @@ pub mod prelude;
 pub use bstr::BString;
 pub use ffi::{self, lua_CFunction, lua_State};
 
+pub use crate::actor::{LuaActor, LuaHandle, LuaTask};
+pub use crate::channel::{Channel, Transferable};
 pub use crate::chunk::{AsChunk, Chunk, ChunkMode};
+pub use crate::coverage::{Coverage, CoverageReport, FileCoverage};
 pub use crate::error::{Error, ErrorContext, ExternalError, ExternalResult, Result};
 pub use crate::function::{Function, FunctionInfo};
 pub use crate::hook::{Debug, DebugEvent, DebugNames, DebugSource, DebugStack};
+pub use crate::limits::ExecutionLimits;
+pub use crate::memory::{AllocationSite, MemoryGrowth, MemoryReport, UserDataGrowth, UserDataMemory};
 pub use crate::multi::{MultiValue, Variadic};
+pub use crate::pool::{LuaPool, LuaPoolBuilder, LuaPoolStats, PooledLua};
+pub use crate::profiler::{FunctionProfile, Profile, ProfilerOptions};
 pub use crate::scope::Scope;
 pub use crate::state::{GCMode, Lua, LuaOptions};
 pub use crate::stdlib::StdLib;
 pub use crate::string::{BorrowedBytes, BorrowedStr, String};
 pub use crate::table::{Table, TablePairs, TableSequence};
+pub use crate::testing::{TestReport, TestResult, TestRunner, TestStatus};
 pub use crate::thread::{Thread, ThreadStatus};
 pub use crate::traits::{
     FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, LuaNativeFn, LuaNativeFnMut, ObjectLike,
 };
+pub use crate::typedef::{TypeDefinitions, TypeDesc};
 pub use crate::types::{
     AppDataRef, AppDataRefMut, Either, Integer, LightUserData, MaybeSend, Number, RegistryKey, VmState,
 };
@@ -121,16 +146,37 @@ pub use crate::userdata::{
 };
 pub use crate::value::{Nil, Value};
 
+pub use crate::buffer::{Buffer, BufferCursor};
+
 #[cfg(not(feature = "luau"))]
-pub use crate::hook::HookTriggers;
+pub use crate::{
+    hook::{HookHandle, HookTriggers},
+    searcher::{DirectorySearcher, EmbeddedSearcher, ModuleSearcher, ModuleSource},
+};
+
+#[cfg(not(feature = "luau"))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+pub use crate::{persist::Persist, snapshot::Snapshot};
 
 #[cfg(any(feature = "luau", doc))]
 #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
-pub use crate::{buffer::Buffer, chunk::Compiler, function::CoverageInfo, vector::Vector};
+pub use crate::{chunk::Compiler, function::CoverageInfo, vector::Vector};
+
+#[cfg(feature = "luau")]
+#[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+pub use crate::luau::{FileSystemResolver, ModuleResolver};
 
 #[cfg(feature = "async")]
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
-pub use crate::{thread::AsyncThread, traits::LuaNativeAsyncFn};
+pub use crate::{
+    cancel::{CancellationToken, Cancelled},
+    thread::AsyncThread,
+    traits::LuaNativeAsyncFn,
+};
+
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub use crate::userdata::{HasUserDataMethods, NoUserDataMethods, UserDataMethodsProbe};
 
 #[cfg(feature = "serialize")]
 #[doc(inline)]
@@ -140,6 +186,10 @@ pub use crate::serde::{de::Options as DeserializeOptions, ser::Options as Serial
 #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
 pub mod serde;
 
+#[cfg(not(feature = "luau"))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+pub mod debugger;
+
 #[cfg(feature = "mlua_derive")]
 #[allow(unused_imports)]
 #[macro_use]
@@ -199,12 +249,167 @@ pub use mlua_derive::chunk;
 
 /// Derive [`FromLua`] for a Rust type.
 ///
-/// Current implementation generate code that takes [`UserData`] value, borrow it (of the Rust type)
+/// By default the generated code takes [`UserData`] value, borrow it (of the Rust type)
 /// and clone.
+///
+/// With the `#[lua(table)]` attribute the type is instead converted from a Lua table,
+/// field by field. See [`IntoLua`](derive@IntoLua) for the list of supported attributes.
+/// Errors of converting a field are reported as [`Error::FromLuaFieldError`].
 #[cfg(feature = "macros")]
 #[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
 pub use mlua_derive::FromLua;
 
+/// Derive [`IntoLua`] for a Rust type that is represented as a Lua table.
+///
+/// The type must be marked with the `#[lua(table)]` attribute ([`UserData`] types already
+/// implement [`IntoLua`]). Structs with named fields become tables with string keys, tuple
+/// structs become sequences. Enums follow the same representations as serde.
+///
+/// ```
+/// use mlua::{FromLua, IntoLua, Lua, Result};
+///
+/// #[derive(Debug, PartialEq, FromLua, IntoLua)]
+/// #[lua(table, rename_all = "camelCase")]
+/// struct Server {
+///     host_name: String,
+///     #[lua(default)]
+///     port: u16,
+///     tls: Option<bool>,
+/// }
+///
+/// fn main() -> Result<()> {
+///     let lua = Lua::new();
+///     let server: Server = lua.load("{ hostName = 'localhost' }").eval()?;
+///     assert_eq!(server, Server { host_name: "localhost".into(), port: 0, tls: None });
+///     Ok(())
+/// }
+/// ```
+///
+/// Container attributes:
+///
+/// * `table` - convert the type to/from a Lua table (required)
+/// * `rename_all = "..."` - rename all fields and variants according to the given case convention
+///   (`lowercase`, `UPPERCASE`, `PascalCase`, `camelCase`, `snake_case`, `SCREAMING_SNAKE_CASE`,
+///   `kebab-case`, `SCREAMING-KEBAB-CASE`)
+/// * `tag = "..."` - use internally tagged enum representation: `{ [tag] = "Variant", ... }`
+/// * `tag = "...", content = "..."` - use adjacently tagged enum representation:
+///   `{ [tag] = "Variant", [content] = ... }`
+/// * `untagged` - use untagged enum representation, the first matching variant wins
+///
+/// By default enums are externally tagged: unit variants are strings and other variants are
+/// tables with a single `{ Variant = ... }` key.
+///
+/// Field attributes:
+///
+/// * `rename = "..."` - use a different key for this field
+/// * `default` / `default = "path"` - use [`Default::default`] (or the given function) if the key
+///   is missing
+/// * `skip` - do not read or write this field, use the default value instead
+///
+/// Variant attributes:
+///
+/// * `rename = "..."` - use a different name for this variant
+///
+/// Errors of converting a field are reported as [`Error::FromLuaFieldError`] with the path to the
+/// failing field (eg. `server.ports[2]`) and the underlying error as its cause.
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::IntoLua;
+
+/// Derive [`UserData`] for a Rust type.
+///
+/// Public named fields are exposed to Lua as fields with a getter (that clones the value) and a
+/// setter. Field types must implement [`Clone`], [`IntoLua`] and [`FromLua`].
+///
+/// Methods are collected from an `impl` block marked with the [`methods`] attribute (if any).
+///
+/// Field attributes:
+///
+/// * `rename = "..."` - use a different field name in Lua
+/// * `skip` - do not expose this field
+///
+/// ```
+/// use mlua::{Lua, Result, UserData};
+///
+/// #[derive(Default, UserData)]
+/// struct Counter {
+///     pub value: i64,
+///     #[lua(rename = "label")]
+///     pub name: String,
+/// }
+///
+/// fn main() -> Result<()> {
+///     let lua = Lua::new();
+///     lua.globals().set("counter", Counter::default())?;
+///     lua.load("counter.value = counter.value + 1; counter.label = 'clicks'").exec()?;
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::UserData;
+
+/// Registers functions of an `impl` block as methods of a [`UserData`] type.
+///
+/// Must be used together with the [`UserData`](derive@UserData) derive macro. Every function in
+/// the block is exported to Lua (unless marked with `#[lua(skip)]`):
+///
+/// * `fn(&self, ...)` and `fn(&mut self, ...)` become methods ([`UserDataMethods::add_method`] and
+///   [`UserDataMethods::add_method_mut`])
+/// * functions without receiver become functions ([`UserDataMethods::add_function`])
+/// * `async fn` become async methods or functions (requires `feature = "async"`)
+///
+/// If the first argument (after the receiver) is `&Lua` (or `Lua` for async functions), the Lua
+/// state is passed to it. Other arguments must implement [`FromLua`] and the return value must
+/// implement [`IntoLuaMulti`]. Errors of functions returning `Result<T, E>` (where
+/// `E: Into<Error>`) are raised as Lua errors.
+///
+/// Method attributes:
+///
+/// * `rename = "..."` - use a different method name in Lua
+/// * `meta = "..."` - register as a metamethod, either [`MetaMethod`] variant name (eg. `Add`) or
+///   the raw metamethod name (eg. `__add`)
+/// * `skip` - do not export this function
+///
+/// ```
+/// use mlua::{FromLua, Lua, Result, UserData};
+///
+/// #[derive(Clone, FromLua, UserData)]
+/// struct Vec2 {
+///     pub x: f64,
+///     pub y: f64,
+/// }
+///
+/// #[mlua::methods]
+/// impl Vec2 {
+///     fn new(x: f64, y: f64) -> Self {
+///         Vec2 { x, y }
+///     }
+///
+///     fn length(&self) -> f64 {
+///         (self.x * self.x + self.y * self.y).sqrt()
+///     }
+///
+///     #[lua(meta = "Add")]
+///     fn add(&self, other: Vec2) -> Vec2 {
+///         Vec2::new(self.x + other.x, self.y + other.y)
+///     }
+///
+///     #[lua(skip)]
+///     fn helper() {}
+/// }
+///
+/// fn main() -> Result<()> {
+///     let lua = Lua::new();
+///     lua.globals().set("Vec2", lua.create_proxy::<Vec2>()?)?;
+///     lua.load("assert((Vec2.new(1, 2) + Vec2.new(2, 2)):length() == 5)").exec()?;
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::methods;
+
 /// Registers Lua module entrypoint.
 ///
 /// You can register multiple entrypoints as required.
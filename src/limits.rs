@@ -0,0 +1,150 @@
+use std::time::{Duration, Instant};
+
+use crate::state::RawLua;
+
+// Instructions executed between two checks of the execution limits (Lua 5.x)
+#[cfg(not(feature = "luau"))]
+const CHECK_INTERVAL: u64 = 1000;
+
+// Interrupts between two checks of the deadline (Luau)
+#[cfg(feature = "luau")]
+const DEADLINE_CHECK_INTERRUPTS: u64 = 64;
+
+/// Limits on the amount of work Lua code is allowed to do.
+///
+/// Used by [`Lua::set_execution_limits`] and [`Function::call_with_budget`]. When any of the limits
+/// is exceeded, the running Lua code is interrupted with [`Error::ExecutionLimitExceeded`].
+///
+/// The limits are checked periodically (every 1000 VM instructions in Lua 5.x and at interrupts in
+/// Luau), so the code can slightly overrun them. Time spent in Rust callbacks is not interrupted,
+/// but is counted towards the deadline.
+///
+/// In LuaJIT compiled code does not trigger hooks, so the JIT compiler should be turned off
+/// (`jit.off()`) for the limits to be enforced reliably.
+///
+/// [`Lua::set_execution_limits`]: crate::Lua::set_execution_limits
+/// [`Function::call_with_budget`]: crate::Function::call_with_budget
+/// [`Error::ExecutionLimitExceeded`]: crate::Error::ExecutionLimitExceeded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    instructions: Option<u64>,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl ExecutionLimits {
+    /// Creates a new set of execution limits without any limit.
+    pub const fn new() -> Self {
+        ExecutionLimits {
+            instructions: None,
+            timeout: None,
+            deadline: None,
+        }
+    }
+
+    /// Limits the number of VM instructions to execute.
+    ///
+    /// Luau does not count instructions, instead each interrupt (function call or loop iteration)
+    /// counts as one instruction.
+    #[must_use]
+    pub const fn instructions(mut self, count: u64) -> Self {
+        self.instructions = Some(count);
+        self
+    }
+
+    /// Limits the (wall-clock) execution time, starting from the moment the limits are applied.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the (wall-clock) deadline of execution.
+    #[must_use]
+    pub const fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+}
+
+// Active execution limits, relative to the number of instructions executed by the Lua instance
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ExecutionBudget {
+    max_instructions: Option<u64>,
+    deadline: Option<Instant>,
+    exceeded: bool,
+}
+
+impl ExecutionBudget {
+    pub(crate) fn new(limits: ExecutionLimits, executed: u64) -> Self {
+        let timeout_deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+        ExecutionBudget {
+            max_instructions: limits.instructions.map(|count| executed.saturating_add(count)),
+            deadline: min_option(limits.deadline, timeout_deadline),
+            exceeded: false,
+        }
+    }
+
+    /// Restricts the budget to not exceed the outer one.
+    pub(crate) fn within(self, outer: Option<ExecutionBudget>) -> Self {
+        match outer {
+            Some(outer) => ExecutionBudget {
+                max_instructions: min_option(self.max_instructions, outer.max_instructions),
+                deadline: min_option(self.deadline, outer.deadline),
+                exceeded: self.exceeded || outer.exceeded,
+            },
+            None => self,
+        }
+    }
+
+    /// Returns the number of instructions between two checks of the budget (Lua 5.x).
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn check_interval(&self, executed: u64) -> u64 {
+        match self.max_instructions {
+            Some(max) => max.saturating_sub(executed).clamp(1, CHECK_INTERVAL),
+            None => CHECK_INTERVAL,
+        }
+    }
+
+    /// Checks whether the budget is exceeded after executing `executed` instructions.
+    ///
+    /// Once exceeded, the budget stays exceeded.
+    pub(crate) fn check(&mut self, executed: u64) -> bool {
+        if self.exceeded || matches!(self.max_instructions, Some(max) if executed >= max) {
+            self.exceeded = true;
+            return true;
+        }
+        // Reading the clock at every interrupt is too expensive
+        #[cfg(feature = "luau")]
+        if executed % DEADLINE_CHECK_INTERRUPTS != 0 {
+            return false;
+        }
+        self.exceeded = matches!(self.deadline, Some(deadline) if Instant::now() >= deadline);
+        self.exceeded
+    }
+}
+
+fn min_option<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// Restores the previous execution budget when dropped
+pub(crate) struct ExecutionBudgetGuard<'a> {
+    lua: &'a RawLua,
+    prev: Option<ExecutionBudget>,
+}
+
+impl<'a> ExecutionBudgetGuard<'a> {
+    pub(crate) fn new(lua: &'a RawLua, prev: Option<ExecutionBudget>) -> Self {
+        ExecutionBudgetGuard { lua, prev }
+    }
+}
+
+impl Drop for ExecutionBudgetGuard<'_> {
+    fn drop(&mut self) {
+        unsafe { self.lua.set_execution_budget(self.prev.take()) };
+    }
+}
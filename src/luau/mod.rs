@@ -64,6 +64,10 @@ unsafe extern "C-unwind" fn lua_collectgarbage(state: *mut ffi::lua_State) -> c_
     }
 }
 
+pub use require::{FileSystemResolver, ModuleResolver};
+
 pub(crate) use package::register_package_module;
+pub(crate) use require::create_require_function;
 
 mod package;
+mod require;
@@ -0,0 +1,412 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::result::Result as StdResult;
+use std::string::String as StdString;
+use std::{fs, str};
+
+use crate::chunk::ChunkMode;
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::multi::MultiValue;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::MaybeSend;
+use crate::value::Value;
+
+// Registry key of the table with loaded modules (by resolved path)
+const MODULES_KEY: &str = "__mlua_modules";
+
+/// A source of Luau modules for the `require` function.
+///
+/// Modules are addressed by virtual paths: components are separated by `/`, the path is relative to
+/// the resolver root and never contains `.` or `..` components (eg. `lib/utils.luau`).
+///
+/// The resolver is installed using [`Lua::set_module_resolver`]. `require` follows Luau
+/// require-by-string semantics:
+/// - `./name` and `../name` are resolved relative to the directory of the requiring chunk;
+/// - `@alias/name` is resolved using aliases defined in `.luaurc` files (searched in the directory of
+///   the requiring chunk and all its ancestors), `@self/name` refers to the requiring chunk directory;
+/// - for the resolved path `path`, the files `path.luau`, `path.lua`, `path/init.luau` and
+///   `path/init.lua` are tried in order.
+///
+/// Loaded modules have chunk names `@path`, which are used for resolving nested requires and are
+/// shown in error messages and tracebacks.
+///
+/// Implementations are provided for [`HashMap`] (eg. with [`include_str!`]-ed sources),
+/// [`FileSystemResolver`] and closures `Fn(&str) -> Result<Option<Vec<u8>>>`.
+#[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+pub trait ModuleResolver: MaybeSend + 'static {
+    /// Returns contents of the file at `path` or `None` if the file does not exist.
+    fn read(&self, path: &str) -> Result<Option<Vec<u8>>>;
+}
+
+impl<F> ModuleResolver for F
+where
+    F: Fn(&str) -> Result<Option<Vec<u8>>> + MaybeSend + 'static,
+{
+    fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        self(path)
+    }
+}
+
+impl<K, V, S> ModuleResolver for HashMap<K, V, S>
+where
+    K: Borrow<str> + Eq + Hash + MaybeSend + 'static,
+    V: AsRef<[u8]> + MaybeSend + 'static,
+    S: BuildHasher + MaybeSend + 'static,
+{
+    fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.get(path).map(|source| source.as_ref().to_vec()))
+    }
+}
+
+/// A [`ModuleResolver`] that loads modules from a directory on the filesystem.
+///
+/// Modules cannot be loaded from outside of the root directory.
+#[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+#[derive(Debug, Clone)]
+pub struct FileSystemResolver {
+    root: PathBuf,
+}
+
+impl FileSystemResolver {
+    /// Creates a new resolver with the given root directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileSystemResolver { root: root.into() }
+    }
+}
+
+impl ModuleResolver for FileSystemResolver {
+    fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let path = Path::new(path);
+        // Virtual paths are always normalized, but double check to not escape the root
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Ok(None);
+        }
+        let path = self.root.join(path);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        match fs::read(path) {
+            Ok(buf) => Ok(Some(buf)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::external(err)),
+        }
+    }
+}
+
+pub(crate) fn create_require_function(lua: &Lua, resolver: impl ModuleResolver) -> Result<Function> {
+    lua.set_named_registry_value(MODULES_KEY, lua.create_table()?)?;
+    lua.create_function(move |lua, name: StdString| {
+        let modules = lua.named_registry_value::<Table>(MODULES_KEY)?;
+
+        if !(name.starts_with("./") || name.starts_with("../") || name.starts_with('@')) {
+            // Allow modules preloaded by the host into `package.loaded`
+            let loaded = lua.named_registry_value::<Table>("_LOADED")?;
+            return match loaded.raw_get::<Value>(&*name)? {
+                Value::Nil => Err(Error::runtime(format!(
+                    "module '{name}' not found: require path must start with './', '../' or '@'"
+                ))),
+                value => Ok(value),
+            };
+        }
+
+        let caller_dir = caller_chunk_dir(lua);
+        let path = resolve_path(&resolver, &caller_dir, &name)
+            .map_err(|err| Error::runtime(format!("error requiring module '{name}': {err}")))?;
+
+        match modules.raw_get::<Value>(&*path)? {
+            Value::Nil => {}
+            Value::Boolean(false) => {
+                return Err(Error::runtime(format!(
+                    "error requiring module '{name}': cyclic module dependency"
+                )))
+            }
+            value => return Ok(value),
+        }
+
+        let (file_path, source) = ["luau", "lua"]
+            .iter()
+            .map(|ext| format!("{path}.{ext}"))
+            .chain(
+                ["luau", "lua"]
+                    .iter()
+                    .map(|ext| join_path(&path, &format!("init.{ext}"))),
+            )
+            .find_map(|file_path| match resolver.read(&file_path) {
+                Ok(Some(source)) => Some(Ok((file_path, source))),
+                Ok(None) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .transpose()?
+            .ok_or_else(|| Error::runtime(format!("module '{name}' not found (resolved to '{path}')")))?;
+
+        // Mark the module as loading to detect cycles
+        modules.raw_set(&*path, false)?;
+        let result = (lua.load(source))
+            .set_name(format!("@{file_path}"))
+            .set_mode(ChunkMode::Text)
+            .call::<MultiValue>(());
+        let value = match result {
+            Ok(values) if values.len() == 1 => values.into_iter().next().unwrap(),
+            Ok(_) => {
+                modules.raw_remove(&*path)?;
+                let err = format!("error requiring module '{name}': module must return a single value");
+                return Err(Error::runtime(err));
+            }
+            Err(err) => {
+                modules.raw_remove(&*path)?;
+                return Err(err);
+            }
+        };
+        modules.raw_set(&*path, &value)?;
+        Ok(value)
+    })
+}
+
+/// Returns directory of the chunk that called `require`.
+fn caller_chunk_dir(lua: &Lua) -> StdString {
+    let source = lua
+        .inspect_stack(1)
+        .and_then(|debug| debug.source().source.map(|s| s.into_owned()));
+    match source.as_deref().and_then(|s| s.strip_prefix('@')) {
+        Some(path) => match normalize_path(&[], path) {
+            Some(mut components) => {
+                components.pop();
+                components.join("/")
+            }
+            None => StdString::new(),
+        },
+        // Chunks without file names are considered to be located in the root
+        None => StdString::new(),
+    }
+}
+
+/// Resolves `name` (without extension) to a normalized virtual path.
+fn resolve_path(resolver: &impl ModuleResolver, caller_dir: &str, name: &str) -> Result<StdString> {
+    let (base_dir, rest) = match name.strip_prefix('@') {
+        Some(name) => {
+            let (alias, rest) = name.split_once('/').unwrap_or((name, ""));
+            if alias.eq_ignore_ascii_case("self") {
+                (caller_dir.to_string(), rest)
+            } else {
+                (find_alias(resolver, caller_dir, alias)?, rest)
+            }
+        }
+        None => (caller_dir.to_string(), name),
+    };
+
+    let base = base_dir.split('/').filter(|c| !c.is_empty()).collect::<Vec<_>>();
+    let components =
+        normalize_path(&base, rest).ok_or_else(|| Error::runtime("require path escapes the module root"))?;
+    if components.is_empty() {
+        return Err(Error::runtime("require path must point to a module"));
+    }
+    Ok(components.join("/"))
+}
+
+/// Joins `base` components with the relative `path`, resolving `.` and `..` components.
+///
+/// Returns `None` if the path goes above the root.
+fn normalize_path<'a>(base: &[&'a str], path: &'a str) -> Option<Vec<&'a str>> {
+    let mut components = base.to_vec();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    Some(components)
+}
+
+fn join_path(dir: &str, name: &str) -> StdString {
+    match dir {
+        "" => name.to_string(),
+        dir => format!("{dir}/{name}"),
+    }
+}
+
+/// Finds `alias` in the nearest `.luaurc` file and returns the aliased (normalized) directory.
+fn find_alias(resolver: &impl ModuleResolver, dir: &str, alias: &str) -> Result<StdString> {
+    let mut dir = dir.split('/').filter(|c| !c.is_empty()).collect::<Vec<_>>();
+    loop {
+        let config_path = join_path(&dir.join("/"), ".luaurc");
+        if let Some(config) = resolver.read(&config_path)? {
+            let config = str::from_utf8(&config)
+                .map_err(|_| Error::runtime(format!("'{config_path}' is not a valid UTF-8 file")))?;
+            let aliases = parse_luaurc_aliases(config)
+                .map_err(|err| Error::runtime(format!("error parsing '{config_path}': {err}")))?;
+            if let Some((_, target)) = aliases.iter().find(|(name, _)| name.eq_ignore_ascii_case(alias)) {
+                let components = match target.strip_prefix('/') {
+                    Some(target) => normalize_path(&[], target),
+                    None => normalize_path(&dir, target),
+                };
+                return components
+                    .map(|components| components.join("/"))
+                    .ok_or_else(|| Error::runtime(format!("alias '@{alias}' escapes the module root")));
+            }
+        }
+        if dir.pop().is_none() {
+            return Err(Error::runtime(format!("@{alias} is not a valid alias")));
+        }
+    }
+}
+
+/// Parses `.luaurc` (JSON) file and returns the list of aliases.
+fn parse_luaurc_aliases(config: &str) -> StdResult<Vec<(StdString, StdString)>, StdString> {
+    let mut parser = JsonParser {
+        input: config.as_bytes(),
+        pos: 0,
+    };
+    let mut aliases = Vec::new();
+    parser.parse_object(|parser, key| {
+        if key != "aliases" {
+            return parser.skip_value();
+        }
+        parser.parse_object(|parser, alias| {
+            let target = parser.parse_string()?;
+            aliases.push((alias, target));
+            Ok(())
+        })
+    })?;
+    parser.skip_whitespace();
+    if parser.pos < parser.input.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(aliases)
+}
+
+/// Minimal JSON parser sufficient to read `.luaurc` files.
+///
+/// Supports `//` comments and trailing commas, similar to the Luau implementation.
+struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, msg: &str) -> StdString {
+        format!("{msg} at position {}", self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.input.len() {
+            match self.input[self.pos] {
+                b' ' | b'\t' | b'\r' | b'\n' => self.pos += 1,
+                b'/' if self.input.get(self.pos + 1) == Some(&b'/') => {
+                    while self.pos < self.input.len() && self.input[self.pos] != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> StdResult<(), StdString> {
+        match self.peek() {
+            Some(next) if next == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error(&format!("expected '{}'", c as char))),
+        }
+    }
+
+    fn parse_object(
+        &mut self,
+        mut f: impl FnMut(&mut Self, StdString) -> StdResult<(), StdString>,
+    ) -> StdResult<(), StdString> {
+        self.expect(b'{')?;
+        loop {
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                return Ok(());
+            }
+            let key = self.parse_string()?;
+            self.expect(b':')?;
+            f(self, key)?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {}
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> StdResult<StdString, StdString> {
+        self.expect(b'"')?;
+        let mut buf = Vec::new();
+        loop {
+            match self.input.get(self.pos) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return StdString::from_utf8(buf).map_err(|_| self.error("invalid UTF-8 string"));
+                }
+                Some(b'\\') => {
+                    let c = match self.input.get(self.pos + 1) {
+                        Some(b'n') => b'\n',
+                        Some(b't') => b'\t',
+                        Some(b'r') => b'\r',
+                        Some(&c @ (b'"' | b'\\' | b'/')) => c,
+                        _ => return Err(self.error("unsupported escape sequence")),
+                    };
+                    buf.push(c);
+                    self.pos += 2;
+                }
+                Some(&c) => {
+                    buf.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn skip_value(&mut self) -> StdResult<(), StdString> {
+        match self.peek() {
+            Some(b'{') => self.parse_object(|parser, _| parser.skip_value()),
+            Some(b'[') => {
+                self.pos += 1;
+                loop {
+                    if self.peek() == Some(b']') {
+                        self.pos += 1;
+                        return Ok(());
+                    }
+                    self.skip_value()?;
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {}
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some(b'"') => self.parse_string().map(|_| ()),
+            Some(_) => {
+                // Numbers and literals
+                let start = self.pos;
+                while self.pos < self.input.len()
+                    && matches!(self.input[self.pos], b'a'..=b'z' | b'0'..=b'9' | b'-' | b'+' | b'.' | b'E')
+                {
+                    self.pos += 1;
+                }
+                match self.pos > start {
+                    true => Ok(()),
+                    false => Err(self.error("unexpected character")),
+                }
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+}
@@ -1,20 +1,251 @@
 use std::alloc::{self, Layout};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::os::raw::c_void;
-use std::ptr;
+use std::ptr::{self, NonNull};
+use std::string::String as StdString;
+
+use crate::function::FunctionInfo;
+use crate::util::TypeKey;
 
 pub(crate) static ALLOCATOR: ffi::lua_Alloc = allocator;
 
+/// A snapshot of memory usage returned by [`Lua::memory_report`].
+///
+/// Apart from `used_memory`, the report is populated only when memory tracking is enabled using
+/// [`Lua::set_memory_tracking`].
+///
+/// [`Lua::memory_report`]: crate::Lua::memory_report
+/// [`Lua::set_memory_tracking`]: crate::Lua::set_memory_tracking
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReport {
+    /// Amount of memory (in bytes) currently used by the Lua state.
+    pub used_memory: usize,
+    /// Number of allocations made since tracking was enabled.
+    pub allocations: u64,
+    /// Total number of bytes allocated since tracking was enabled (freed memory is not subtracted).
+    pub allocated_bytes: u64,
+    /// Live userdata created since tracking was enabled, grouped by Rust type.
+    ///
+    /// Sorted by the number of bytes, in descending order.
+    pub userdata: Vec<UserDataMemory>,
+    /// Functions that allocated memory since tracking was enabled.
+    ///
+    /// Sorted by the number of bytes, in descending order (ties are ordered by the number of
+    /// allocations and then by the function location).
+    pub top_allocators: Vec<AllocationSite>,
+    // Allocating stacks in the collapsed format
+    pub(crate) stacks: Vec<(StdString, u64)>,
+}
+
+/// Memory used by userdata of a Rust type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserDataMemory {
+    /// Name of the Rust type.
+    pub type_name: StdString,
+    /// Number of live (not yet collected) userdata.
+    pub count: usize,
+    /// Memory used by the userdata blocks (not including memory owned by the Rust values).
+    pub bytes: usize,
+}
+
+/// Memory allocated by a Lua (or C) function.
+#[derive(Clone, Debug)]
+pub struct AllocationSite {
+    /// Information about the function.
+    pub info: FunctionInfo,
+    /// Number of allocations.
+    pub allocations: u64,
+    /// Number of allocated bytes.
+    pub bytes: u64,
+}
+
+impl AllocationSite {
+    // Orders sites by the number of bytes (descending), ties are broken by the number of
+    // allocations and then by the function location, so the order is deterministic.
+    pub(crate) fn cmp_by_bytes(a: &Self, b: &Self) -> Ordering {
+        (b.bytes.cmp(&a.bytes))
+            .then_with(|| b.allocations.cmp(&a.allocations))
+            .then_with(|| a.info.source.cmp(&b.info.source))
+            .then_with(|| a.info.line_defined.cmp(&b.info.line_defined))
+            .then_with(|| a.info.name.cmp(&b.info.name))
+    }
+}
+
+/// Difference between two memory reports, returned by [`MemoryReport::growth_since`].
+#[derive(Clone, Debug, Default)]
+pub struct MemoryGrowth {
+    /// Change of the used memory (in bytes).
+    pub used_memory: isize,
+    /// Number of allocations made between the reports.
+    pub allocations: u64,
+    /// Number of bytes allocated between the reports.
+    pub allocated_bytes: u64,
+    /// Change of live userdata per Rust type (types without changes are omitted).
+    ///
+    /// Sorted by the number of bytes, in descending order.
+    pub userdata: Vec<UserDataGrowth>,
+    /// Functions that allocated memory between the reports.
+    ///
+    /// Sorted in the same order as [`MemoryReport::top_allocators`].
+    pub top_allocators: Vec<AllocationSite>,
+}
+
+/// Change of memory used by userdata of a Rust type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserDataGrowth {
+    /// Name of the Rust type.
+    pub type_name: StdString,
+    /// Change of the number of live userdata.
+    pub count: isize,
+    /// Change of memory used by the userdata blocks.
+    pub bytes: isize,
+}
+
+impl MemoryReport {
+    /// Returns the difference between this report and an `earlier` one.
+    ///
+    /// This can be used to find leaks: types and functions that keep growing between snapshots.
+    pub fn growth_since(&self, earlier: &MemoryReport) -> MemoryGrowth {
+        let mut userdata = HashMap::new();
+        for ud in &self.userdata {
+            userdata.insert(&ud.type_name, (ud.count as isize, ud.bytes as isize));
+        }
+        for ud in &earlier.userdata {
+            let entry = userdata.entry(&ud.type_name).or_default();
+            entry.0 -= ud.count as isize;
+            entry.1 -= ud.bytes as isize;
+        }
+        let mut userdata = (userdata.into_iter())
+            .filter(|(_, (count, bytes))| *count != 0 || *bytes != 0)
+            .map(|(type_name, (count, bytes))| UserDataGrowth {
+                type_name: type_name.clone(),
+                count,
+                bytes,
+            })
+            .collect::<Vec<_>>();
+        userdata.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.type_name.cmp(&b.type_name)));
+
+        let site_key = |site: &AllocationSite| {
+            let info = &site.info;
+            (info.source.clone(), info.line_defined, info.name.clone())
+        };
+        let earlier_sites = (earlier.top_allocators.iter())
+            .map(|site| (site_key(site), (site.allocations, site.bytes)))
+            .collect::<HashMap<_, _>>();
+        let mut top_allocators = (self.top_allocators.iter())
+            .filter_map(|site| {
+                let (allocations, bytes) = earlier_sites.get(&site_key(site)).copied().unwrap_or_default();
+                let site = AllocationSite {
+                    info: site.info.clone(),
+                    allocations: site.allocations.saturating_sub(allocations),
+                    bytes: site.bytes.saturating_sub(bytes),
+                };
+                (site.bytes > 0).then_some(site)
+            })
+            .collect::<Vec<_>>();
+        top_allocators.sort_by(AllocationSite::cmp_by_bytes);
+
+        MemoryGrowth {
+            used_memory: self.used_memory as isize - earlier.used_memory as isize,
+            allocations: self.allocations.saturating_sub(earlier.allocations),
+            allocated_bytes: self.allocated_bytes.saturating_sub(earlier.allocated_bytes),
+            userdata,
+            top_allocators,
+        }
+    }
+
+    /// Returns the allocating call stacks in the collapsed stack format, that can be used to
+    /// generate flamegraphs.
+    ///
+    /// Each line contains a call stack (functions separated by `;`) and the number of bytes
+    /// allocated.
+    pub fn to_collapsed_stacks(&self) -> StdString {
+        crate::profiler::collapsed_stacks(&self.stacks)
+    }
+}
+
 #[repr(C)]
 #[derive(Default)]
 pub(crate) struct MemoryState {
     used_memory: isize,
     memory_limit: isize,
+    // Total number of allocations and allocated bytes (never decremented)
+    allocations: u64,
+    allocated_bytes: u64,
     // Can be set to temporary ignore the memory limit.
     // This is used when calling `lua_pushcfunction` for lua5.1/jit/luau.
     ignore_limit: bool,
     // Indicates that the memory limit was reached on the last allocation.
     #[cfg(feature = "luau")]
     limit_reached: bool,
+    // Memory accounting of the running thread (if it has a limit)
+    thread_memory: Option<NonNull<ThreadMemory>>,
+    // Set when at least one thread has a memory limit
+    has_thread_memory: bool,
+    // Next free memory category to assign to a thread
+    #[cfg(feature = "luau")]
+    next_memory_category: u8,
+}
+
+/// Memory accounting of a thread (see [`Thread::set_memory_limit`]).
+///
+/// [`Thread::set_memory_limit`]: crate::Thread::set_memory_limit
+pub(crate) struct ThreadMemory {
+    limit: isize,
+    // Memory allocated (minus freed) while the thread was running
+    #[cfg(not(feature = "luau"))]
+    used: isize,
+    // Luau tracks memory per thread using memory categories
+    #[cfg(feature = "luau")]
+    category: u8,
+    #[cfg(feature = "luau")]
+    main_state: *mut ffi::lua_State,
+}
+
+static THREAD_MEMORY_TYPE_KEY: u8 = 0;
+
+impl TypeKey for ThreadMemory {
+    #[inline(always)]
+    fn type_key() -> *const c_void {
+        &THREAD_MEMORY_TYPE_KEY as *const u8 as *const c_void
+    }
+}
+
+impl ThreadMemory {
+    #[inline]
+    pub(crate) fn used_memory(&self) -> usize {
+        #[cfg(not(feature = "luau"))]
+        return self.used.max(0) as usize;
+        #[cfg(feature = "luau")]
+        return unsafe { ffi::lua_totalbytes(self.main_state, self.category as _) };
+    }
+
+    #[inline]
+    pub(crate) fn set_memory_limit(&mut self, limit: usize) -> usize {
+        let prev_limit = self.limit;
+        self.limit = limit as isize;
+        prev_limit as usize
+    }
+
+    // Checks if the allocation of `mem_diff` bytes fits to the thread limit
+    #[inline]
+    fn fits(&self, mem_diff: isize, ignore_limit: bool) -> bool {
+        let limited = self.limit > 0 && mem_diff > 0 && !ignore_limit;
+        !limited || self.used_memory() as isize + mem_diff <= self.limit
+    }
+}
+
+/// Makes memory accounting of a thread current while the thread is running.
+pub(crate) struct ThreadMemoryGuard {
+    mem_state: *mut MemoryState,
+    prev: Option<NonNull<ThreadMemory>>,
+}
+
+impl Drop for ThreadMemoryGuard {
+    fn drop(&mut self) {
+        unsafe { (*self.mem_state).thread_memory = self.prev };
+    }
 }
 
 impl MemoryState {
@@ -38,6 +269,12 @@ impl MemoryState {
         self.used_memory as usize
     }
 
+    /// Returns the total number of allocations and allocated bytes.
+    #[inline]
+    pub(crate) fn allocated(&self) -> (u64, u64) {
+        (self.allocations, self.allocated_bytes)
+    }
+
     #[inline]
     pub(crate) fn memory_limit(&self) -> usize {
         self.memory_limit as usize
@@ -50,6 +287,47 @@ impl MemoryState {
         prev_limit as usize
     }
 
+    /// Returns `true` if at least one thread has a memory limit.
+    #[inline]
+    pub(crate) fn has_thread_memory(&self) -> bool {
+        self.has_thread_memory
+    }
+
+    /// Creates memory accounting for a thread.
+    pub(crate) unsafe fn new_thread_memory(
+        &mut self,
+        #[allow(unused)] thread_state: *mut ffi::lua_State,
+    ) -> Option<ThreadMemory> {
+        #[cfg(feature = "luau")]
+        let category = {
+            // Category `0` is the default one
+            let category = self.next_memory_category.checked_add(1)?;
+            self.next_memory_category = category;
+            ffi::lua_setmemcat(thread_state, category as _);
+            category
+        };
+        self.has_thread_memory = true;
+        Some(ThreadMemory {
+            limit: 0,
+            #[cfg(not(feature = "luau"))]
+            used: 0,
+            #[cfg(feature = "luau")]
+            category,
+            #[cfg(feature = "luau")]
+            main_state: crate::util::get_main_state(thread_state).unwrap_or(thread_state),
+        })
+    }
+
+    /// Makes `thread_memory` current until the returned guard is dropped.
+    #[inline]
+    pub(crate) fn enter_thread(&mut self, thread_memory: NonNull<ThreadMemory>) -> ThreadMemoryGuard {
+        let prev = self.thread_memory.replace(thread_memory);
+        ThreadMemoryGuard {
+            mem_state: self,
+            prev,
+        }
+    }
+
     // This function is used primarily for calling `lua_pushcfunction` in lua5.1/jit/luau
     // to bypass the memory limit (if set).
     #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
@@ -99,6 +377,10 @@ unsafe extern "C-unwind" fn allocator(
             let layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
             alloc::dealloc(ptr as *mut u8, layout);
             mem_state.used_memory -= osize as isize;
+            #[cfg(not(feature = "luau"))]
+            if let Some(mut thread_memory) = mem_state.thread_memory {
+                thread_memory.as_mut().used -= osize as isize;
+            }
         }
         return ptr::null_mut();
     }
@@ -115,16 +397,20 @@ unsafe extern "C-unwind" fn allocator(
     }
     let mem_limit = mem_state.memory_limit;
     let new_used_memory = mem_state.used_memory + mem_diff;
-    if mem_limit > 0 && new_used_memory > mem_limit && !mem_state.ignore_limit {
+    let fits = (mem_limit <= 0 || new_used_memory <= mem_limit || mem_state.ignore_limit)
+        && match mem_state.thread_memory {
+            Some(thread_memory) => thread_memory.as_ref().fits(mem_diff, mem_state.ignore_limit),
+            None => true,
+        };
+    if !fits {
         #[cfg(feature = "luau")]
         {
             mem_state.limit_reached = true;
         }
         return ptr::null_mut();
     }
-    mem_state.used_memory += mem_diff;
 
-    if ptr.is_null() {
+    let new_ptr = if ptr.is_null() {
         // Allocate new memory
         let new_layout = match Layout::from_size_align(nsize, ffi::SYS_MIN_ALIGN) {
             Ok(layout) => layout,
@@ -134,14 +420,26 @@ unsafe extern "C-unwind" fn allocator(
         if new_ptr.is_null() {
             alloc::handle_alloc_error(new_layout);
         }
-        return new_ptr;
-    }
+        mem_state.allocations += 1;
+        new_ptr
+    } else {
+        // Reallocate memory
+        let old_layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
+        let new_ptr = alloc::realloc(ptr as *mut u8, old_layout, nsize) as *mut c_void;
+        if new_ptr.is_null() {
+            alloc::handle_alloc_error(old_layout);
+        }
+        new_ptr
+    };
 
-    // Reallocate memory
-    let old_layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
-    let new_ptr = alloc::realloc(ptr as *mut u8, old_layout, nsize) as *mut c_void;
-    if new_ptr.is_null() {
-        alloc::handle_alloc_error(old_layout);
+    // Account the memory only after it was successfully allocated
+    mem_state.used_memory = new_used_memory;
+    if mem_diff > 0 {
+        mem_state.allocated_bytes += mem_diff as u64;
+    }
+    #[cfg(not(feature = "luau"))]
+    if let Some(mut thread_memory) = mem_state.thread_memory {
+        thread_memory.as_mut().used += mem_diff;
     }
     new_ptr
 }
@@ -8,6 +8,7 @@ use std::result::Result as StdResult;
 use crate::error::Result;
 use crate::state::{Lua, RawLua};
 use crate::traits::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti};
+use crate::typedef::TypeDesc;
 use crate::util::check_stack;
 use crate::value::{Nil, Value};
 
@@ -29,6 +30,11 @@ impl<T: IntoLua, E: IntoLua> IntoLuaMulti for StdResult<T, E> {
             Err(err) => (Nil, err).push_into_stack_multi(lua),
         }
     }
+
+    #[inline]
+    fn lua_types() -> Vec<TypeDesc> {
+        vec![T::lua_type().optional(), E::lua_type().optional()]
+    }
 }
 
 impl<E: IntoLua> IntoLuaMulti for StdResult<(), E> {
@@ -47,6 +53,11 @@ impl<E: IntoLua> IntoLuaMulti for StdResult<(), E> {
             Err(err) => (Nil, err).push_into_stack_multi(lua),
         }
     }
+
+    #[inline]
+    fn lua_types() -> Vec<TypeDesc> {
+        vec![TypeDesc::Nil, E::lua_type().optional()]
+    }
 }
 
 impl<T: IntoLua> IntoLuaMulti for T {
@@ -62,6 +73,11 @@ impl<T: IntoLua> IntoLuaMulti for T {
         self.push_into_stack(lua)?;
         Ok(1)
     }
+
+    #[inline]
+    fn lua_types() -> Vec<TypeDesc> {
+        vec![T::lua_type()]
+    }
 }
 
 impl<T: FromLua> FromLuaMulti for T {
@@ -90,6 +106,11 @@ impl<T: FromLua> FromLuaMulti for T {
         }
         T::from_stack_arg(-nargs, i, to, lua)
     }
+
+    #[inline]
+    fn lua_types() -> Vec<TypeDesc> {
+        vec![T::lua_type()]
+    }
 }
 
 /// Multiple Lua values used for both argument passing and also for multiple return values.
@@ -297,6 +318,11 @@ impl<T: IntoLua> IntoLuaMulti for Variadic<T> {
     fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
         MultiValue::from_lua_iter(lua, self)
     }
+
+    #[inline]
+    fn lua_types() -> Vec<TypeDesc> {
+        vec![TypeDesc::Variadic(Box::new(T::lua_type()))]
+    }
 }
 
 impl<T: FromLua> FromLuaMulti for Variadic<T> {
@@ -308,6 +334,11 @@ impl<T: FromLua> FromLuaMulti for Variadic<T> {
             .collect::<Result<Vec<T>>>()
             .map(Variadic)
     }
+
+    #[inline]
+    fn lua_types() -> Vec<TypeDesc> {
+        vec![TypeDesc::Variadic(Box::new(T::lua_type()))]
+    }
 }
 
 macro_rules! impl_tuple {
@@ -322,6 +353,11 @@ macro_rules! impl_tuple {
             unsafe fn push_into_stack_multi(self, _lua: &RawLua) -> Result<c_int> {
                 Ok(0)
             }
+
+            #[inline]
+            fn lua_types() -> Vec<TypeDesc> {
+                Vec::new()
+            }
         }
 
         impl FromLuaMulti for () {
@@ -334,6 +370,11 @@ macro_rules! impl_tuple {
             unsafe fn from_stack_multi(_nvals: c_int, _lua: &RawLua) -> Result<Self> {
                 Ok(())
             }
+
+            #[inline]
+            fn lua_types() -> Vec<TypeDesc> {
+                Vec::new()
+            }
         }
     );
 
@@ -368,6 +409,13 @@ macro_rules! impl_tuple {
                 nresults += $last.push_into_stack_multi(lua)?;
                 Ok(nresults)
             }
+
+            #[inline]
+            fn lua_types() -> Vec<TypeDesc> {
+                let mut types = vec![$(<$name as IntoLua>::lua_type(),)*];
+                types.extend($last::lua_types());
+                types
+            }
         }
 
         impl<$($name,)* $last> FromLuaMulti for ($($name,)* $last,)
@@ -423,6 +471,13 @@ macro_rules! impl_tuple {
                 let $last = FromLuaMulti::from_stack_args(nargs, i, to, lua)?;
                 Ok(($($name,)* $last,))
             }
+
+            #[inline]
+            fn lua_types() -> Vec<TypeDesc> {
+                let mut types = vec![$(<$name as FromLua>::lua_type(),)*];
+                types.extend(<$last as FromLuaMulti>::lua_types());
+                types
+            }
         }
     );
 }
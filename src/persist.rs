@@ -0,0 +1,514 @@
+//! Persisting of Lua values (including closures) to bytes.
+
+use std::collections::{HashMap, HashSet};
+use std::os::raw::{c_int, c_void};
+use std::string::String as StdString;
+
+use crate::chunk::ChunkMode;
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::snapshot::{c_function, get_upvalues, set_upvalue};
+use crate::state::Lua;
+use crate::table::Table;
+use crate::traits::IntoLua;
+use crate::types::MaybeSend;
+use crate::userdata::{AnyUserData, UserData};
+use crate::util::field_path;
+use crate::value::{Nil, Value};
+
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+use crate::snapshot::join_upvalues;
+
+#[cfg(any(feature = "lua51", feature = "luajit"))]
+use crate::snapshot::{get_environment, set_environment};
+
+/// Trait for userdata types that can be persisted by [`Lua::persist`].
+///
+/// A persistable type is represented by a Lua value (which is persisted as well, so it can contain
+/// tables, functions or other persistable userdata). To restore the userdata, the type must be
+/// registered using [`Lua::register_persist`] before calling [`Lua::unpersist`].
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{Lua, Persist, Result, UserData, Value};
+/// # fn main() -> Result<()> {
+/// struct Point(i64, i64);
+///
+/// impl UserData for Point {}
+///
+/// impl Persist for Point {
+///     const NAME: &'static str = "Point";
+///
+///     fn persist(&self, lua: &Lua) -> Result<Value> {
+///         lua.pack([self.0, self.1])
+///     }
+///
+///     fn unpersist(lua: &Lua, value: Value) -> Result<Self> {
+///         let [x, y] = lua.unpack::<[i64; 2]>(value)?;
+///         Ok(Point(x, y))
+///     }
+/// }
+///
+/// let lua = Lua::new();
+/// lua.register_persist::<Point>();
+/// let data = lua.persist(None, Point(1, 2))?;
+/// let point = lua.unpersist(None, &data)?;
+/// assert_eq!(point.as_userdata().unwrap().borrow::<Point>()?.1, 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Lua::persist`]: crate::Lua::persist
+/// [`Lua::register_persist`]: crate::Lua::register_persist
+/// [`Lua::unpersist`]: crate::Lua::unpersist
+pub trait Persist: UserData + MaybeSend + Sized + 'static {
+    /// Unique name of the type in persisted data.
+    const NAME: &'static str;
+
+    /// Returns a Lua value that represents this userdata.
+    fn persist(&self, lua: &Lua) -> Result<Value>;
+
+    /// Restores the userdata from the value returned by [`Persist::persist`].
+    fn unpersist(lua: &Lua, value: Value) -> Result<Self>;
+}
+
+// Type-erased `Persist::persist` and `Persist::unpersist`
+pub(crate) type PersistFn = fn(&AnyUserData, &Lua) -> Result<Value>;
+pub(crate) type UnpersistFn = fn(&Lua, Value) -> Result<AnyUserData>;
+
+pub(crate) fn persist_userdata<T: Persist>(ud: &AnyUserData, lua: &Lua) -> Result<Value> {
+    T::persist(&*ud.borrow::<T>()?, lua)
+}
+
+pub(crate) fn unpersist_userdata<T: Persist>(lua: &Lua, value: Value) -> Result<AnyUserData> {
+    lua.create_userdata(T::unpersist(lua, value)?)
+}
+
+const MAGIC: &[u8] = b"\x1bMLuaP";
+const FORMAT_VERSION: u8 = 1;
+
+// Bytecode is not compatible between Lua versions
+#[cfg(feature = "lua54")]
+const LUA_VERSION: u8 = 0x54;
+#[cfg(feature = "lua53")]
+const LUA_VERSION: u8 = 0x53;
+#[cfg(feature = "lua52")]
+const LUA_VERSION: u8 = 0x52;
+#[cfg(feature = "lua51")]
+const LUA_VERSION: u8 = 0x51;
+#[cfg(feature = "luajit")]
+const LUA_VERSION: u8 = 0x4a;
+
+// Nesting limit to protect the Rust stack
+const MAX_DEPTH: usize = 256;
+
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_NUMBER: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_REF: u8 = 6;
+const TAG_PERMANENT: u8 = 7;
+const TAG_TABLE: u8 = 8;
+const TAG_FUNCTION: u8 = 9;
+const TAG_USERDATA: u8 = 10;
+const TAG_BUFFER: u8 = 11;
+
+// Upvalue kinds
+const UPVALUE_VALUE: u8 = 0;
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+const UPVALUE_SHARED: u8 = 1;
+
+// Location of a value in the persisted graph (used in error messages)
+enum Segment {
+    Field(Value),
+    Metatable,
+    Upvalue(StdString),
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    Environment,
+    UserData(&'static str),
+}
+
+pub(crate) fn persist(lua: &Lua, permanents: Option<&Table>, value: &Value) -> Result<Vec<u8>> {
+    let mut persister = Persister {
+        lua,
+        permanents,
+        buf: Vec::new(),
+        refs: HashMap::new(),
+        incomplete: HashSet::new(),
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        upvalues: HashMap::new(),
+        path: Vec::new(),
+    };
+    persister.buf.extend_from_slice(MAGIC);
+    persister.buf.extend_from_slice(&[FORMAT_VERSION, LUA_VERSION]);
+    persister.write_value(value)?;
+    Ok(persister.buf)
+}
+
+pub(crate) fn unpersist(lua: &Lua, permanents: Option<&Table>, data: &[u8]) -> Result<Value> {
+    let header = [MAGIC, &[FORMAT_VERSION, LUA_VERSION]].concat();
+    let Some(data) = data.strip_prefix(header.as_slice()) else {
+        return Err(Error::runtime(
+            "invalid persisted data (unknown format or Lua version)",
+        ));
+    };
+    let mut unpersister = Unpersister {
+        lua,
+        permanents,
+        data,
+        refs: Vec::new(),
+        depth: 0,
+    };
+    let value = unpersister.read_value()?;
+    if !unpersister.data.is_empty() {
+        return Err(Error::runtime("invalid persisted data (trailing bytes)"));
+    }
+    Ok(value)
+}
+
+struct Persister<'a> {
+    lua: &'a Lua,
+    permanents: Option<&'a Table>,
+    buf: Vec<u8>,
+    // Indices of the persisted objects (by pointer)
+    refs: HashMap<*const c_void, u64>,
+    // Userdata which are being persisted (they cannot be referenced until restored)
+    incomplete: HashSet<u64>,
+    // Function index and upvalue number of the persisted upvalues (by identifier)
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    upvalues: HashMap<*mut c_void, (u64, u32)>,
+    path: Vec<Segment>,
+}
+
+impl Persister<'_> {
+    fn write_value(&mut self, value: &Value) -> Result<()> {
+        match value {
+            Value::Nil => self.buf.push(TAG_NIL),
+            Value::Boolean(false) => self.buf.push(TAG_FALSE),
+            Value::Boolean(true) => self.buf.push(TAG_TRUE),
+            #[allow(clippy::useless_conversion)]
+            Value::Integer(i) => {
+                self.buf.push(TAG_INTEGER);
+                self.buf.extend_from_slice(&i64::from(*i).to_le_bytes());
+            }
+            Value::Number(n) => {
+                self.buf.push(TAG_NUMBER);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::String(s) => {
+                self.buf.push(TAG_STRING);
+                self.write_bytes(&s.as_bytes());
+            }
+            _ => return self.write_object(value),
+        }
+        Ok(())
+    }
+
+    fn write_object(&mut self, value: &Value) -> Result<()> {
+        if self.path.len() >= MAX_DEPTH {
+            return Err(self.error(value.type_name(), "nesting is too deep"));
+        }
+
+        let ptr = value.to_pointer();
+        if let Some(&idx) = self.refs.get(&ptr) {
+            if self.incomplete.contains(&idx) {
+                return Err(self.error(value.type_name(), "cyclic reference through userdata"));
+            }
+            self.buf.push(TAG_REF);
+            self.write_u64(idx);
+            return Ok(());
+        }
+
+        if let Some(permanents) = self.permanents {
+            let name = permanents.raw_get::<Value>(value)?;
+            if !name.is_nil() {
+                self.buf.push(TAG_PERMANENT);
+                return self.write_value(&name);
+            }
+        }
+
+        let idx = self.refs.len() as u64;
+        match value {
+            Value::Table(t) => {
+                self.refs.insert(ptr, idx);
+                self.write_table(t)
+            }
+            Value::Function(f) if c_function(f).is_some() => {
+                Err(self.error("C function", "it must be added to the permanents table"))
+            }
+            Value::Function(f) => {
+                self.refs.insert(ptr, idx);
+                self.write_function(f, idx)
+            }
+            Value::UserData(ud) => {
+                if let Some(buf) = crate::Buffer::from_userdata(ud) {
+                    self.refs.insert(ptr, idx);
+                    self.buf.push(TAG_BUFFER);
+                    self.write_bytes(&buf.to_vec()?);
+                    return Ok(());
+                }
+                let persist = unsafe { ud.0.lua.lock().get_userdata_ref_persist(&ud.0) }.unwrap_or(None);
+                let Some((name, persist)) = persist else {
+                    let type_name = ud.type_name().ok().flatten().unwrap_or_else(|| "userdata".into());
+                    let what = format!("userdata `{type_name}`");
+                    return Err(self.error(&what, "the type does not implement `Persist`"));
+                };
+                self.refs.insert(ptr, idx);
+                self.incomplete.insert(idx);
+                self.buf.push(TAG_USERDATA);
+                self.write_bytes(name.as_bytes());
+                let value = persist(ud, self.lua)?;
+                self.path.push(Segment::UserData(name));
+                self.write_value(&value)?;
+                self.path.pop();
+                self.incomplete.remove(&idx);
+                Ok(())
+            }
+            // The call stack of a coroutine cannot be saved or rebuilt through the Lua C API
+            Value::Thread(_) => Err(self.error("thread", "threads cannot be persisted")),
+            value => Err(self.error(value.type_name(), "it must be added to the permanents table")),
+        }
+    }
+
+    fn write_table(&mut self, t: &Table) -> Result<()> {
+        self.buf.push(TAG_TABLE);
+        for pair in t.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            self.write_value(&key)?;
+            self.path.push(Segment::Field(key));
+            self.write_value(&value)?;
+            self.path.pop();
+        }
+        self.buf.push(TAG_NIL);
+
+        // Metatable
+        let metatable = t.metatable().map(Value::Table).unwrap_or(Nil);
+        self.path.push(Segment::Metatable);
+        self.write_value(&metatable)?;
+        self.path.pop();
+        Ok(())
+    }
+
+    fn write_function(&mut self, f: &Function, _idx: u64) -> Result<()> {
+        self.buf.push(TAG_FUNCTION);
+        self.write_bytes(&f.dump(false));
+
+        let upvalues = get_upvalues(f)?;
+        self.write_u64(upvalues.len() as u64);
+        for (i, (name, value, _id)) in upvalues.into_iter().enumerate() {
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            {
+                if let Some(&(func_idx, n)) = self.upvalues.get(&_id) {
+                    self.buf.push(UPVALUE_SHARED);
+                    self.write_u64(func_idx);
+                    self.write_u64(n as u64);
+                    continue;
+                }
+                self.upvalues.insert(_id, (_idx, i as u32 + 1));
+            }
+            #[cfg(any(feature = "lua51", feature = "luajit"))]
+            let _ = i;
+            self.buf.push(UPVALUE_VALUE);
+            self.path.push(Segment::Upvalue(name));
+            self.write_value(&value)?;
+            self.path.pop();
+        }
+
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        {
+            let env = get_environment(f)?;
+            self.path.push(Segment::Environment);
+            self.write_value(&env)?;
+            self.path.pop();
+        }
+        Ok(())
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn error(&self, what: &str, reason: &str) -> Error {
+        let mut path = StdString::from("value");
+        for segment in &self.path {
+            path = match segment {
+                Segment::Field(key) => field_path(&path, key),
+                Segment::Metatable => format!("getmetatable({path})"),
+                Segment::Upvalue(name) => format!("upvalue `{name}` of {path}"),
+                #[cfg(any(feature = "lua51", feature = "luajit"))]
+                Segment::Environment => format!("getfenv({path})"),
+                Segment::UserData(name) => format!("{path} (persisted `{name}`)"),
+            };
+        }
+        Error::runtime(format!("cannot persist {what} at `{path}`: {reason}"))
+    }
+}
+
+struct Unpersister<'a> {
+    lua: &'a Lua,
+    permanents: Option<&'a Table>,
+    data: &'a [u8],
+    // Restored objects (`None` while a userdata is being restored)
+    refs: Vec<Option<Value>>,
+    depth: usize,
+}
+
+impl<'a> Unpersister<'a> {
+    fn read_value(&mut self) -> Result<Value> {
+        if self.depth >= MAX_DEPTH {
+            return Err(invalid_data("nesting is too deep"));
+        }
+        self.depth += 1;
+        let value = self.read_value_inner();
+        self.depth -= 1;
+        value
+    }
+
+    fn read_value_inner(&mut self) -> Result<Value> {
+        let value = match self.read_u8()? {
+            TAG_NIL => Nil,
+            TAG_FALSE => Value::Boolean(false),
+            TAG_TRUE => Value::Boolean(true),
+            TAG_INTEGER => Value::Integer(i64::from_le_bytes(self.read_array()?) as _),
+            TAG_NUMBER => Value::Number(f64::from_le_bytes(self.read_array()?) as _),
+            TAG_STRING => Value::String(self.lua.create_string(self.read_bytes()?)?),
+            TAG_REF => {
+                let idx = self.read_u64()?;
+                match self.refs.get(idx as usize) {
+                    Some(Some(value)) => value.clone(),
+                    _ => return Err(invalid_data("invalid reference")),
+                }
+            }
+            TAG_PERMANENT => {
+                let name = self.read_value()?;
+                let value = match self.permanents {
+                    Some(permanents) => permanents.raw_get::<Value>(&name)?,
+                    None => Nil,
+                };
+                if value.is_nil() {
+                    let name = name.to_string().unwrap_or_else(|_| name.type_name().into());
+                    return Err(Error::runtime(format!("missing permanent value `{name}`")));
+                }
+                value
+            }
+            TAG_TABLE => {
+                let t = self.lua.create_table()?;
+                self.refs.push(Some(Value::Table(t.clone())));
+                loop {
+                    let key = self.read_value()?;
+                    if key.is_nil() {
+                        break;
+                    }
+                    let value = self.read_value()?;
+                    t.raw_set(key, value)?;
+                }
+                match self.read_value()? {
+                    Value::Table(mt) => t.set_metatable(Some(mt)),
+                    Value::Nil => {}
+                    _ => return Err(invalid_data("invalid metatable")),
+                }
+                Value::Table(t)
+            }
+            TAG_FUNCTION => Value::Function(self.read_function()?),
+            TAG_USERDATA => {
+                let name = StdString::from_utf8_lossy(self.read_bytes()?).into_owned();
+                let idx = self.reserve();
+                let value = self.read_value()?;
+                let Some(unpersist) = self.lua.lock().get_unpersist(&name) else {
+                    let message =
+                        format!("userdata type `{name}` is not registered (see `Lua::register_persist`)");
+                    return Err(Error::runtime(message));
+                };
+                let ud = Value::UserData(unpersist(self.lua, value)?);
+                self.refs[idx] = Some(ud.clone());
+                ud
+            }
+            TAG_BUFFER => {
+                let buf = self.lua.create_buffer(self.read_bytes()?)?.into_lua(self.lua)?;
+                self.refs.push(Some(buf.clone()));
+                buf
+            }
+            _ => return Err(invalid_data("unknown value tag")),
+        };
+        Ok(value)
+    }
+
+    fn read_function(&mut self) -> Result<Function> {
+        let bytecode = self.read_bytes()?;
+        let f = (self.lua.lock()).load_chunk(None, None, Some(ChunkMode::Binary), bytecode)?;
+        self.refs.push(Some(Value::Function(f.clone())));
+
+        let nups = self.read_u64()?;
+        for n in 1..=nups {
+            let n = n as c_int;
+            match self.read_u8()? {
+                UPVALUE_VALUE => {
+                    let value = self.read_value()?;
+                    set_upvalue(&f, n, &value)?;
+                }
+                #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+                UPVALUE_SHARED => {
+                    let (idx, m) = (self.read_u64()?, self.read_u64()?);
+                    let Some(Some(Value::Function(g))) = self.refs.get(idx as usize) else {
+                        return Err(invalid_data("invalid shared upvalue"));
+                    };
+                    join_upvalues(&f, n, g, m as c_int)?;
+                }
+                _ => return Err(invalid_data("invalid upvalue")),
+            }
+        }
+
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        {
+            let env = self.read_value()?;
+            if let Value::Table(_) = env {
+                set_environment(&f, &env)?;
+            }
+        }
+        Ok(f)
+    }
+
+    fn reserve(&mut self) -> usize {
+        self.refs.push(None);
+        self.refs.len() - 1
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.read_exact(N)?;
+        Ok(bytes.try_into().expect("invalid length"))
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_u64()?;
+        self.read_exact(usize::try_from(len).map_err(|_| invalid_data("invalid length"))?)
+    }
+
+    fn read_exact(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(invalid_data("unexpected end of data"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+}
+
+fn invalid_data(reason: &str) -> Error {
+    Error::runtime(format!("invalid persisted data ({reason})"))
+}
@@ -0,0 +1,434 @@
+//! A pool of pre-created Lua states.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::os::raw::c_void;
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::{Error, Result};
+use crate::state::{Lua, LuaOptions};
+use crate::stdlib::StdLib;
+use crate::table::Table;
+use crate::types::{MaybeSend, XRc};
+use crate::value::{Nil, Value};
+
+#[cfg(feature = "async")]
+use std::task::{Poll, Waker};
+
+#[cfg(feature = "send")]
+type InitCallback = Box<dyn Fn(&Lua) -> Result<()> + Send>;
+
+#[cfg(not(feature = "send"))]
+type InitCallback = Box<dyn Fn(&Lua) -> Result<()>>;
+
+/// A pool of Lua states, which are reused between short-lived tasks (eg. requests).
+///
+/// The pool pre-creates a fixed number of states, each initialized by an optional init function.
+/// When a [`PooledLua`] guard is dropped, its state is reset and returned to the pool:
+/// - Globals, and tables directly referenced by globals (eg. `string`), are restored to their
+///   contents after initialization. Deeper changes are not tracked.
+/// - App data is cleared.
+/// - A full garbage collection cycle is performed.
+///
+/// If the reset fails, the state is replaced by a new one.
+///
+/// The pool is cheap to clone, and is `Send + Sync` with the `send` feature enabled.
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{LuaPool, Result};
+/// # fn main() -> Result<()> {
+/// let pool = LuaPool::builder(4)
+///     .init(|lua| lua.load("greeting = 'hello'").exec())
+///     .memory_limit(16 * 1024 * 1024)
+///     .build()?;
+///
+/// let lua = pool.get()?;
+/// lua.load("greeting = greeting .. ' world'").exec()?;
+/// drop(lua);
+///
+/// let lua = pool.get()?;
+/// assert_eq!(lua.globals().get::<String>("greeting")?, "hello");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct LuaPool {
+    inner: XRc<PoolInner>,
+}
+
+/// A builder of [`LuaPool`].
+pub struct LuaPoolBuilder {
+    size: usize,
+    libs: StdLib,
+    options: LuaOptions,
+    init: Option<InitCallback>,
+    memory_limit: Option<usize>,
+}
+
+/// Health metrics of a [`LuaPool`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LuaPoolStats {
+    /// Number of states managed by the pool.
+    pub size: usize,
+    /// Number of states available for checkout.
+    pub idle: usize,
+    /// Total number of checkouts.
+    pub checkouts: u64,
+    /// Number of states that failed to reset and were replaced.
+    pub failed_resets: u64,
+    /// Peak memory (in bytes) used by a state at the end of a checkout.
+    pub peak_memory: usize,
+}
+
+/// A Lua state checked out from a [`LuaPool`].
+///
+/// The state is reset and returned to the pool when the guard is dropped.
+pub struct PooledLua {
+    pool: XRc<PoolInner>,
+    entry: Option<PoolEntry>,
+}
+
+struct PoolInner {
+    libs: StdLib,
+    options: LuaOptions,
+    init: Option<Mutex<InitCallback>>,
+    memory_limit: Option<usize>,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+struct PoolState {
+    idle: Vec<PoolEntry>,
+    stats: LuaPoolStats,
+    #[cfg(feature = "async")]
+    wakers: Vec<Waker>,
+}
+
+struct PoolEntry {
+    lua: Lua,
+    baseline: Baseline,
+}
+
+// Contents (and metatables) of the global tables after initialization
+struct Baseline {
+    tables: Vec<TableBaseline>,
+}
+
+struct TableBaseline {
+    table: Table,
+    entries: Vec<(Value, Value)>,
+    metatable: Option<Table>,
+}
+
+impl LuaPool {
+    /// Creates a new pool of `size` Lua states with the safe subset of the standard libraries.
+    pub fn new(size: usize) -> Result<LuaPool> {
+        Self::builder(size).build()
+    }
+
+    /// Returns a builder of a new pool of `size` Lua states.
+    pub fn builder(size: usize) -> LuaPoolBuilder {
+        LuaPoolBuilder {
+            size,
+            libs: StdLib::ALL_SAFE,
+            options: LuaOptions::default(),
+            init: None,
+            memory_limit: None,
+        }
+    }
+
+    /// Checks out a Lua state, blocking the current thread until one is available.
+    pub fn get(&self) -> Result<PooledLua> {
+        let mut state = self.inner.state.lock();
+        loop {
+            if let Some(entry) = self.inner.take(&mut state)? {
+                drop(state);
+                return self.checkout(entry);
+            }
+            self.inner.available.wait(&mut state);
+        }
+    }
+
+    /// Checks out a Lua state if one is available.
+    pub fn try_get(&self) -> Result<Option<PooledLua>> {
+        let entry = self.inner.take(&mut self.inner.state.lock())?;
+        entry.map(|entry| self.checkout(entry)).transpose()
+    }
+
+    /// Checks out a Lua state, waiting asynchronously until one is available.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn get_async(&self) -> Result<PooledLua> {
+        let entry = std::future::poll_fn(|cx| {
+            let mut state = self.inner.state.lock();
+            match self.inner.take(&mut state) {
+                Ok(Some(entry)) => Poll::Ready(Ok(entry)),
+                Ok(None) => {
+                    if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                        state.wakers.push(cx.waker().clone());
+                    }
+                    Poll::Pending
+                }
+                Err(err) => Poll::Ready(Err(err)),
+            }
+        })
+        .await?;
+        self.checkout(entry)
+    }
+
+    /// Returns health metrics of the pool.
+    pub fn stats(&self) -> LuaPoolStats {
+        let state = self.inner.state.lock();
+        LuaPoolStats {
+            idle: state.idle.len(),
+            ..state.stats
+        }
+    }
+
+    fn checkout(&self, entry: PoolEntry) -> Result<PooledLua> {
+        let guard = PooledLua {
+            pool: self.inner.clone(),
+            entry: Some(entry),
+        };
+        if let Some(limit) = self.inner.memory_limit {
+            guard.set_memory_limit(guard.used_memory() + limit)?;
+        }
+        Ok(guard)
+    }
+}
+
+impl fmt::Debug for LuaPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LuaPool").field("stats", &self.stats()).finish()
+    }
+}
+
+impl LuaPoolBuilder {
+    /// Sets the standard libraries to load into each state.
+    ///
+    /// Only safe libraries can be loaded. Default: [`StdLib::ALL_SAFE`].
+    #[must_use]
+    pub fn std_libs(mut self, libs: StdLib) -> Self {
+        self.libs = libs;
+        self
+    }
+
+    /// Sets the options of each state.
+    #[must_use]
+    pub fn options(mut self, options: LuaOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets a function to initialize each state.
+    ///
+    /// The globals after initialization are used as a baseline to reset the state.
+    #[must_use]
+    pub fn init<F>(mut self, init: F) -> Self
+    where
+        F: Fn(&Lua) -> Result<()> + MaybeSend + 'static,
+    {
+        self.init = Some(Box::new(init));
+        self
+    }
+
+    /// Sets a memory limit (in bytes) for each checkout.
+    ///
+    /// The limit is added to the memory used by the state at the moment of checkout.
+    #[must_use]
+    pub fn memory_limit(mut self, limit: usize) -> Self {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// Creates the pool with all its states.
+    pub fn build(self) -> Result<LuaPool> {
+        if self.size == 0 {
+            return Err(Error::runtime("pool size must be greater than zero"));
+        }
+        let inner = PoolInner {
+            libs: self.libs,
+            options: self.options,
+            init: self.init.map(Mutex::new),
+            memory_limit: self.memory_limit,
+            state: Mutex::new(PoolState {
+                idle: Vec::with_capacity(self.size),
+                stats: LuaPoolStats::default(),
+                #[cfg(feature = "async")]
+                wakers: Vec::new(),
+            }),
+            available: Condvar::new(),
+        };
+        let mut idle = Vec::with_capacity(self.size);
+        for _ in 0..self.size {
+            idle.push(inner.create_entry()?);
+        }
+        {
+            let mut state = inner.state.lock();
+            state.idle = idle;
+            state.stats.size = self.size;
+        }
+        Ok(LuaPool {
+            inner: XRc::new(inner),
+        })
+    }
+}
+
+impl fmt::Debug for LuaPoolBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LuaPoolBuilder")
+            .field("size", &self.size)
+            .field("libs", &self.libs)
+            .field("options", &self.options)
+            .field("memory_limit", &self.memory_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PoolInner {
+    fn create_entry(&self) -> Result<PoolEntry> {
+        let lua = Lua::new_with(self.libs, self.options.clone())?;
+        if let Some(init) = &self.init {
+            (init.lock())(&lua)?;
+        }
+        let baseline = Baseline::capture(&lua)?;
+        Ok(PoolEntry { lua, baseline })
+    }
+
+    // Takes an idle state (if any)
+    fn take(&self, state: &mut PoolState) -> Result<Option<PoolEntry>> {
+        if state.stats.size == 0 {
+            return Err(Error::runtime("the pool has no Lua states left"));
+        }
+        let entry = state.idle.pop();
+        if entry.is_some() {
+            state.stats.checkouts += 1;
+        }
+        Ok(entry)
+    }
+
+    fn release(&self, entry: PoolEntry) {
+        let used_memory = entry.lua.used_memory();
+        let reset_failed = self.reset(&entry).is_err();
+        let entry = match reset_failed {
+            false => Some(entry),
+            true => {
+                drop(entry);
+                self.create_entry().ok()
+            }
+        };
+
+        let mut state = self.state.lock();
+        state.stats.peak_memory = state.stats.peak_memory.max(used_memory);
+        state.stats.failed_resets += reset_failed as u64;
+        match entry {
+            Some(entry) => state.idle.push(entry),
+            None => state.stats.size -= 1,
+        }
+        #[cfg(feature = "async")]
+        let wakers = std::mem::take(&mut state.wakers);
+        drop(state);
+
+        self.available.notify_one();
+        #[cfg(feature = "async")]
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    fn reset(&self, entry: &PoolEntry) -> Result<()> {
+        let lua = &entry.lua;
+        if self.memory_limit.is_some() {
+            lua.set_memory_limit(0)?;
+        }
+        entry.baseline.restore()?;
+        lua.clear_app_data();
+        lua.gc_collect()
+    }
+}
+
+impl Baseline {
+    fn capture(lua: &Lua) -> Result<Self> {
+        let globals = lua.globals();
+        let mut tables = Vec::new();
+        let mut seen = HashSet::<*const c_void>::new();
+        let mut capture = |table: Table| -> Result<()> {
+            #[cfg(feature = "luau")]
+            if table.is_readonly() {
+                return Ok(());
+            }
+            if !seen.insert(table.to_pointer()) {
+                return Ok(());
+            }
+            let entries = table.pairs::<Value, Value>().collect::<Result<Vec<_>>>()?;
+            let metatable = table.metatable();
+            tables.push(TableBaseline {
+                table,
+                entries,
+                metatable,
+            });
+            Ok(())
+        };
+        capture(globals.clone())?;
+        for pair in globals.pairs::<Value, Value>() {
+            if let (_, Value::Table(table)) = pair? {
+                capture(table)?;
+            }
+        }
+        Ok(Baseline { tables })
+    }
+
+    fn restore(&self) -> Result<()> {
+        for baseline in &self.tables {
+            let table = &baseline.table;
+            let keys = (table.pairs::<Value, Value>())
+                .map(|pair| pair.map(|(key, _)| key))
+                .collect::<Result<Vec<_>>>()?;
+            for key in keys {
+                table.raw_set(key, Nil)?;
+            }
+            for (key, value) in &baseline.entries {
+                table.raw_set(key, value)?;
+            }
+            table.set_metatable(baseline.metatable.clone());
+        }
+        Ok(())
+    }
+}
+
+impl Deref for PooledLua {
+    type Target = Lua;
+
+    fn deref(&self) -> &Lua {
+        &self
+            .entry
+            .as_ref()
+            .expect("Lua state is returned to the pool")
+            .lua
+    }
+}
+
+impl Drop for PooledLua {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            self.pool.release(entry);
+        }
+    }
+}
+
+impl fmt::Debug for PooledLua {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PooledLua").field(&**self).finish()
+    }
+}
+
+#[cfg(all(test, feature = "send"))]
+mod assertions {
+    use super::*;
+
+    static_assertions::assert_impl_all!(LuaPool: Send, Sync);
+    static_assertions::assert_impl_all!(PooledLua: Send);
+}
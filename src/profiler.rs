@@ -0,0 +1,657 @@
+//! CPU and memory allocation profiler for Lua code.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fmt::Write as _;
+use std::mem;
+use std::os::raw::{c_char, c_int};
+use std::string::String as StdString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::function::FunctionInfo;
+use crate::memory::{AllocationSite, MemoryState};
+use crate::state::WeakLua;
+use crate::types::XRc;
+use crate::util::{linenumber_to_usize, ptr_to_lossy_str, ptr_to_str};
+
+/// Options for [`Lua::start_profiler`].
+///
+/// [`Lua::start_profiler`]: crate::Lua::start_profiler
+#[derive(Clone, Copy, Debug)]
+pub struct ProfilerOptions {
+    mode: ProfilerMode,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ProfilerMode {
+    Sampling(u32),
+    #[cfg(not(feature = "luau"))]
+    Instrumenting,
+    // Attributes allocated memory to functions (see `Lua::set_memory_tracking`)
+    Allocations,
+}
+
+impl Default for ProfilerOptions {
+    fn default() -> Self {
+        Self::sampling(1000)
+    }
+}
+
+impl ProfilerOptions {
+    /// Returns options for a sampling profiler.
+    ///
+    /// The call stack is sampled every `interval` VM instructions (Lua 5.x) or every `interval`
+    /// interrupts (Luau, which are triggered at function calls and loop iterations).
+    ///
+    /// Sampling has low overhead, the results are represented as a number of samples.
+    pub const fn sampling(interval: u32) -> Self {
+        let interval = if interval == 0 { 1 } else { interval };
+        ProfilerOptions {
+            mode: ProfilerMode::Sampling(interval),
+        }
+    }
+
+    /// Returns options for an instrumenting profiler.
+    ///
+    /// Every function call and return is recorded. This gives exact call counts and timings,
+    /// but has a significant overhead.
+    ///
+    /// Requires `feature = "lua54/lua53/lua52/lua51/luajit"`
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub const fn instrumenting() -> Self {
+        ProfilerOptions {
+            mode: ProfilerMode::Instrumenting,
+        }
+    }
+
+    pub(crate) const fn allocations() -> Self {
+        ProfilerOptions {
+            mode: ProfilerMode::Allocations,
+        }
+    }
+}
+
+/// Statistics of a profiled function.
+#[derive(Clone, Debug)]
+pub struct FunctionProfile {
+    /// Information about the function.
+    pub info: FunctionInfo,
+    /// Number of calls (instrumenting profiler only).
+    pub calls: u64,
+    /// Number of samples where the function was running (sampling profiler only).
+    pub samples: u64,
+    /// Number of samples where the function was on the call stack (sampling profiler only).
+    pub total_samples: u64,
+    /// Time spent in the function itself (instrumenting profiler only).
+    pub self_time: Duration,
+    /// Time spent in the function, including called functions (instrumenting profiler only).
+    pub total_time: Duration,
+}
+
+/// A running (or finished) profiling session returned by [`Lua::start_profiler`].
+///
+/// Profiling stops when [`Profile::stop`] is called or the profile is dropped.
+///
+/// Functions are identified by their source and the line where they are defined. Each coroutine
+/// has its own call stack, samples taken while a coroutine is running are attributed to the
+/// coroutine functions only. Coroutines created before the profiler was started are not
+/// profiled (Lua 5.x).
+///
+/// [`Lua::start_profiler`]: crate::Lua::start_profiler
+pub struct Profile {
+    profiler: XRc<Profiler>,
+    lua: WeakLua,
+    running: AtomicBool,
+}
+
+impl Profile {
+    pub(crate) fn new(lua: WeakLua, profiler: XRc<Profiler>) -> Self {
+        Profile {
+            profiler,
+            lua,
+            running: AtomicBool::new(true),
+        }
+    }
+
+    /// Stops profiling.
+    ///
+    /// Any hook (or interrupt) set before the profiler was started is restored.
+    pub fn stop(&self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if let Some(lua) = self.lua.try_lock() {
+            unsafe { lua.remove_profiler(&self.profiler) };
+        }
+        let mut data = self.profiler.data.lock();
+        let now = self.profiler.elapsed();
+        data.finish(now);
+    }
+
+    /// Returns `true` if the profiler is still running.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Returns statistics of the profiled functions.
+    ///
+    /// Functions are sorted by the time (or number of samples) spent in the function itself, in
+    /// descending order.
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let data = self.profiler.data.lock();
+        let mut functions = data.functions.clone();
+        functions.sort_by_key(|f| Reverse((f.samples, f.self_time)));
+        functions
+    }
+
+    /// Returns the profile in the collapsed stack format, that can be used to generate
+    /// flamegraphs (eg. using [inferno] or [flamegraph.pl]).
+    ///
+    /// Each line contains a call stack (functions separated by `;`) and a weight: number of samples
+    /// for the sampling profiler or microseconds for the instrumenting profiler.
+    ///
+    /// [inferno]: https://github.com/jonhoo/inferno
+    /// [flamegraph.pl]: https://github.com/brendangregg/FlameGraph
+    pub fn to_collapsed_stacks(&self) -> StdString {
+        let data = self.profiler.data.lock();
+        collapsed_stacks(&data.collapsed_stacks())
+    }
+
+    /// Returns the profile in the [Chrome trace event] JSON format, that can be loaded into
+    /// `chrome://tracing`, [Perfetto] or [Speedscope].
+    ///
+    /// Each coroutine is represented as a separate thread.
+    ///
+    /// [Chrome trace event]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+    /// [Perfetto]: https://ui.perfetto.dev
+    /// [Speedscope]: https://www.speedscope.app
+    pub fn to_chrome_trace(&self) -> StdString {
+        let data = self.profiler.data.lock();
+        let mut events = Vec::new();
+        let mut threads = data.threads.values().collect::<Vec<_>>();
+        threads.sort_by_key(|t| t.tid);
+        for thread in threads {
+            let name = match thread.tid {
+                1 => "main".to_string(),
+                tid => format!("coroutine {}", tid - 1),
+            };
+            events.push(format!(
+                r#"{{"name":"thread_name","ph":"M","pid":1,"tid":{},"args":{{"name":"{name}"}}}}"#,
+                thread.tid
+            ));
+        }
+        for event in &data.trace {
+            let info = &data.functions[event.function].info;
+            events.push(format!(
+                r#"{{"name":{},"cat":"lua","ph":"X","pid":1,"tid":{},"ts":{:.3},"dur":{:.3},"args":{{"source":{},"line":{}}}}}"#,
+                json_string(&data.label(event.function)),
+                event.tid,
+                event.ts.as_secs_f64() * 1e6,
+                event.dur.as_secs_f64() * 1e6,
+                json_string(info.short_src.as_deref().unwrap_or("?")),
+                info.line_defined.unwrap_or(0),
+            ));
+        }
+        format!(
+            r#"{{"traceEvents":[{}],"displayTimeUnit":"ms"}}"#,
+            events.join(",")
+        )
+    }
+}
+
+impl Drop for Profile {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+pub(crate) struct Profiler {
+    mode: ProfilerMode,
+    start: Instant,
+    data: Mutex<ProfileData>,
+}
+
+#[derive(Default)]
+struct ProfileData {
+    functions: Vec<FunctionProfile>,
+    // Lua functions by source and line defined
+    function_ids: HashMap<Vec<u8>, HashMap<c_int, usize>>,
+    // C functions by name
+    c_function_ids: HashMap<Vec<u8>, usize>,
+    threads: HashMap<usize, ThreadData>,
+    // Stack (from root) to weight (samples or microseconds)
+    stacks: HashMap<Vec<usize>, u64>,
+    trace: Vec<TraceEvent>,
+    // Thread and timestamp of the last event (instrumenting profiler)
+    #[cfg(not(feature = "luau"))]
+    last_event: Option<(usize, Duration)>,
+    // Number of interrupts left until the next sample (Luau)
+    #[cfg(feature = "luau")]
+    countdown: u32,
+    // Function to number of allocations and allocated bytes (allocations profiler)
+    allocated: HashMap<usize, (u64, u64)>,
+    // Allocation counters at the start and at the last event
+    base_allocated: (u64, u64),
+    last_allocated: (u64, u64),
+}
+
+struct ThreadData {
+    tid: usize,
+    // Active frames (from root) with their start times
+    frames: Vec<Frame>,
+    // Time when the thread was running
+    #[cfg(not(feature = "luau"))]
+    clock: Duration,
+}
+
+struct Frame {
+    function: usize,
+    #[cfg(not(feature = "luau"))]
+    start_clock: Duration,
+    start_ts: Duration,
+}
+
+struct TraceEvent {
+    function: usize,
+    tid: usize,
+    ts: Duration,
+    dur: Duration,
+}
+
+impl Profiler {
+    pub(crate) fn new(options: ProfilerOptions) -> Self {
+        Profiler {
+            mode: options.mode,
+            start: Instant::now(),
+            data: Mutex::new(ProfileData::default()),
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn hook_triggers(&self) -> crate::HookTriggers {
+        match self.mode {
+            ProfilerMode::Sampling(interval) => crate::HookTriggers::new().every_nth_instruction(interval),
+            ProfilerMode::Instrumenting | ProfilerMode::Allocations => {
+                crate::HookTriggers::new().on_calls().on_returns()
+            }
+        }
+    }
+
+    /// Sets the current allocation counters as a starting point.
+    pub(crate) fn set_base_allocated(&self, allocated: (u64, u64)) {
+        let mut data = self.data.lock();
+        data.base_allocated = allocated;
+        data.last_allocated = allocated;
+    }
+
+    /// Returns the number of allocations and allocated bytes since the start, top allocating
+    /// functions and allocating stacks (in the collapsed format).
+    pub(crate) fn allocations_report(
+        &self,
+        allocated: (u64, u64),
+    ) -> (u64, u64, Vec<AllocationSite>, Vec<(StdString, u64)>) {
+        let data = self.data.lock();
+        let mut sites = (data.allocated.iter())
+            .map(|(&id, &(allocations, bytes))| AllocationSite {
+                info: data.functions[id].info.clone(),
+                allocations,
+                bytes,
+            })
+            .collect::<Vec<_>>();
+        sites.sort_by(AllocationSite::cmp_by_bytes);
+        (
+            allocated.0 - data.base_allocated.0,
+            allocated.1 - data.base_allocated.1,
+            sites,
+            data.collapsed_stacks(),
+        )
+    }
+
+    /// Handles a hook event (Lua 5.x).
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn on_hook(&self, state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+        let now = self.elapsed();
+        let mut data = self.data.lock();
+        match (self.mode, (*ar).event) {
+            (ProfilerMode::Sampling(_), ffi::LUA_HOOKCOUNT) => data.sample(state, now),
+            (ProfilerMode::Instrumenting, event) if event != ffi::LUA_HOOKCOUNT => {
+                data.advance(state, now);
+                match event {
+                    ffi::LUA_HOOKCALL => data.enter(state, ar, now),
+                    #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+                    ffi::LUA_HOOKTAILCALL => {
+                        data.leave(state, now);
+                        data.enter(state, ar, now);
+                    }
+                    // `LUA_HOOKTAILRET` in Lua 5.1: return from a function replaced by a tail call
+                    #[cfg(any(feature = "lua51", feature = "luajit"))]
+                    ffi::LUA_HOOKTAILCALL => data.leave(state, now),
+                    ffi::LUA_HOOKRET => data.leave(state, now),
+                    _ => {}
+                }
+            }
+            // On call the new function is already on the stack, allocations were made by the caller
+            (ProfilerMode::Allocations, ffi::LUA_HOOKCALL) => data.attribute_allocations(state, 1),
+            #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+            (ProfilerMode::Allocations, ffi::LUA_HOOKTAILCALL) => data.attribute_allocations(state, 1),
+            (ProfilerMode::Allocations, _) => data.attribute_allocations(state, 0),
+            _ => {}
+        }
+    }
+
+    /// Handles an interrupt (Luau).
+    #[cfg(feature = "luau")]
+    pub(crate) unsafe fn on_interrupt(&self, state: *mut ffi::lua_State) {
+        let mut data = self.data.lock();
+        match self.mode {
+            ProfilerMode::Sampling(_) if data.countdown > 1 => data.countdown -= 1,
+            ProfilerMode::Sampling(interval) => {
+                data.countdown = interval;
+                data.sample(state, self.elapsed());
+            }
+            ProfilerMode::Allocations => data.attribute_allocations(state, 0),
+        }
+    }
+}
+
+impl ProfileData {
+    // Records a call stack sample
+    unsafe fn sample(&mut self, state: *mut ffi::lua_State, now: Duration) {
+        let stack = self.stack(state, 0);
+        if stack.is_empty() {
+            return;
+        }
+
+        self.functions[stack[stack.len() - 1]].samples += 1;
+        for (i, &id) in stack.iter().enumerate() {
+            // Count recursive functions once
+            if !stack[..i].contains(&id) {
+                self.functions[id].total_samples += 1;
+            }
+        }
+
+        // Convert consecutive samples to trace events
+        let thread = self.thread(state);
+        let common = (thread.frames.iter().zip(&stack))
+            .take_while(|(frame, &id)| frame.function == id)
+            .count();
+        let tid = thread.tid;
+        let closed = thread.frames.drain(common..).rev().collect::<Vec<_>>();
+        for &function in &stack[common..] {
+            thread.frames.push(Frame {
+                function,
+                #[cfg(not(feature = "luau"))]
+                start_clock: Duration::ZERO,
+                start_ts: now,
+            });
+        }
+        for frame in closed {
+            self.trace.push(TraceEvent {
+                function: frame.function,
+                tid,
+                ts: frame.start_ts,
+                dur: now - frame.start_ts,
+            });
+        }
+
+        *self.stacks.entry(stack).or_default() += 1;
+    }
+
+    // Attributes memory allocated since the last event to the call stack starting at `level`
+    unsafe fn attribute_allocations(&mut self, state: *mut ffi::lua_State, level: c_int) {
+        let allocated = (*MemoryState::get(state)).allocated();
+        let allocations = allocated.0 - self.last_allocated.0;
+        let bytes = allocated.1 - self.last_allocated.1;
+        self.last_allocated = allocated;
+        if allocations == 0 && bytes == 0 {
+            return;
+        }
+
+        let stack = self.stack(state, level);
+        let Some(&function) = stack.last() else {
+            // Allocated outside of Lua functions
+            return;
+        };
+        let entry = self.allocated.entry(function).or_default();
+        entry.0 += allocations;
+        entry.1 += bytes;
+        *self.stacks.entry(stack).or_default() += bytes;
+    }
+
+    // Returns the call stack (from root) of the thread starting at `level`
+    unsafe fn stack(&mut self, state: *mut ffi::lua_State, mut level: c_int) -> Vec<usize> {
+        let mut stack = Vec::new();
+        let mut ar: ffi::lua_Debug = mem::zeroed();
+        #[cfg(not(feature = "luau"))]
+        while ffi::lua_getstack(state, level, &mut ar) != 0 {
+            mlua_assert!(
+                ffi::lua_getinfo(state, cstr!("Sn"), &mut ar) != 0,
+                "lua_getinfo failed with `Sn`"
+            );
+            stack.push(self.function_id(&ar));
+            level += 1;
+        }
+        #[cfg(feature = "luau")]
+        while ffi::lua_getinfo(state, level, cstr!("sn"), &mut ar) != 0 {
+            stack.push(self.function_id(&ar));
+            level += 1;
+        }
+        stack.reverse();
+        stack
+    }
+
+    // Accounts time passed since the last event to the previously running thread
+    #[cfg(not(feature = "luau"))]
+    fn advance(&mut self, state: *mut ffi::lua_State, now: Duration) {
+        let (last_state, last_ts) = self.last_event.replace((state as usize, now)).unwrap_or((0, now));
+        let Some(thread) = self.threads.get_mut(&last_state) else {
+            return;
+        };
+        let elapsed = now - last_ts;
+        thread.clock += elapsed;
+        if let Some(frame) = thread.frames.last() {
+            self.functions[frame.function].self_time += elapsed;
+            let stack = thread.frames.iter().map(|f| f.function).collect();
+            *self.stacks.entry(stack).or_default() += elapsed.as_micros() as u64;
+        }
+    }
+
+    // Function call (instrumenting profiler)
+    #[cfg(not(feature = "luau"))]
+    unsafe fn enter(&mut self, state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug, now: Duration) {
+        mlua_assert!(
+            ffi::lua_getinfo(state, cstr!("Sn"), ar) != 0,
+            "lua_getinfo failed with `Sn`"
+        );
+        let function = self.function_id(&*ar);
+        self.functions[function].calls += 1;
+
+        // Frames can be unwound by errors without calling the return hook
+        let mut depth = 0;
+        let mut ar2: ffi::lua_Debug = mem::zeroed();
+        while ffi::lua_getstack(state, depth, &mut ar2) != 0 {
+            depth += 1;
+        }
+        while self.thread(state).frames.len() >= depth as usize && !self.thread(state).frames.is_empty() {
+            self.leave(state, now);
+        }
+
+        let thread = self.thread(state);
+        let start_clock = thread.clock;
+        thread.frames.push(Frame {
+            function,
+            start_clock,
+            start_ts: now,
+        });
+    }
+
+    // Function return (instrumenting profiler)
+    #[cfg(not(feature = "luau"))]
+    fn leave(&mut self, state: *mut ffi::lua_State, now: Duration) {
+        let thread = self.thread(state);
+        let Some(frame) = thread.frames.pop() else {
+            return;
+        };
+        let total_time = thread.clock - frame.start_clock;
+        let tid = thread.tid;
+        // Count recursive functions once
+        if !thread.frames.iter().any(|f| f.function == frame.function) {
+            self.functions[frame.function].total_time += total_time;
+        }
+        self.trace.push(TraceEvent {
+            function: frame.function,
+            tid,
+            ts: frame.start_ts,
+            dur: now - frame.start_ts,
+        });
+    }
+
+    // Closes all active frames
+    fn finish(&mut self, now: Duration) {
+        for thread in self.threads.values_mut() {
+            for frame in thread.frames.drain(..).rev() {
+                self.trace.push(TraceEvent {
+                    function: frame.function,
+                    tid: thread.tid,
+                    ts: frame.start_ts,
+                    dur: now - frame.start_ts,
+                });
+            }
+        }
+        self.trace.sort_by_key(|event| (event.tid, event.ts));
+    }
+
+    fn thread(&mut self, state: *mut ffi::lua_State) -> &mut ThreadData {
+        let next_tid = self.threads.len() + 1;
+        self.threads.entry(state as usize).or_insert_with(|| ThreadData {
+            tid: next_tid,
+            frames: Vec::new(),
+            #[cfg(not(feature = "luau"))]
+            clock: Duration::ZERO,
+        })
+    }
+
+    unsafe fn function_id(&mut self, ar: &ffi::lua_Debug) -> usize {
+        let what = ptr_to_str(ar.what).unwrap_or("main");
+        let name = ptr_to_bytes(ar.name);
+        let source = ptr_to_bytes(ar.source).unwrap_or_default();
+        let id = self.functions.len();
+        if what == "C" {
+            let name = name.unwrap_or_default();
+            match self.c_function_ids.get(name) {
+                Some(&id) => return id,
+                None => _ = self.c_function_ids.insert(name.to_vec(), id),
+            }
+        } else {
+            let ids = match self.function_ids.get_mut(source) {
+                Some(ids) => ids,
+                None => self.function_ids.entry(source.to_vec()).or_default(),
+            };
+            match ids.get(&ar.linedefined) {
+                Some(&id) => return id,
+                None => _ = ids.insert(ar.linedefined, id),
+            }
+        }
+
+        let info = FunctionInfo {
+            name: ptr_to_lossy_str(ar.name).map(|s| s.into_owned()),
+            #[cfg(not(feature = "luau"))]
+            name_what: match ptr_to_str(ar.namewhat) {
+                Some("") => None,
+                val => val,
+            },
+            #[cfg(feature = "luau")]
+            name_what: None,
+            what,
+            source: ptr_to_lossy_str(ar.source).map(|s| s.into_owned()),
+            #[cfg(not(feature = "luau"))]
+            short_src: ptr_to_lossy_str(ar.short_src.as_ptr()).map(|s| s.into_owned()),
+            #[cfg(feature = "luau")]
+            short_src: ptr_to_lossy_str(ar.short_src).map(|s| s.into_owned()),
+            line_defined: linenumber_to_usize(ar.linedefined),
+            #[cfg(not(feature = "luau"))]
+            last_line_defined: linenumber_to_usize(ar.lastlinedefined),
+            #[cfg(feature = "luau")]
+            last_line_defined: None,
+        };
+        self.functions.push(FunctionProfile {
+            info,
+            calls: 0,
+            samples: 0,
+            total_samples: 0,
+            self_time: Duration::ZERO,
+            total_time: Duration::ZERO,
+        });
+        id
+    }
+
+    // Returns stacks with their weights in the collapsed format (sorted)
+    fn collapsed_stacks(&self) -> Vec<(StdString, u64)> {
+        let mut stacks = (self.stacks.iter())
+            .filter(|(_, &weight)| weight > 0)
+            .map(|(stack, &weight)| {
+                let frames = stack.iter().map(|&id| self.label(id)).collect::<Vec<_>>();
+                (frames.join(";"), weight)
+            })
+            .collect::<Vec<_>>();
+        stacks.sort();
+        stacks
+    }
+
+    // Returns a human-readable function name (eg. `fib (fib.lua:1)`)
+    fn label(&self, id: usize) -> StdString {
+        let info = &self.functions[id].info;
+        let src = info.short_src.as_deref().unwrap_or("?");
+        let label = match (info.what, &info.name) {
+            ("C", Some(name)) => format!("{name} [C]"),
+            ("C", None) => "? [C]".to_string(),
+            ("main", _) => format!("main chunk ({src})"),
+            (_, Some(name)) => format!("{name} ({src}:{})", info.line_defined.unwrap_or(0)),
+            (_, None) => format!("anonymous ({src}:{})", info.line_defined.unwrap_or(0)),
+        };
+        label.replace(';', ",")
+    }
+}
+
+unsafe fn ptr_to_bytes<'a>(input: *const c_char) -> Option<&'a [u8]> {
+    if input.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(input).to_bytes())
+}
+
+// Formats stacks in the collapsed format (one stack per line)
+pub(crate) fn collapsed_stacks(stacks: &[(StdString, u64)]) -> StdString {
+    let mut output = StdString::new();
+    for (stack, weight) in stacks {
+        _ = writeln!(output, "{stack} {weight}");
+    }
+    output
+}
+
+fn json_string(s: &str) -> StdString {
+    let mut output = StdString::with_capacity(s.len() + 2);
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => _ = write!(output, "\\u{:04x}", c as u32),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
@@ -0,0 +1,217 @@
+//! Custom module searchers for `require` (Lua 5.x).
+
+use std::borrow::{Borrow, Cow};
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{BuildHasher, Hash};
+use std::io;
+use std::path::PathBuf;
+use std::string::String as StdString;
+
+use crate::chunk::ChunkMode;
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::multi::MultiValue;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::traits::IntoLuaMulti;
+use crate::types::MaybeSend;
+
+/// Source code of a module found by a [`ModuleSearcher`].
+#[derive(Debug, Clone)]
+pub struct ModuleSource {
+    chunk_name: StdString,
+    source: Cow<'static, [u8]>,
+}
+
+impl ModuleSource {
+    /// Creates a new module source with the given chunk name.
+    ///
+    /// The chunk name is passed to [`Chunk::set_name`] and is shown in error messages and
+    /// tracebacks. Names starting with `@` are treated as (virtual) file paths.
+    ///
+    /// [`Chunk::set_name`]: crate::Chunk::set_name
+    pub fn new(chunk_name: impl Into<StdString>, source: impl Into<Cow<'static, [u8]>>) -> Self {
+        ModuleSource {
+            chunk_name: chunk_name.into(),
+            source: source.into(),
+        }
+    }
+
+    /// Returns the module chunk name.
+    pub fn chunk_name(&self) -> &str {
+        &self.chunk_name
+    }
+
+    /// Returns the module source code.
+    pub fn source(&self) -> &[u8] {
+        &self.source
+    }
+}
+
+/// A source of Lua modules for the `require` function.
+///
+/// Searchers are installed into `package.searchers` (`package.loaders` for Lua 5.1/LuaJIT) using
+/// [`Lua::add_module_searcher`] and resolve module names (eg. `foo.bar`) to their source code.
+///
+/// Implementations are provided for [`HashMap`] (module name to source code),
+/// [`DirectorySearcher`], [`EmbeddedSearcher`] and closures
+/// `Fn(&str) -> Result<Option<ModuleSource>>`.
+pub trait ModuleSearcher: MaybeSend + 'static {
+    /// Searches for the module `name`.
+    ///
+    /// Returns `None` if the module cannot be found by this searcher.
+    fn search(&self, name: &str) -> Result<Option<ModuleSource>>;
+}
+
+impl<F> ModuleSearcher for F
+where
+    F: Fn(&str) -> Result<Option<ModuleSource>> + MaybeSend + 'static,
+{
+    fn search(&self, name: &str) -> Result<Option<ModuleSource>> {
+        self(name)
+    }
+}
+
+/// Chunk names of the modules are virtual paths, eg. `@foo/bar.lua` for the `foo.bar` module.
+impl<K, V, S> ModuleSearcher for HashMap<K, V, S>
+where
+    K: Borrow<str> + Eq + Hash + MaybeSend + 'static,
+    V: AsRef<[u8]> + MaybeSend + 'static,
+    S: BuildHasher + MaybeSend + 'static,
+{
+    fn search(&self, name: &str) -> Result<Option<ModuleSource>> {
+        Ok((self.get(name)).map(|source| {
+            let chunk_name = format!("@{}.lua", name.replace('.', "/"));
+            ModuleSource::new(chunk_name, source.as_ref().to_vec())
+        }))
+    }
+}
+
+/// A [`ModuleSearcher`] that loads modules from a directory on the filesystem.
+///
+/// Module `foo.bar` is searched in the files `foo/bar.lua` and `foo/bar/init.lua` relative to the
+/// root directory. Modules cannot be loaded from outside of the root directory (including via
+/// symbolic links).
+///
+/// Chunk names of the modules are paths relative to the root directory.
+#[derive(Debug, Clone)]
+pub struct DirectorySearcher {
+    root: PathBuf,
+}
+
+impl DirectorySearcher {
+    /// Creates a new searcher with the given root directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        DirectorySearcher { root: root.into() }
+    }
+}
+
+impl ModuleSearcher for DirectorySearcher {
+    fn search(&self, name: &str) -> Result<Option<ModuleSource>> {
+        let Some(path) = module_path(name) else {
+            return Ok(None);
+        };
+        let root = match self.root.canonicalize() {
+            Ok(root) => root,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::external(err)),
+        };
+        for file_path in [format!("{path}.lua"), format!("{path}/init.lua")] {
+            let full_path = match root.join(&file_path).canonicalize() {
+                Ok(full_path) if full_path.starts_with(&root) && full_path.is_file() => full_path,
+                Ok(_) => continue,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(Error::external(err)),
+            };
+            let source = fs::read(&full_path).map_err(Error::external)?;
+            return Ok(Some(ModuleSource::new(format!("@{file_path}"), source)));
+        }
+        Ok(None)
+    }
+}
+
+/// A [`ModuleSearcher`] that loads modules from files embedded into the binary.
+///
+/// Files are given as a list of `(path, contents)` pairs, where path is relative to the bundle
+/// root (eg. `foo/bar.lua`). Module `foo.bar` is searched in the files `foo/bar.lua` and
+/// `foo/bar/init.lua`.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::EmbeddedSearcher;
+/// static MODULES: &[(&str, &[u8])] = &[
+///     ("utils.lua", b"return {}"),
+///     // ("config/init.lua", include_bytes!("../lua/config/init.lua")),
+/// ];
+///
+/// let searcher = EmbeddedSearcher::new(MODULES);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedSearcher {
+    files: &'static [(&'static str, &'static [u8])],
+}
+
+impl EmbeddedSearcher {
+    /// Creates a new searcher from the list of embedded files.
+    pub const fn new(files: &'static [(&'static str, &'static [u8])]) -> Self {
+        EmbeddedSearcher { files }
+    }
+}
+
+impl ModuleSearcher for EmbeddedSearcher {
+    fn search(&self, name: &str) -> Result<Option<ModuleSource>> {
+        let Some(path) = module_path(name) else {
+            return Ok(None);
+        };
+        for file_path in [format!("{path}.lua"), format!("{path}/init.lua")] {
+            let file = self
+                .files
+                .iter()
+                .find(|(p, _)| p.trim_start_matches("./") == file_path);
+            if let Some(&(_, source)) = file {
+                return Ok(Some(ModuleSource::new(format!("@{file_path}"), source)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Converts module name to a relative path (without extension).
+///
+/// Returns `None` if the name cannot be safely converted.
+fn module_path(name: &str) -> Option<StdString> {
+    // Splitting by `.` also guarantees absence of `.` and `..` path components
+    let valid = (name.split('.')).all(|part| !part.is_empty() && !part.contains(['/', '\\', ':', '\0']));
+    valid.then(|| name.replace('.', "/"))
+}
+
+pub(crate) fn create_searcher_function(lua: &Lua, searcher: impl ModuleSearcher) -> Result<Function> {
+    lua.create_function(move |lua, name: StdString| -> Result<MultiValue> {
+        match searcher.search(&name)? {
+            Some(module) => {
+                let loader = (lua.load(&*module.source))
+                    .set_name(&module.chunk_name)
+                    .set_mode(ChunkMode::Text)
+                    .into_function()?;
+                (loader, module.chunk_name).into_lua_multi(lua)
+            }
+            None if cfg!(feature = "lua54") => {
+                format!("no module '{name}' in custom searcher").into_lua_multi(lua)
+            }
+            None => format!("\n\tno module '{name}' in custom searcher").into_lua_multi(lua),
+        }
+    })
+}
+
+pub(crate) fn searchers_table(lua: &Lua) -> Result<Table> {
+    let loaded = lua.named_registry_value::<Table>("_LOADED")?;
+    let package = (loaded.raw_get::<Option<Table>>("package")?)
+        .ok_or_else(|| Error::runtime("package library is not loaded"))?;
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    let searchers = package.raw_get::<Table>("loaders")?;
+    #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+    let searchers = package.raw_get::<Table>("searchers")?;
+    Ok(searchers)
+}
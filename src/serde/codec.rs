@@ -0,0 +1,157 @@
+//! JSON, MessagePack and CBOR codecs exposed to Lua.
+
+use std::result::Result as StdResult;
+use std::string::String as StdString;
+
+use serde_value::Value as SerdeValue;
+
+use super::{de, ser, LuaSerdeExt};
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::string::String as LuaString;
+use crate::table::Table;
+use crate::util::json::Json;
+use crate::value::{SerializableValue, Value};
+
+type EncodeFn = fn(&SerializableValue) -> StdResult<Vec<u8>, StdString>;
+type DecodeFn = fn(&Lua, &[u8], ser::Options) -> StdResult<Result<Value>, StdString>;
+
+const CODECS: [(&str, EncodeFn, DecodeFn); 3] = [
+    ("json", encode_json, decode_json),
+    ("msgpack", encode_msgpack, decode_msgpack),
+    ("cbor", encode_cbor, decode_cbor),
+];
+
+// Object keys are sorted, as `serde_value` stores maps in a `BTreeMap`
+fn encode_json(value: &SerializableValue) -> StdResult<Vec<u8>, StdString> {
+    let value = serde_value::to_value(value).map_err(|err| err.to_string())?;
+    Ok(serde_to_json(value)?.to_string().into_bytes())
+}
+
+fn decode_json(lua: &Lua, data: &[u8], options: ser::Options) -> StdResult<Result<Value>, StdString> {
+    let value = Json::parse(data)?;
+    Ok(lua.to_value_with(&json_to_serde(value), options))
+}
+
+fn serde_to_json(value: SerdeValue) -> StdResult<Json, StdString> {
+    Ok(match value {
+        SerdeValue::Unit | SerdeValue::Option(None) => Json::Null,
+        SerdeValue::Option(Some(value)) | SerdeValue::Newtype(value) => serde_to_json(*value)?,
+        SerdeValue::Bool(b) => Json::Bool(b),
+        SerdeValue::I8(n) => n.into(),
+        SerdeValue::I16(n) => n.into(),
+        SerdeValue::I32(n) => n.into(),
+        SerdeValue::I64(n) => n.into(),
+        SerdeValue::U8(n) => n.into(),
+        SerdeValue::U16(n) => n.into(),
+        SerdeValue::U32(n) => n.into(),
+        SerdeValue::U64(n) => n.into(),
+        SerdeValue::F32(n) => Json::Float(n as f64),
+        SerdeValue::F64(n) => Json::Float(n),
+        SerdeValue::Char(c) => Json::String(c.to_string()),
+        SerdeValue::String(s) => Json::String(s),
+        SerdeValue::Bytes(bytes) => bytes.into(),
+        SerdeValue::Seq(seq) => Json::Array(seq.into_iter().map(serde_to_json).collect::<StdResult<_, _>>()?),
+        SerdeValue::Map(map) => Json::Object(
+            (map.into_iter())
+                .map(|(key, value)| Ok((json_key(key)?, serde_to_json(value)?)))
+                .collect::<StdResult<_, StdString>>()?,
+        ),
+    })
+}
+
+// JSON object keys must be strings, numbers and booleans are converted to strings
+fn json_key(key: SerdeValue) -> StdResult<StdString, StdString> {
+    match key {
+        SerdeValue::String(s) => Ok(s),
+        SerdeValue::Char(c) => Ok(c.to_string()),
+        SerdeValue::Newtype(key) => json_key(*key),
+        key @ (SerdeValue::Bool(_)
+        | SerdeValue::I8(_)
+        | SerdeValue::I16(_)
+        | SerdeValue::I32(_)
+        | SerdeValue::I64(_)
+        | SerdeValue::U8(_)
+        | SerdeValue::U16(_)
+        | SerdeValue::U32(_)
+        | SerdeValue::U64(_)) => Ok(serde_to_json(key)?.to_string()),
+        _ => Err("key must be a string".into()),
+    }
+}
+
+fn json_to_serde(value: Json) -> SerdeValue {
+    match value {
+        Json::Null => SerdeValue::Unit,
+        Json::Bool(b) => SerdeValue::Bool(b),
+        Json::Integer(i) => SerdeValue::I64(i),
+        Json::Float(n) => SerdeValue::F64(n),
+        Json::String(s) => SerdeValue::String(s),
+        Json::Array(array) => SerdeValue::Seq(array.into_iter().map(json_to_serde).collect()),
+        Json::Object(entries) => SerdeValue::Map(
+            (entries.into_iter())
+                .map(|(key, value)| (SerdeValue::String(key), json_to_serde(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn encode_msgpack(value: &SerializableValue) -> StdResult<Vec<u8>, StdString> {
+    rmp_serde::to_vec(value).map_err(|err| err.to_string())
+}
+
+fn decode_msgpack(lua: &Lua, data: &[u8], options: ser::Options) -> StdResult<Result<Value>, StdString> {
+    let value: SerdeValue = rmp_serde::from_slice(data).map_err(|err| err.to_string())?;
+    Ok(lua.to_value_with(&value, options))
+}
+
+fn encode_cbor(value: &SerializableValue) -> StdResult<Vec<u8>, StdString> {
+    let mut buf = Vec::new();
+    ciborium::into_writer(value, &mut buf).map_err(|err| err.to_string())?;
+    Ok(buf)
+}
+
+fn decode_cbor(lua: &Lua, data: &[u8], options: ser::Options) -> StdResult<Result<Value>, StdString> {
+    let value: SerdeValue = ciborium::from_reader(data).map_err(|err| err.to_string())?;
+    Ok(lua.to_value_with(&value, options))
+}
+
+/// Loads the `json`, `msgpack` and `cbor` modules into the globals table.
+pub(crate) fn load_codecs(lua: &Lua, ser_options: ser::Options, de_options: de::Options) -> Result<()> {
+    let globals = lua.globals();
+    for (name, encode, decode) in CODECS {
+        let module = create_module(lua, name, encode, decode, ser_options, de_options)?;
+        globals.raw_set(name, module)?;
+    }
+    Ok(())
+}
+
+fn create_module(
+    lua: &Lua,
+    name: &'static str,
+    encode: EncodeFn,
+    decode: DecodeFn,
+    ser_options: ser::Options,
+    de_options: de::Options,
+) -> Result<Table> {
+    let module = lua.create_table()?;
+
+    // Lua values are encoded using the same rules as `LuaSerdeExt::from_value_with`
+    let encode = lua.create_function(move |lua, value: Value| {
+        let value = SerializableValue::new(&value, de_options, None);
+        let data = encode(&value).map_err(|err| Error::SerializeError(format!("{name}: {err}")))?;
+        lua.create_string(data)
+    })?;
+    module.raw_set("encode", encode)?;
+
+    // Decoded data is converted using the same rules as `LuaSerdeExt::to_value_with`
+    let decode = lua.create_function(move |lua, data: LuaString| {
+        decode(lua, &data.as_bytes(), ser_options)
+            .map_err(|err| Error::deserialize_error(format!("{name}: {err}")))?
+    })?;
+    module.raw_set("decode", decode)?;
+
+    module.raw_set("null", lua.null())?;
+    module.raw_set("array_mt", lua.array_metatable())?;
+
+    Ok(module)
+}
@@ -1,17 +1,20 @@
 //! Deserialize Lua values to a Rust data structure.
 
 use std::cell::RefCell;
-use std::os::raw::c_void;
+use std::os::raw::{c_int, c_void};
 use std::rc::Rc;
 use std::result::Result as StdResult;
 use std::string::String as StdString;
+use std::{slice, str};
 
 use rustc_hash::FxHashSet;
 use serde::de::{self, IntoDeserializer};
 
 use crate::error::{Error, Result};
+use crate::state::RawLua;
 use crate::table::{Table, TablePairs, TableSequence};
 use crate::userdata::AnyUserData;
+use crate::util::{check_stack, index_segment, join_path, key_segment, value_key_segment, StackGuard};
 use crate::value::Value;
 
 /// A struct for deserializing Lua values into Rust values.
@@ -147,7 +150,7 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
                 serde_userdata(ud, |value| value.deserialize_any(visitor))
             }
             #[cfg(feature = "luau")]
-            Value::Buffer(buf) => visitor.visit_bytes(unsafe { buf.as_slice() }),
+            Value::Buffer(buf) => buf.with_slice(|data| visitor.visit_bytes(data))?,
             Value::Function(_)
             | Value::Thread(_)
             | Value::UserData(_)
@@ -207,8 +210,9 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
                         &"map with a single key",
                     ));
                 }
-                let skip = check_value_for_skip(&value, self.options, &self.visited)
-                    .map_err(|err| Error::DeserializeError(err.to_string()))?;
+                let skip = check_value_for_skip(&value, self.options, &self.visited).map_err(|err| {
+                    with_path_segment(Error::deserialize_error(err), || key_segment(&variant))
+                })?;
                 if skip {
                     return Err(de::Error::custom("bad enum value"));
                 }
@@ -252,6 +256,7 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
                 let len = t.raw_len();
                 let mut deserializer = SeqDeserializer {
                     seq: t.sequence_values(),
+                    index: 0,
                     options: self.options,
                     visited: self.visited,
                 };
@@ -299,6 +304,7 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
 
                 let mut deserializer = MapDeserializer {
                     pairs: MapPairs::new(&t, self.options.sort_keys)?,
+                    key: None,
                     value: None,
                     options: self.options,
                     visited: self.visited,
@@ -373,14 +379,57 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
         }
     }
 
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Value::String(ref s) => match s.to_str() {
+                Ok(s) => visitor.visit_str(&s),
+                Err(_) => visitor.visit_bytes(&s.as_bytes()),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    #[inline]
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Value::String(ref s) => visitor.visit_bytes(&s.as_bytes()),
+            #[cfg(feature = "luau")]
+            Value::Buffer(ref buf) => buf.with_slice(|data| visitor.visit_bytes(data))?,
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
     serde::forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
-        byte_buf identifier ignored_any
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char identifier ignored_any
     }
 }
 
 struct SeqDeserializer<'a> {
     seq: TableSequence<'a, Value>,
+    index: usize,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
 }
@@ -396,14 +445,18 @@ impl<'de> de::SeqAccess<'de> for SeqDeserializer<'_> {
             match self.seq.next() {
                 Some(value) => {
                     let value = value?;
-                    let skip = check_value_for_skip(&value, self.options, &self.visited)
-                        .map_err(|err| Error::DeserializeError(err.to_string()))?;
+                    self.index += 1;
+                    let index = self.index;
+                    let skip = check_value_for_skip(&value, self.options, &self.visited).map_err(|err| {
+                        with_path_segment(Error::deserialize_error(err), || index_segment(index))
+                    })?;
                     if skip {
                         continue;
                     }
                     let visited = Rc::clone(&self.visited);
                     let deserializer = Deserializer::from_parts(value, self.options, visited);
-                    return seed.deserialize(deserializer).map(Some);
+                    return (seed.deserialize(deserializer).map(Some))
+                        .map_err(|err| with_path_segment(err, || index_segment(index)));
                 }
                 None => return Ok(None),
             }
@@ -439,7 +492,9 @@ impl<'de> de::SeqAccess<'de> for VecDeserializer {
                 self.next += 1;
                 let visited = Rc::clone(&self.visited);
                 let deserializer = Deserializer::from_parts(Value::Number(n as _), self.options, visited);
-                seed.deserialize(deserializer).map(Some)
+                let index = self.next;
+                (seed.deserialize(deserializer).map(Some))
+                    .map_err(|err| with_path_segment(err, || index_segment(index)))
             }
             None => Ok(None),
         }
@@ -494,6 +549,8 @@ impl Iterator for MapPairs<'_> {
 
 struct MapDeserializer<'a> {
     pairs: MapPairs<'a>,
+    // The current key is kept to report the error path
+    key: Option<Value>,
     value: Option<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
@@ -507,13 +564,16 @@ impl MapDeserializer<'_> {
                 Some(item) => {
                     let (key, value) = item?;
                     let skip_key = check_value_for_skip(&key, self.options, &self.visited)
-                        .map_err(|err| Error::DeserializeError(err.to_string()))?;
-                    let skip_value = check_value_for_skip(&value, self.options, &self.visited)
-                        .map_err(|err| Error::DeserializeError(err.to_string()))?;
+                        .map_err(Error::deserialize_error)?;
+                    let skip_value =
+                        check_value_for_skip(&value, self.options, &self.visited).map_err(|err| {
+                            with_path_segment(Error::deserialize_error(err), || value_key_segment(&key))
+                        })?;
                     if skip_key || skip_value {
                         continue;
                     }
                     self.processed += 1;
+                    self.key = Some(key.clone());
                     self.value = Some(value);
                     let visited = Rc::clone(&self.visited);
                     let key_de = Deserializer::from_parts(key, self.options, visited);
@@ -554,7 +614,10 @@ impl<'de> de::MapAccess<'de> for MapDeserializer<'_> {
         T: de::DeserializeSeed<'de>,
     {
         match self.next_value_deserializer() {
-            Ok(value_de) => seed.deserialize(value_de),
+            Ok(value_de) => seed.deserialize(value_de).map_err(|err| match self.key.take() {
+                Some(key) => with_path_segment(err, || value_key_segment(&key)),
+                None => err,
+            }),
             Err(error) => Err(error),
         }
     }
@@ -582,17 +645,19 @@ impl<'de> de::EnumAccess<'de> for EnumDeserializer {
     where
         T: de::DeserializeSeed<'de>,
     {
-        let variant = self.variant.into_deserializer();
         let variant_access = VariantDeserializer {
+            variant: self.variant.clone(),
             value: self.value,
             options: self.options,
             visited: self.visited,
         };
+        let variant = self.variant.into_deserializer();
         seed.deserialize(variant).map(|v| (v, variant_access))
     }
 }
 
 struct VariantDeserializer {
+    variant: StdString,
     value: Option<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
@@ -616,7 +681,9 @@ impl<'de> de::VariantAccess<'de> for VariantDeserializer {
         T: de::DeserializeSeed<'de>,
     {
         match self.value {
-            Some(value) => seed.deserialize(Deserializer::from_parts(value, self.options, self.visited)),
+            Some(value) => seed
+                .deserialize(Deserializer::from_parts(value, self.options, self.visited))
+                .map_err(|err| with_path_segment(err, || key_segment(&self.variant))),
             None => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
                 &"newtype variant",
@@ -632,7 +699,8 @@ impl<'de> de::VariantAccess<'de> for VariantDeserializer {
             Some(value) => serde::Deserializer::deserialize_seq(
                 Deserializer::from_parts(value, self.options, self.visited),
                 visitor,
-            ),
+            )
+            .map_err(|err| with_path_segment(err, || key_segment(&self.variant))),
             None => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
                 &"tuple variant",
@@ -648,7 +716,8 @@ impl<'de> de::VariantAccess<'de> for VariantDeserializer {
             Some(value) => serde::Deserializer::deserialize_map(
                 Deserializer::from_parts(value, self.options, self.visited),
                 visitor,
-            ),
+            )
+            .map_err(|err| with_path_segment(err, || key_segment(&self.variant))),
             None => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
                 &"struct variant",
@@ -657,6 +726,499 @@ impl<'de> de::VariantAccess<'de> for VariantDeserializer {
     }
 }
 
+/// A deserializer that reads a value directly from the Lua stack.
+///
+/// Tables are traversed in place and strings are borrowed from the stack, so unlike
+/// [`Deserializer`] no [`Value`] handles are created for nested strings and tables.
+/// Other value types (and enums) fall back to the [`Deserializer`].
+pub(crate) struct StackDeserializer<'a> {
+    lua: &'a RawLua,
+    index: c_int,
+    options: Options,
+    visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+}
+
+impl<'a> StackDeserializer<'a> {
+    /// Creates a new deserializer for the value at the given stack index.
+    ///
+    /// The value must stay on the stack while the deserializer is in use.
+    pub(crate) unsafe fn new(lua: &'a RawLua, index: c_int, options: Options) -> Self {
+        let visited = Rc::new(RefCell::new(FxHashSet::default()));
+        Self::from_parts(lua, index, options, visited)
+    }
+
+    unsafe fn from_parts(
+        lua: &'a RawLua,
+        index: c_int,
+        options: Options,
+        visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    ) -> Self {
+        let index = ffi::lua_absindex(lua.state(), index);
+        StackDeserializer {
+            lua,
+            index,
+            options,
+            visited,
+        }
+    }
+
+    #[inline(always)]
+    fn value_type(&self) -> c_int {
+        unsafe { ffi::lua_type(self.lua.state(), self.index) }
+    }
+
+    // Returns bytes of the string value, borrowed from the stack
+    fn to_bytes(&self) -> &[u8] {
+        unsafe {
+            let mut size = 0;
+            let data = ffi::lua_tolstring(self.lua.state(), self.index, &mut size);
+            slice::from_raw_parts(data as *const u8, size)
+        }
+    }
+
+    // Returns `true` if the table is a sequence (has non-zero length or the array metatable)
+    fn is_sequence(&self) -> Result<bool> {
+        let state = self.lua.state();
+        unsafe {
+            if ffi::lua_rawlen(state, self.index) > 0 {
+                return Ok(true);
+            }
+            let _sg = StackGuard::new(state);
+            check_stack(state, 2)?;
+            if ffi::lua_getmetatable(state, self.index) == 0 {
+                return Ok(false);
+            }
+            crate::serde::push_array_metatable(state);
+            Ok(ffi::lua_rawequal(state, -1, -2) != 0)
+        }
+    }
+
+    // Converts the stack value to `Value` and uses the regular deserializer
+    fn into_value_deserializer(self) -> Deserializer {
+        let value = unsafe { self.lua.stack_value(self.index, None) };
+        Deserializer::from_parts(value, self.options, self.visited)
+    }
+
+    fn deserialize_table_seq<'de, V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let state = self.lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            let ptr = ffi::lua_topointer(state, self.index);
+            let _guard = RecursionGuard::from_pointer(ptr, &self.visited);
+
+            let len = ffi::lua_rawlen(state, self.index);
+            let mut deserializer = StackSeqDeserializer {
+                lua: self.lua,
+                table: self.index,
+                next: 1,
+                len,
+                options: self.options,
+                visited: self.visited,
+            };
+            let seq = visitor.visit_seq(&mut deserializer)?;
+            if deserializer.remaining()? == 0 {
+                Ok(seq)
+            } else {
+                Err(de::Error::invalid_length(len, &"fewer elements in the table"))
+            }
+        }
+    }
+
+    fn deserialize_table_map<'de, V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let state = self.lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            let ptr = ffi::lua_topointer(state, self.index);
+            let _guard = RecursionGuard::from_pointer(ptr, &self.visited);
+
+            check_stack(state, 1)?;
+            ffi::lua_pushnil(state);
+            let mut deserializer = StackMapDeserializer {
+                lua: self.lua,
+                table: self.index,
+                has_value: false,
+                finished: false,
+                options: self.options,
+                visited: self.visited,
+                processed: 0,
+            };
+            let map = visitor.visit_map(&mut deserializer)?;
+            let count = deserializer.remaining()?;
+            if count == 0 {
+                Ok(map)
+            } else {
+                Err(de::Error::invalid_length(
+                    deserializer.processed + count,
+                    &"fewer elements in the table",
+                ))
+            }
+        }
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for StackDeserializer<'_> {
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value_type() {
+            ffi::LUA_TSTRING => self.deserialize_str(visitor),
+            ffi::LUA_TTABLE if self.is_sequence()? => self.deserialize_table_seq(visitor),
+            ffi::LUA_TTABLE if !self.options.sort_keys => self.deserialize_table_map(visitor),
+            _ => self.into_value_deserializer().deserialize_any(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value_type() {
+            ffi::LUA_TNIL => visitor.visit_none(),
+            ffi::LUA_TLIGHTUSERDATA
+                if unsafe { ffi::lua_touserdata(self.lua.state(), self.index) }.is_null() =>
+            {
+                visitor.visit_none()
+            }
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    #[inline]
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.into_value_deserializer()
+            .deserialize_enum(name, variants, visitor)
+    }
+
+    #[inline]
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value_type() {
+            ffi::LUA_TTABLE => self.deserialize_table_seq(visitor),
+            _ => self.into_value_deserializer().deserialize_seq(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    #[inline]
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    #[inline]
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value_type() {
+            // Sorting keys requires collecting all pairs first
+            ffi::LUA_TTABLE if !self.options.sort_keys => self.deserialize_table_map(visitor),
+            _ => self.into_value_deserializer().deserialize_map(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value_type() {
+            ffi::LUA_TUSERDATA => self
+                .into_value_deserializer()
+                .deserialize_newtype_struct(name, visitor),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    #[inline]
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value_type() {
+            ffi::LUA_TSTRING | ffi::LUA_TTABLE => self.deserialize_any(visitor),
+            _ => self.into_value_deserializer().deserialize_unit(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value_type() {
+            ffi::LUA_TSTRING => {
+                let bytes = self.to_bytes();
+                match str::from_utf8(bytes) {
+                    Ok(s) => visitor.visit_str(s),
+                    Err(_) => visitor.visit_bytes(bytes),
+                }
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    #[inline]
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value_type() {
+            ffi::LUA_TSTRING => visitor.visit_bytes(self.to_bytes()),
+            _ => self.into_value_deserializer().deserialize_bytes(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char identifier ignored_any
+    }
+}
+
+struct StackSeqDeserializer<'a> {
+    lua: &'a RawLua,
+    table: c_int,
+    next: ffi::lua_Integer,
+    len: usize,
+    options: Options,
+    visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+}
+
+impl StackSeqDeserializer<'_> {
+    // Returns number of elements left in the sequence
+    unsafe fn remaining(&mut self) -> Result<usize> {
+        let state = self.lua.state();
+        check_stack(state, 1)?;
+        let mut count = 0;
+        while ffi::lua_rawgeti(state, self.table, self.next) != ffi::LUA_TNIL {
+            ffi::lua_pop(state, 1);
+            self.next += 1;
+            count += 1;
+        }
+        ffi::lua_pop(state, 1);
+        Ok(count)
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for StackSeqDeserializer<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let state = self.lua.state();
+        unsafe {
+            check_stack(state, 1)?;
+            loop {
+                let _sg = StackGuard::new(state);
+                if ffi::lua_rawgeti(state, self.table, self.next) == ffi::LUA_TNIL {
+                    return Ok(None);
+                }
+                self.next += 1;
+                let seq_index = self.next as usize - 1;
+                let index = ffi::lua_gettop(state);
+                let skip = check_stack_value_for_skip(self.lua, index, self.options, &self.visited)
+                    .map_err(|err| with_path_segment(err, || index_segment(seq_index)))?;
+                if skip {
+                    continue;
+                }
+                let visited = Rc::clone(&self.visited);
+                let deserializer = StackDeserializer::from_parts(self.lua, index, self.options, visited);
+                return (seed.deserialize(deserializer).map(Some))
+                    .map_err(|err| with_path_segment(err, || index_segment(seq_index)));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        // The hint is used only for preallocation, the actual sequence can be shorter
+        Some((self.len + 1).saturating_sub(self.next as usize))
+    }
+}
+
+// Keeps the current key (and the value, if not consumed yet) on top of the table
+struct StackMapDeserializer<'a> {
+    lua: &'a RawLua,
+    table: c_int,
+    has_value: bool,
+    finished: bool,
+    options: Options,
+    visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    processed: usize,
+}
+
+impl StackMapDeserializer<'_> {
+    // Returns number of pairs left in the table
+    unsafe fn remaining(&mut self) -> Result<usize> {
+        let state = self.lua.state();
+        if self.has_value {
+            ffi::lua_pop(state, 1);
+            self.has_value = false;
+        }
+        let mut count = 0;
+        if !self.finished {
+            check_stack(state, 2)?;
+            while ffi::lua_next(state, self.table) != 0 {
+                ffi::lua_pop(state, 1);
+                count += 1;
+            }
+            self.finished = true;
+        }
+        Ok(count)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for StackMapDeserializer<'_> {
+    type Error = Error;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.finished {
+            return Ok(None);
+        }
+        let state = self.lua.state();
+        unsafe {
+            if self.has_value {
+                ffi::lua_pop(state, 1);
+                self.has_value = false;
+            }
+            check_stack(state, 2)?;
+            loop {
+                if ffi::lua_next(state, self.table) == 0 {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                let top = ffi::lua_gettop(state);
+                let skip_key = check_stack_value_for_skip(self.lua, top - 1, self.options, &self.visited)?;
+                let skip_value = check_stack_value_for_skip(self.lua, top, self.options, &self.visited)
+                    .map_err(|err| with_path_segment(err, || stack_key_segment(self.lua, top - 1)))?;
+                if skip_key || skip_value {
+                    ffi::lua_pop(state, 1);
+                    continue;
+                }
+                self.processed += 1;
+                self.has_value = true;
+                let visited = Rc::clone(&self.visited);
+                let deserializer = StackDeserializer::from_parts(self.lua, top - 1, self.options, visited);
+                return seed.deserialize(deserializer).map(Some);
+            }
+        }
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if !self.has_value {
+            return Err(de::Error::custom("value is missing"));
+        }
+        let state = self.lua.state();
+        unsafe {
+            let visited = Rc::clone(&self.visited);
+            let deserializer = StackDeserializer::from_parts(self.lua, -1, self.options, visited);
+            // The key is still on the stack (below the value) and can be used to report the error path
+            let result = (seed.deserialize(deserializer))
+                .map_err(|err| with_path_segment(err, || stack_key_segment(self.lua, -2)));
+            ffi::lua_pop(state, 1);
+            self.has_value = false;
+            result
+        }
+    }
+}
+
+// Same as `check_value_for_skip` but for a value on the stack.
+// Avoids creating `Value` for strings and tables.
+unsafe fn check_stack_value_for_skip(
+    lua: &RawLua,
+    index: c_int,
+    options: Options,
+    visited: &RefCell<FxHashSet<*const c_void>>,
+) -> Result<bool> {
+    let state = lua.state();
+    let skip = match ffi::lua_type(state, index) {
+        ffi::LUA_TNIL | ffi::LUA_TBOOLEAN | ffi::LUA_TNUMBER | ffi::LUA_TSTRING => Ok(false),
+        ffi::LUA_TTABLE => {
+            let ptr = ffi::lua_topointer(state, index);
+            match visited.borrow().contains(&ptr) {
+                true if options.deny_recursive_tables => Err("recursive table detected"),
+                true => Ok(true),
+                false => Ok(false),
+            }
+        }
+        _ => check_value_for_skip(&lua.stack_value(index, None), options, visited),
+    };
+    skip.map_err(Error::deserialize_error)
+}
+
 // Adds `ptr` to the `visited` map and removes on drop
 // Used to track recursive tables but allow to traverse same tables multiple times
 pub(crate) struct RecursionGuard {
@@ -667,8 +1229,12 @@ pub(crate) struct RecursionGuard {
 impl RecursionGuard {
     #[inline]
     pub(crate) fn new(table: &Table, visited: &Rc<RefCell<FxHashSet<*const c_void>>>) -> Self {
+        Self::from_pointer(table.to_pointer(), visited)
+    }
+
+    #[inline]
+    fn from_pointer(ptr: *const c_void, visited: &Rc<RefCell<FxHashSet<*const c_void>>>) -> Self {
         let visited = Rc::clone(visited);
-        let ptr = table.to_pointer();
         visited.borrow_mut().insert(ptr);
         RecursionGuard { ptr, visited }
     }
@@ -711,6 +1277,21 @@ pub(crate) fn check_value_for_skip(
     Ok(false) // do not skip
 }
 
+// Prepends the table key (or index) `segment` to the path of a deserialization error
+fn with_path_segment(err: Error, segment: impl FnOnce() -> StdString) -> Error {
+    match err {
+        Error::DeserializeError { path, message } => {
+            let path = join_path(&segment(), &path);
+            Error::DeserializeError { path, message }
+        }
+        err => err,
+    }
+}
+
+unsafe fn stack_key_segment(lua: &RawLua, index: c_int) -> StdString {
+    value_key_segment(&lua.stack_value(index, None))
+}
+
 fn serde_userdata<V>(
     ud: AnyUserData,
     f: impl FnOnce(serde_value::Value) -> std::result::Result<V, serde_value::DeserializerError>,
@@ -718,7 +1299,7 @@ fn serde_userdata<V>(
     match serde_value::to_value(ud) {
         Ok(value) => match f(value) {
             Ok(r) => Ok(r),
-            Err(error) => Err(Error::DeserializeError(error.to_string())),
+            Err(error) => Err(Error::deserialize_error(error)),
         },
         Err(error) => Err(Error::SerializeError(error.to_string())),
     }
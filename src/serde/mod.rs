@@ -6,10 +6,14 @@ use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 
 use crate::error::Result;
+use crate::function::Function;
 use crate::private::Sealed;
-use crate::state::Lua;
+use crate::state::{Lua, RawLua};
+use crate::string::String;
 use crate::table::Table;
-use crate::util::check_stack;
+use crate::thread::Thread;
+use crate::userdata::AnyUserData;
+use crate::util::{check_stack, StackGuard};
 use crate::value::Value;
 
 /// Trait for serializing/deserializing Lua values using Serde.
@@ -185,6 +189,85 @@ pub trait LuaSerdeExt: Sealed {
     /// ```
     #[allow(clippy::wrong_self_convention)]
     fn from_value_with<T: DeserializeOwned>(&self, value: Value, options: de::Options) -> Result<T>;
+
+    /// Deserializes a [`Value`] into an existing object, reusing its allocations where possible.
+    ///
+    /// This is the same as [`from_value`] but uses [`Deserialize::deserialize_in_place`], which
+    /// is useful to repeatedly reload large structures (e.g. configuration) without reallocating
+    /// them.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`from_value`]: #method.from_value
+    /// [`Deserialize::deserialize_in_place`]: serde::Deserialize::deserialize_in_place
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let mut names = Vec::<String>::with_capacity(16);
+    ///     let val = lua.load(r#"{"Alice", "Bob"}"#).eval()?;
+    ///     lua.from_value_in_place(val, &mut names)?;
+    ///
+    ///     assert_eq!(names, ["Alice", "Bob"]);
+    ///     assert!(names.capacity() >= 16);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[allow(clippy::wrong_self_convention)]
+    fn from_value_in_place<T: DeserializeOwned>(&self, value: Value, place: &mut T) -> Result<()>;
+
+    /// Loads the `json`, `msgpack` and `cbor` modules into the global environment.
+    ///
+    /// Each module has `encode(value)` and `decode(data)` functions, [`null`] and
+    /// [`array_metatable`] (as `null` and `array_mt` fields).
+    /// Lua values are encoded and decoded using the default [`de::Options`] and [`ser::Options`]
+    /// respectively, the same way as [`from_value`] and [`to_value`] do.
+    ///
+    /// Requires `feature = "codecs"`
+    ///
+    /// [`null`]: #method.null
+    /// [`array_metatable`]: #method.array_metatable
+    /// [`from_value`]: #method.from_value
+    /// [`to_value`]: #method.to_value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     lua.load_serde_codecs()?;
+    ///
+    ///     lua.load(r#"
+    ///         local data = json.decode('{"a": [1, 2, 3], "b": null}')
+    ///         assert(#data.a == 3 and data.b == json.null)
+    ///         assert(json.encode(setmetatable({}, json.array_mt)) == "[]")
+    ///
+    ///         local value = msgpack.decode(msgpack.encode({ s = "hello" }))
+    ///         assert(value.s == "hello")
+    ///     "#).exec()
+    /// }
+    /// ```
+    #[cfg(feature = "codecs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codecs")))]
+    fn load_serde_codecs(&self) -> Result<()>;
+
+    /// Loads the `json`, `msgpack` and `cbor` modules into the global environment using custom
+    /// options.
+    ///
+    /// `ser_options` are used to convert decoded data to Lua values and `de_options` are used to
+    /// encode Lua values.
+    ///
+    /// Requires `feature = "codecs"`
+    #[cfg(feature = "codecs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codecs")))]
+    fn load_serde_codecs_with(&self, ser_options: ser::Options, de_options: de::Options) -> Result<()>;
 }
 
 impl LuaSerdeExt for Lua {
@@ -218,14 +301,72 @@ impl LuaSerdeExt for Lua {
     where
         T: DeserializeOwned,
     {
-        T::deserialize(de::Deserializer::new(value))
+        self.from_value_with(value, de::Options::default())
     }
 
     fn from_value_with<T>(&self, value: Value, options: de::Options) -> Result<T>
     where
         T: DeserializeOwned,
     {
-        T::deserialize(de::Deserializer::new_with_options(value, options))
+        // Values of another Lua state cannot be pushed to the stack
+        if !is_owned_by(&value, &self.lock()) {
+            return T::deserialize(de::Deserializer::new_with_options(value, options));
+        }
+        let lua = self.lock();
+        unsafe {
+            let state = lua.state();
+            let _sg = StackGuard::new(state);
+            check_stack(state, 1)?;
+
+            lua.push_value(&value)?;
+            T::deserialize(de::StackDeserializer::new(&lua, -1, options))
+        }
+    }
+
+    fn from_value_in_place<T>(&self, value: Value, place: &mut T) -> Result<()>
+    where
+        T: DeserializeOwned,
+    {
+        if !is_owned_by(&value, &self.lock()) {
+            let deserializer = de::Deserializer::new_with_options(value, de::Options::default());
+            return T::deserialize_in_place(deserializer, place);
+        }
+        let lua = self.lock();
+        unsafe {
+            let state = lua.state();
+            let _sg = StackGuard::new(state);
+            check_stack(state, 1)?;
+
+            lua.push_value(&value)?;
+            let deserializer = de::StackDeserializer::new(&lua, -1, de::Options::default());
+            T::deserialize_in_place(deserializer, place)
+        }
+    }
+
+    #[cfg(feature = "codecs")]
+    fn load_serde_codecs(&self) -> Result<()> {
+        codec::load_codecs(self, ser::Options::default(), de::Options::default())
+    }
+
+    #[cfg(feature = "codecs")]
+    fn load_serde_codecs_with(&self, ser_options: ser::Options, de_options: de::Options) -> Result<()> {
+        codec::load_codecs(self, ser_options, de_options)
+    }
+}
+
+// Checks that the value does not refer to an object of another Lua state, so it can be pushed
+// to the stack of `lua`
+fn is_owned_by(value: &Value, lua: &RawLua) -> bool {
+    match value {
+        Value::String(String(vref))
+        | Value::Table(Table(vref))
+        | Value::Function(Function(vref))
+        | Value::Thread(Thread(vref, ..))
+        | Value::UserData(AnyUserData(vref))
+        | Value::Other(vref) => &vref.lua == lua.weak(),
+        #[cfg(feature = "luau")]
+        Value::Buffer(crate::Buffer(vref)) => &vref.lua == lua.weak(),
+        _ => true,
     }
 }
 
@@ -251,6 +392,8 @@ pub(crate) unsafe fn push_array_metatable(state: *mut ffi::lua_State) {
 
 static ARRAY_METATABLE_REGISTRY_KEY: u8 = 0;
 
+#[cfg(feature = "codecs")]
+mod codec;
 pub mod de;
 pub mod ser;
 
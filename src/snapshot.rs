@@ -0,0 +1,433 @@
+//! Copying of Lua states (forks and snapshots).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::string::String as StdString;
+
+use crate::chunk::ChunkMode;
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::userdata::AnyUserData;
+use crate::util::{check_stack, field_path, StackGuard};
+use crate::value::{Nil, Value};
+
+// Depth of the global tables whose contents are matched between the states (eg. `package.searchers[1]`)
+const BUILTINS_DEPTH: usize = 3;
+
+/// A copy of a Lua state, which can be restored any number of times.
+///
+/// Created by [`Lua::snapshot`]. The snapshot holds a separate Lua instance that never runs any
+/// code, so later changes of the original state do not affect it.
+///
+/// [`Lua::snapshot`]: crate::Lua::snapshot
+pub struct Snapshot {
+    lua: Lua,
+}
+
+impl Snapshot {
+    pub(crate) fn new(lua: Lua) -> Self {
+        Snapshot { lua }
+    }
+
+    /// Creates a new Lua instance with a copy of the snapshot state.
+    ///
+    /// See [`Lua::fork`] for details.
+    ///
+    /// [`Lua::fork`]: crate::Lua::fork
+    pub fn restore(&self) -> Result<Lua> {
+        self.lua.fork()
+    }
+}
+
+impl fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Snapshot").finish_non_exhaustive()
+    }
+}
+
+/// Copies globals and named registry values of the `src` state into the (new) `dst` state.
+pub(crate) fn copy_state(src: &Lua, dst: &Lua) -> Result<()> {
+    let mut copier = Copier::new(src, dst)?;
+    copier.copy(&Value::Table(src.globals()), || "_G".into())?;
+
+    // Copy registry values that are not set by Lua or mlua
+    let (src_registry, dst_registry) = (registry(src), registry(dst));
+    for pair in src_registry.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        let Value::String(name) = &key else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        if name.starts_with("__mlua") || dst_registry.raw_get::<Value>(&*name)? != Nil {
+            continue;
+        }
+        let value = copier.copy(&value, || format!("registry[{name:?}]"))?;
+        dst_registry.raw_set(&*name, value)?;
+    }
+
+    copier.finish()
+}
+
+struct Copier<'a> {
+    dst: &'a Lua,
+    // Values already copied to the target state (by the source pointer)
+    copied: HashMap<*const c_void, Value>,
+    // Paths of the builtin tables and functions in the source state
+    builtin_paths: HashMap<*const c_void, Vec<Vec<PathKey>>>,
+    // Builtin tables, functions and userdata of the target state
+    dst_builtins: HashMap<Vec<PathKey>, Value>,
+    // Copied functions by the (source) upvalue identifiers, to share upvalues between closures
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    upvalues: HashMap<*mut c_void, (Function, c_int)>,
+    pending: Vec<Pending>,
+}
+
+// Key of a builtin value in the global environment
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum PathKey {
+    Name(StdString),
+    Index(Integer),
+}
+
+// Tables and functions which contents are not copied yet
+enum Pending {
+    Table {
+        src: Table,
+        dst: Table,
+        path: StdString,
+        builtin: bool,
+    },
+    Function {
+        src: Function,
+        dst: Function,
+        path: StdString,
+    },
+}
+
+impl<'a> Copier<'a> {
+    fn new(src: &Lua, dst: &'a Lua) -> Result<Self> {
+        let mut builtin_paths = HashMap::<_, Vec<_>>::new();
+        for (path, value) in collect_builtins(src.globals())? {
+            builtin_paths.entry(value.to_pointer()).or_default().push(path);
+        }
+        let dst_builtins = collect_builtins(dst.globals())?.into_iter().collect();
+        Ok(Copier {
+            dst,
+            copied: HashMap::new(),
+            builtin_paths,
+            dst_builtins,
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            upvalues: HashMap::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    // Returns a copy of the value in the target state
+    //
+    // Contents of tables and functions are copied later (see `finish`).
+    fn copy(&mut self, value: &Value, path: impl FnOnce() -> StdString) -> Result<Value> {
+        match value {
+            Value::Nil
+            | Value::Boolean(_)
+            | Value::LightUserData(_)
+            | Value::Integer(_)
+            | Value::Number(_) => return Ok(value.clone()),
+            Value::String(s) => return Ok(Value::String(self.dst.create_string(s.as_bytes())?)),
+            Value::Error(_) => return Ok(value.clone()),
+            _ => {}
+        }
+
+        let ptr = value.to_pointer();
+        if let Some(copied) = self.copied.get(&ptr) {
+            return Ok(copied.clone());
+        }
+        let copied = match value {
+            Value::Table(t) => {
+                let (dst, builtin) = match self.builtin(value) {
+                    Some(Value::Table(dst)) => (dst, true),
+                    _ => (self.dst.create_table()?, false),
+                };
+                self.pending.push(Pending::Table {
+                    src: t.clone(),
+                    dst: dst.clone(),
+                    path: path(),
+                    builtin,
+                });
+                Value::Table(dst)
+            }
+            Value::Function(f) if c_function(f).is_some() => (self.builtin(value))
+                .ok_or_else(|| unsupported("C function", &path(), "it's not a standard library function"))?,
+            Value::Function(f) => {
+                let bytecode = f.dump(false);
+                let dst = (self.dst.lock()).load_chunk(None, None, Some(ChunkMode::Binary), &bytecode)?;
+                self.pending.push(Pending::Function {
+                    src: f.clone(),
+                    dst: dst.clone(),
+                    path: path(),
+                });
+                Value::Function(dst)
+            }
+            Value::UserData(ud) => Value::UserData(self.copy_userdata(ud, path)?),
+            Value::Thread(_) => return Err(unsupported("thread", &path(), "threads cannot be copied")),
+            value => return Err(unsupported(value.type_name(), &path(), "unsupported type")),
+        };
+        self.copied.insert(ptr, copied.clone());
+        Ok(copied)
+    }
+
+    fn copy_userdata(&mut self, ud: &AnyUserData, path: impl FnOnce() -> StdString) -> Result<AnyUserData> {
+        // Userdata of the standard library (eg. `io.stdout`)
+        if let Some(Value::UserData(ud)) = self.builtin(&Value::UserData(ud.clone())) {
+            return Ok(ud);
+        }
+        if let Some(buf) = crate::Buffer::from_userdata(ud) {
+            return Ok(self.dst.create_buffer(buf.to_vec()?)?.into_userdata());
+        }
+        // Userdata not created by mlua cannot have a cloner
+        let cloner = unsafe { ud.0.lua.lock().get_userdata_ref_cloner(&ud.0) }.unwrap_or(None);
+        if let Some(cloner) = cloner {
+            if let Some(copy) = cloner(ud, self.dst)? {
+                return Ok(copy);
+            }
+        }
+        let type_name = ud.type_name()?.unwrap_or_else(|| "userdata".into());
+        let what = format!("userdata `{type_name}`");
+        Err(unsupported(
+            &what,
+            &path(),
+            "`UserData::clone_into` is not implemented",
+        ))
+    }
+
+    // Returns the target builtin value (eg. `string.format`) matching the source value
+    fn builtin(&self, value: &Value) -> Option<Value> {
+        let paths = self.builtin_paths.get(&value.to_pointer())?;
+        let mut candidates = paths.iter().filter_map(|path| self.dst_builtins.get(path));
+        candidates
+            .find(|dst_value| match (value, dst_value) {
+                (Value::Table(_), Value::Table(_)) => true,
+                (Value::Function(f), Value::Function(g)) => c_function(f) == c_function(g),
+                (Value::UserData(_), Value::UserData(_)) => true,
+                _ => false,
+            })
+            .cloned()
+    }
+
+    // Copies contents of the pending tables and functions
+    fn finish(mut self) -> Result<()> {
+        while let Some(pending) = self.pending.pop() {
+            match pending {
+                Pending::Table {
+                    src,
+                    dst,
+                    path,
+                    builtin,
+                } => self.copy_table(&src, &dst, &path, builtin)?,
+                Pending::Function { src, dst, path } => self.copy_function(&src, &dst, &path)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_table(&mut self, src: &Table, dst: &Table, path: &str, builtin: bool) -> Result<()> {
+        if builtin {
+            // Remove values that are not present in the source table
+            let keys = (dst.pairs::<Value, Value>())
+                .map(|pair| pair.map(|(key, _)| key))
+                .collect::<Result<Vec<_>>>()?;
+            for key in keys {
+                dst.raw_set(key, Nil)?;
+            }
+        }
+
+        for pair in src.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            let dst_key = self.copy(&key, || format!("{path} (key)"))?;
+            let dst_value = self.copy(&value, || field_path(path, &key))?;
+            dst.raw_set(dst_key, dst_value)?;
+        }
+
+        let metatable = match src.metatable() {
+            Some(mt) => match self.copy(&Value::Table(mt), || format!("getmetatable({path})"))? {
+                Value::Table(mt) => Some(mt),
+                _ => None,
+            },
+            None => None,
+        };
+        dst.set_metatable(metatable);
+        Ok(())
+    }
+
+    fn copy_function(&mut self, src: &Function, dst: &Function, path: &str) -> Result<()> {
+        for (i, (name, value, _id)) in get_upvalues(src)?.into_iter().enumerate() {
+            let n = i as c_int + 1;
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            if let Some((f, m)) = self.upvalues.get(&_id) {
+                join_upvalues(dst, n, f, *m)?;
+                continue;
+            }
+            let value = self.copy(&value, || format!("upvalue `{name}` of {path}"))?;
+            set_upvalue(dst, n, &value)?;
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            self.upvalues.insert(_id, (dst.clone(), n));
+        }
+
+        // Lua 5.1 functions have environments
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        {
+            let env = get_environment(src)?;
+            let env = self.copy(&env, || format!("getfenv({path})"))?;
+            set_environment(dst, &env)?;
+        }
+        Ok(())
+    }
+}
+
+// Collects tables, functions and userdata of the global environment (up to `BUILTINS_DEPTH`
+// levels deep)
+fn collect_builtins(globals: Table) -> Result<Vec<(Vec<PathKey>, Value)>> {
+    let mut builtins = vec![(Vec::new(), Value::Table(globals))];
+    let mut i = 0;
+    while i < builtins.len() {
+        let (path, value) = &builtins[i];
+        i += 1;
+        let Value::Table(table) = value else {
+            continue;
+        };
+        if path.len() >= BUILTINS_DEPTH {
+            continue;
+        }
+        let (path, table) = (path.clone(), table.clone());
+        for pair in table.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            let key = match key {
+                Value::String(s) => PathKey::Name(s.to_string_lossy()),
+                Value::Integer(i) => PathKey::Index(i),
+                _ => continue,
+            };
+            if matches!(value, Value::Table(_) | Value::Function(_) | Value::UserData(_)) {
+                let mut path = path.clone();
+                path.push(key);
+                builtins.push((path, value));
+            }
+        }
+    }
+    Ok(builtins)
+}
+
+fn unsupported(what: &str, path: &str, reason: &str) -> Error {
+    Error::runtime(format!("cannot copy {what} at `{path}`: {reason}"))
+}
+
+fn registry(lua: &Lua) -> Table {
+    let lua = lua.lock();
+    let state = lua.state();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        ffi::lua_pushvalue(state, ffi::LUA_REGISTRYINDEX);
+        Table(lua.pop_ref())
+    }
+}
+
+// Returns address of the C function (or `None` for Lua functions)
+//
+// LuaJIT fast functions have no address (null).
+pub(crate) fn c_function(f: &Function) -> Option<*const ()> {
+    let lua = f.0.lua.lock();
+    let state = lua.state();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        check_stack(state, 1).ok()?;
+        lua.push_ref(&f.0);
+        match ffi::lua_iscfunction(state, -1) {
+            0 => None,
+            _ => Some(ffi::lua_tocfunction(state, -1).map_or(ptr::null(), |f| f as *const ())),
+        }
+    }
+}
+
+// Returns upvalues of the Lua function with their names and identifiers (Lua 5.2+)
+pub(crate) fn get_upvalues(f: &Function) -> Result<Vec<(StdString, Value, *mut c_void)>> {
+    let lua = f.0.lua.lock();
+    let state = lua.state();
+    let mut upvalues = Vec::new();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        check_stack(state, 4)?;
+        lua.push_ref(&f.0);
+        for n in 1.. {
+            let name = ffi::lua_getupvalue(state, -1, n);
+            if name.is_null() {
+                break;
+            }
+            let name = std::ffi::CStr::from_ptr(name).to_string_lossy().into_owned();
+            let value = lua.pop_value();
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            let id = ffi::lua_upvalueid(state, -1, n);
+            #[cfg(any(feature = "lua51", feature = "luajit"))]
+            let id = std::ptr::null_mut();
+            upvalues.push((name, value, id));
+        }
+    }
+    Ok(upvalues)
+}
+
+pub(crate) fn set_upvalue(f: &Function, n: c_int, value: &Value) -> Result<()> {
+    let lua = f.0.lua.lock();
+    let state = lua.state();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        check_stack(state, 3)?;
+        lua.push_ref(&f.0);
+        lua.push_value(value)?;
+        ffi::lua_setupvalue(state, -2, n);
+    }
+    Ok(())
+}
+
+// Makes the `n`-th upvalue of `f` refer to the `m`-th upvalue of `g`
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+pub(crate) fn join_upvalues(f: &Function, n: c_int, g: &Function, m: c_int) -> Result<()> {
+    let lua = f.0.lua.lock();
+    let state = lua.state();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        check_stack(state, 2)?;
+        lua.push_ref(&f.0);
+        lua.push_ref(&g.0);
+        ffi::lua_upvaluejoin(state, -2, n, -1, m);
+    }
+    Ok(())
+}
+
+#[cfg(any(feature = "lua51", feature = "luajit"))]
+pub(crate) fn get_environment(f: &Function) -> Result<Value> {
+    let lua = f.0.lua.lock();
+    let state = lua.state();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        check_stack(state, 3)?;
+        lua.push_ref(&f.0);
+        ffi::lua_getfenv(state, -1);
+        Ok(lua.pop_value())
+    }
+}
+
+#[cfg(any(feature = "lua51", feature = "luajit"))]
+pub(crate) fn set_environment(f: &Function, env: &Value) -> Result<()> {
+    let lua = f.0.lua.lock();
+    let state = lua.state();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        check_stack(state, 3)?;
+        lua.push_ref(&f.0);
+        lua.push_value(env)?;
+        ffi::lua_setfenv(state, -2);
+    }
+    Ok(())
+}
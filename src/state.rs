@@ -7,18 +7,26 @@ use std::panic::Location;
 use std::result::Result as StdResult;
 use std::{fmt, mem, ptr};
 
+use parking_lot::Mutex;
+
+use crate::buffer::Buffer;
+use crate::channel::{transfer_userdata, Channel, Transferable};
 use crate::chunk::{AsChunk, Chunk};
+use crate::coverage::{Coverage, CoverageData};
 use crate::error::{Error, Result};
 use crate::function::Function;
 use crate::hook::Debug;
-use crate::memory::MemoryState;
+use crate::limits::{ExecutionBudget, ExecutionLimits};
+use crate::memory::{MemoryReport, MemoryState};
 use crate::multi::MultiValue;
+use crate::profiler::{Profile, Profiler, ProfilerOptions};
 use crate::scope::Scope;
 use crate::stdlib::StdLib;
 use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
 use crate::traits::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti};
+use crate::typedef::TypeDefinitions;
 use crate::types::{
     AppDataRef, AppDataRefMut, ArcReentrantMutexGuard, Integer, LuaType, MaybeSend, Number, ReentrantMutex,
     ReentrantMutexGuard, RegistryKey, VmState, XRc, XWeak,
@@ -30,10 +38,18 @@ use crate::util::{
 use crate::value::{Nil, Value};
 
 #[cfg(not(feature = "luau"))]
-use crate::hook::HookTriggers;
+use crate::{
+    hook::{HookHandle, HookTriggers},
+    persist::{self, persist_userdata, unpersist_userdata, Persist},
+    searcher::ModuleSearcher,
+    snapshot::{copy_state, Snapshot},
+};
 
 #[cfg(any(feature = "luau", doc))]
-use crate::{buffer::Buffer, chunk::Compiler};
+use crate::chunk::Compiler;
+
+#[cfg(feature = "luau")]
+use crate::luau::ModuleResolver;
 
 #[cfg(feature = "async")]
 use {
@@ -261,6 +277,11 @@ impl Lua {
         #[cfg(feature = "luau")]
         mlua_expect!(lua.configure_luau(), "Error configuring Luau");
 
+        #[cfg(not(feature = "luau"))]
+        if libs.contains(StdLib::BUFFER) {
+            mlua_expect!(crate::buffer::load_lib(&lua), "Error loading buffer library");
+        }
+
         lua
     }
 
@@ -335,6 +356,46 @@ impl Lua {
         unsafe { self.lock().load_std_libs(libs) }
     }
 
+    /// Installs a custom module searcher used by the `require` function.
+    ///
+    /// The searcher is inserted into `package.searchers` (`package.loaders` for Lua 5.1/LuaJIT)
+    /// right after the preload searcher, so it takes priority over the searchers that load modules
+    /// from the filesystem. Searchers added later take priority over the ones added earlier.
+    ///
+    /// Loaded modules get chunk names provided by the searcher (see [`ModuleSource`]), which are
+    /// shown in error messages and tracebacks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use mlua::{Lua, Result};
+    /// # #[cfg(not(feature = "luau"))]
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    ///
+    /// let modules = HashMap::from([("utils.math", "return { add = function(a, b) return a + b end }")]);
+    /// lua.add_module_searcher(modules)?;
+    ///
+    /// let sum: i32 = lua.load("return require('utils.math').add(1, 2)").eval()?;
+    /// assert_eq!(sum, 3);
+    /// # Ok(())
+    /// # }
+    ///
+    /// # #[cfg(feature = "luau")]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// [`ModuleSource`]: crate::ModuleSource
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn add_module_searcher(&self, searcher: impl ModuleSearcher) -> Result<()> {
+        let searchers = crate::searcher::searchers_table(self)?;
+        let searcher = crate::searcher::create_searcher_function(self, searcher)?;
+        // The first searcher is always `package.preload` searcher
+        searchers.raw_insert(2.min(searchers.raw_len() as Integer + 1), searcher)
+    }
+
     /// Loads module `modname` into an existing Lua state using the specified entrypoint
     /// function.
     ///
@@ -519,6 +580,7 @@ impl Lua {
     /// [`Thread::set_hook`] instead.
     ///
     /// Please note you cannot have more than one hook function set at a time for this Lua instance.
+    /// Use [`Lua::add_hook`] to add more hooks.
     ///
     /// # Example
     ///
@@ -571,9 +633,56 @@ impl Lua {
             };
             (*lua.extra.get()).hook_callback = None;
             (*lua.extra.get()).hook_thread = ptr::null_mut();
+            // Keep the internal hook (if any) running
+            lua.remove_chained_hook();
         }
     }
 
+    /// Adds a hook function that will be called as Lua code executes, alongside other hooks.
+    ///
+    /// Unlike [`Lua::set_hook`], any number of hooks can be added. Each hook is called only for
+    /// the events requested by its `triggers`. When several hooks are called for the same event,
+    /// the VM yields if any of them returns [`VmState::Yield`], and the first error aborts the
+    /// execution (the remaining hooks are not called).
+    ///
+    /// The hooks are set for the current thread and inherited by coroutines created afterwards.
+    ///
+    /// Returns a [`HookHandle`] that can be used to remove the hook.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use std::sync::Arc;
+    /// # use mlua::{Lua, HookTriggers, Result, VmState};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    /// let calls2 = calls.clone();
+    /// let handle = lua.add_hook(HookTriggers::ON_CALLS, move |_lua, _debug| {
+    ///     calls2.fetch_add(1, Ordering::Relaxed);
+    ///     Ok(VmState::Continue)
+    /// });
+    ///
+    /// lua.load("local function f() end; f(); f()").exec()?;
+    /// assert!(calls.load(Ordering::Relaxed) >= 2);
+    /// handle.remove();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`HookHandle`]: crate::HookHandle
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn add_hook<F>(&self, triggers: HookTriggers, callback: F) -> HookHandle
+    where
+        F: Fn(&Lua, Debug) -> Result<VmState> + MaybeSend + 'static,
+    {
+        let lua = self.lock();
+        let id = unsafe { lua.add_hook(triggers, std::rc::Rc::new(callback)) };
+        HookHandle::new(lua.weak().clone(), id)
+    }
+
     /// Sets an interrupt function that will periodically be called by Luau VM.
     ///
     /// Any Luau code is guaranteed to call this handler "eventually"
@@ -653,8 +762,13 @@ impl Lua {
         // Set interrupt callback
         let lua = self.lock();
         unsafe {
-            (*lua.extra.get()).interrupt_callback = Some(Rc::new(callback));
-            (*ffi::lua_callbacks(lua.main_state())).interrupt = Some(interrupt_proc);
+            let extra = lua.extra.get();
+            (*extra).interrupt_callback = Some(Rc::new(callback));
+            match (*extra).hook_prev {
+                // Call the interrupt from the internal one
+                Some(ref mut prev) => *prev = Some(interrupt_proc),
+                None => (*ffi::lua_callbacks(lua.main_state())).interrupt = Some(interrupt_proc),
+            }
         }
     }
 
@@ -667,10 +781,329 @@ impl Lua {
         let lua = self.lock();
         unsafe {
             (*lua.extra.get()).interrupt_callback = None;
-            (*ffi::lua_callbacks(lua.main_state())).interrupt = None;
+            match (*lua.extra.get()).hook_prev {
+                Some(_) => lua.remove_chained_hook(),
+                None => (*ffi::lua_callbacks(lua.main_state())).interrupt = None,
+            }
+        }
+    }
+
+    /// Starts a CPU profiler for Lua code executed by this Lua instance.
+    ///
+    /// Returns a [`Profile`] that collects statistics until it is stopped (or dropped).
+    ///
+    /// In Lua 5.x the profiler uses a hook, in Luau it samples the call stack at interrupts.
+    /// Any hook set by [`Lua::set_hook`] (or interrupt set by [`Lua::set_interrupt`]) is still
+    /// called.
+    ///
+    /// Only one profiler can be running at a time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, ProfilerOptions, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let profile = lua.start_profiler(ProfilerOptions::sampling(100))?;
+    /// lua.load(r#"
+    ///     local function fib(n)
+    ///         if n < 2 then return n end
+    ///         return fib(n - 1) + fib(n - 2)
+    ///     end
+    ///     fib(20)
+    /// "#).exec()?;
+    /// profile.stop();
+    ///
+    /// // Write a flamegraph input
+    /// println!("{}", profile.to_collapsed_stacks());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::set_hook`]: #method.set_hook
+    /// [`Lua::set_interrupt`]: #method.set_interrupt
+    pub fn start_profiler(&self, options: ProfilerOptions) -> Result<Profile> {
+        let lua = self.lock();
+        let profiler = XRc::new(Profiler::new(options));
+        unsafe { lua.set_profiler(profiler.clone())? };
+        Ok(Profile::new(lua.weak().clone(), profiler))
+    }
+
+    /// Starts collecting line coverage of Lua code.
+    ///
+    /// Returns a [`Coverage`] that records hit counts per source file and line until it is stopped
+    /// (or dropped). The report can be exported in the LCOV or Cobertura XML formats.
+    ///
+    /// In Lua 5.x the coverage is collected by a hook (see [`Lua::add_hook`]), which is inherited
+    /// by coroutines created afterwards. Lines of a function are known only once the function is
+    /// called. In LuaJIT the JIT compiler should be turned off for reliable results.
+    ///
+    /// In Luau the chunks loaded while the collection is running are compiled with coverage
+    /// support (see [`Compiler::set_coverage_level`]).
+    ///
+    /// Only one coverage collection can be running at a time in Luau.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let coverage = lua.start_coverage()?;
+    /// lua.load(r#"
+    ///     local function abs(x)
+    ///         if x < 0 then
+    ///             return -x
+    ///         end
+    ///         return x
+    ///     end
+    ///     abs(1)
+    /// "#)
+    /// .set_name("@abs.lua")
+    /// .exec()?;
+    /// coverage.stop();
+    ///
+    /// let report = coverage.report();
+    /// assert_eq!(report.files[0].lines_hit(), 4);
+    /// println!("{}", report.to_lcov());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::add_hook`]: #method.add_hook
+    /// [`Compiler::set_coverage_level`]: crate::chunk::Compiler::set_coverage_level
+    pub fn start_coverage(&self) -> Result<Coverage> {
+        let lua = self.lock();
+        let data = XRc::new(Mutex::new(CoverageData::default()));
+        #[cfg(not(feature = "luau"))]
+        {
+            let data2 = data.clone();
+            let triggers = HookTriggers::EVERY_LINE.on_calls();
+            let hook = self.add_hook(triggers, move |_, debug| {
+                data2.lock().on_hook(&debug);
+                Ok(VmState::Continue)
+            });
+            Ok(Coverage::new(lua.weak().clone(), data, hook))
+        }
+        #[cfg(feature = "luau")]
+        {
+            unsafe { lua.set_coverage(data.clone())? };
+            Ok(Coverage::new(lua.weak().clone(), data))
+        }
+    }
+
+    /// Creates a new Lua instance with a copy of this state.
+    ///
+    /// The new instance has the same standard libraries and options loaded, and a deep copy of
+    /// the global environment and of the values stored in the named registry. Values shared
+    /// between several places (including upvalues shared between closures, except in Lua 5.1 and
+    /// LuaJIT) stay shared in the copy.
+    ///
+    /// The following values are copied:
+    /// - nil, booleans, numbers, strings, light userdata, tables (with metatables)
+    /// - Lua functions (via bytecode dump) together with their upvalues
+    /// - Functions of the standard library, which are matched by their location in the globals.
+    /// - Userdata types that implement [`UserData::clone_into`].
+    ///
+    /// Any other value (threads, Rust functions, userdata that do not implement cloning) results
+    /// in an error, which describes the value and its location.
+    ///
+    /// Values created through the Rust API (registry keys, app data, hooks, etc.) are not copied.
+    ///
+    /// Not available on Luau, as Luau functions cannot be dumped to bytecode (and loaded into
+    /// another state).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.load(r#"
+    ///     local count = 0
+    ///     function incr() count = count + 1; return count end
+    /// "#).exec()?;
+    ///
+    /// let fork = lua.fork()?;
+    /// assert_eq!(lua.load("incr()").eval::<i64>()?, 1);
+    /// assert_eq!(fork.load("incr()").eval::<i64>()?, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`UserData::clone_into`]: crate::UserData::clone_into
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn fork(&self) -> Result<Lua> {
+        let (libs, safe, options) = {
+            let lua = self.lock();
+            let extra = unsafe { &*lua.extra.get() };
+            (extra.libs, extra.safe, extra.options.clone())
+        };
+        let lua = match safe {
+            true => Lua::new_with(libs, options)?,
+            false => unsafe { Lua::unsafe_new_with(libs, options) },
+        };
+        copy_state(self, &lua)?;
+        Ok(lua)
+    }
+
+    /// Takes a snapshot of this state, which can be restored later.
+    ///
+    /// Each call to [`Snapshot::restore`] returns a new Lua instance with a copy of the state
+    /// at the moment of the snapshot. See [`Lua::fork`] for what is copied.
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Ok(Snapshot::new(self.fork()?))
+    }
+
+    /// Serializes a Lua value, with everything reachable from it, to bytes.
+    ///
+    /// The whole object graph is persisted, preserving shared references and cycles. Supported
+    /// values are:
+    /// - nil, booleans, numbers, strings, tables (with metatables)
+    /// - Lua functions (via bytecode dump) together with their upvalues
+    /// - Userdata types that implement [`Persist`]
+    ///
+    /// Threads (coroutines) cannot be persisted, as their call stack is not accessible through the
+    /// Lua C API. Any other value (such as C functions or threads) must be present in the `permanents` table, which maps
+    /// values to their names. Only the name is stored, and the value is looked up by the name
+    /// in the permanents table passed to [`Lua::unpersist`]. As Lua functions usually refer to the
+    /// global environment, the globals table is commonly added to the permanents.
+    ///
+    /// The persisted data can be restored only by the same Lua version.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Function, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let counter: Function = lua.load(r#"
+    ///     local count = 0
+    ///     return function() count = count + 1; return count end
+    /// "#).eval()?;
+    /// counter.call::<()>(())?;
+    ///
+    /// let permanents = lua.create_table_from([(lua.globals(), "_G")])?;
+    /// let data = lua.persist(Some(&permanents), &counter)?;
+    ///
+    /// let lua2 = Lua::new();
+    /// let permanents = lua2.create_table_from([("_G", lua2.globals())])?;
+    /// let counter = lua2.unpersist(Some(&permanents), &data)?;
+    /// let counter = counter.as_function().unwrap();
+    /// assert_eq!(counter.call::<i64>(())?, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn persist(&self, permanents: Option<&Table>, value: impl IntoLua) -> Result<Vec<u8>> {
+        let value = value.into_lua(self)?;
+        persist::persist(self, permanents, &value)
+    }
+
+    /// Restores a value serialized by [`Lua::persist`].
+    ///
+    /// The `permanents` table maps names to values, which are referenced by the persisted data.
+    /// Userdata types must be registered using [`Lua::register_persist`].
+    ///
+    /// Be aware, Lua does not check the consistency of the restored bytecode, so only trusted data
+    /// should be passed to this function.
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn unpersist(&self, permanents: Option<&Table>, data: &[u8]) -> Result<Value> {
+        persist::unpersist(self, permanents, data)
+    }
+
+    /// Registers a userdata type that can be restored by [`Lua::unpersist`].
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn register_persist<T: Persist>(&self) {
+        let lua = self.lock();
+        let extra = unsafe { &mut *lua.extra.get() };
+        let type_id = TypeId::of::<T>();
+        extra
+            .persist_types
+            .insert(type_id, (T::NAME, persist_userdata::<T>));
+        extra.unpersist_types.insert(T::NAME, unpersist_userdata::<T>);
+    }
+
+    /// Creates a new [`Channel`] for passing values between Lua states.
+    ///
+    /// The channel can be shared with other Lua states (running on other threads) by cloning it,
+    /// or by sending it through another channel.
+    ///
+    /// This also prepares the `Channel` userdata type for scripts of this Lua instance and
+    /// registers it as [`Transferable`], so channels can be sent through channels from this state.
+    pub fn create_channel(&self) -> Result<Channel> {
+        let lua = self.lock();
+        unsafe { lua.get_or_create_userdata_metatable::<Channel>()? };
+        let extra = unsafe { &mut *lua.extra.get() };
+        extra
+            .transfer_types
+            .insert(TypeId::of::<Channel>(), transfer_userdata::<Channel>);
+        Ok(Channel::new())
+    }
+
+    /// Registers a userdata type that can be sent through a [`Channel`].
+    pub fn register_transferable<T: Transferable>(&self) {
+        let lua = self.lock();
+        let extra = unsafe { &mut *lua.extra.get() };
+        extra
+            .transfer_types
+            .insert(TypeId::of::<T>(), transfer_userdata::<T>);
+    }
+
+    /// Sets limits on execution of Lua code by this Lua instance.
+    ///
+    /// Once any of the limits is exceeded, the running Lua code is interrupted with
+    /// [`Error::ExecutionLimitExceeded`], and so is any Lua code executed afterwards until the
+    /// limits are removed (or replaced).
+    ///
+    /// In Lua 5.x the limits are enforced by a hook on the current thread, which is inherited by
+    /// coroutines created afterwards. In Luau they are enforced by an interrupt.
+    /// Any hook set by [`Lua::set_hook`] (or interrupt set by [`Lua::set_interrupt`]) is still
+    /// called.
+    ///
+    /// To limit a single call, use [`Function::call_with_budget`] instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use mlua::{Error, ExecutionLimits, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_execution_limits(ExecutionLimits::new().timeout(Duration::from_millis(100)));
+    /// match lua.load("while true do end").exec() {
+    ///     Err(Error::ExecutionLimitExceeded) => {}
+    ///     r => panic!("unexpected result: {r:?}"),
+    /// }
+    /// lua.remove_execution_limits();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::set_hook`]: #method.set_hook
+    /// [`Lua::set_interrupt`]: #method.set_interrupt
+    pub fn set_execution_limits(&self, limits: ExecutionLimits) {
+        let lua = self.lock();
+        unsafe {
+            let executed = (*lua.extra.get()).executed_instructions;
+            lua.set_execution_budget(Some(ExecutionBudget::new(limits, executed)));
         }
     }
 
+    /// Removes the execution limits previously set by [`Lua::set_execution_limits`].
+    ///
+    /// This function has no effect if limits were not previously set.
+    pub fn remove_execution_limits(&self) {
+        let lua = self.lock();
+        unsafe { lua.set_execution_budget(None) };
+    }
+
     /// Sets the warning function to be used by Lua to emit warnings.
     ///
     /// Requires `feature = "lua54"`
@@ -797,6 +1230,58 @@ impl Lua {
         }
     }
 
+    /// Enables or disables memory tracking for this Lua state.
+    ///
+    /// When enabled, memory allocated by Lua code is attributed to the allocating functions
+    /// (and call stacks), and live userdata are counted per Rust type. The collected data is
+    /// available via [`Lua::memory_report`]. Disabling tracking discards the collected data.
+    ///
+    /// Allocations are attributed at function calls and returns using a hook (an interrupt in
+    /// Luau), any hook set by [`Lua::set_hook`] is still called.
+    /// Only userdata created after tracking was enabled are counted.
+    ///
+    /// Does not work in module mode where Lua state is managed externally.
+    ///
+    /// [`Lua::set_hook`]: #method.set_hook
+    pub fn set_memory_tracking(&self, enabled: bool) -> Result<()> {
+        let lua = self.lock();
+        unsafe { lua.set_memory_tracking(enabled) }
+    }
+
+    /// Returns a snapshot of memory usage of this Lua state.
+    ///
+    /// If memory tracking is not enabled (see [`Lua::set_memory_tracking`]), only
+    /// [`MemoryReport::used_memory`] is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_memory_tracking(true)?;
+    ///
+    /// let before = lua.memory_report()?;
+    /// lua.load(r#"
+    ///     cache = {}
+    ///     local function leak(n)
+    ///         for i = 1, n do cache[#cache + 1] = {i} end
+    ///     end
+    ///     leak(1000)
+    /// "#).exec()?;
+    /// let growth = lua.memory_report()?.growth_since(&before);
+    /// assert!(growth.used_memory > 0);
+    /// assert_eq!(growth.top_allocators[0].info.name.as_deref(), Some("leak"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::set_memory_tracking`]: #method.set_memory_tracking
+    pub fn memory_report(&self) -> Result<MemoryReport> {
+        let lua = self.lock();
+        unsafe { lua.memory_report() }
+    }
+
     /// Returns `true` if the garbage collector is currently running automatically.
     ///
     /// Requires `feature = "lua54/lua53/lua52/luau"`
@@ -968,6 +1453,46 @@ impl Lua {
         unsafe { (*lua.extra.get()).compiler = Some(compiler) };
     }
 
+    /// Replaces the `require` function with one that loads modules from the given resolver.
+    ///
+    /// Module paths are resolved using Luau require-by-string semantics (`./`, `../` and `@alias`
+    /// prefixes with aliases defined in `.luaurc` files) and loaded from the resolver instead of
+    /// the filesystem. See [`ModuleResolver`] for details.
+    ///
+    /// Modules that were put to `package.loaded` by the host can still be required by name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use mlua::{Lua, Result};
+    /// # #[cfg(feature = "luau")]
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    ///
+    /// let modules = HashMap::from([
+    ///     ("lib/math.luau", "return { add = function(a, b) return a + b end }"),
+    ///     (".luaurc", r#"{ "aliases": { "lib": "lib" } }"#),
+    /// ]);
+    /// lua.set_module_resolver(modules)?;
+    ///
+    /// let sum: i32 = lua.load("return require('@lib/math').add(1, 2)").eval()?;
+    /// assert_eq!(sum, 3);
+    /// # Ok(())
+    /// # }
+    ///
+    /// # #[cfg(not(feature = "luau"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// Requires `feature = "luau"`
+    #[cfg(feature = "luau")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub fn set_module_resolver(&self, resolver: impl ModuleResolver) -> Result<()> {
+        let require = crate::luau::create_require_function(self, resolver)?;
+        self.globals().raw_set("require", require)
+    }
+
     /// Toggles JIT compilation mode for new chunks of code.
     ///
     /// By default JIT is enabled. Changing this option does not have any effect on
@@ -1031,13 +1556,10 @@ impl Lua {
         unsafe { self.lock().create_string(s) }
     }
 
-    /// Create and return a Luau [buffer] object from a byte slice of data.
+    /// Create and return a [`Buffer`] object from a byte slice of data.
     ///
-    /// Requires `feature = "luau"`
-    ///
-    /// [buffer]: https://luau.org/library#buffer-library
-    #[cfg(any(feature = "luau", doc))]
-    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    /// On Luau this is a native buffer, on other Lua versions the buffer is backed by userdata.
+    #[cfg(feature = "luau")]
     pub fn create_buffer(&self, buf: impl AsRef<[u8]>) -> Result<Buffer> {
         let lua = self.lock();
         let state = lua.state();
@@ -1054,6 +1576,14 @@ impl Lua {
         }
     }
 
+    /// Create and return a [`Buffer`] object from a byte slice of data.
+    ///
+    /// On Luau this is a native buffer, on other Lua versions the buffer is backed by userdata.
+    #[cfg(not(feature = "luau"))]
+    pub fn create_buffer(&self, buf: impl AsRef<[u8]>) -> Result<Buffer> {
+        Buffer::create_userdata(self, buf.as_ref().into())
+    }
+
     /// Creates and returns a new empty table.
     #[inline]
     pub fn create_table(&self) -> Result<Table> {
@@ -1199,6 +1729,10 @@ impl Lua {
     ///
     /// The family of `call_async()` functions takes care about creating [`Thread`].
     ///
+    /// The function can get the [`CancellationToken`] of the calling thread using
+    /// [`Lua::cancellation_token`]. Once the thread is cancelled, the future is dropped and the
+    /// call raises [`Error::Cancelled`].
+    ///
     /// Requires `feature = "async"`
     ///
     /// # Examples
@@ -1225,6 +1759,7 @@ impl Lua {
     /// ```
     ///
     /// [`AsyncThread`]: crate::AsyncThread
+    /// [`CancellationToken`]: crate::CancellationToken
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub fn create_async_function<F, A, FR, R>(&self, func: F) -> Result<Function>
@@ -1247,6 +1782,21 @@ impl Lua {
         }))
     }
 
+    /// Returns the [`CancellationToken`] of the async thread that is being polled.
+    ///
+    /// Returns `None` if no async thread is being polled (for example, when a function is called
+    /// synchronously).
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`CancellationToken`]: crate::CancellationToken
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn cancellation_token(&self) -> Option<crate::CancellationToken> {
+        let lua = self.lock();
+        unsafe { lua.cancellation_token().cloned() }
+    }
+
     /// Wraps a Lua function into a new thread (or coroutine).
     ///
     /// Equivalent to `coroutine.create`.
@@ -1324,11 +1874,58 @@ impl Lua {
             }
 
             // Add to "pending" registration map
-            ((*lua.extra.get()).pending_userdata_reg).insert(type_id, registry.into_raw());
+            let mut registry = registry.into_raw();
+            let type_def = mem::take(&mut registry.type_def);
+            ((*lua.extra.get()).userdata_type_defs).insert(type_def.name.clone(), type_def);
+            ((*lua.extra.get()).pending_userdata_reg).insert(type_id, registry);
         }
         Ok(())
     }
 
+    /// Returns type definitions of all userdata types known to this Lua instance.
+    ///
+    /// This includes types registered using [`Lua::register_userdata_type`] and types of
+    /// userdata objects created using [`Lua::create_userdata`] (and similar methods).
+    ///
+    /// The definitions can be rendered as LuaLS annotations or Luau type definitions to provide
+    /// editor completion for scripts. Argument and return types are described using
+    /// [`IntoLua::lua_type`] and [`FromLua::lua_type`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, UserData, UserDataFields, UserDataMethods};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// struct Counter(i64);
+    ///
+    /// impl UserData for Counter {
+    ///     fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+    ///         fields.add_field_method_get("value", |_, this| Ok(this.0));
+    ///     }
+    ///
+    ///     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    ///         methods.add_method_mut("add", |_, this, n: i64| {
+    ///             this.0 += n;
+    ///             Ok(this.0)
+    ///         });
+    ///     }
+    /// }
+    ///
+    /// lua.globals().set("counter", Counter(0))?;
+    ///
+    /// let defs = lua.type_definitions();
+    /// assert!(defs.to_luals().contains("---@class Counter"));
+    /// assert!(defs.to_luau().contains("function add(self, arg1: number): number"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn type_definitions(&self) -> TypeDefinitions {
+        let lua = self.lock();
+        let type_defs = unsafe { &(*lua.extra.get()).userdata_type_defs };
+        TypeDefinitions::new(type_defs.values().cloned())
+    }
+
     /// Create a Lua userdata "proxy" object from a custom userdata type.
     ///
     /// Proxy object is an empty userdata object that has `T` metatable attached.
@@ -1906,6 +2503,14 @@ impl Lua {
         extra.app_data.remove()
     }
 
+    /// Removes all app data from the Lua state.
+    #[track_caller]
+    pub(crate) fn clear_app_data(&self) {
+        let lua = self.lock();
+        let extra = unsafe { &*lua.extra.get() };
+        extra.app_data.clear()
+    }
+
     /// Returns an internal `Poll::Pending` constant used for executing async callbacks.
     #[cfg(feature = "async")]
     #[doc(hidden)]
@@ -9,20 +9,33 @@ use std::sync::Arc;
 use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
 
+use crate::channel::TransferFn;
 use crate::error::Result;
+use crate::limits::ExecutionBudget;
+use crate::profiler::Profiler;
 use crate::state::RawLua;
 use crate::stdlib::StdLib;
+use crate::typedef::UserDataTypeDef;
 use crate::types::{AppData, ReentrantMutex, XRc};
 use crate::userdata::RawUserDataRegistry;
 use crate::util::{get_internal_metatable, push_internal_userdata, TypeKey, WrappedFailure};
 
 #[cfg(any(feature = "luau", doc))]
 use crate::chunk::Compiler;
+#[cfg(feature = "luau")]
+use crate::coverage::CoverageData;
+#[cfg(not(feature = "luau"))]
+use crate::{
+    persist::{PersistFn, UnpersistFn},
+    userdata::UserDataCloner,
+};
 
 #[cfg(feature = "async")]
-use {futures_util::task::noop_waker_ref, std::ptr::NonNull, std::task::Waker};
+use {
+    crate::cancel::CancellationToken, futures_util::task::noop_waker_ref, std::ptr::NonNull, std::task::Waker,
+};
 
-use super::{Lua, WeakLua};
+use super::{Lua, LuaOptions, WeakLua};
 
 // Unique key to store `ExtraData` in the registry
 static EXTRA_REGISTRY_KEY: u8 = 0;
@@ -30,6 +43,12 @@ static EXTRA_REGISTRY_KEY: u8 = 0;
 const WRAPPED_FAILURE_POOL_DEFAULT_CAPACITY: usize = 64;
 const REF_STACK_RESERVE: c_int = 1;
 
+// The user hook (or interrupt) called by the internal one
+#[cfg(not(feature = "luau"))]
+pub(super) type ChainedHook = (*mut ffi::lua_State, Option<ffi::lua_Hook>, c_int, c_int);
+#[cfg(feature = "luau")]
+pub(super) type ChainedHook = Option<unsafe extern "C-unwind" fn(*mut ffi::lua_State, c_int)>;
+
 /// Data associated with the Lua state.
 pub(crate) struct ExtraData {
     pub(super) lua: MaybeUninit<Lua>,
@@ -40,6 +59,22 @@ pub(crate) struct ExtraData {
     pub(super) registered_userdata_t: FxHashMap<TypeId, c_int>,
     pub(super) registered_userdata_mt: FxHashMap<*const c_void, Option<TypeId>>,
     pub(super) last_checked_userdata_mt: (*const c_void, Option<TypeId>),
+    // Type definitions of registered userdata types (by class name)
+    pub(super) userdata_type_defs: FxHashMap<String, UserDataTypeDef>,
+    // Names of registered userdata types (used in memory reports)
+    pub(super) userdata_type_names: FxHashMap<TypeId, String>,
+    // Copy functions of registered userdata types (used when forking)
+    #[cfg(not(feature = "luau"))]
+    pub(super) userdata_cloners: FxHashMap<TypeId, UserDataCloner>,
+    // Persistable userdata types (by type and by persisted name)
+    #[cfg(not(feature = "luau"))]
+    pub(super) persist_types: FxHashMap<TypeId, (&'static str, PersistFn)>,
+    #[cfg(not(feature = "luau"))]
+    pub(super) unpersist_types: FxHashMap<&'static str, UnpersistFn>,
+    // Borrow state of buffers (by pointer): number of shared borrows or -1 if mutably borrowed
+    pub(super) buffer_borrows: FxHashMap<*const c_void, isize>,
+    // Userdata types that can be sent through channels
+    pub(super) transfer_types: FxHashMap<TypeId, TransferFn>,
 
     // When Lua instance dropped, setting `None` would prevent collecting `RegistryKey`s
     pub(super) registry_unref_list: Arc<Mutex<Option<Vec<c_int>>>>,
@@ -49,6 +84,7 @@ pub(crate) struct ExtraData {
 
     pub(super) safe: bool,
     pub(super) libs: StdLib,
+    pub(super) options: LuaOptions,
     // Used in module mode
     pub(super) skip_memory_check: bool,
 
@@ -71,15 +107,35 @@ pub(crate) struct ExtraData {
     // Waker for polling futures
     #[cfg(feature = "async")]
     pub(super) waker: NonNull<Waker>,
+    // Cancellation token of the async thread being polled
+    #[cfg(feature = "async")]
+    pub(super) cancellation_token: Option<CancellationToken>,
 
     #[cfg(not(feature = "luau"))]
     pub(super) hook_callback: Option<crate::types::HookCallback>,
     #[cfg(not(feature = "luau"))]
     pub(super) hook_thread: *mut ffi::lua_State,
+    // Hooks added by `Lua::add_hook`
+    #[cfg(not(feature = "luau"))]
+    pub(super) hooks: Vec<(u64, crate::HookTriggers, crate::types::HookCallback)>,
+    #[cfg(not(feature = "luau"))]
+    pub(super) last_hook_id: u64,
+    #[cfg(all(feature = "async", not(feature = "luau")))]
+    pub(super) suspended_by_hook: bool,
     #[cfg(feature = "lua54")]
     pub(super) warn_callback: Option<crate::types::WarnCallback>,
+    // Active profilers, execution limits and the hook (or interrupt) chained from the internal one
+    pub(super) profiler: Option<XRc<Profiler>>,
+    pub(super) memory_profiler: Option<XRc<Profiler>>,
+    pub(super) execution_budget: Option<ExecutionBudget>,
+    pub(super) executed_instructions: u64,
+    #[cfg(not(feature = "luau"))]
+    pub(super) hook_counter: u64,
+    pub(super) hook_prev: Option<ChainedHook>,
     #[cfg(feature = "luau")]
     pub(super) interrupt_callback: Option<crate::types::InterruptCallback>,
+    #[cfg(feature = "luau")]
+    pub(super) coverage: Option<XRc<Mutex<CoverageData>>>,
 
     #[cfg(feature = "luau")]
     pub(super) sandboxed: bool,
@@ -151,10 +207,21 @@ impl ExtraData {
             registered_userdata_t: FxHashMap::default(),
             registered_userdata_mt: FxHashMap::default(),
             last_checked_userdata_mt: (ptr::null(), None),
+            userdata_type_defs: FxHashMap::default(),
+            userdata_type_names: FxHashMap::default(),
+            #[cfg(not(feature = "luau"))]
+            userdata_cloners: FxHashMap::default(),
+            #[cfg(not(feature = "luau"))]
+            persist_types: FxHashMap::default(),
+            #[cfg(not(feature = "luau"))]
+            unpersist_types: FxHashMap::default(),
+            buffer_borrows: FxHashMap::default(),
+            transfer_types: FxHashMap::default(),
             registry_unref_list: Arc::new(Mutex::new(Some(Vec::new()))),
             app_data: AppData::default(),
             safe: false,
             libs: StdLib::NONE,
+            options: LuaOptions::new(),
             skip_memory_check: false,
             ref_thread,
             // We need some reserved stack space to move values in and out of the ref stack.
@@ -168,15 +235,32 @@ impl ExtraData {
             wrapped_failure_mt_ptr,
             #[cfg(feature = "async")]
             waker: NonNull::from(noop_waker_ref()),
+            #[cfg(feature = "async")]
+            cancellation_token: None,
             #[cfg(not(feature = "luau"))]
             hook_callback: None,
             #[cfg(not(feature = "luau"))]
             hook_thread: ptr::null_mut(),
+            #[cfg(not(feature = "luau"))]
+            hooks: Vec::new(),
+            #[cfg(not(feature = "luau"))]
+            last_hook_id: 0,
+            #[cfg(all(feature = "async", not(feature = "luau")))]
+            suspended_by_hook: false,
             #[cfg(feature = "lua54")]
             warn_callback: None,
+            profiler: None,
+            memory_profiler: None,
+            execution_budget: None,
+            executed_instructions: 0,
+            #[cfg(not(feature = "luau"))]
+            hook_counter: 0,
+            hook_prev: None,
             #[cfg(feature = "luau")]
             interrupt_callback: None,
             #[cfg(feature = "luau")]
+            coverage: None,
+            #[cfg(feature = "luau")]
             sandboxed: false,
             #[cfg(feature = "luau")]
             compiler: None,
@@ -8,10 +8,15 @@ use std::ptr::{self, NonNull};
 use std::result::Result as StdResult;
 use std::sync::Arc;
 
+use rustc_hash::FxHashMap;
+
+use crate::channel::TransferFn;
 use crate::chunk::ChunkMode;
 use crate::error::{Error, Result};
 use crate::function::Function;
-use crate::memory::{MemoryState, ALLOCATOR};
+use crate::limits::{ExecutionBudget, ExecutionBudgetGuard, ExecutionLimits};
+use crate::memory::{MemoryReport, MemoryState, ThreadMemory, ThreadMemoryGuard, UserDataMemory, ALLOCATOR};
+use crate::profiler::{Profiler, ProfilerOptions};
 use crate::state::util::{callback_error_ext, ref_stack_pop, StateGuard};
 use crate::stdlib::StdLib;
 use crate::string::String;
@@ -37,16 +42,32 @@ use super::extra::ExtraData;
 use super::{Lua, LuaOptions, WeakLua};
 
 #[cfg(not(feature = "luau"))]
-use crate::hook::{Debug, HookTriggers};
+use {
+    crate::hook::{Debug, HookTriggers},
+    crate::persist::{PersistFn, UnpersistFn},
+    crate::types::{HookCallback, VmState},
+    crate::userdata::{clone_userdata_into, UserDataCloner},
+    std::rc::Rc,
+};
+
+#[cfg(feature = "luau")]
+use {crate::coverage::CoverageData, parking_lot::Mutex};
 
 #[cfg(feature = "async")]
 use {
+    crate::cancel::CancellationToken,
     crate::multi::MultiValue,
     crate::traits::FromLuaMulti,
     crate::types::{AsyncCallback, AsyncCallbackUpvalue, AsyncPollUpvalue},
+    std::future,
     std::task::{Context, Poll, Waker},
 };
 
+// Registry key of the table with live userdata (when memory tracking is enabled)
+static TRACKED_USERDATA_KEY: u8 = 0;
+// Registry key of the table with memory accounting of threads (with weak keys)
+static THREAD_MEMORY_KEY: u8 = 0;
+
 /// An inner Lua struct which holds a raw Lua state.
 #[doc(hidden)]
 pub struct RawLua {
@@ -136,6 +157,7 @@ impl RawLua {
             "Error during loading standard libraries"
         );
         (*extra).libs |= libs;
+        (*extra).options = options.clone();
 
         if !options.catch_rust_panics {
             mlua_expect!(
@@ -186,6 +208,7 @@ impl RawLua {
                 init_internal_metatable::<XRc<UnsafeCell<ExtraData>>>(state, None)?;
                 init_internal_metatable::<Callback>(state, None)?;
                 init_internal_metatable::<CallbackUpvalue>(state, None)?;
+                init_internal_metatable::<ThreadMemory>(state, None)?;
                 #[cfg(feature = "async")]
                 {
                     init_internal_metatable::<AsyncCallback>(state, None)?;
@@ -268,6 +291,11 @@ impl RawLua {
         }
 
         let res = load_std_libs(self.main_state(), libs);
+        #[cfg(not(feature = "luau"))]
+        let res = res.and_then(|_| match libs.contains(StdLib::BUFFER) {
+            true => crate::buffer::load_lib(self.lua()),
+            false => Ok(()),
+        });
 
         // If `package` library loaded into a safe lua state then disable C modules
         let curr_libs = (*self.extra.get()).libs;
@@ -381,51 +409,364 @@ impl RawLua {
         triggers: HookTriggers,
         callback: F,
     ) where
-        F: Fn(&Lua, Debug) -> Result<crate::VmState> + MaybeSend + 'static,
+        F: Fn(&Lua, Debug) -> Result<VmState> + MaybeSend + 'static,
     {
-        use crate::types::VmState;
-        use std::rc::Rc;
-
-        unsafe extern "C-unwind" fn hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
-            let extra = ExtraData::get(state);
-            if (*extra).hook_thread != state {
-                // Hook was destined for a different thread, ignore
-                ffi::lua_sethook(state, None, 0, 0);
+        let extra = self.extra.get();
+        (*extra).hook_callback = Some(Rc::new(callback));
+        (*extra).hook_thread = state; // Mark for what thread the hook is set
+        match (*extra).hook_prev {
+            Some((hook_state, ..)) if hook_state == state => {
+                // Call the hook from the internal one
+                (*extra).hook_prev = Some((state, Some(hook_proc), triggers.mask(), triggers.count()));
+                self.update_internal_hook();
+            }
+            _ => {
+                // Only one user hook can be set at a time
+                self.remove_chained_hook();
+                ffi::lua_sethook(state, Some(hook_proc), triggers.mask(), triggers.count());
+            }
+        }
+    }
+
+    /// See [`Lua::add_hook`]
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn add_hook(&self, triggers: HookTriggers, callback: HookCallback) -> u64 {
+        let extra = self.extra.get();
+        (*extra).last_hook_id += 1;
+        let id = (*extra).last_hook_id;
+        (*extra).hooks.push((id, triggers, callback));
+        self.update_internal_hook();
+        id
+    }
+
+    /// Removes the hook added by [`Lua::add_hook`].
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn remove_hook_by_id(&self, id: u64) {
+        let hooks = &mut (*self.extra.get()).hooks;
+        let len = hooks.len();
+        hooks.retain(|(hook_id, ..)| *hook_id != id);
+        if hooks.len() != len {
+            self.update_internal_hook();
+        }
+    }
+
+    /// Installs a CPU profiler.
+    pub(crate) unsafe fn set_profiler(&self, profiler: XRc<Profiler>) -> Result<()> {
+        let extra = self.extra.get();
+        if (*extra).profiler.is_some() {
+            return Err(Error::runtime("profiler is already running"));
+        }
+        (*extra).profiler = Some(profiler);
+        self.update_internal_hook();
+        Ok(())
+    }
+
+    /// Removes the CPU profiler (if it's the current one).
+    pub(crate) unsafe fn remove_profiler(&self, profiler: &XRc<Profiler>) {
+        let extra = self.extra.get();
+        match (*extra).profiler {
+            Some(ref current) if XRc::ptr_eq(current, profiler) => {}
+            _ => return,
+        }
+        (*extra).profiler = None;
+        self.update_internal_hook();
+    }
+
+    /// Sets the active coverage collection (Luau).
+    #[cfg(feature = "luau")]
+    pub(crate) unsafe fn set_coverage(&self, data: XRc<Mutex<CoverageData>>) -> Result<()> {
+        let extra = self.extra.get();
+        if (*extra).coverage.is_some() {
+            return Err(Error::runtime("coverage is already running"));
+        }
+        (*extra).coverage = Some(data);
+        Ok(())
+    }
+
+    /// Removes the coverage collection (if it's the current one).
+    #[cfg(feature = "luau")]
+    pub(crate) unsafe fn remove_coverage(&self, data: &XRc<Mutex<CoverageData>>) {
+        let extra = self.extra.get();
+        if matches!((*extra).coverage, Some(ref current) if XRc::ptr_eq(current, data)) {
+            (*extra).coverage = None;
+        }
+    }
+
+    /// Returns the active coverage collection (Luau).
+    #[cfg(feature = "luau")]
+    pub(crate) fn coverage(&self) -> Option<XRc<Mutex<CoverageData>>> {
+        unsafe { (*self.extra.get()).coverage.clone() }
+    }
+
+    /// See [`Lua::set_memory_tracking`]
+    pub(crate) unsafe fn set_memory_tracking(&self, enabled: bool) -> Result<()> {
+        let mem_state = MemoryState::get(self.main_state());
+        if mem_state.is_null() {
+            return Err(Error::MemoryControlNotAvailable);
+        }
+        let extra = self.extra.get();
+        if enabled == (*extra).memory_profiler.is_some() {
+            return Ok(());
+        }
+
+        let state = self.state();
+        let _sg = StackGuard::new(state);
+        check_stack(state, 3)?;
+        let key = &TRACKED_USERDATA_KEY as *const u8 as *const c_void;
+        if enabled {
+            // Live userdata are tracked in a table with weak keys
+            push_table(state, 0, 0, true)?;
+            push_table(state, 0, 1, true)?;
+            push_string(state, b"k", true)?;
+            rawset_field(state, -2, "__mode")?;
+            ffi::lua_setmetatable(state, -2);
+            protect_lua!(state, 1, 0, |state| ffi::lua_rawsetp(
+                state,
+                ffi::LUA_REGISTRYINDEX,
+                key
+            ))?;
+
+            let profiler = XRc::new(Profiler::new(ProfilerOptions::allocations()));
+            profiler.set_base_allocated((*mem_state).allocated());
+            (*extra).memory_profiler = Some(profiler);
+        } else {
+            ffi::lua_pushnil(state);
+            ffi::lua_rawsetp(state, ffi::LUA_REGISTRYINDEX, key);
+            (*extra).memory_profiler = None;
+        }
+        self.update_internal_hook();
+        Ok(())
+    }
+
+    /// See [`Lua::memory_report`]
+    pub(crate) unsafe fn memory_report(&self) -> Result<MemoryReport> {
+        let mut report = MemoryReport {
+            used_memory: self.lua().used_memory(),
+            ..Default::default()
+        };
+        let Some(profiler) = (*self.extra.get()).memory_profiler.clone() else {
+            return Ok(report);
+        };
+        let mem_state = MemoryState::get(self.main_state());
+        let (allocations, bytes, sites, stacks) = profiler.allocations_report((*mem_state).allocated());
+        report.allocations = allocations;
+        report.allocated_bytes = bytes;
+        report.top_allocators = sites;
+        report.stacks = stacks;
+
+        // Count live userdata
+        let state = self.state();
+        let _sg = StackGuard::new(state);
+        check_stack(state, 4)?;
+        let mut userdata = FxHashMap::<TypeId, (usize, usize)>::default();
+        let key = &TRACKED_USERDATA_KEY as *const u8 as *const c_void;
+        ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, key);
+        ffi::lua_pushnil(state);
+        while ffi::lua_next(state, -2) != 0 {
+            ffi::lua_pop(state, 1);
+            // Destructed userdata are not registered
+            if let Ok(Some(type_id)) = self.get_userdata_type_id_inner(state, -1) {
+                let entry = userdata.entry(type_id).or_default();
+                entry.0 += 1;
+                entry.1 += ffi::lua_rawlen(state, -1);
+            }
+        }
+        let type_names = &(*self.extra.get()).userdata_type_names;
+        report.userdata = (userdata.into_iter())
+            .map(|(type_id, (count, bytes))| UserDataMemory {
+                type_name: type_names.get(&type_id).cloned().unwrap_or_default(),
+                count,
+                bytes,
+            })
+            .collect();
+        (report.userdata).sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.type_name.cmp(&b.type_name)));
+
+        Ok(report)
+    }
+
+    /// Returns memory accounting of the thread, creating it if `create` is true.
+    pub(crate) unsafe fn thread_memory(
+        &self,
+        thread: &Thread,
+        create: bool,
+    ) -> Result<Option<NonNull<ThreadMemory>>> {
+        let mem_state = MemoryState::get(self.main_state());
+        if mem_state.is_null() {
+            return Err(Error::MemoryControlNotAvailable);
+        }
+        if !create && !(*mem_state).has_thread_memory() {
+            return Ok(None);
+        }
+
+        let state = self.state();
+        let _sg = StackGuard::new(state);
+        check_stack(state, 5)?;
+
+        let key = &THREAD_MEMORY_KEY as *const u8 as *const c_void;
+        if ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, key) == ffi::LUA_TNIL {
+            if !create {
+                return Ok(None);
+            }
+            ffi::lua_pop(state, 1);
+            push_table(state, 0, 0, true)?;
+            push_table(state, 0, 1, true)?;
+            push_string(state, b"k", true)?;
+            rawset_field(state, -2, "__mode")?;
+            ffi::lua_setmetatable(state, -2);
+            ffi::lua_pushvalue(state, -1);
+            protect_lua!(state, 1, 0, |state| ffi::lua_rawsetp(
+                state,
+                ffi::LUA_REGISTRYINDEX,
+                key
+            ))?;
+        }
+        self.push_ref(&thread.0);
+        if ffi::lua_rawget(state, -2) != ffi::LUA_TNIL {
+            return Ok(NonNull::new(get_userdata::<ThreadMemory>(state, -1)));
+        }
+        if !create {
+            return Ok(None);
+        }
+
+        ffi::lua_pop(state, 1);
+        let thread_memory = (*mem_state).new_thread_memory(thread.state());
+        let thread_memory =
+            thread_memory.ok_or_else(|| Error::runtime("too many threads with memory limits"))?;
+        self.push_ref(&thread.0);
+        push_internal_userdata(state, thread_memory, true)?;
+        let ptr = get_userdata::<ThreadMemory>(state, -1);
+        protect_lua!(state, 3, 0, fn(state) ffi::lua_rawset(state, -3))?;
+        Ok(NonNull::new(ptr))
+    }
+
+    /// Makes memory accounting of the thread (if any) current while the guard is alive.
+    pub(crate) unsafe fn enter_thread_memory(&self, thread: &Thread) -> Result<Option<ThreadMemoryGuard>> {
+        let mem_state = MemoryState::get(self.main_state());
+        if mem_state.is_null() || !(*mem_state).has_thread_memory() {
+            return Ok(None);
+        }
+        Ok(
+            (self.thread_memory(thread, false)?)
+                .map(|thread_memory| (*mem_state).enter_thread(thread_memory)),
+        )
+    }
+
+    // Adds userdata at the top of the stack to the tracked ones (if memory tracking is enabled)
+    // Uses 3 stack spaces, does not call checkstack.
+    unsafe fn track_userdata(&self, state: *mut ffi::lua_State, protect: bool) -> Result<()> {
+        if (*self.extra.get()).memory_profiler.is_none() {
+            return Ok(());
+        }
+        let key = &TRACKED_USERDATA_KEY as *const u8 as *const c_void;
+        ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, key);
+        ffi::lua_pushvalue(state, -2);
+        ffi::lua_pushboolean(state, 1);
+        if protect {
+            protect_lua!(state, 3, 0, fn(state) ffi::lua_rawset(state, -3))?;
+        } else {
+            ffi::lua_rawset(state, -3);
+            ffi::lua_pop(state, 1);
+        }
+        Ok(())
+    }
+
+    /// Replaces the active execution budget, returning the previous one.
+    pub(crate) unsafe fn set_execution_budget(
+        &self,
+        budget: Option<ExecutionBudget>,
+    ) -> Option<ExecutionBudget> {
+        let prev = mem::replace(&mut (*self.extra.get()).execution_budget, budget);
+        self.update_internal_hook();
+        prev
+    }
+
+    /// Applies the execution limits (within the active ones) until the guard is dropped.
+    pub(crate) unsafe fn enter_execution_limits(&self, limits: ExecutionLimits) -> ExecutionBudgetGuard<'_> {
+        let extra = self.extra.get();
+        let budget = ExecutionBudget::new(limits, (*extra).executed_instructions);
+        let prev = self.set_execution_budget(Some(budget.within((*extra).execution_budget)));
+        ExecutionBudgetGuard::new(self, prev)
+    }
+
+    /// Stops calling the user hook (interrupt in Luau) from the internal one.
+    pub(crate) unsafe fn remove_chained_hook(&self) {
+        let extra = self.extra.get();
+        #[cfg(not(feature = "luau"))]
+        if let Some((state, ..)) = (*extra).hook_prev {
+            (*extra).hook_prev = Some((state, None, 0, 0));
+            self.update_internal_hook();
+        }
+        #[cfg(feature = "luau")]
+        if let Some(ref mut prev) = (*extra).hook_prev {
+            *prev = None;
+        }
+    }
+
+    // Installs (or removes) the hook (interrupt in Luau) that drives the active profilers and
+    // execution limits. The hook set by the user is called from it.
+    pub(crate) unsafe fn update_internal_hook(&self) {
+        let extra = self.extra.get();
+        let profilers = [&(*extra).profiler, &(*extra).memory_profiler];
+        let active = profilers.iter().any(|p| p.is_some()) || (*extra).execution_budget.is_some();
+        #[cfg(not(feature = "luau"))]
+        let active = active || !(*extra).hooks.is_empty();
+
+        #[cfg(not(feature = "luau"))]
+        {
+            if !active {
+                if let Some((state, hook, mask, count)) = (*extra).hook_prev.take() {
+                    ffi::lua_sethook(state, hook, mask, count);
+                }
                 return;
             }
-            let result = callback_error_ext(state, extra, move |extra, _| {
-                let hook_cb = (*extra).hook_callback.clone();
-                let hook_cb = mlua_expect!(hook_cb, "no hook callback set in hook_proc");
-                if Rc::strong_count(&hook_cb) > 2 {
-                    return Ok(VmState::Continue); // Don't allow recursion
+
+            let (state, _, mut mask, mut count) = *(*extra).hook_prev.get_or_insert_with(|| {
+                // Save the current hook to call it from the internal one
+                let state = self.state();
+                let hook = ffi::lua_gethook(state);
+                let mask = ffi::lua_gethookmask(state);
+                let count = ffi::lua_gethookcount(state);
+                match hook {
+                    // Left over from a previous use (inherited by a coroutine)
+                    Some(hook) if hook as *const () == internal_hook_proc as *const () => (state, None, 0, 0),
+                    _ => (state, hook, mask, count),
                 }
-                let rawlua = (*extra).raw_lua();
-                let _guard = StateGuard::new(rawlua, state);
-                let debug = Debug::new(rawlua, ar);
-                hook_cb((*extra).lua(), debug)
             });
-            match result {
-                VmState::Continue => {}
-                VmState::Yield => {
-                    // Only count and line events can yield
-                    if (*ar).event == ffi::LUA_HOOKCOUNT || (*ar).event == ffi::LUA_HOOKLINE {
-                        #[cfg(any(feature = "lua54", feature = "lua53"))]
-                        if ffi::lua_isyieldable(state) != 0 {
-                            ffi::lua_yield(state, 0);
-                        }
-                        #[cfg(any(feature = "lua52", feature = "lua51", feature = "luajit"))]
-                        {
-                            ffi::lua_pushliteral(state, "attempt to yield from a hook");
-                            ffi::lua_error(state);
-                        }
-                    }
+            if mask & ffi::LUA_MASKCOUNT == 0 {
+                count = 0;
+            }
+            let hooks = (*extra).hooks.iter().map(|(_, triggers, _)| *triggers);
+            for triggers in (profilers.into_iter().flatten().map(|p| p.hook_triggers())).chain(hooks) {
+                mask |= triggers.mask();
+                count = gcd(count, triggers.count());
+            }
+            if let Some(budget) = (*extra).execution_budget {
+                let interval = budget.check_interval((*extra).executed_instructions) as c_int;
+                if count == 0 || count > interval {
+                    count = gcd(count, interval);
                 }
+                mask |= ffi::LUA_MASKCOUNT;
             }
+            (*extra).hook_counter = 0;
+            ffi::lua_sethook(state, Some(internal_hook_proc), mask, count);
         }
 
-        (*self.extra.get()).hook_callback = Some(Rc::new(callback));
-        (*self.extra.get()).hook_thread = state; // Mark for what thread the hook is set
-        ffi::lua_sethook(state, Some(hook_proc), triggers.mask(), triggers.count());
+        #[cfg(feature = "luau")]
+        {
+            let callbacks = ffi::lua_callbacks(self.main_state());
+            match (*extra).hook_prev {
+                None if active => {
+                    // Save the interrupt to call it from the internal one
+                    let prev = (*callbacks).interrupt.replace(internal_interrupt_proc);
+                    (*extra).hook_prev = Some(prev);
+                }
+                Some(prev) if !active => {
+                    (*callbacks).interrupt = prev;
+                    (*extra).hook_prev = None;
+                }
+                _ => {}
+            }
+        }
     }
 
     /// See [`Lua::create_string`]
@@ -771,19 +1112,26 @@ impl RawLua {
     where
         T: UserData + 'static,
     {
-        self.make_userdata_with_metatable(data, || {
-            // Check if userdata/metatable is already registered
-            let type_id = TypeId::of::<T>();
-            if let Some(&table_id) = (*self.extra.get()).registered_userdata_t.get(&type_id) {
-                return Ok(table_id as Integer);
-            }
+        self.make_userdata_with_metatable(data, || self.get_or_create_userdata_metatable::<T>())
+    }
 
-            // Create a new metatable from `UserData` definition
-            let mut registry = UserDataRegistry::new(self.lua(), type_id);
-            T::register(&mut registry);
+    // Returns the registry id of the `T` metatable, creating it from `UserData` definition if needed
+    pub(crate) unsafe fn get_or_create_userdata_metatable<T: UserData + 'static>(&self) -> Result<Integer> {
+        // Check if userdata/metatable is already registered
+        let type_id = TypeId::of::<T>();
+        if let Some(&table_id) = (*self.extra.get()).registered_userdata_t.get(&type_id) {
+            return Ok(table_id as Integer);
+        }
 
-            self.create_userdata_metatable(registry.into_raw())
-        })
+        // Create a new metatable from `UserData` definition
+        let mut registry = UserDataRegistry::new(self.lua(), type_id);
+        T::register(&mut registry);
+        #[cfg(not(feature = "luau"))]
+        (*self.extra.get())
+            .userdata_cloners
+            .insert(type_id, clone_userdata_into::<T>);
+
+        self.create_userdata_metatable(registry.into_raw())
     }
 
     pub(crate) unsafe fn make_any_userdata<T>(&self, data: UserDataStorage<T>) -> Result<AnyUserData>
@@ -813,7 +1161,7 @@ impl RawLua {
     ) -> Result<AnyUserData> {
         let state = self.state();
         let _sg = StackGuard::new(state);
-        check_stack(state, 3)?;
+        check_stack(state, 4)?;
 
         // We push metatable first to ensure having correct metatable with `__gc` method
         ffi::lua_pushnil(state);
@@ -822,6 +1170,7 @@ impl RawLua {
         crate::util::push_userdata(state, data, protect)?;
         ffi::lua_replace(state, -3);
         ffi::lua_setmetatable(state, -2);
+        self.track_userdata(state, protect)?;
 
         // Set empty environment for Lua 5.1
         #[cfg(any(feature = "lua51", feature = "luajit"))]
@@ -838,9 +1187,19 @@ impl RawLua {
         Ok(AnyUserData(self.pop_ref()))
     }
 
-    pub(crate) unsafe fn create_userdata_metatable(&self, registry: RawUserDataRegistry) -> Result<Integer> {
+    pub(crate) unsafe fn create_userdata_metatable(
+        &self,
+        mut registry: RawUserDataRegistry,
+    ) -> Result<Integer> {
         let state = self.state();
         let type_id = registry.type_id;
+        let type_name = registry.type_name.clone();
+
+        if type_id.is_some() {
+            let type_def = mem::take(&mut registry.type_def);
+            let type_defs = &mut (*self.extra.get()).userdata_type_defs;
+            type_defs.entry(type_def.name.clone()).or_insert(type_def);
+        }
 
         self.push_userdata_metatable(registry)?;
 
@@ -851,6 +1210,7 @@ impl RawLua {
 
         if let Some(type_id) = type_id {
             (*self.extra.get()).registered_userdata_t.insert(type_id, id);
+            (*self.extra.get()).userdata_type_names.insert(type_id, type_name);
         }
         self.register_userdata_metatable(mt_ptr, type_id);
 
@@ -1022,6 +1382,56 @@ impl RawLua {
         self.get_userdata_type_id_inner(self.ref_thread(), vref.index)
     }
 
+    // Returns the function to copy the userdata into another Lua instance (if the type supports it)
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn get_userdata_ref_cloner(&self, vref: &ValueRef) -> Result<Option<UserDataCloner>> {
+        let type_id = self.get_userdata_ref_type_id(vref)?;
+        Ok(type_id.and_then(|type_id| (*self.extra.get()).userdata_cloners.get(&type_id).copied()))
+    }
+
+    // Returns the persisted name and function of the userdata (if the type is persistable)
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn get_userdata_ref_persist(
+        &self,
+        vref: &ValueRef,
+    ) -> Result<Option<(&'static str, PersistFn)>> {
+        let type_id = self.get_userdata_ref_type_id(vref)?;
+        Ok(type_id.and_then(|type_id| (*self.extra.get()).persist_types.get(&type_id).copied()))
+    }
+
+    // Returns the function to copy the userdata to a channel (if the type is transferable)
+    pub(crate) unsafe fn get_userdata_ref_transfer(&self, vref: &ValueRef) -> Result<Option<TransferFn>> {
+        let type_id = self.get_userdata_ref_type_id(vref)?;
+        Ok(type_id.and_then(|type_id| (*self.extra.get()).transfer_types.get(&type_id).copied()))
+    }
+
+    // Registers a borrow of the buffer data, returns `false` if it conflicts with existing borrows
+    pub(crate) unsafe fn borrow_buffer(&self, ptr: *const c_void, mutable: bool) -> bool {
+        let borrows = (*self.extra.get()).buffer_borrows.entry(ptr).or_insert(0);
+        match (mutable, *borrows) {
+            (false, n) if n >= 0 => *borrows += 1,
+            (true, 0) => *borrows = -1,
+            _ => return false,
+        }
+        true
+    }
+
+    pub(crate) unsafe fn release_buffer(&self, ptr: *const c_void, mutable: bool) {
+        let borrows = &mut (*self.extra.get()).buffer_borrows;
+        match borrows.get_mut(&ptr) {
+            Some(n) if !mutable && *n > 1 => *n -= 1,
+            _ => {
+                borrows.remove(&ptr);
+            }
+        }
+    }
+
+    // Returns the function to restore a persisted userdata by its name
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn get_unpersist(&self, name: &str) -> Option<UnpersistFn> {
+        unsafe { (*self.extra.get()).unpersist_types.get(name).copied() }
+    }
+
     // Same as `get_userdata_ref_type_id` but assumes the userdata is already on the stack.
     pub(crate) unsafe fn get_userdata_type_id<T>(&self, idx: c_int) -> Result<Option<TypeId>> {
         match self.get_userdata_type_id_inner(self.state(), idx) {
@@ -1157,6 +1567,15 @@ impl RawLua {
                 let rawlua = (*extra).raw_lua();
                 let _guard = StateGuard::new(rawlua, state);
 
+                if rawlua
+                    .cancellation_token()
+                    .is_some_and(|token| token.is_cancelled())
+                {
+                    // Drop the future to release its resources
+                    (*upvalue).data = Box::pin(future::pending());
+                    return Err(Error::Cancelled);
+                }
+
                 let fut = &mut (*upvalue).data;
                 let mut ctx = Context::from_waker(rawlua.waker());
                 match fut.as_mut().poll(&mut ctx) {
@@ -1262,6 +1681,35 @@ impl RawLua {
     pub(crate) unsafe fn set_waker(&self, waker: NonNull<Waker>) -> NonNull<Waker> {
         mem::replace(&mut (*self.extra.get()).waker, waker)
     }
+
+    #[cfg(feature = "async")]
+    #[inline]
+    pub(crate) unsafe fn cancellation_token(&self) -> Option<&CancellationToken> {
+        (*self.extra.get()).cancellation_token.as_ref()
+    }
+
+    #[cfg(feature = "async")]
+    #[inline]
+    pub(crate) unsafe fn set_cancellation_token(
+        &self,
+        token: Option<CancellationToken>,
+    ) -> Option<CancellationToken> {
+        mem::replace(&mut (*self.extra.get()).cancellation_token, token)
+    }
+
+    /// Marks the thread yielded by a hook as suspended, so it will not be polled again until the
+    /// current waker is woken up.
+    #[cfg(all(feature = "async", not(feature = "luau")))]
+    #[inline]
+    pub(crate) unsafe fn set_suspended_by_hook(&self) {
+        (*self.extra.get()).suspended_by_hook = true;
+    }
+
+    #[cfg(all(feature = "async", not(feature = "luau")))]
+    #[inline]
+    pub(crate) unsafe fn take_suspended_by_hook(&self) -> bool {
+        mem::take(&mut (*self.extra.get()).suspended_by_hook)
+    }
 }
 
 // Uses 3 stack spaces
@@ -1402,3 +1850,167 @@ unsafe fn load_std_libs(state: *mut ffi::lua_State, libs: StdLib) -> Result<()>
 
     Ok(())
 }
+
+// Drives the active profilers and execution limits, and calls the user hook (Lua 5.x)
+#[cfg(not(feature = "luau"))]
+unsafe extern "C-unwind" fn internal_hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+    let extra = ExtraData::get(state);
+    let Some((hook_state, hook, hook_mask, hook_count)) = (*extra).hook_prev else {
+        // Internal hook is no longer active, the hook was inherited by a coroutine
+        ffi::lua_sethook(state, None, 0, 0);
+        return;
+    };
+
+    let event = (*ar).event;
+    let mut counter = 0;
+    if event == ffi::LUA_HOOKCOUNT {
+        let count = ffi::lua_gethookcount(state) as u64;
+        (*extra).executed_instructions += count;
+        (*extra).hook_counter += count;
+        counter = (*extra).hook_counter;
+        if let Some(ref mut budget) = (*extra).execution_budget {
+            if budget.check((*extra).executed_instructions) {
+                // Check at every instruction from now on to not let Lua code catch the error
+                ffi::lua_sethook(state, Some(internal_hook_proc), ffi::lua_gethookmask(state), 1);
+                raise_execution_limit_exceeded(state);
+            }
+        }
+    }
+
+    // Checks whether the event matches the hook triggers
+    let event_mask = match event {
+        // `LUA_HOOKTAILRET` in Lua 5.1
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        ffi::LUA_HOOKTAILCALL => ffi::LUA_MASKRET,
+        #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+        ffi::LUA_HOOKTAILCALL => ffi::LUA_MASKCALL,
+        event => 1 << event,
+    };
+    let triggered = |mask: c_int, count: c_int| match event {
+        ffi::LUA_HOOKCOUNT => mask & ffi::LUA_MASKCOUNT != 0 && count > 0 && counter % count as u64 == 0,
+        _ => mask & event_mask != 0,
+    };
+
+    for profiler in [&(*extra).profiler, &(*extra).memory_profiler]
+        .into_iter()
+        .flatten()
+    {
+        let triggers = profiler.hook_triggers();
+        if triggered(triggers.mask(), triggers.count()) {
+            profiler.on_hook(state, ar);
+        }
+    }
+
+    // User hooks
+    let hook = hook.filter(|_| state == hook_state && triggered(hook_mask, hook_count));
+    // The hook set by `Lua::set_hook` is called together with the added ones
+    let set_hook = hook.is_some_and(|hook| hook as *const () == hook_proc as *const ());
+    let hooks_triggered = ((*extra).hooks.iter()).any(|(_, t, _)| triggered(t.mask(), t.count()));
+    if set_hook || hooks_triggered {
+        let result = callback_error_ext(state, extra, move |extra, _| {
+            let set_hook_cb = (*extra).hook_callback.clone().filter(|_| set_hook);
+            let hooks = ((*extra).hooks.iter()).filter(|(_, t, _)| triggered(t.mask(), t.count()));
+            let callbacks = (set_hook_cb.into_iter())
+                .chain(hooks.map(|(_, _, cb)| cb.clone()))
+                .collect::<Vec<_>>();
+            let rawlua = (*extra).raw_lua();
+            let _guard = StateGuard::new(rawlua, state);
+            let mut vm_state = VmState::Continue;
+            for callback in callbacks {
+                if Rc::strong_count(&callback) > 2 {
+                    continue; // Don't allow recursion
+                }
+                // Any `Yield` wins, the first error aborts
+                if let VmState::Yield = callback((*extra).lua(), Debug::new(rawlua, ar))? {
+                    vm_state = VmState::Yield;
+                }
+            }
+            Ok(vm_state)
+        });
+        if let VmState::Yield = result {
+            yield_from_hook(state, ar);
+        }
+    } else if let Some(hook) = hook {
+        hook(state, ar);
+    }
+}
+
+// Calls the hook set by `Lua::set_hook` (or `Thread::set_hook`)
+#[cfg(not(feature = "luau"))]
+unsafe extern "C-unwind" fn hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+    let extra = ExtraData::get(state);
+    if (*extra).hook_thread != state {
+        // Hook was destined for a different thread, ignore
+        ffi::lua_sethook(state, None, 0, 0);
+        return;
+    }
+    let result = callback_error_ext(state, extra, move |extra, _| {
+        let hook_cb = (*extra).hook_callback.clone();
+        let hook_cb = mlua_expect!(hook_cb, "no hook callback set in hook_proc");
+        if Rc::strong_count(&hook_cb) > 2 {
+            return Ok(VmState::Continue); // Don't allow recursion
+        }
+        let rawlua = (*extra).raw_lua();
+        let _guard = StateGuard::new(rawlua, state);
+        let debug = Debug::new(rawlua, ar);
+        hook_cb((*extra).lua(), debug)
+    });
+    if let VmState::Yield = result {
+        yield_from_hook(state, ar);
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+unsafe fn yield_from_hook(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+    // Only count and line events can yield
+    if (*ar).event == ffi::LUA_HOOKCOUNT || (*ar).event == ffi::LUA_HOOKLINE {
+        #[cfg(any(feature = "lua54", feature = "lua53"))]
+        if ffi::lua_isyieldable(state) != 0 {
+            ffi::lua_yield(state, 0);
+        }
+        #[cfg(any(feature = "lua52", feature = "lua51", feature = "luajit"))]
+        {
+            ffi::lua_pushliteral(state, "attempt to yield from a hook");
+            ffi::lua_error(state);
+        }
+    }
+}
+
+// Drives the active profilers and execution limits, and calls the user interrupt (Luau)
+#[cfg(feature = "luau")]
+unsafe extern "C-unwind" fn internal_interrupt_proc(state: *mut ffi::lua_State, gc: c_int) {
+    let extra = ExtraData::get(state);
+    if gc < 0 {
+        (*extra).executed_instructions += 1;
+        if let Some(ref mut budget) = (*extra).execution_budget {
+            if budget.check((*extra).executed_instructions) {
+                raise_execution_limit_exceeded(state);
+            }
+        }
+        for profiler in [&(*extra).profiler, &(*extra).memory_profiler]
+            .into_iter()
+            .flatten()
+        {
+            profiler.on_interrupt(state);
+        }
+    }
+    if let Some(Some(prev)) = (*extra).hook_prev {
+        prev(state, gc);
+    }
+}
+
+unsafe fn raise_execution_limit_exceeded(state: *mut ffi::lua_State) -> ! {
+    #[cfg(feature = "luau")]
+    ffi::lua_rawcheckstack(state, 2);
+    // The error is not wrapped into `CallbackError` to be easily matched
+    let _ = push_internal_userdata(state, WrappedFailure::Error(Error::ExecutionLimitExceeded), false);
+    ffi::lua_error(state)
+}
+
+#[cfg(not(feature = "luau"))]
+fn gcd(a: c_int, b: c_int) -> c_int {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
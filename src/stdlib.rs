@@ -44,8 +44,10 @@ impl StdLib {
     pub const PACKAGE: StdLib = StdLib(1 << 8);
 
     /// [`buffer`](https://luau.org/library#buffer-library) library
-    #[cfg(any(feature = "luau", doc))]
-    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    ///
+    /// On Lua versions other than Luau the library is provided by mlua and operates on
+    /// userdata-backed [`Buffer`](crate::Buffer) objects. It's not included in
+    /// [`StdLib::ALL_SAFE`] there and must be requested explicitly.
     pub const BUFFER: StdLib = StdLib(1 << 9);
 
     /// [`vector`](https://luau.org/library#vector-library) library
@@ -76,7 +78,7 @@ impl StdLib {
     pub const ALL: StdLib = StdLib(u32::MAX);
     /// The safe subset of the standard libraries
     #[cfg(not(feature = "luau"))]
-    pub const ALL_SAFE: StdLib = StdLib((1 << 30) - 1);
+    pub const ALL_SAFE: StdLib = StdLib(((1 << 30) - 1) & !(1 << 9));
     #[cfg(feature = "luau")]
     pub const ALL_SAFE: StdLib = StdLib(u32::MAX);
 
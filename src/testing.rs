@@ -0,0 +1,592 @@
+//! A harness for running suites of Lua unit tests.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::string::String as StdString;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::multi::Variadic;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::thread::ThreadStatus;
+use crate::types::XRc;
+use crate::userdata::{UserData, UserDataFields, UserDataMethods};
+use crate::util::xml_escape;
+use crate::value::Value;
+
+// Suffixes of the files discovered as test files
+const TEST_FILE_SUFFIXES: &[&str] = &[
+    "_spec.lua",
+    "_test.lua",
+    ".spec.lua",
+    ".test.lua",
+    "_spec.luau",
+    "_test.luau",
+    ".spec.luau",
+    ".test.luau",
+];
+
+// Maximum depth of nested tables compared by `to_equal`
+const MAX_COMPARE_DEPTH: usize = 64;
+
+/// Runs Lua test files written in the `describe`/`it`/`expect` style.
+///
+/// Each test file is loaded with its own environment table (see [`Chunk::set_environment`]),
+/// which proxies reads to the global environment and provides the following functions:
+///
+/// - `describe(name, fn)` groups tests, groups can be nested.
+/// - `it(name, fn)` registers a test.
+/// - `expect(value)` returns an expectation with the `to_be`, `to_equal`, `to_be_nil`,
+///   `to_be_truthy`, `to_be_falsy` and `to_throw` methods. The `never` field negates the
+///   expectation, eg. `expect(x).never:to_be(1)`.
+/// - `print(...)` captures its output into the test result.
+///
+/// Each test runs in a fresh [`Thread`]. A test fails if it raises an error (or yields).
+///
+/// In Luau, the tests can be run in a sandbox (see [`Lua::sandbox`]).
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{Lua, Result, TestRunner};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let report = TestRunner::new()
+///     .add_chunk("math_spec.lua", r#"
+///         describe("math", function()
+///             it("adds", function()
+///                 expect(1 + 1):to_be(2)
+///             end)
+///         end)
+///     "#)
+///     .run(&lua)?;
+/// assert!(report.is_success());
+/// println!("{}", report.to_tap());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Chunk::set_environment`]: crate::Chunk::set_environment
+/// [`Thread`]: crate::Thread
+/// [`Lua::sandbox`]: crate::Lua::sandbox
+#[derive(Clone, Debug, Default)]
+pub struct TestRunner {
+    sources: Vec<TestSource>,
+    #[cfg(feature = "luau")]
+    sandbox: bool,
+}
+
+#[derive(Clone, Debug)]
+enum TestSource {
+    Chunk(StdString, Vec<u8>),
+    File(PathBuf),
+    Dir(PathBuf),
+}
+
+/// Status of a finished test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestStatus {
+    /// The test passed.
+    Passed,
+    /// The test failed with the given message.
+    Failed(StdString),
+}
+
+/// Result of a single test.
+#[derive(Clone, Debug)]
+pub struct TestResult {
+    /// Name of the test file.
+    pub file: StdString,
+    /// Full name of the test, including names of the enclosing `describe` groups.
+    ///
+    /// Failures to load or run the test file itself are reported with an empty name.
+    pub name: StdString,
+    /// Test status.
+    pub status: TestStatus,
+    /// Output captured from `print` calls.
+    pub output: StdString,
+    /// Time spent running the test.
+    pub duration: Duration,
+}
+
+/// Results of a test run, which can be exported to the JUnit XML or TAP formats.
+#[derive(Clone, Debug, Default)]
+pub struct TestReport {
+    /// Results of all tests, in execution order.
+    pub results: Vec<TestResult>,
+}
+
+impl TestRunner {
+    /// Creates a new test runner without test files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a test file from source code.
+    ///
+    /// The `name` is used as a chunk name (prefixed with `@`) and is shown in the report.
+    #[must_use]
+    pub fn add_chunk(mut self, name: impl Into<StdString>, source: impl AsRef<[u8]>) -> Self {
+        (self.sources).push(TestSource::Chunk(name.into(), source.as_ref().to_vec()));
+        self
+    }
+
+    /// Adds a test file from the filesystem.
+    ///
+    /// The file is read when the tests are run.
+    #[must_use]
+    pub fn add_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.push(TestSource::File(path.into()));
+        self
+    }
+
+    /// Adds all test files found (recursively) in a directory.
+    ///
+    /// Test files are files whose names end with `_spec.lua`, `_test.lua`, `.spec.lua` or
+    /// `.test.lua` (or the same with the `.luau` extension). They are discovered when the tests are
+    /// run and executed in the order of their paths.
+    #[must_use]
+    pub fn add_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.push(TestSource::Dir(path.into()));
+        self
+    }
+
+    /// Enables or disables running tests in a Luau sandbox (see [`Lua::sandbox`]).
+    ///
+    /// The sandbox is disabled once the tests are finished, which discards changes made to the
+    /// global environment.
+    ///
+    /// Requires `feature = "luau"`
+    ///
+    /// [`Lua::sandbox`]: crate::Lua::sandbox
+    #[cfg(any(feature = "luau", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    #[must_use]
+    pub fn sandbox(mut self, enabled: bool) -> Self {
+        self.sandbox = enabled;
+        self
+    }
+
+    /// Runs all tests and returns the report.
+    ///
+    /// Test failures (including errors in the test files) are recorded in the report, an error is
+    /// returned only if the test files cannot be discovered or the test environment cannot be set up.
+    pub fn run(&self, lua: &Lua) -> Result<TestReport> {
+        let mut files = Vec::new();
+        for source in &self.sources {
+            match source {
+                TestSource::Chunk(name, source) => files.push((name.clone(), Ok(source.clone()))),
+                TestSource::File(path) => {
+                    let source = fs::read(path).map_err(|err| err.to_string());
+                    files.push((path.display().to_string(), source));
+                }
+                TestSource::Dir(path) => {
+                    let mut paths = Vec::new();
+                    discover_files(path, &mut paths).map_err(Error::external)?;
+                    paths.sort();
+                    for path in paths {
+                        let source = fs::read(&path).map_err(|err| err.to_string());
+                        files.push((path.display().to_string(), source));
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "luau")]
+        if self.sandbox {
+            lua.sandbox(true)?;
+        }
+        let mut report = TestReport::default();
+        let result = (files.into_iter()).try_for_each(|(name, source)| match source {
+            Ok(source) => run_file(lua, &name, &source, &mut report.results),
+            Err(err) => {
+                report
+                    .results
+                    .push(TestResult::file_failure(name, err, Duration::ZERO));
+                Ok(())
+            }
+        });
+        #[cfg(feature = "luau")]
+        if self.sandbox {
+            lua.sandbox(false)?;
+        }
+        result.map(|_| report)
+    }
+}
+
+impl TestResult {
+    fn file_failure(file: StdString, message: StdString, duration: Duration) -> Self {
+        TestResult {
+            file,
+            name: StdString::new(),
+            status: TestStatus::Failed(message),
+            output: StdString::new(),
+            duration,
+        }
+    }
+
+    /// Returns `true` if the test passed.
+    pub fn is_passed(&self) -> bool {
+        self.status == TestStatus::Passed
+    }
+}
+
+impl TestReport {
+    /// Returns the number of passed tests.
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.is_passed()).count()
+    }
+
+    /// Returns the number of failed tests.
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    /// Returns `true` if all tests passed.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|r| r.is_passed())
+    }
+
+    /// Returns the report in the [JUnit XML] format.
+    ///
+    /// Each test file is represented as a test suite.
+    ///
+    /// [JUnit XML]: https://github.com/testmoapp/junitxml
+    pub fn to_junit(&self) -> StdString {
+        let total_time = self.results.iter().map(|r| r.duration).sum::<Duration>();
+        let mut output = StdString::new();
+        output.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        _ = writeln!(
+            output,
+            "<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.6}\">",
+            self.results.len(),
+            self.failed(),
+            total_time.as_secs_f64(),
+        );
+        let mut start = 0;
+        while start < self.results.len() {
+            let file = &self.results[start].file;
+            let count = (self.results[start..].iter())
+                .take_while(|r| &r.file == file)
+                .count();
+            let suite = &self.results[start..start + count];
+            start += count;
+
+            let failures = suite.iter().filter(|r| !r.is_passed()).count();
+            let time = suite.iter().map(|r| r.duration).sum::<Duration>();
+            _ = writeln!(
+                output,
+                "  <testsuite name=\"{}\" tests=\"{count}\" failures=\"{failures}\" time=\"{:.6}\">",
+                xml_escape(file),
+                time.as_secs_f64(),
+            );
+            for result in suite {
+                _ = write!(
+                    output,
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.6}\"",
+                    xml_escape(&result.name),
+                    xml_escape(file),
+                    result.duration.as_secs_f64(),
+                );
+                if result.is_passed() && result.output.is_empty() {
+                    output.push_str("/>\n");
+                    continue;
+                }
+                output.push_str(">\n");
+                if let TestStatus::Failed(message) = &result.status {
+                    let message = xml_escape(message);
+                    _ = writeln!(output, "      <failure message=\"{message}\">{message}</failure>");
+                }
+                if !result.output.is_empty() {
+                    let out = xml_escape(&result.output);
+                    _ = writeln!(output, "      <system-out>{out}</system-out>");
+                }
+                output.push_str("    </testcase>\n");
+            }
+            output.push_str("  </testsuite>\n");
+        }
+        output.push_str("</testsuites>\n");
+        output
+    }
+
+    /// Returns the report in the [TAP] (version 13) format.
+    ///
+    /// Captured output is written as diagnostic lines.
+    ///
+    /// [TAP]: https://testanything.org/tap-version-13-specification.html
+    pub fn to_tap(&self) -> StdString {
+        let mut output = StdString::new();
+        _ = writeln!(output, "TAP version 13\n1..{}", self.results.len());
+        for (i, result) in self.results.iter().enumerate() {
+            let name = match result.name.as_str() {
+                "" => result.file.clone(),
+                name => format!("{}: {name}", result.file),
+            };
+            let name = name.replace('#', "\\#");
+            match &result.status {
+                TestStatus::Passed => _ = writeln!(output, "ok {} - {name}", i + 1),
+                TestStatus::Failed(message) => {
+                    _ = writeln!(output, "not ok {} - {name}", i + 1);
+                    output.push_str("  ---\n  message: |\n");
+                    for line in message.lines() {
+                        _ = writeln!(output, "    {line}");
+                    }
+                    output.push_str("  ...\n");
+                }
+            }
+            for line in result.output.lines() {
+                _ = writeln!(output, "# {line}");
+            }
+        }
+        output
+    }
+}
+
+// State of the test file being run
+#[derive(Default)]
+struct FileState {
+    groups: Vec<StdString>,
+    tests: Vec<(StdString, Function)>,
+    output: StdString,
+}
+
+fn run_file(lua: &Lua, name: &str, source: &[u8], results: &mut Vec<TestResult>) -> Result<()> {
+    let state = XRc::new(Mutex::new(FileState::default()));
+    let env = create_environment(lua, &state)?;
+
+    // Collect tests
+    let start = Instant::now();
+    let result = (lua.load(source))
+        .set_name(format!("@{name}"))
+        .set_environment(env)
+        .into_function()
+        .and_then(|func| run_in_thread(lua, func));
+    if let Err(err) = result {
+        let message = failure_message(&err);
+        results.push(TestResult::file_failure(
+            name.to_string(),
+            message,
+            start.elapsed(),
+        ));
+        return Ok(());
+    }
+
+    let tests = std::mem::take(&mut state.lock().tests);
+    for (test_name, func) in tests {
+        state.lock().output.clear();
+        let start = Instant::now();
+        let status = match run_in_thread(lua, func) {
+            Ok(()) => TestStatus::Passed,
+            Err(err) => TestStatus::Failed(failure_message(&err)),
+        };
+        results.push(TestResult {
+            file: name.to_string(),
+            name: test_name,
+            status,
+            output: std::mem::take(&mut state.lock().output),
+            duration: start.elapsed(),
+        });
+    }
+    Ok(())
+}
+
+fn run_in_thread(lua: &Lua, func: Function) -> Result<()> {
+    let thread = lua.create_thread(func)?;
+    thread.resume::<()>(())?;
+    if thread.status() == ThreadStatus::Resumable {
+        return Err(Error::runtime("test yielded"));
+    }
+    Ok(())
+}
+
+fn create_environment(lua: &Lua, state: &XRc<Mutex<FileState>>) -> Result<Table> {
+    let env = lua.create_table()?;
+    let env_mt = lua.create_table()?;
+    env_mt.raw_set("__index", lua.globals())?;
+    env.set_metatable(Some(env_mt));
+
+    let st = state.clone();
+    let describe = lua.create_function(move |_, (name, func): (StdString, Function)| {
+        st.lock().groups.push(name);
+        let result = func.call::<()>(());
+        st.lock().groups.pop();
+        result
+    })?;
+    env.raw_set("describe", describe)?;
+
+    let st = state.clone();
+    let it = lua.create_function(move |_, (name, func): (StdString, Function)| {
+        let mut state = st.lock();
+        let mut full_name = state.groups.join(" ");
+        if !full_name.is_empty() {
+            full_name.push(' ');
+        }
+        full_name.push_str(&name);
+        state.tests.push((full_name, func));
+        Ok(())
+    })?;
+    env.raw_set("it", it)?;
+
+    let expect = lua.create_function(|_, value: Value| {
+        Ok(Expectation {
+            value,
+            negated: false,
+        })
+    })?;
+    env.raw_set("expect", expect)?;
+
+    let st = state.clone();
+    let print = lua.create_function(move |_, args: Variadic<Value>| {
+        let mut line = StdString::new();
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                line.push('\t');
+            }
+            line.push_str(&arg.to_string()?);
+        }
+        let mut state = st.lock();
+        state.output.push_str(&line);
+        state.output.push('\n');
+        Ok(())
+    })?;
+    env.raw_set("print", print)?;
+
+    Ok(env)
+}
+
+// Returns the error message without the traceback
+fn failure_message(err: &Error) -> StdString {
+    match err {
+        Error::CallbackError { cause, .. } => failure_message(cause),
+        Error::RuntimeError(message) | Error::SyntaxError { message, .. } => message.clone(),
+        err => err.to_string(),
+    }
+}
+
+struct Expectation {
+    value: Value,
+    negated: bool,
+}
+
+impl Expectation {
+    fn check(&self, lua: &Lua, passed: bool, description: impl FnOnce() -> StdString) -> Result<()> {
+        if passed != self.negated {
+            return Ok(());
+        }
+        let not = if self.negated { "not " } else { "" };
+        let mut message = format!("expected {} {not}{}", describe_value(&self.value), description());
+        // Add location of the failed expectation
+        if let Some(debug) = lua.inspect_stack(1) {
+            let line = debug.curr_line();
+            if let Some(src) = debug.source().short_src {
+                if line > 0 {
+                    message = format!("{src}:{line}: {message}");
+                }
+            }
+        }
+        Err(Error::runtime(message))
+    }
+}
+
+impl UserData for Expectation {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("never", |_, this| {
+            Ok(Expectation {
+                value: this.value.clone(),
+                negated: !this.negated,
+            })
+        });
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("to_be", |lua, this, expected: Value| {
+            let passed = this.value.equals(&expected)?;
+            this.check(lua, passed, || format!("to be {}", describe_value(&expected)))
+        });
+
+        methods.add_method("to_equal", |lua, this, expected: Value| {
+            let passed = deep_equals(&this.value, &expected, 0)?;
+            this.check(lua, passed, || format!("to equal {}", describe_value(&expected)))
+        });
+
+        methods.add_method("to_be_nil", |lua, this, ()| {
+            this.check(lua, this.value.is_nil(), || "to be nil".into())
+        });
+
+        methods.add_method("to_be_truthy", |lua, this, ()| {
+            let passed = !matches!(this.value, Value::Nil | Value::Boolean(false));
+            this.check(lua, passed, || "to be truthy".into())
+        });
+
+        methods.add_method("to_be_falsy", |lua, this, ()| {
+            let passed = matches!(this.value, Value::Nil | Value::Boolean(false));
+            this.check(lua, passed, || "to be falsy".into())
+        });
+
+        methods.add_method("to_throw", |lua, this, pattern: Option<StdString>| {
+            let Value::Function(func) = &this.value else {
+                return this.check(lua, false, || "to be a function".into());
+            };
+            let (passed, error) = match func.call::<()>(()) {
+                Ok(()) => (false, None),
+                Err(err) => {
+                    let message = failure_message(&err);
+                    let passed = (pattern.as_ref()).map_or(true, |pattern| message.contains(pattern));
+                    (passed, Some(message))
+                }
+            };
+            this.check(lua, passed, || match (&pattern, error) {
+                (Some(pattern), Some(error)) => format!("to throw {pattern:?}, got {error:?}"),
+                (Some(pattern), None) => format!("to throw {pattern:?}"),
+                (None, _) => "to throw".into(),
+            })
+        });
+    }
+}
+
+fn describe_value(value: &Value) -> StdString {
+    match value {
+        Value::String(s) => format!("{:?}", s.to_string_lossy()),
+        value => (value.to_string()).unwrap_or_else(|_| value.type_name().to_string()),
+    }
+}
+
+fn deep_equals(a: &Value, b: &Value, depth: usize) -> Result<bool> {
+    let (Value::Table(ta), Value::Table(tb)) = (a, b) else {
+        return a.equals(b);
+    };
+    if ta == tb {
+        return Ok(true);
+    }
+    if depth >= MAX_COMPARE_DEPTH {
+        return Err(Error::runtime("tables are nested too deeply to compare"));
+    }
+    let mut count = 0;
+    for pair in ta.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        if !deep_equals(&value, &tb.raw_get::<Value>(&key)?, depth + 1)? {
+            return Ok(false);
+        }
+        count += 1;
+    }
+    Ok(count == tb.pairs::<Value, Value>().count())
+}
+
+fn discover_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            discover_files(&path, files)?;
+        } else if (path.file_name().and_then(|name| name.to_str()))
+            .is_some_and(|name| TEST_FILE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -17,6 +17,7 @@ use crate::{
 
 #[cfg(feature = "async")]
 use {
+    crate::cancel::CancellationToken,
     futures_util::stream::Stream,
     std::{
         future::Future,
@@ -65,11 +66,12 @@ pub struct AsyncThread<A, R> {
     init_args: Option<A>,
     ret: PhantomData<R>,
     recycle: bool,
+    token: CancellationToken,
 }
 
 impl Thread {
     #[inline(always)]
-    fn state(&self) -> *mut ffi::lua_State {
+    pub(crate) fn state(&self) -> *mut ffi::lua_State {
         self.1
     }
 
@@ -144,6 +146,9 @@ impl Thread {
     ///
     /// It's similar to `resume()` but leaves `nresults` values on the thread stack.
     unsafe fn resume_inner(&self, lua: &RawLua, args: impl IntoLuaMulti) -> Result<c_int> {
+        #[cfg(feature = "luau")]
+        crate::buffer::check_not_borrowed()?;
+
         let state = lua.state();
         let thread_state = self.state();
 
@@ -154,7 +159,9 @@ impl Thread {
         }
 
         let mut nresults = 0;
+        let thread_memory = lua.enter_thread_memory(self)?;
         let ret = ffi::lua_resume(thread_state, state, nargs, &mut nresults as *mut c_int);
+        drop(thread_memory);
         if ret != ffi::LUA_OK && ret != ffi::LUA_YIELD {
             if ret == ffi::LUA_ERRMEM {
                 // Don't call error handler for memory errors
@@ -173,6 +180,42 @@ impl Thread {
         self.status_inner(&self.0.lua.lock())
     }
 
+    /// Sets a memory limit (in bytes) on this thread.
+    ///
+    /// Once an allocation occurs that would pass this memory limit, the thread fails with
+    /// `Error::MemoryError`, without affecting other threads.
+    /// Returns previous limit (zero means no limit).
+    ///
+    /// Memory is accounted while the thread is running (resumed using [`Thread::resume`] or
+    /// polled as [`AsyncThread`]), including coroutines resumed by the thread.
+    /// In Lua 5.x, memory freed (eg. by the garbage collector) while the thread is running is
+    /// subtracted from the thread usage, so accounting is approximate. Luau uses memory
+    /// categories to attribute memory to the thread that allocated it (up to 255 threads).
+    ///
+    /// Does not work in module mode where Lua state is managed externally.
+    ///
+    /// [`AsyncThread`]: crate::AsyncThread
+    pub fn set_memory_limit(&self, limit: usize) -> Result<usize> {
+        let lua = self.0.lua.lock();
+        unsafe {
+            let mut thread_memory =
+                mlua_expect!(lua.thread_memory(self, true)?, "thread memory is not created");
+            Ok(thread_memory.as_mut().set_memory_limit(limit))
+        }
+    }
+
+    /// Returns the amount of memory (in bytes) used by this thread.
+    ///
+    /// Memory is accounted only after setting a limit using [`Thread::set_memory_limit`] (zero
+    /// limit can be used to enable accounting without limiting). Returns `0` otherwise.
+    pub fn used_memory(&self) -> usize {
+        let lua = self.0.lua.lock();
+        match unsafe { lua.thread_memory(self, false) } {
+            Ok(Some(thread_memory)) => unsafe { thread_memory.as_ref().used_memory() },
+            _ => 0,
+        }
+    }
+
     /// Gets the status of the thread (internal implementation).
     pub(crate) fn status_inner(&self, lua: &RawLua) -> ThreadStatus {
         let thread_state = self.state();
@@ -312,6 +355,7 @@ impl Thread {
             init_args: Some(args),
             ret: PhantomData,
             recycle: false,
+            token: CancellationToken::new(),
         }
     }
 
@@ -393,10 +437,64 @@ impl LuaType for Thread {
 
 #[cfg(feature = "async")]
 impl<A, R> AsyncThread<A, R> {
+    /// Returns the cancellation token of this thread.
+    ///
+    /// The token can be used to cancel the thread while it's being awaited.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Cancels the thread.
+    ///
+    /// The thread must be polled to completion to unwind it: async function calls in the thread
+    /// raise [`Error::Cancelled`], which can be caught by the Lua code to run cleanup code.
+    /// If the thread is not started yet, it finishes with [`Error::Cancelled`] immediately.
+    ///
+    /// Refer to [`CancellationToken`] for more details.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
     #[inline]
     pub(crate) fn set_recyclable(&mut self, recyclable: bool) {
         self.recycle = recyclable;
     }
+
+    // Returns `true` if the thread is cancelled before it was started
+    unsafe fn cancel_unstarted(&self) -> bool {
+        self.token.is_cancelled()
+            && ffi::lua_status(self.thread.state()) == ffi::LUA_OK
+            && self.init_args.is_some()
+    }
+
+    // Finishes the thread that raised an error
+    unsafe fn finish_error(&self, lua: &RawLua, err: Error) -> Error {
+        if !self.token.is_cancelled() {
+            return err;
+        }
+        // Close pending to-be-closed variables
+        #[cfg(feature = "lua54")]
+        {
+            #[cfg(not(feature = "vendored"))]
+            let status = ffi::lua_resetthread(self.thread.state());
+            #[cfg(feature = "vendored")]
+            let status = ffi::lua_closethread(self.thread.state(), lua.state());
+            if status != ffi::LUA_OK {
+                // Error object is on top, drop it
+                ffi::lua_settop(self.thread.state(), 0);
+            }
+        }
+        #[cfg(not(feature = "lua54"))]
+        let _ = lua;
+        let mut cause = Some(&err);
+        while let Some(err) = cause {
+            if let Error::Cancelled = err {
+                return Error::Cancelled;
+            }
+            cause = err.parent();
+        }
+        err
+    }
 }
 
 #[cfg(feature = "async")]
@@ -436,15 +534,24 @@ impl<A: IntoLuaMulti, R: FromLuaMulti> Stream for AsyncThread<A, R> {
         let thread_state = self.thread.state();
         unsafe {
             let _sg = StackGuard::new(state);
+            // Also removes the function of a thread that is cancelled before start
             let _thread_sg = StackGuard::with_top(thread_state, 0);
-            let _wg = WakerGuard::new(&lua, cx.waker());
+            if self.cancel_unstarted() {
+                return Poll::Ready(Some(Err(Error::Cancelled)));
+            }
+            self.token.register(cx.waker());
+            let _wg = WakerGuard::new(&lua, cx.waker(), &self.token);
 
             // This is safe as we are not moving the whole struct
             let this = self.get_unchecked_mut();
-            let nresults = if let Some(args) = this.init_args.take() {
-                this.thread.resume_inner(&lua, args)?
+            let res = if let Some(args) = this.init_args.take() {
+                this.thread.resume_inner(&lua, args)
             } else {
-                this.thread.resume_inner(&lua, ())?
+                this.thread.resume_inner(&lua, ())
+            };
+            let nresults = match res {
+                Ok(nresults) => nresults,
+                Err(err) => return Poll::Ready(Some(Err(this.finish_error(&lua, err)))),
             };
 
             if nresults == 1 && is_poll_pending(thread_state) {
@@ -474,15 +581,24 @@ impl<A: IntoLuaMulti, R: FromLuaMulti> Future for AsyncThread<A, R> {
         let thread_state = self.thread.state();
         unsafe {
             let _sg = StackGuard::new(state);
+            // Also removes the function of a thread that is cancelled before start
             let _thread_sg = StackGuard::with_top(thread_state, 0);
-            let _wg = WakerGuard::new(&lua, cx.waker());
+            if self.cancel_unstarted() {
+                return Poll::Ready(Err(Error::Cancelled));
+            }
+            self.token.register(cx.waker());
+            let _wg = WakerGuard::new(&lua, cx.waker(), &self.token);
 
             // This is safe as we are not moving the whole struct
             let this = self.get_unchecked_mut();
-            let nresults = if let Some(args) = this.init_args.take() {
-                this.thread.resume_inner(&lua, args)?
+            let res = if let Some(args) = this.init_args.take() {
+                this.thread.resume_inner(&lua, args)
             } else {
-                this.thread.resume_inner(&lua, ())?
+                this.thread.resume_inner(&lua, ())
+            };
+            let nresults = match res {
+                Ok(nresults) => nresults,
+                Err(err) => return Poll::Ready(Err(this.finish_error(&lua, err))),
             };
 
             if nresults == 1 && is_poll_pending(thread_state) {
@@ -490,6 +606,11 @@ impl<A: IntoLuaMulti, R: FromLuaMulti> Future for AsyncThread<A, R> {
             }
 
             if ffi::lua_status(thread_state) == ffi::LUA_YIELD {
+                // Thread suspended by a hook will be woken up when it's ready to continue
+                #[cfg(not(feature = "luau"))]
+                if lua.take_suspended_by_hook() {
+                    return Poll::Pending;
+                }
                 // Ignore value returned via yield()
                 cx.waker().wake_by_ref();
                 return Poll::Pending;
@@ -513,26 +634,32 @@ unsafe fn is_poll_pending(state: *mut ffi::lua_State) -> bool {
 struct WakerGuard<'lua, 'a> {
     lua: &'lua RawLua,
     prev: NonNull<Waker>,
+    prev_token: Option<CancellationToken>,
     _phantom: PhantomData<&'a ()>,
 }
 
 #[cfg(feature = "async")]
 impl<'lua, 'a> WakerGuard<'lua, 'a> {
     #[inline]
-    pub fn new(lua: &'lua RawLua, waker: &'a Waker) -> Result<WakerGuard<'lua, 'a>> {
+    pub fn new(lua: &'lua RawLua, waker: &'a Waker, token: &CancellationToken) -> WakerGuard<'lua, 'a> {
         let prev = unsafe { lua.set_waker(NonNull::from(waker)) };
-        Ok(WakerGuard {
+        let prev_token = unsafe { lua.set_cancellation_token(Some(token.clone())) };
+        WakerGuard {
             lua,
             prev,
+            prev_token,
             _phantom: PhantomData,
-        })
+        }
     }
 }
 
 #[cfg(feature = "async")]
 impl Drop for WakerGuard<'_, '_> {
     fn drop(&mut self) {
-        unsafe { self.lua.set_waker(self.prev) };
+        unsafe {
+            self.lua.set_waker(self.prev);
+            self.lua.set_cancellation_token(self.prev_token.take());
+        }
     }
 }
 
@@ -6,6 +6,7 @@ use crate::error::{Error, Result};
 use crate::multi::MultiValue;
 use crate::private::Sealed;
 use crate::state::{Lua, RawLua};
+use crate::typedef::TypeDesc;
 use crate::types::MaybeSend;
 use crate::util::{check_stack, short_type_name};
 use crate::value::Value;
@@ -18,6 +19,14 @@ pub trait IntoLua: Sized {
     /// Performs the conversion.
     fn into_lua(self, lua: &Lua) -> Result<Value>;
 
+    /// Returns description of the Lua type produced by the conversion.
+    ///
+    /// Used to generate type definitions, see [`Lua::type_definitions`].
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Any
+    }
+
     /// Pushes the value into the Lua stack.
     ///
     /// # Safety
@@ -34,6 +43,14 @@ pub trait FromLua: Sized {
     /// Performs the conversion.
     fn from_lua(value: Value, lua: &Lua) -> Result<Self>;
 
+    /// Returns description of the Lua type accepted by the conversion.
+    ///
+    /// Used to generate type definitions, see [`Lua::type_definitions`].
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::Any
+    }
+
     /// Performs the conversion for an argument (eg. function argument).
     ///
     /// `i` is the argument index (position),
@@ -77,6 +94,14 @@ pub trait IntoLuaMulti: Sized {
     /// Performs the conversion.
     fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue>;
 
+    /// Returns descriptions of the Lua types produced by the conversion.
+    ///
+    /// Used to generate type definitions, see [`Lua::type_definitions`].
+    #[inline]
+    fn lua_types() -> Vec<TypeDesc> {
+        vec![TypeDesc::Variadic(Box::new(TypeDesc::Any))]
+    }
+
     /// Pushes the values into the Lua stack.
     ///
     /// Returns number of pushed values.
@@ -109,6 +134,14 @@ pub trait FromLuaMulti: Sized {
     /// any missing values are nil.
     fn from_lua_multi(values: MultiValue, lua: &Lua) -> Result<Self>;
 
+    /// Returns descriptions of the Lua types accepted by the conversion.
+    ///
+    /// Used to generate type definitions, see [`Lua::type_definitions`].
+    #[inline]
+    fn lua_types() -> Vec<TypeDesc> {
+        vec![TypeDesc::Variadic(Box::new(TypeDesc::Any))]
+    }
+
     /// Performs the conversion for a list of arguments.
     ///
     /// `i` is an index (position) of the first argument,
@@ -0,0 +1,472 @@
+//! Type definitions for userdata types exposed to Lua.
+//!
+//! Type information is collected when a userdata type is registered (see [`UserDataRegistry`]) and
+//! can be rendered as [LuaLS] annotations or [Luau] type definitions using
+//! [`Lua::type_definitions`].
+//!
+//! [`UserDataRegistry`]: crate::UserDataRegistry
+//! [`Lua::type_definitions`]: crate::Lua::type_definitions
+//! [LuaLS]: https://luals.github.io/wiki/annotations/
+//! [Luau]: https://luau.org/typecheck
+
+use std::fmt::Write as _;
+use std::string::String as StdString;
+
+use crate::util::short_type_name;
+
+/// Description of a Lua type, used to generate type definitions.
+///
+/// Implement [`IntoLua::lua_type`] and [`FromLua::lua_type`] to provide type information for
+/// custom types.
+///
+/// [`IntoLua::lua_type`]: crate::IntoLua::lua_type
+/// [`FromLua::lua_type`]: crate::FromLua::lua_type
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TypeDesc {
+    /// Any Lua value (type is unknown).
+    Any,
+    /// The `nil` value.
+    Nil,
+    /// Lua `boolean`.
+    Boolean,
+    /// Lua integer number.
+    Integer,
+    /// Lua floating point number.
+    Number,
+    /// Lua `string`.
+    String,
+    /// Lua `table` with unknown structure.
+    Table,
+    /// Lua `function` with unknown signature.
+    Function,
+    /// Lua `thread` (coroutine).
+    Thread,
+    /// Userdata object of the named class.
+    UserData(StdString),
+    /// Light userdata.
+    LightUserData,
+    /// Luau `buffer`.
+    Buffer,
+    /// Luau `vector`.
+    Vector,
+    /// Optional value (the type or `nil`).
+    Optional(Box<TypeDesc>),
+    /// Sequence of values of the same type.
+    Array(Box<TypeDesc>),
+    /// Table with keys and values of the given types.
+    Map(Box<TypeDesc>, Box<TypeDesc>),
+    /// One of the given types.
+    Union(Vec<TypeDesc>),
+    /// Variable number of values of the same type.
+    ///
+    /// Only meaningful as the last argument or return value.
+    Variadic(Box<TypeDesc>),
+}
+
+impl TypeDesc {
+    /// Returns type description for a userdata class of the Rust type `T`.
+    ///
+    /// Smart pointer and lock wrappers (eg. `Arc<Mutex<T>>`) are unwrapped.
+    pub fn userdata<T: ?Sized>() -> Self {
+        TypeDesc::UserData(class_name(&short_type_name::<T>()))
+    }
+
+    /// Wraps the type into [`TypeDesc::Optional`] (unless it's already optional).
+    pub fn optional(self) -> Self {
+        match self {
+            TypeDesc::Any | TypeDesc::Nil | TypeDesc::Optional(_) => self,
+            ty => TypeDesc::Optional(Box::new(ty)),
+        }
+    }
+
+    /// Renders the type using LuaLS annotation syntax.
+    pub fn to_luals(&self) -> StdString {
+        match self {
+            TypeDesc::Any => "any".into(),
+            TypeDesc::Nil => "nil".into(),
+            TypeDesc::Boolean => "boolean".into(),
+            TypeDesc::Integer => "integer".into(),
+            TypeDesc::Number => "number".into(),
+            TypeDesc::String => "string".into(),
+            TypeDesc::Table => "table".into(),
+            TypeDesc::Function => "function".into(),
+            TypeDesc::Thread => "thread".into(),
+            TypeDesc::UserData(name) => name.clone(),
+            TypeDesc::LightUserData => "lightuserdata".into(),
+            TypeDesc::Buffer => "buffer".into(),
+            TypeDesc::Vector => "vector".into(),
+            TypeDesc::Optional(ty) => match **ty {
+                TypeDesc::Union(_) => format!("({})?", ty.to_luals()),
+                _ => format!("{}?", ty.to_luals()),
+            },
+            TypeDesc::Array(ty) => match **ty {
+                TypeDesc::Union(_) | TypeDesc::Optional(_) => format!("({})[]", ty.to_luals()),
+                _ => format!("{}[]", ty.to_luals()),
+            },
+            TypeDesc::Map(key, value) => format!("table<{}, {}>", key.to_luals(), value.to_luals()),
+            TypeDesc::Union(types) => join(types.iter().map(|ty| ty.to_luals()), " | "),
+            TypeDesc::Variadic(ty) => format!("{}...", ty.to_luals()),
+        }
+    }
+
+    /// Renders the type using Luau type syntax.
+    pub fn to_luau(&self) -> StdString {
+        match self {
+            TypeDesc::Any | TypeDesc::LightUserData => "any".into(),
+            TypeDesc::Nil => "nil".into(),
+            TypeDesc::Boolean => "boolean".into(),
+            TypeDesc::Integer | TypeDesc::Number => "number".into(),
+            TypeDesc::String => "string".into(),
+            TypeDesc::Table => "{ [any]: any }".into(),
+            TypeDesc::Function => "(...any) -> ...any".into(),
+            TypeDesc::Thread => "thread".into(),
+            TypeDesc::UserData(name) => name.clone(),
+            TypeDesc::Buffer => "buffer".into(),
+            TypeDesc::Vector => "vector".into(),
+            TypeDesc::Optional(ty) => match **ty {
+                TypeDesc::Union(_) | TypeDesc::Function => format!("({})?", ty.to_luau()),
+                _ => format!("{}?", ty.to_luau()),
+            },
+            TypeDesc::Array(ty) => format!("{{ {} }}", ty.to_luau()),
+            TypeDesc::Map(key, value) => format!("{{ [{}]: {} }}", key.to_luau(), value.to_luau()),
+            TypeDesc::Union(types) => join(types.iter().map(|ty| ty.to_luau()), " | "),
+            TypeDesc::Variadic(ty) => format!("...{}", ty.to_luau()),
+        }
+    }
+}
+
+/// Kind of a userdata function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FunctionKind {
+    Method,
+    Function,
+    MetaMethod,
+    MetaFunction,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct FieldDef {
+    pub(crate) name: StdString,
+    pub(crate) ty: TypeDesc,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct FunctionDef {
+    pub(crate) name: StdString,
+    pub(crate) kind: FunctionKind,
+    pub(crate) is_async: bool,
+    pub(crate) args: Vec<TypeDesc>,
+    pub(crate) returns: Vec<TypeDesc>,
+}
+
+/// Type information collected for a single userdata type.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UserDataTypeDef {
+    pub(crate) name: StdString,
+    pub(crate) fields: Vec<FieldDef>,
+    pub(crate) functions: Vec<FunctionDef>,
+}
+
+impl UserDataTypeDef {
+    pub(crate) fn new<T>() -> Self {
+        UserDataTypeDef {
+            name: class_name(&short_type_name::<T>()),
+            fields: Vec::new(),
+            functions: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add_field(&mut self, name: &str, ty: TypeDesc) {
+        // Getter and setter of the same field may have different types
+        match self.fields.iter_mut().find(|f| f.name == name) {
+            Some(field) if field.ty != ty => {
+                field.ty = match field.ty.clone() {
+                    TypeDesc::Union(mut types) if !types.contains(&ty) => {
+                        types.push(ty);
+                        TypeDesc::Union(types)
+                    }
+                    TypeDesc::Union(types) => TypeDesc::Union(types),
+                    TypeDesc::Any => TypeDesc::Any,
+                    _ if ty == TypeDesc::Any => TypeDesc::Any,
+                    prev => TypeDesc::Union(vec![prev, ty]),
+                };
+            }
+            Some(_) => {}
+            None => self.fields.push(FieldDef {
+                name: name.into(),
+                ty,
+            }),
+        }
+    }
+
+    pub(crate) fn add_function(
+        &mut self,
+        name: &str,
+        kind: FunctionKind,
+        is_async: bool,
+        args: Vec<TypeDesc>,
+        returns: Vec<TypeDesc>,
+    ) {
+        self.functions.push(FunctionDef {
+            name: name.into(),
+            kind,
+            is_async,
+            args,
+            returns,
+        });
+    }
+
+    pub(crate) fn extend(&mut self, other: UserDataTypeDef) {
+        for field in other.fields {
+            self.add_field(&field.name, field.ty);
+        }
+        self.functions.extend(other.functions);
+    }
+}
+
+/// Type definitions of registered userdata types.
+///
+/// Returned by [`Lua::type_definitions`].
+///
+/// [`Lua::type_definitions`]: crate::Lua::type_definitions
+#[derive(Debug, Clone, Default)]
+pub struct TypeDefinitions {
+    pub(crate) types: Vec<UserDataTypeDef>,
+}
+
+impl TypeDefinitions {
+    pub(crate) fn new(types: impl IntoIterator<Item = UserDataTypeDef>) -> Self {
+        let mut types = types.into_iter().collect::<Vec<_>>();
+        types.sort_by(|a, b| a.name.cmp(&b.name));
+        TypeDefinitions { types }
+    }
+
+    /// Returns names of the described userdata classes.
+    pub fn class_names(&self) -> impl Iterator<Item = &str> {
+        self.types.iter().map(|ty| ty.name.as_str())
+    }
+
+    /// Renders the definitions as a LuaLS (`---@meta`) annotation file.
+    pub fn to_luals(&self) -> StdString {
+        let mut out = StdString::from("---@meta\n");
+        for ty in &self.types {
+            let name = &ty.name;
+            let _ = write!(out, "\n---@class {name}\n");
+            for field in &ty.fields {
+                let _ = writeln!(out, "---@field {} {}", field.name, field.ty.to_luals());
+            }
+            for func in ty.functions.iter().filter(|f| is_meta(f.kind)) {
+                if let Some(op) = luals_operator(func) {
+                    let _ = writeln!(out, "---@operator {op}");
+                }
+            }
+            let _ = writeln!(out, "local {name} = {{}}");
+
+            for func in ty.functions.iter().filter(|f| !is_meta(f.kind)) {
+                out.push('\n');
+                if func.is_async {
+                    out.push_str("---@async\n");
+                }
+                let mut params = Vec::with_capacity(func.args.len());
+                for (i, arg) in func.args.iter().enumerate() {
+                    match arg {
+                        TypeDesc::Variadic(ty) => {
+                            let _ = writeln!(out, "---@param ... {}", ty.to_luals());
+                            params.push("...".to_string());
+                        }
+                        ty => {
+                            let _ = writeln!(out, "---@param arg{} {}", i + 1, ty.to_luals());
+                            params.push(format!("arg{}", i + 1));
+                        }
+                    }
+                }
+                for ret in &func.returns {
+                    match ret {
+                        TypeDesc::Variadic(ty) => {
+                            let _ = writeln!(out, "---@return {} ...", ty.to_luals());
+                        }
+                        ty => {
+                            let _ = writeln!(out, "---@return {}", ty.to_luals());
+                        }
+                    }
+                }
+                let sep = if func.kind == FunctionKind::Method {
+                    ":"
+                } else {
+                    "."
+                };
+                let _ = writeln!(
+                    out,
+                    "function {name}{sep}{}({}) end",
+                    func.name,
+                    params.join(", ")
+                );
+            }
+        }
+        out
+    }
+
+    /// Renders the definitions as a Luau type definition (`.d.luau`) file.
+    pub fn to_luau(&self) -> StdString {
+        let mut out = StdString::new();
+        for ty in &self.types {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let _ = writeln!(out, "declare class {}", ty.name);
+            for field in &ty.fields {
+                let _ = writeln!(out, "\t{}: {}", luau_key(&field.name), field.ty.to_luau());
+            }
+            for func in &ty.functions {
+                let params = luau_params(&func.args);
+                match func.kind {
+                    FunctionKind::Method | FunctionKind::MetaMethod => {
+                        let params = join(Some("self".to_string()).into_iter().chain(params), ", ");
+                        let _ = write!(out, "\tfunction {}({params})", func.name);
+                        if !func.returns.is_empty() {
+                            let _ = write!(out, ": {}", luau_returns(&func.returns));
+                        }
+                        out.push('\n');
+                    }
+                    // The first argument of a metafunction is the userdata itself
+                    FunctionKind::MetaFunction => {
+                        let params = join(Some("self".to_string()).into_iter().chain(params.skip(1)), ", ");
+                        let _ = write!(out, "\tfunction {}({params})", func.name);
+                        if !func.returns.is_empty() {
+                            let _ = write!(out, ": {}", luau_returns(&func.returns));
+                        }
+                        out.push('\n');
+                    }
+                    FunctionKind::Function => {
+                        let _ = writeln!(
+                            out,
+                            "\t{}: ({}) -> {}",
+                            luau_key(&func.name),
+                            join(params, ", "),
+                            luau_returns(&func.returns)
+                        );
+                    }
+                }
+            }
+            out.push_str("end\n");
+        }
+        out
+    }
+}
+
+/// Returns a class name suitable for type definitions.
+///
+/// Strips well-known wrappers and replaces characters that are not allowed in identifiers.
+pub(crate) fn class_name(type_name: &str) -> StdString {
+    const WRAPPERS: &[&str] = &["UserDataProxy", "Rc", "Arc", "RefCell", "Mutex", "RwLock"];
+
+    let mut name = type_name;
+    'outer: loop {
+        for wrapper in WRAPPERS {
+            if let Some(inner) = (name.strip_prefix(wrapper))
+                .and_then(|s| s.strip_prefix('<'))
+                .and_then(|s| s.strip_suffix('>'))
+            {
+                name = inner;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+
+    let mut result = StdString::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            c if c.is_ascii_alphanumeric() || c == '_' => result.push(c),
+            ' ' => {}
+            _ if result.ends_with('_') => {}
+            _ => result.push('_'),
+        }
+    }
+    result.trim_end_matches('_').to_string()
+}
+
+fn is_meta(kind: FunctionKind) -> bool {
+    matches!(kind, FunctionKind::MetaMethod | FunctionKind::MetaFunction)
+}
+
+fn join(iter: impl IntoIterator<Item = StdString>, sep: &str) -> StdString {
+    iter.into_iter().collect::<Vec<_>>().join(sep)
+}
+
+// Returns LuaLS `@operator` annotation for the metamethod (if supported)
+fn luals_operator(func: &FunctionDef) -> Option<StdString> {
+    let op = func.name.strip_prefix("__")?;
+    let unary = matches!(op, "unm" | "bnot" | "len");
+    let binary = matches!(
+        op,
+        "add"
+            | "sub"
+            | "mul"
+            | "div"
+            | "mod"
+            | "pow"
+            | "idiv"
+            | "band"
+            | "bor"
+            | "bxor"
+            | "shl"
+            | "shr"
+            | "concat"
+    );
+    if !(unary || binary || op == "call") {
+        return None;
+    }
+
+    // Skip `self` argument for metafunctions
+    let skip = (func.kind == FunctionKind::MetaFunction) as usize;
+    let args = func.args.iter().skip(skip).map(|arg| arg.to_luals());
+    let ret = match &func.returns[..] {
+        [] => "nil".to_string(),
+        [ret, ..] => ret.to_luals(),
+    };
+    match unary {
+        true => Some(format!("{op}: {ret}")),
+        false => Some(format!("{op}({}): {ret}", join(args, ", "))),
+    }
+}
+
+fn luau_params(args: &[TypeDesc]) -> impl Iterator<Item = StdString> + '_ {
+    args.iter().enumerate().map(|(i, arg)| match arg {
+        TypeDesc::Variadic(ty) => format!("...: {}", ty.to_luau()),
+        ty => format!("arg{}: {}", i + 1, ty.to_luau()),
+    })
+}
+
+fn luau_returns(returns: &[TypeDesc]) -> StdString {
+    match returns {
+        [ret] if !matches!(ret, TypeDesc::Function) => ret.to_luau(),
+        returns => format!("({})", join(returns.iter().map(|ret| ret.to_luau()), ", ")),
+    }
+}
+
+fn luau_key(name: &str) -> StdString {
+    let is_ident = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match is_ident {
+        true => name.to_string(),
+        false => format!("[{name:?}]"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::class_name;
+
+    #[test]
+    fn test_class_name() {
+        assert_eq!(class_name("MyType"), "MyType");
+        assert_eq!(class_name("Arc<Mutex<MyType>>"), "MyType");
+        assert_eq!(class_name("UserDataProxy<MyType>"), "MyType");
+        assert_eq!(class_name("MyType<i32, String>"), "MyType_i32_String");
+    }
+}
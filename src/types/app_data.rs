@@ -116,6 +116,15 @@ impl AppData {
             .ok()
             .map(|data| *data)
     }
+
+    #[track_caller]
+    pub(crate) fn clear(&self) {
+        if self.borrow.get() != 0 {
+            panic!("cannot mutably borrow app data container");
+        }
+        // SAFETY: we checked that there are no other references to the container
+        unsafe { &mut *self.container.get() }.clear();
+    }
 }
 
 /// A wrapper type for an immutably borrowed value from an app data container.
@@ -30,6 +30,9 @@ pub use cell::{UserDataRef, UserDataRefMut};
 pub use registry::UserDataRegistry;
 pub(crate) use registry::{RawUserDataRegistry, UserDataProxy};
 
+#[cfg(feature = "macros")]
+pub use derive::{HasUserDataMethods, NoUserDataMethods, UserDataMethodsProbe};
+
 /// Kinds of metamethods that can be overridden.
 ///
 /// Currently, this mechanism does not allow overriding the `__gc` metamethod, since there is
@@ -601,6 +604,32 @@ pub trait UserData: Sized {
         Self::add_fields(registry);
         Self::add_methods(registry);
     }
+
+    /// Creates a copy of this userdata in another Lua instance.
+    ///
+    /// Called when a Lua state is copied by [`Lua::fork`] or [`Lua::snapshot`]. Returns `None` if
+    /// the userdata cannot be copied (default), in which case copying the state fails.
+    ///
+    /// [`Lua::fork`]: crate::Lua::fork
+    /// [`Lua::snapshot`]: crate::Lua::snapshot
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    #[allow(unused_variables)]
+    fn clone_into(&self, lua: &Lua) -> Result<Option<AnyUserData>> {
+        Ok(None)
+    }
+}
+
+// Type-erased `UserData::clone_into`
+#[cfg(not(feature = "luau"))]
+pub(crate) type UserDataCloner = fn(&AnyUserData, &Lua) -> Result<Option<AnyUserData>>;
+
+#[cfg(not(feature = "luau"))]
+pub(crate) fn clone_userdata_into<T: UserData + 'static>(
+    ud: &AnyUserData,
+    lua: &Lua,
+) -> Result<Option<AnyUserData>> {
+    T::clone_into(&*ud.borrow::<T>()?, lua)
 }
 
 /// Handle to an internal Lua userdata for any type that implements [`UserData`].
@@ -1062,6 +1091,11 @@ impl Serialize for AnyUserData {
     where
         S: Serializer,
     {
+        // Buffer data must be borrowed before reading
+        #[cfg(not(feature = "luau"))]
+        if let Some(buf) = crate::Buffer::from_userdata(self) {
+            return buf.serialize(serializer);
+        }
         let lua = self.0.lua.lock();
         unsafe {
             let _ = lua
@@ -1104,6 +1138,8 @@ where
 }
 
 mod cell;
+#[cfg(feature = "macros")]
+mod derive;
 mod lock;
 mod object;
 mod registry;
@@ -10,6 +10,7 @@ use serde::ser::{Serialize, Serializer};
 use crate::error::{Error, Result};
 use crate::state::{Lua, RawLua};
 use crate::traits::FromLua;
+use crate::typedef::TypeDesc;
 use crate::types::XRc;
 use crate::userdata::AnyUserData;
 use crate::util::get_userdata;
@@ -226,6 +227,11 @@ impl<T: 'static> FromLua for UserDataRef<T> {
             _ => Err(Error::UserDataTypeMismatch),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::userdata::<T>()
+    }
 }
 
 /// A wrapper type for a userdata value that provides read and write access.
@@ -294,6 +300,11 @@ impl<T: 'static> FromLua for UserDataRefMut<T> {
             _ => Err(Error::UserDataTypeMismatch),
         }
     }
+
+    #[inline]
+    fn lua_type() -> TypeDesc {
+        TypeDesc::userdata::<T>()
+    }
 }
 
 /// A type that provides read access to a userdata value (borrowing the value).
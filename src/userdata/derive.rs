@@ -0,0 +1,41 @@
+//! Support code for the `UserData` derive macro and the `#[mlua::methods]` attribute.
+
+use std::marker::PhantomData;
+
+use crate::userdata::UserDataMethods;
+
+// The derive macro cannot know whether the `#[mlua::methods]` attribute was applied to an `impl`
+// block of the same type. It uses autoref-based specialization instead: the attribute implements
+// `HasUserDataMethods` for `UserDataMethodsProbe<T>`, which takes priority over the blanket
+// `NoUserDataMethods` implementation for `&UserDataMethodsProbe<T>`.
+
+#[doc(hidden)]
+pub struct UserDataMethodsProbe<T>(PhantomData<T>);
+
+impl<T> UserDataMethodsProbe<T> {
+    #[doc(hidden)]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        UserDataMethodsProbe(PhantomData)
+    }
+}
+
+impl<T> Default for UserDataMethodsProbe<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[doc(hidden)]
+pub trait HasUserDataMethods<T> {
+    fn add_methods<M: UserDataMethods<T>>(&self, methods: &mut M);
+}
+
+#[doc(hidden)]
+pub trait NoUserDataMethods<T> {
+    #[inline(always)]
+    fn add_methods<M: UserDataMethods<T>>(&self, _methods: &mut M) {}
+}
+
+impl<T> NoUserDataMethods<T> for &UserDataMethodsProbe<T> {}
@@ -9,6 +9,7 @@ use std::string::String as StdString;
 use crate::error::{Error, Result};
 use crate::state::{Lua, LuaGuard};
 use crate::traits::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti};
+use crate::typedef::{FunctionKind, UserDataTypeDef};
 use crate::types::{Callback, MaybeSend};
 use crate::userdata::{AnyUserData, MetaMethod, UserData, UserDataFields, UserDataMethods, UserDataStorage};
 use crate::util::{get_userdata, short_type_name};
@@ -73,6 +74,9 @@ pub(crate) struct RawUserDataRegistry {
     pub(crate) destructor: ffi::lua_CFunction,
     pub(crate) type_id: Option<TypeId>,
     pub(crate) type_name: StdString,
+
+    // Type information for generating type definitions
+    pub(crate) type_def: UserDataTypeDef,
 }
 
 impl UserDataTypeId {
@@ -129,6 +133,7 @@ impl<T> UserDataRegistry<T> {
             destructor: super::util::userdata_destructor::<T>,
             type_id: ud_type_id.type_id(),
             type_name: short_type_name::<T>(),
+            type_def: UserDataTypeDef::new::<T>(),
         };
 
         UserDataRegistry {
@@ -551,6 +556,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
         V: IntoLua + 'static,
     {
         let name = name.to_string();
+        self.raw.type_def.add_field(&name, V::lua_type());
         self.raw.fields.push((name, value.into_lua(self.lua.lua())));
     }
 
@@ -561,6 +567,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_method(&name, move |lua, data, ()| method(lua, data));
+        self.raw.type_def.add_field(&name, R::lua_type());
         self.raw.field_getters.push((name, callback));
     }
 
@@ -571,6 +578,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_method_mut(&name, method);
+        self.raw.type_def.add_field(&name, A::lua_type());
         self.raw.field_setters.push((name, callback));
     }
 
@@ -581,6 +589,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_function(&name, function);
+        self.raw.type_def.add_field(&name, R::lua_type());
         self.raw.field_getters.push((name, callback));
     }
 
@@ -591,6 +600,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_function_mut(&name, move |lua, (data, val)| function(lua, data, val));
+        self.raw.type_def.add_field(&name, A::lua_type());
         self.raw.field_setters.push((name, callback));
     }
 
@@ -625,6 +635,9 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_method(&name, method);
+        self.raw
+            .type_def
+            .add_function(&name, FunctionKind::Method, false, A::lua_types(), R::lua_types());
         self.raw.methods.push((name, callback));
     }
 
@@ -636,6 +649,9 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_method_mut(&name, method);
+        self.raw
+            .type_def
+            .add_function(&name, FunctionKind::Method, false, A::lua_types(), R::lua_types());
         self.raw.methods.push((name, callback));
     }
 
@@ -650,6 +666,9 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_async_method(&name, method);
+        self.raw
+            .type_def
+            .add_function(&name, FunctionKind::Method, true, A::lua_types(), R::lua_types());
         self.raw.async_methods.push((name, callback));
     }
 
@@ -664,6 +683,9 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_async_method_mut(&name, method);
+        self.raw
+            .type_def
+            .add_function(&name, FunctionKind::Method, true, A::lua_types(), R::lua_types());
         self.raw.async_methods.push((name, callback));
     }
 
@@ -675,6 +697,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_function(&name, function);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::Function,
+            false,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.methods.push((name, callback));
     }
 
@@ -686,6 +715,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_function_mut(&name, function);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::Function,
+            false,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.methods.push((name, callback));
     }
 
@@ -699,6 +735,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_async_function(&name, function);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::Function,
+            true,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.async_methods.push((name, callback));
     }
 
@@ -710,6 +753,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_method(&name, method);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::MetaMethod,
+            false,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.meta_methods.push((name, callback));
     }
 
@@ -721,6 +771,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_method_mut(&name, method);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::MetaMethod,
+            false,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.meta_methods.push((name, callback));
     }
 
@@ -735,6 +792,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_async_method(&name, method);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::MetaMethod,
+            true,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.async_meta_methods.push((name, callback));
     }
 
@@ -749,6 +813,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_async_method_mut(&name, method);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::MetaMethod,
+            true,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.async_meta_methods.push((name, callback));
     }
 
@@ -760,6 +831,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_function(&name, function);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::MetaFunction,
+            false,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.meta_methods.push((name, callback));
     }
 
@@ -771,6 +849,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_function_mut(&name, function);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::MetaFunction,
+            false,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.meta_methods.push((name, callback));
     }
 
@@ -784,6 +869,13 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.to_string();
         let callback = self.box_async_function(&name, function);
+        self.raw.type_def.add_function(
+            &name,
+            FunctionKind::MetaFunction,
+            true,
+            A::lua_types(),
+            R::lua_types(),
+        );
         self.raw.async_meta_methods.push((name, callback));
     }
 }
@@ -810,6 +902,7 @@ macro_rules! lua_userdata_impl {
                 (registry.raw.meta_methods).extend(orig_registry.raw.meta_methods);
                 #[cfg(feature = "async")]
                 (registry.raw.async_meta_methods).extend(orig_registry.raw.async_meta_methods);
+                (registry.raw.type_def).extend(orig_registry.raw.type_def);
             }
         }
     };
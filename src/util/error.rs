@@ -170,6 +170,10 @@ pub(crate) unsafe fn protect_lua_call(
     nargs: c_int,
     f: unsafe extern "C-unwind" fn(*mut ffi::lua_State) -> c_int,
 ) -> Result<()> {
+    // Lua code must not run while buffer data is borrowed
+    #[cfg(feature = "luau")]
+    crate::buffer::check_not_borrowed()?;
+
     let stack_start = ffi::lua_gettop(state) - nargs;
 
     MemoryState::relax_limit_with(state, || {
@@ -231,6 +235,9 @@ where
         }
     }
 
+    #[cfg(feature = "luau")]
+    crate::buffer::check_not_borrowed()?;
+
     let stack_start = ffi::lua_gettop(state) - nargs;
 
     MemoryState::relax_limit_with(state, || {
@@ -0,0 +1,417 @@
+//! A minimal JSON value with a parser and a (compact) writer.
+//!
+//! `serde_json` is not used on purpose: linking it brings `PartialEq` impls between primitive
+//! types and `serde_json::Value` into every dependent crate, which breaks type inference in
+//! otherwise unrelated code (eg. `assert_eq!(vec_of_i64, vec![])`).
+
+use std::fmt::{self, Write as _};
+use std::ops::Index;
+use std::string::String as StdString;
+
+// Maximum nesting of arrays and objects accepted by the parser
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(StdString),
+    Array(Vec<Json>),
+    // Keys are kept in insertion order
+    Object(Vec<(StdString, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+/// Creates a [`Json::Object`] from a list of `"key": value` pairs.
+#[cfg(all(feature = "dap", not(feature = "luau")))]
+macro_rules! json_object {
+    ($($key:literal: $value:expr),* $(,)?) => {
+        $crate::util::json::Json::Object(vec![
+            $(($key.into(), $crate::util::json::Json::from($value))),*
+        ])
+    };
+}
+
+#[cfg(all(feature = "dap", not(feature = "luau")))]
+pub(crate) use json_object;
+
+impl Json {
+    /// Parses a JSON document.
+    pub(crate) fn parse(data: &[u8]) -> Result<Json, StdString> {
+        let mut parser = Parser { data, pos: 0 };
+        let value = parser.parse_value(0)?;
+        parser.skip_whitespace();
+        if parser.pos < data.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    #[cfg(all(feature = "dap", not(feature = "luau")))]
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    #[cfg(all(feature = "dap", not(feature = "luau")))]
+    pub(crate) fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Json::Integer(i) => u64::try_from(i).ok(),
+            _ => None,
+        }
+    }
+
+    #[cfg(all(feature = "dap", not(feature = "luau")))]
+    pub(crate) fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Sets the `key` of an object to `value` (replacing the existing value).
+    ///
+    /// Does nothing if the value is not an object.
+    #[cfg(all(feature = "dap", not(feature = "luau")))]
+    pub(crate) fn set(&mut self, key: &str, value: impl Into<Json>) {
+        if let Json::Object(entries) = self {
+            let value = value.into();
+            match entries.iter_mut().find(|(k, _)| k == key) {
+                Some((_, v)) => *v = value,
+                None => entries.push((key.to_string(), value)),
+            }
+        }
+    }
+
+    /// Removes the `key` from an object and returns its value.
+    #[cfg(all(feature = "dap", not(feature = "luau")))]
+    pub(crate) fn remove(&mut self, key: &str) -> Option<Json> {
+        match self {
+            Json::Object(entries) => {
+                let pos = entries.iter().position(|(k, _)| k == key)?;
+                Some(entries.remove(pos).1)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Index<&str> for Json {
+    type Output = Json;
+
+    // Returns `Null` for missing keys and non-object values
+    fn index(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(entries) => (entries.iter().rev())
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Json {
+                fn from(i: $t) -> Self {
+                    match i64::try_from(i) {
+                        Ok(i) => Json::Integer(i),
+                        Err(_) => Json::Float(i as f64),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(i8, u8, i16, u16, i32, u32, i64, u64, usize);
+
+impl From<f64> for Json {
+    fn from(n: f64) -> Self {
+        Json::Float(n)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<StdString> for Json {
+    fn from(s: StdString) -> Self {
+        Json::String(s)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(vec: Vec<T>) -> Self {
+        Json::Array(vec.into_iter().map(Into::into).collect())
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Integer(i) => write!(f, "{i}"),
+            // `Debug` keeps the fractional part (`1.0`) and uses the exponent for large numbers
+            Json::Float(n) if n.is_finite() => write!(f, "{n:?}"),
+            Json::Float(_) => f.write_str("null"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(array) => {
+                f.write_char('[')?;
+                for (i, value) in array.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(entries) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> StdString {
+        format!("{msg} at position {}", self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.data.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str, value: Json) -> Result<Json, StdString> {
+        match self.data[self.pos..].starts_with(literal.as_bytes()) {
+            true => {
+                self.pos += literal.len();
+                Ok(value)
+            }
+            false => Err(self.error("expected value")),
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<Json, StdString> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+        self.skip_whitespace();
+        match self.data.get(self.pos) {
+            Some(b'n') => self.expect("null", Json::Null),
+            Some(b't') => self.expect("true", Json::Bool(true)),
+            Some(b'f') => self.expect("false", Json::Bool(false)),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut array = Vec::new();
+                self.skip_whitespace();
+                if self.data.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(array));
+                }
+                loop {
+                    array.push(self.parse_value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.data.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(array));
+                        }
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.data.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(entries));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.data.get(self.pos) != Some(&b'"') {
+                        return Err(self.error("expected string key"));
+                    }
+                    let key = self.parse_string()?;
+                    self.skip_whitespace();
+                    if self.data.get(self.pos) != Some(&b':') {
+                        return Err(self.error("expected `:`"));
+                    }
+                    self.pos += 1;
+                    entries.push((key, self.parse_value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.data.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(entries));
+                        }
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some(_) => Err(self.error("expected value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, StdString> {
+        let start = self.pos;
+        let mut is_float = false;
+        if self.data[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        let digits = |this: &mut Self| {
+            let start = this.pos;
+            while let Some(b'0'..=b'9') = this.data.get(this.pos) {
+                this.pos += 1;
+            }
+            this.pos > start
+        };
+        if !digits(self) {
+            return Err(self.error("invalid number"));
+        }
+        if self.data.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            is_float = true;
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.data.get(self.pos) {
+            self.pos += 1;
+            is_float = true;
+            if let Some(b'+' | b'-') = self.data.get(self.pos) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        // The slice contains only ASCII characters
+        let text = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or_default();
+        if !is_float {
+            if let Ok(i) = text.parse::<i64>() {
+                return Ok(Json::Integer(i));
+            }
+        }
+        (text.parse::<f64>().map(Json::Float)).map_err(|_| self.error("invalid number"))
+    }
+
+    fn parse_string(&mut self) -> Result<StdString, StdString> {
+        self.pos += 1; // opening quote
+        let mut buf = Vec::new();
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = match self.data.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.pos += 1;
+                    buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(&b) if b < 0x20 => return Err(self.error("control character in string")),
+                Some(&b) => {
+                    buf.push(b);
+                    self.pos += 1;
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+        StdString::from_utf8(buf).map_err(|_| self.error("invalid utf-8 in string"))
+    }
+
+    // Parses `XXXX` (and a following `\uXXXX` low surrogate) after `\u`, leaving the position at
+    // the last hex digit
+    fn parse_unicode_escape(&mut self) -> Result<char, StdString> {
+        let hex = |this: &mut Self| {
+            let digits = this.data.get(this.pos + 1..this.pos + 5);
+            let code = digits
+                .and_then(|d| std::str::from_utf8(d).ok())
+                .and_then(|d| u32::from_str_radix(d, 16).ok())
+                .ok_or_else(|| this.error("invalid unicode escape"))?;
+            this.pos += 4;
+            Ok::<_, StdString>(code)
+        };
+        let code = hex(self)?;
+        let code = match code {
+            0xD800..=0xDBFF if self.data.get(self.pos + 1..self.pos + 3) == Some(b"\\u") => {
+                self.pos += 2;
+                let low = hex(self)?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error("invalid unicode escape"));
+                }
+                0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+            }
+            code => code,
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+}
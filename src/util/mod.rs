@@ -9,6 +9,9 @@ pub(crate) use error::{
     error_traceback, error_traceback_thread, init_error_registry, pop_error, protect_lua_call,
     protect_lua_closure, WrappedFailure,
 };
+pub(crate) use path::{field_path, join_path};
+#[cfg(feature = "serialize")]
+pub(crate) use path::{index_segment, key_segment, value_key_segment};
 pub(crate) use short_names::short_type_name;
 pub(crate) use types::TypeKey;
 pub(crate) use userdata::{
@@ -329,7 +332,27 @@ pub(crate) fn linenumber_to_usize(n: c_int) -> Option<usize> {
     }
 }
 
+// Escapes special characters in XML text or attribute values
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+#[cfg(any(all(feature = "dap", not(feature = "luau")), feature = "codecs"))]
+pub(crate) mod json;
+
 mod error;
+mod path;
 mod short_names;
 mod types;
 mod userdata;
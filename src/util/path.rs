@@ -0,0 +1,49 @@
+//! Paths to nested values (like `servers[3].port`) used in error messages.
+
+use std::fmt::Display;
+use std::string::String as StdString;
+
+use crate::value::Value;
+
+/// Returns a path segment for a string key.
+///
+/// Identifier-like keys are written as is (`name`), other strings are quoted (`["a b"]`).
+pub(crate) fn key_segment(key: &str) -> StdString {
+    let mut chars = key.chars();
+    let is_ident = chars.next().is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric());
+    match is_ident {
+        true => key.to_string(),
+        false => format!("[{key:?}]"),
+    }
+}
+
+/// Returns a path segment for an index (`[1]`).
+pub(crate) fn index_segment(index: impl Display) -> StdString {
+    format!("[{index}]")
+}
+
+/// Returns a path segment for a Lua table key.
+pub(crate) fn value_key_segment(key: &Value) -> StdString {
+    match key {
+        Value::String(s) => key_segment(&s.to_string_lossy()),
+        Value::Integer(i) => index_segment(i),
+        Value::Number(n) => index_segment(n),
+        Value::Boolean(b) => index_segment(b),
+        _ => index_segment(key.type_name()),
+    }
+}
+
+/// Appends `subpath` to `path`, separating them by a dot unless `subpath` starts with `[`.
+pub(crate) fn join_path(path: &str, subpath: &str) -> StdString {
+    if path.is_empty() || subpath.is_empty() || subpath.starts_with('[') {
+        format!("{path}{subpath}")
+    } else {
+        format!("{path}.{subpath}")
+    }
+}
+
+/// Returns the path to the field `key` of the value at `path`.
+pub(crate) fn field_path(path: &str, key: &Value) -> StdString {
+    join_path(path, &value_key_segment(key))
+}
@@ -0,0 +1,110 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use mlua::{Error, Lua, LuaActor, LuaHandle, Result, Table};
+
+#[test]
+fn test_actor_exec() -> Result<()> {
+    let actor = LuaActor::new()?;
+    actor
+        .exec(|lua| lua.globals().set("counter", 0).unwrap())
+        .wait()?;
+
+    let threads = (0..4)
+        .map(|_| {
+            let actor = actor.clone();
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    let task = actor.exec(|lua| lua.load("counter = counter + 1").exec().is_ok());
+                    assert!(task.wait().unwrap());
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let counter = actor.exec(|lua| lua.globals().get::<i64>("counter").unwrap());
+    assert_eq!(counter.wait()?, 40);
+
+    Ok(())
+}
+
+#[test]
+fn test_actor_with_lua() -> Result<()> {
+    let actor = LuaActor::with_lua(|| {
+        let lua = Lua::new();
+        lua.globals().set("name", "actor").unwrap();
+        lua
+    })?;
+    let name = actor.exec(|lua| lua.globals().get::<String>("name").unwrap());
+    assert_eq!(name.wait()?, "actor");
+
+    Ok(())
+}
+
+#[test]
+fn test_actor_handles() -> Result<()> {
+    let actor = LuaActor::new()?;
+    let handle = actor
+        .exec(|lua| {
+            let t = lua.create_table().unwrap();
+            t.set("value", 1).unwrap();
+            LuaHandle::<Table>::new(lua, t).unwrap()
+        })
+        .wait()?;
+
+    // The handle can be moved to another thread and back
+    let handle = std::thread::spawn(move || handle).join().unwrap();
+    let value = actor
+        .exec(move |lua| handle.get(lua).and_then(|t| t.get::<i64>("value")).unwrap())
+        .wait()?;
+    assert_eq!(value, 1);
+
+    // Handles cannot be used with another Lua state
+    let handle = actor.exec(|lua| LuaHandle::new(lua, 123).unwrap()).wait()?;
+    let lua = Lua::new();
+    match handle.get(&lua) {
+        Err(Error::MismatchedRegistryKey) => {}
+        res => panic!("expected MismatchedRegistryKey, got {res:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_actor_panic() -> Result<()> {
+    let actor = LuaActor::new()?;
+    let task = actor.exec(|_| -> () { panic!("actor panic") });
+    let err = panic::catch_unwind(AssertUnwindSafe(|| task.wait())).unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&"actor panic"));
+
+    // The actor keeps running
+    let value = actor.exec(|lua| lua.load("1 + 1").eval::<i64>().unwrap());
+    assert_eq!(value.wait()?, 2);
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_actor_async() -> Result<()> {
+    let actor = LuaActor::new()?;
+
+    let tasks = (0..8)
+        .map(|i| {
+            let actor = actor.clone();
+            tokio::spawn(async move {
+                let task = actor.exec(move |lua| lua.load(format!("return {i} * 2")).eval::<i64>().unwrap());
+                task.await.unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut sum = 0;
+    for task in tasks {
+        sum += task.await.unwrap();
+    }
+    assert_eq!(sum, 56);
+
+    Ok(())
+}
@@ -567,3 +567,165 @@ async fn test_async_terminate() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "macros")]
+#[tokio::test]
+async fn test_userdata_derive_async_methods() -> Result<()> {
+    let lua = Lua::new();
+
+    #[derive(mlua::UserData)]
+    struct Sleeper {
+        pub count: u64,
+    }
+
+    #[mlua::methods]
+    impl Sleeper {
+        async fn sleep(&self, lua: Lua, ms: u64) -> Result<u64> {
+            let _ = lua.globals();
+            sleep_ms(ms).await;
+            Ok(self.count + ms)
+        }
+
+        async fn bump(&mut self) {
+            self.count += 1;
+        }
+    }
+
+    lua.globals().set("s", Sleeper { count: 1 })?;
+    let res = lua
+        .load("s:bump(); return s:sleep(10)")
+        .eval_async::<u64>()
+        .await?;
+    assert_eq!(res, 12);
+
+    Ok(())
+}
+
+#[cfg(any(feature = "lua54", feature = "lua53"))]
+#[tokio::test]
+async fn test_async_debugger_suspend() -> Result<()> {
+    use mlua::debugger::{DebugAction, Debugger};
+
+    let lua = Lua::new();
+
+    let debugger = Debugger::new(|_lua, _ctx| Ok(DebugAction::Suspend));
+    debugger.add_breakpoint("suspend.lua", 2);
+
+    let func = (lua.load("local a = 1\nlocal b = a * 2\nreturn b"))
+        .set_name("@suspend.lua")
+        .into_function()?;
+    let thread = lua.create_thread(func)?;
+    debugger.attach_thread(&thread);
+
+    let resume = async {
+        while !debugger.is_suspended() {
+            sleep_ms(10).await;
+        }
+        // Suspended thread must not be polled until resumed
+        sleep_ms(50).await;
+        assert_eq!(thread.status(), mlua::ThreadStatus::Resumable);
+        debugger.resume(DebugAction::Continue);
+    };
+    let (res, _) = tokio::join!(thread.clone().into_async::<i64>(()), resume);
+    assert_eq!(res?, 2);
+    assert!(!debugger.is_suspended());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_thread_cancel() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(|lua, n: u64| async move {
+        let token = lua.cancellation_token().expect("no cancellation token");
+        lua.set_app_data(token);
+        sleep_ms(n).await;
+        Ok(())
+    })?;
+    lua.globals().set("sleep", sleep)?;
+    assert!(lua.cancellation_token().is_none());
+
+    // Uncaught cancellation
+    let func = lua.load("sleep(10000); return 1").into_function()?;
+    let thread = lua.create_thread(func)?.into_async::<i64>(());
+    let token = thread.cancellation_token();
+    let cancel = async {
+        sleep_ms(20).await;
+        token.cancel();
+    };
+    match tokio::join!(thread, cancel).0 {
+        Err(Error::Cancelled) => {}
+        res => panic!("expected cancelled error, got {res:?}"),
+    }
+    // The async function had access to the same token
+    assert!(lua
+        .app_data_ref::<mlua::CancellationToken>()
+        .unwrap()
+        .is_cancelled());
+
+    // Cancellation can be caught to run cleanup code (Lua 5.1 cannot yield across pcall)
+    #[cfg(not(feature = "lua51"))]
+    {
+        let func = lua
+            .load(
+                r#"
+            local ok, err = pcall(sleep, 10000)
+            assert(not ok and tostring(err):find("cancelled"))
+            -- Subsequent async calls fail as well
+            assert(not pcall(sleep, 0))
+            return "cleanup"
+        "#,
+            )
+            .into_function()?;
+        let thread = lua.create_thread(func)?.into_async::<StdString>(());
+        let token = thread.cancellation_token();
+        let (res, _) = tokio::join!(thread, async {
+            sleep_ms(20).await;
+            token.cancel();
+        });
+        assert_eq!(res?, "cleanup");
+    }
+
+    // Threads cancelled before start are not started
+    let func = lua.load("started = true").into_function()?;
+    let thread = lua.create_thread(func)?.into_async::<()>(());
+    thread.cancel();
+    assert!(matches!(thread.await, Err(Error::Cancelled)));
+    assert_eq!(lua.globals().get::<Option<bool>>("started")?, None);
+
+    Ok(())
+}
+
+#[cfg(feature = "lua54")]
+#[tokio::test]
+async fn test_async_thread_cancel_close() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(|_, n: u64| async move {
+        sleep_ms(n).await;
+        Ok(())
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    let func = lua
+        .load(
+            r#"
+            local guard <close> = setmetatable({}, { __close = function(_, err) closed = tostring(err) end })
+            sleep(10000)
+        "#,
+        )
+        .into_function()?;
+    let thread = lua.create_thread(func)?;
+    let async_thread = thread.clone().into_async::<()>(());
+    let token = async_thread.cancellation_token();
+    let (res, _) = tokio::join!(async_thread, async {
+        sleep_ms(20).await;
+        token.cancel();
+    });
+    assert!(matches!(res, Err(Error::Cancelled)));
+    assert!(lua.globals().get::<StdString>("closed")?.contains("cancelled"));
+    assert_eq!(thread.status(), mlua::ThreadStatus::Finished);
+
+    Ok(())
+}
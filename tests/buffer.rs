@@ -1,7 +1,11 @@
-#![cfg(feature = "luau")]
+use mlua::{Error, Lua, LuaOptions, Result, StdLib, Value};
 
-use mlua::{Lua, Result, Value};
+// Creates a Lua state with the `buffer` library (it's not loaded by default on Lua 5.x)
+fn new_lua() -> Result<Lua> {
+    Lua::new_with(StdLib::ALL_SAFE | StdLib::BUFFER, LuaOptions::default())
+}
 
+#[cfg(feature = "luau")]
 #[test]
 fn test_buffer() -> Result<()> {
     let lua = Lua::new();
@@ -28,10 +32,10 @@ fn test_buffer() -> Result<()> {
 
     // Check buffer methods
     assert_eq!(buf1.len(), 5);
-    assert_eq!(buf1.to_vec(), b"hello");
+    assert_eq!(buf1.to_vec()?, b"hello");
     assert_eq!(buf1.read_bytes::<3>(1), [b'e', b'l', b'l']);
     buf1.write_bytes(1, b"i");
-    assert_eq!(buf1.to_vec(), b"hillo");
+    assert_eq!(buf1.to_vec()?, b"hillo");
 
     let buf3 = lua.create_buffer(b"")?;
     assert!(buf3.is_empty());
@@ -40,6 +44,107 @@ fn test_buffer() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_buffer_lib() -> Result<()> {
+    let lua = new_lua()?;
+
+    lua.load(
+        r#"
+        local buf = buffer.create(8)
+        assert(buffer.len(buf) == 8)
+        assert(buffer.tostring(buf) == string.rep("\0", 8))
+        assert(tostring(buf):find("^buffer:"))
+
+        buffer.writeu8(buf, 0, 255)
+        buffer.writei16(buf, 1, -2)
+        assert(buffer.readi8(buf, 0) == -1)
+        assert(buffer.readu16(buf, 1) == 0xFFFE)
+        buffer.writeu32(buf, 4, 0x1FFFFFFFF)
+        assert(buffer.readu32(buf, 4) == 0xFFFFFFFF)
+
+        buffer.writestring(buf, 2, "hello", 3)
+        assert(buffer.readstring(buf, 2, 3) == "hel")
+        buffer.fill(buf, 0, 0x61, 2)
+        buffer.fill(buf, 5, 0)
+        assert(buffer.tostring(buf) == "aahel\0\0\0")
+
+        local src = buffer.fromstring("xyz")
+        buffer.copy(buf, 5, src)
+        buffer.copy(buf, 0, buf, 3, 4)
+        assert(buffer.tostring(buf) == "elxylxyz")
+
+        assert(not pcall(buffer.readu8, buf, 8))
+        assert(not pcall(buffer.readu32, buf, -1))
+        assert(not pcall(buffer.writestring, buf, 6, "abc"))
+        assert(not pcall(buffer.copy, buf, 0, src, 4))
+        assert(not pcall(buffer.create, -1))
+    "#,
+    )
+    .exec()?;
+
+    // The library is not loaded without `StdLib::BUFFER`
+    #[cfg(not(feature = "luau"))]
+    assert!(Lua::new().globals().get::<Value>("buffer")?.is_nil());
+    let lua = Lua::new_with(StdLib::NONE, Default::default())?;
+    assert!(lua.globals().get::<Value>("buffer")?.is_nil());
+    lua.load_std_libs(StdLib::BUFFER)?;
+    assert!(lua.globals().get::<Value>("buffer")?.is_table());
+
+    Ok(())
+}
+
+#[test]
+fn test_buffer_copy() -> Result<()> {
+    let lua = new_lua()?;
+    lua.globals().set("buf", lua.create_buffer(b"data")?)?;
+
+    // Buffers can be sent through channels, forked and persisted (Lua 5.x only)
+    let channel = lua.create_channel()?;
+    channel.send(&lua, lua.globals().get::<Value>("buf")?)?;
+    lua.globals().set("received", channel.try_recv(&lua)?)?;
+
+    #[cfg(not(feature = "luau"))]
+    {
+        let fork = lua.fork()?;
+        fork.load(r#"assert(buffer.tostring(buf) == "data")"#).exec()?;
+
+        let data = lua.persist(None, lua.globals().get::<Value>("buf")?)?;
+        lua.globals().set("restored", lua.unpersist(None, &data)?)?;
+    }
+
+    lua.load(
+        r#"
+        buffer.writestring(buf, 0, "D")
+        assert(buffer.tostring(received) == "data")
+        assert(restored == nil or buffer.tostring(restored) == "data")
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_buffer_userdata() -> Result<()> {
+    let lua = new_lua()?;
+
+    let buf = lua.load(r#"buffer.fromstring("hello")"#).eval::<Value>()?;
+    assert!(buf.is_userdata());
+    let buf = lua.unpack::<mlua::Buffer>(buf)?;
+    assert_eq!(buf.to_vec()?, b"hello");
+
+    // Only buffers can be converted to `Buffer`
+    let ud = lua.create_any_userdata(())?;
+    assert!(lua.unpack::<mlua::Buffer>(Value::UserData(ud)).is_err());
+
+    // Methods of the underlying userdata are not accessible
+    let func = lua.load("local buf = ...; return buf.len").into_function()?;
+    assert!(func.call::<Value>(&buf).is_err());
+
+    Ok(())
+}
+
 #[test]
 #[should_panic(expected = "range end index 14 out of range for slice of length 13")]
 fn test_buffer_out_of_bounds_read() {
@@ -48,6 +153,7 @@ fn test_buffer_out_of_bounds_read() {
     _ = buf.read_bytes::<1>(13);
 }
 
+#[cfg(feature = "luau")]
 #[test]
 #[should_panic(expected = "range end index 16 out of range for slice of length 13")]
 fn test_buffer_out_of_bounds_write() {
@@ -55,3 +161,130 @@ fn test_buffer_out_of_bounds_write() {
     let buf = lua.create_buffer(b"hello, world!").unwrap();
     buf.write_bytes(14, b"!!");
 }
+
+#[test]
+fn test_buffer_views() -> Result<()> {
+    let lua = new_lua()?;
+    let buf = lua.create_buffer(b"hello")?;
+
+    buf.with_slice(|data| {
+        assert_eq!(data, b"hello");
+        // Multiple shared borrows are allowed
+        assert_eq!(buf.with_slice(|data| data.to_vec()).unwrap(), b"hello");
+        assert_eq!(buf.to_vec().unwrap(), b"hello");
+        assert!(matches!(
+            buf.with_mut_slice(|_| ()),
+            Err(Error::BufferBorrowMutError)
+        ));
+        assert!(matches!(
+            buf.try_write_bytes(0, b"j"),
+            Err(Error::BufferBorrowMutError)
+        ));
+    })?;
+
+    // Lua code cannot modify the buffer while it's borrowed
+    lua.globals().set("buf", &buf)?;
+    let write = lua.load("buffer.writeu8(buf, 0, 0)").into_function()?;
+    buf.with_mut_slice(|data| {
+        data[0] = b'j';
+        assert_eq!(buf.len(), 5);
+        assert!(matches!(buf.with_slice(|_| ()), Err(Error::BufferBorrowError)));
+        assert!(matches!(
+            buf.with_mut_slice(|_| ()),
+            Err(Error::BufferBorrowMutError)
+        ));
+        assert!(matches!(buf.to_vec(), Err(Error::BufferBorrowError)));
+        assert!(matches!(
+            buf.try_read_bytes::<1>(0),
+            Err(Error::BufferBorrowError)
+        ));
+        let res = write.call::<()>(());
+        #[cfg(feature = "luau")]
+        assert!(matches!(res, Err(Error::BufferBorrowMutError)));
+        #[cfg(not(feature = "luau"))]
+        assert!(res.is_err());
+    })?;
+    assert_eq!(buf.to_vec()?, b"jello");
+
+    // Changes are visible to Lua
+    lua.load(r#"assert(buffer.tostring(buf) == "jello")"#).exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_buffer_typed_access() -> Result<()> {
+    let lua = new_lua()?;
+    let buf = lua.create_buffer([0u8; 16])?;
+
+    buf.write_i8(0, -1)?;
+    buf.write_u16(1, 0xBEEF)?;
+    buf.write_i32(3, -123456)?;
+    buf.write_f64(8, 1.5)?;
+    assert_eq!(buf.read_u8(0)?, 0xFF);
+    assert_eq!(buf.read_i8(0)?, -1);
+    assert_eq!(buf.read_u16(1)?, 0xBEEF);
+    assert_eq!(buf.read_i32(3)?, -123456);
+    assert_eq!(buf.read_f64(8)?, 1.5);
+
+    // Values are stored in the same format as Luau `buffer` library uses
+    lua.globals().set("buf", &buf)?;
+    lua.load(
+        r#"
+        assert(buffer.readu16(buf, 1) == 0xBEEF)
+        assert(buffer.readi32(buf, 3) == -123456)
+        assert(buffer.readf64(buf, 8) == 1.5)
+        buffer.writef32(buf, 4, 2.25)
+        buffer.writeu32(buf, 0, 0xDEADBEEF)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(buf.read_f32(4)?, 2.25);
+    assert_eq!(buf.read_u32(0)?, 0xDEADBEEF);
+
+    // Out of bounds access returns an error
+    assert!(buf.read_f64(9).is_err());
+    assert!(buf.write_u16(15, 0).is_err());
+    assert!(buf.try_read_bytes::<1>(usize::MAX).is_err());
+    match buf.try_write_bytes(14, b"abc") {
+        Err(err) => assert!(err.to_string().contains("buffer access out of bounds"), "{err}"),
+        Ok(_) => panic!("expected error"),
+    }
+    assert_eq!(buf.try_read_bytes::<2>(14)?, &1.5f64.to_le_bytes()[6..]);
+
+    Ok(())
+}
+
+#[test]
+fn test_buffer_cursor() -> Result<()> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let lua = Lua::new();
+    let buf = lua.create_buffer([0u8; 8])?;
+
+    let mut cursor = buf.cursor();
+    cursor.write_all(b"abcdef").unwrap();
+    assert_eq!(cursor.position(), 6);
+    // Buffer cannot grow
+    assert_eq!(cursor.write(b"ghij").unwrap(), 2);
+    assert_eq!(cursor.write(b"k").unwrap(), 0);
+    assert!(cursor.write_all(b"k").is_err());
+    assert_eq!(buf.to_vec()?, b"abcdefgh");
+
+    cursor.seek(SeekFrom::Start(2)).unwrap();
+    let mut data = [0u8; 3];
+    cursor.read_exact(&mut data).unwrap();
+    assert_eq!(&data, b"cde");
+    assert_eq!(cursor.seek(SeekFrom::End(-1)).unwrap(), 7);
+    assert_eq!(cursor.seek(SeekFrom::Current(-7)).unwrap(), 0);
+    assert!(cursor.seek(SeekFrom::Current(-1)).is_err());
+
+    let mut rest = Vec::new();
+    cursor.seek(SeekFrom::Start(4)).unwrap();
+    cursor.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"efgh");
+    cursor.seek(SeekFrom::Start(100)).unwrap();
+    assert_eq!(cursor.read(&mut data).unwrap(), 0);
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use mlua::{Channel, Lua, Result, Table, Transferable, UserData, UserDataMethods, Value};
+
+#[derive(Clone)]
+struct Job {
+    id: i64,
+}
+
+impl UserData for Job {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("id", |_, this, ()| Ok(this.id));
+    }
+}
+
+impl Transferable for Job {
+    fn transfer(&self) -> Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+struct Socket;
+
+impl UserData for Socket {}
+
+#[test]
+fn test_channel_values() -> Result<()> {
+    let lua = Lua::new();
+    let channel = lua.create_channel()?;
+    lua.globals().set("channel", channel.clone())?;
+    lua.load(
+        r#"
+        local shared = { "shared" }
+        local t = { a = shared, b = shared, [shared] = true, n = 1.5, i = 10, s = "str", flag = false }
+        t.self = t
+        channel:send(t)
+        channel:send("hello")
+        channel:send(42)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(channel.len(), 3);
+
+    let lua2 = Lua::new();
+    lua2.globals().set("channel", channel)?;
+    lua2.load(
+        r#"
+        local t = channel:recv()
+        assert(t.self == t and t.a == t.b and t.a[1] == "shared" and t[t.a] == true)
+        assert(t.n == 1.5 and t.i == 10 and math.type == nil or math.type(t.i) == "integer")
+        assert(t.s == "str" and t.flag == false)
+        assert(channel:try_recv() == "hello")
+        assert(#channel == 1)
+        assert(channel:recv() == 42)
+        assert(channel:try_recv() == nil)
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_channel_errors() -> Result<()> {
+    let lua = Lua::new();
+    let channel = lua.create_channel()?;
+
+    match channel.send(&lua, lua.load("{ f = { function() end } }").eval::<Table>()?) {
+        Err(err) => assert!(
+            err.to_string()
+                .contains("cannot send function at `value.f[1]` through a channel"),
+            "{err}"
+        ),
+        Ok(_) => panic!("expected error"),
+    }
+    let t = lua.load("{ t = setmetatable({}, {}) }").eval::<Table>()?;
+    assert!(channel.send(&lua, t).is_err());
+    let t = lua.create_table_from([("socket", Socket)])?;
+    match channel.send(&lua, t) {
+        Err(err) => assert!(
+            err.to_string().contains("userdata `Socket` at `value.socket`"),
+            "{err}"
+        ),
+        Ok(_) => panic!("expected error"),
+    }
+    assert!(channel.send(&lua, Value::Nil).is_err());
+    assert!(channel.is_empty());
+
+    // Values can be received after closing
+    channel.send(&lua, 1)?;
+    channel.close();
+    assert!(channel.is_closed());
+    assert!(channel.send(&lua, 2).is_err());
+    assert_eq!(channel.recv(&lua)?.and_then(|v| v.as_i64()), Some(1));
+    assert!(channel.recv(&lua)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_channel_userdata() -> Result<()> {
+    let lua = Lua::new();
+    let channel = lua.create_channel()?;
+    assert!(channel.send(&lua, Job { id: 1 }).is_err());
+
+    lua.register_transferable::<Job>();
+    channel.send(&lua, Job { id: 1 })?;
+    // Channels can be sent through channels
+    channel.send(&lua, lua.create_channel()?)?;
+
+    let lua2 = Lua::new();
+    let job = channel.recv(&lua2)?.unwrap();
+    assert_eq!(job.as_userdata().unwrap().borrow::<Job>()?.id, 1);
+    let inner = channel.recv(&lua2)?.unwrap();
+    let inner = inner.as_userdata().unwrap().borrow::<Channel>()?.clone();
+    inner.send(&lua2, "inner")?;
+    assert_eq!(inner.try_recv(&lua)?.unwrap().to_string()?, "inner");
+
+    // `Channel::new` does not register the channel type in the sending state
+    let lua3 = Lua::new();
+    assert!(channel.send(&lua3, Channel::new()).is_err());
+    lua3.create_channel()?;
+    channel.send(&lua3, Channel::new())?;
+
+    Ok(())
+}
+
+#[test]
+fn test_channel_workers() -> Result<()> {
+    let lua = Lua::new();
+    let (jobs, results) = (lua.create_channel()?, lua.create_channel()?);
+
+    let workers = (0..4)
+        .map(|_| {
+            let (jobs, results) = (jobs.clone(), results.clone());
+            std::thread::spawn(move || {
+                let lua = Lua::new();
+                lua.globals().set("jobs", jobs).unwrap();
+                lua.globals().set("results", results).unwrap();
+                lua.load(
+                    r#"
+                    while true do
+                        local job = jobs:recv()
+                        if job == nil then break end
+                        results:send({ n = job.n, square = job.n * job.n })
+                    end
+                "#,
+                )
+                .exec()
+                .unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for n in 1..=20 {
+        jobs.send(&lua, lua.create_table_from([("n", n)])?)?;
+    }
+    jobs.close();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    let mut sum = 0;
+    while let Some(result) = results.try_recv(&lua)? {
+        sum += result.as_table().unwrap().get::<i64>("square")?;
+    }
+    assert_eq!(sum, (1..=20).map(|n| n * n).sum::<i64>());
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_channel_async() -> Result<()> {
+    use std::time::Duration;
+
+    let lua = Lua::new();
+    let channel = lua.create_channel()?;
+    lua.globals().set("channel", channel.clone())?;
+
+    let sender = std::thread::spawn(move || {
+        let lua = Lua::new();
+        for i in 1..=3 {
+            std::thread::sleep(Duration::from_millis(10));
+            channel.send(&lua, i).unwrap();
+        }
+        channel.close();
+    });
+
+    let sum = lua
+        .load(
+            r#"
+            local sum = 0
+            while true do
+                local value = channel:recv_async()
+                if value == nil then return sum end
+                sum = sum + value
+            end
+        "#,
+        )
+        .eval_async::<i64>()
+        .await?;
+    assert_eq!(sum, 6);
+    sender.join().unwrap();
+
+    Ok(())
+}
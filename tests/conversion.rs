@@ -657,3 +657,266 @@ fn test_either_from_lua() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_table_derive() -> Result<()> {
+    use mlua::FromLua;
+
+    let lua = Lua::new();
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    struct Point(i32, i32);
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table, rename_all = "camelCase")]
+    struct Server {
+        host_name: String,
+        #[lua(default = "default_port")]
+        port: u16,
+        #[lua(rename = "pos")]
+        position: Point,
+        tls: Option<bool>,
+        #[lua(skip)]
+        connected: bool,
+    }
+
+    fn default_port() -> u16 {
+        8080
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    struct Config {
+        servers: Vec<Server>,
+    }
+
+    let config: Config = lua
+        .load(
+            r#"
+        {
+            servers = {
+                { hostName = "a", pos = {1, 2} },
+                { hostName = "b", port = 1234, pos = {3, 4}, tls = true },
+            }
+        }
+    "#,
+        )
+        .eval()?;
+    let server_a = Server {
+        host_name: "a".into(),
+        port: 8080,
+        position: Point(1, 2),
+        tls: None,
+        connected: false,
+    };
+    let server_b = Server {
+        host_name: "b".into(),
+        port: 1234,
+        position: Point(3, 4),
+        tls: Some(true),
+        connected: false,
+    };
+    assert_eq!(config.servers, vec![server_a.clone(), server_b.clone()]);
+
+    // Roundtrip
+    let value = config.clone().into_lua(&lua)?;
+    lua.globals().set("config", &value)?;
+    lua.load(
+        r#"
+        local b = config.servers[2]
+        assert(b.hostName == "b" and b.port == 1234 and b.tls == true and b.connected == nil)
+        assert(b.pos[1] == 3 and b.pos[2] == 4)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(Config::from_lua(value, &lua)?, config);
+
+    // Errors contain the path to the failing field
+    let value = lua
+        .load(r#"{ hostName = "b", pos = {3, "x"} }"#)
+        .eval::<Value>()?;
+    match Server::from_lua(value, &lua) {
+        Err(Error::FromLuaFieldError { to, path, cause }) => {
+            assert_eq!(to, "Server");
+            assert_eq!(path, "pos[2]");
+            match *cause {
+                Error::FromLuaConversionError { from: "string", .. } => {}
+                ref err => panic!("expected FromLuaConversionError, got {err:?}"),
+            }
+        }
+        r => panic!("expected FromLuaFieldError, got {r:?}"),
+    }
+    match Server::from_lua(Value::Boolean(true), &lua) {
+        Err(Error::FromLuaConversionError { from: "boolean", .. }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    // Messages of nested errors are kept as is
+    #[derive(Debug)]
+    struct Custom;
+
+    impl FromLua for Custom {
+        fn from_lua(value: Value, _: &Lua) -> Result<Self> {
+            Err(Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Custom".into(),
+                message: Some("field `z`: custom message".into()),
+            })
+        }
+    }
+
+    #[derive(Debug, FromLua)]
+    #[lua(table)]
+    #[allow(unused)]
+    struct Outer {
+        inner: Inner,
+    }
+
+    #[derive(Debug, FromLua)]
+    #[lua(table)]
+    #[allow(unused)]
+    struct Inner {
+        #[lua(rename = "`a`")]
+        custom: Custom,
+    }
+
+    let value = lua.load("{ inner = { ['`a`'] = 1 } }").eval::<Value>()?;
+    match Outer::from_lua(value, &lua) {
+        Err(Error::FromLuaFieldError { to, path, cause }) => {
+            assert_eq!(to, "Outer");
+            assert_eq!(path, "inner.`a`");
+            match *cause {
+                Error::FromLuaConversionError { ref message, .. } => {
+                    assert_eq!(message.as_deref(), Some("field `z`: custom message"));
+                }
+                ref err => panic!("expected FromLuaConversionError, got {err:?}"),
+            }
+        }
+        r => panic!("expected FromLuaFieldError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_table_derive_enum() -> Result<()> {
+    use mlua::FromLua;
+
+    let lua = Lua::new();
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    enum External {
+        Unit,
+        Newtype(i32),
+        Tuple(i32, String),
+        Struct { x: f64 },
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table, tag = "type", rename_all = "snake_case")]
+    enum Internal {
+        Unit,
+        Struct { x: f64 },
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table, tag = "t", content = "c")]
+    enum Adjacent {
+        Unit,
+        Tuple(i32, i32),
+        #[lua(rename = "s")]
+        Struct {
+            x: f64,
+        },
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table, untagged)]
+    enum Untagged {
+        Number(i64),
+        Struct { name: String },
+    }
+
+    fn roundtrip<T: FromLua + IntoLua + Clone + PartialEq + std::fmt::Debug>(
+        lua: &Lua,
+        value: T,
+        check: &str,
+    ) -> Result<()> {
+        let lua_value = value.clone().into_lua(lua)?;
+        lua.load(check).call::<()>(&lua_value)?;
+        assert_eq!(T::from_lua(lua_value, lua)?, value);
+        Ok(())
+    }
+
+    roundtrip(&lua, External::Unit, "assert(... == 'Unit')")?;
+    roundtrip(&lua, External::Newtype(1), "assert((...).Newtype == 1)")?;
+    roundtrip(
+        &lua,
+        External::Tuple(1, "a".into()),
+        "assert((...).Tuple[2] == 'a')",
+    )?;
+    roundtrip(&lua, External::Struct { x: 1.5 }, "assert((...).Struct.x == 1.5)")?;
+
+    roundtrip(&lua, Internal::Unit, "assert((...).type == 'unit')")?;
+    let check = "local v = ...; assert(v.type == 'struct' and v.x == 2)";
+    roundtrip(&lua, Internal::Struct { x: 2.0 }, check)?;
+
+    roundtrip(&lua, Adjacent::Unit, "assert((...).t == 'Unit')")?;
+    roundtrip(&lua, Adjacent::Tuple(1, 2), "assert((...).c[2] == 2)")?;
+    let check = "local v = ...; assert(v.t == 's' and v.c.x == 3)";
+    roundtrip(&lua, Adjacent::Struct { x: 3.0 }, check)?;
+
+    roundtrip(&lua, Untagged::Number(5), "assert(... == 5)")?;
+    let check = "assert((...).name == 'foo')";
+    roundtrip(&lua, Untagged::Struct { name: "foo".into() }, check)?;
+
+    // Errors
+    let value = lua.load("{ Struct = { x = 'x' } }").eval::<Value>()?;
+    match External::from_lua(value, &lua) {
+        Err(Error::FromLuaFieldError { path, .. }) => assert_eq!(path, "Struct.x"),
+        r => panic!("expected FromLuaFieldError, got {r:?}"),
+    }
+    let value = lua
+        .load("setmetatable({}, { __index = function() error('boom') end })")
+        .eval::<Value>()?;
+    match External::from_lua(value, &lua) {
+        Err(Error::FromLuaFieldError { path, .. }) => assert_eq!(path, "Newtype"),
+        r => panic!("expected FromLuaFieldError, got {r:?}"),
+    }
+    let value = lua.load(r#"{ type = "\255" }"#).eval::<Value>()?;
+    match Internal::from_lua(value, &lua) {
+        Err(Error::FromLuaFieldError { path, .. }) => assert_eq!(path, "type"),
+        r => panic!("expected FromLuaFieldError, got {r:?}"),
+    }
+    let value = lua.create_string("Other")?;
+    match External::from_lua(Value::String(value), &lua) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            let message = message.unwrap();
+            assert_eq!(
+                message,
+                "unknown variant `Other`, expected one of `Unit`, `Newtype`, `Tuple`, `Struct`"
+            );
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    let value = lua.load("{ type = 'struct' }").eval::<Value>()?;
+    match Internal::from_lua(value, &lua) {
+        Err(Error::FromLuaFieldError { path, .. }) => assert_eq!(path, "x"),
+        r => panic!("expected FromLuaFieldError, got {r:?}"),
+    }
+    assert!(Untagged::from_lua(Value::Boolean(true), &lua).is_err());
+
+    // Variant names starting with a non-ASCII character
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table, rename_all = "camelCase")]
+    enum NonAscii {
+        Élan,
+    }
+    roundtrip(&lua, NonAscii::Élan, "assert(... == 'élan')")?;
+
+    Ok(())
+}
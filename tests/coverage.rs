@@ -0,0 +1,116 @@
+use mlua::{Lua, Result};
+
+fn new_lua() -> Result<Lua> {
+    let lua = Lua::new();
+    // For LuaJIT disable JIT, as compiled code does not trigger hooks
+    #[cfg(feature = "luajit")]
+    lua.load("jit.off()").exec()?;
+    Ok(lua)
+}
+
+#[test]
+fn test_coverage() -> Result<()> {
+    let lua = new_lua()?;
+
+    let coverage = lua.start_coverage()?;
+    assert!(coverage.is_running());
+    lua.load(
+        r#"
+        local function f(x)
+            if x > 1 then
+                return 1
+            else
+                return 2
+            end
+        end
+        for i = 1, 3 do
+            f(i)
+        end
+    "#,
+    )
+    .set_name("@cov.lua")
+    .exec()?;
+    coverage.stop();
+    assert!(!coverage.is_running());
+
+    let report = coverage.report();
+    let file = (report.files.iter())
+        .find(|f| f.path == "cov.lua")
+        .expect("file `cov.lua` is not covered");
+    assert_eq!(file.lines.get(&3), Some(&3));
+    assert_eq!(file.lines.get(&4), Some(&2));
+    assert_eq!(file.lines.get(&6), Some(&1));
+    assert!(!file.lines.contains_key(&1));
+
+    let lcov = report.to_lcov();
+    assert!(lcov.contains("SF:cov.lua\n"));
+    assert!(lcov.contains("DA:3,3\n"));
+    assert!(lcov.contains("end_of_record\n"));
+
+    let cobertura = report.to_cobertura();
+    assert!(cobertura.starts_with("<?xml"));
+    assert!(cobertura.contains(r#"<class name="cov.lua" filename="cov.lua""#));
+    assert!(cobertura.contains(r#"<line number="3" hits="3"/>"#));
+
+    // Collection is stopped
+    lua.load("local x = 1").set_name("@other.lua").exec()?;
+    assert!(coverage.report().files.iter().all(|f| f.path != "other.lua"));
+
+    Ok(())
+}
+
+#[test]
+fn test_coverage_not_executed_lines() -> Result<()> {
+    let lua = new_lua()?;
+
+    let coverage = lua.start_coverage()?;
+    lua.load(
+        r#"
+        local function g(x)
+            if x then
+                return 1
+            end
+            return 2
+        end
+        g(true)
+    "#,
+    )
+    .set_name("@lines.lua")
+    .exec()?;
+
+    let report = coverage.report();
+    let file = &report.files[0];
+    assert_eq!(file.path, "lines.lua");
+    assert_eq!(file.lines.get(&4), Some(&1));
+    assert_eq!(file.lines.get(&6), Some(&0));
+    assert!(report.to_lcov().contains("DA:6,0\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_coverage_coroutines() -> Result<()> {
+    let lua = new_lua()?;
+
+    let coverage = lua.start_coverage()?;
+    lua.load(
+        r#"
+        local co = coroutine.wrap(function()
+            for i = 1, 3 do
+                coroutine.yield(i)
+            end
+        end)
+        for _ = 1, 3 do
+            co()
+        end
+    "#,
+    )
+    .set_name("@co.lua")
+    .exec()?;
+    coverage.stop();
+
+    let report = coverage.report();
+    assert_eq!(report.files[0].lines.get(&4), Some(&3));
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+#![cfg(all(feature = "dap", not(feature = "luau")))]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use mlua::debugger::DapServer;
+use mlua::{Lua, Result};
+use serde_json::{json, Value as JsonValue};
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    seq: i64,
+}
+
+impl Client {
+    fn request(&mut self, command: &str, arguments: JsonValue) -> JsonValue {
+        self.seq += 1;
+        let message = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{message}", message.len()).unwrap();
+        loop {
+            let message = self.read();
+            if message["type"] == "response" {
+                assert_eq!(message["request_seq"], self.seq);
+                return message;
+            }
+        }
+    }
+
+    fn event(&mut self, event: &str) -> JsonValue {
+        loop {
+            let message = self.read();
+            if message["type"] == "event" && message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn read(&mut self) -> JsonValue {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            match line.trim_end() {
+                "" => break,
+                line => content_length = line["Content-Length:".len()..].trim().parse().unwrap(),
+            }
+        }
+        let mut body = vec![0; content_length];
+        self.reader.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+}
+
+#[test]
+fn test_dap_session() -> Result<()> {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("script.lua");
+    std::fs::write(
+        &script,
+        r#"local t = { answer = 42 }
+local function f(n)
+    local m = n * 2
+    return m
+end
+local x = f(10)
+return x + t.answer
+"#,
+    )
+    .unwrap();
+    let script_path = script.to_string_lossy().to_string();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut client = Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+            seq: 0,
+        };
+
+        let resp = client.request("initialize", json!({ "adapterID": "mlua" }));
+        assert_eq!(resp["success"], true);
+        assert_eq!(resp["body"]["supportsSetVariable"], true);
+        client.event("initialized");
+
+        let resp = client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": script_path }, "breakpoints": [{ "line": 3 }] }),
+        );
+        assert_eq!(resp["body"]["breakpoints"][0]["verified"], true);
+        let resp = client.request("stackTrace", json!({ "threadId": 1 }));
+        assert_eq!(resp["success"], false);
+        client.request("configurationDone", json!({}));
+
+        // Breakpoint in `f`
+        let stopped = client.event("stopped");
+        assert_eq!(stopped["reason"], "breakpoint");
+        let resp = client.request("stackTrace", json!({ "threadId": 1 }));
+        let frames = &resp["body"]["stackFrames"];
+        assert_eq!(frames[0]["name"], "f");
+        assert_eq!(frames[0]["line"], 3);
+        assert_eq!(frames[0]["source"]["path"], script_path);
+        assert_eq!(frames[1]["line"], 6);
+        let frame_id = frames[0]["id"].clone();
+
+        let resp = client.request("scopes", json!({ "frameId": frame_id }));
+        let locals_ref = resp["body"]["scopes"][0]["variablesReference"].clone();
+        let resp = client.request("variables", json!({ "variablesReference": locals_ref }));
+        assert_eq!(resp["body"]["variables"][0]["name"], "n");
+        assert_eq!(resp["body"]["variables"][0]["value"], "10");
+
+        let resp = client.request(
+            "setVariable",
+            json!({ "variablesReference": locals_ref, "name": "n", "value": "n + 1" }),
+        );
+        assert_eq!(resp["body"]["value"], "11");
+
+        let resp = client.request("evaluate", json!({ "expression": "n * 3", "frameId": frame_id }));
+        assert_eq!(resp["body"]["result"], "33");
+
+        // Step out of `f`
+        client.request("stepOut", json!({ "threadId": 1 }));
+        let stopped = client.event("stopped");
+        assert_eq!(stopped["reason"], "step");
+        let resp = client.request("stackTrace", json!({ "threadId": 1 }));
+        let frame_id = resp["body"]["stackFrames"][0]["id"].clone();
+        let resp = client.request("evaluate", json!({ "expression": "t", "frameId": frame_id }));
+        let table_ref = resp["body"]["variablesReference"].clone();
+        let resp = client.request("variables", json!({ "variablesReference": table_ref }));
+        assert_eq!(resp["body"]["variables"][0]["name"], "answer");
+        assert_eq!(resp["body"]["variables"][0]["value"], "42");
+
+        client.request("continue", json!({ "threadId": 1 }));
+        client.event("terminated");
+    });
+
+    let lua = Lua::new();
+    let server = DapServer::accept(&listener).unwrap();
+    server.attach(&lua);
+    server.wait_for_configuration(&lua)?;
+
+    let result: i64 = lua.load(script.as_path()).eval()?;
+    assert_eq!(result, 22 + 42);
+    server.terminate();
+    client.join().unwrap();
+
+    Ok(())
+}
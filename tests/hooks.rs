@@ -4,6 +4,7 @@ use std::ops::Deref;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::{Arc, Mutex};
 
+use mlua::debugger::{DebugAction, Debugger, PauseReason};
 use mlua::{DebugEvent, Error, HookTriggers, Lua, Result, ThreadStatus, Value, VmState};
 
 #[test]
@@ -297,3 +298,206 @@ fn test_hook_yield() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_add_hook() -> Result<()> {
+    let lua = Lua::new();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events2 = events.clone();
+    let calls = lua.add_hook(HookTriggers::ON_CALLS, move |_lua, debug| {
+        events2.lock().unwrap().push(("calls", debug.event()));
+        Ok(VmState::Continue)
+    });
+    let events2 = events.clone();
+    let lines = lua.add_hook(HookTriggers::EVERY_LINE, move |_lua, debug| {
+        events2.lock().unwrap().push(("lines", debug.event()));
+        Ok(VmState::Continue)
+    });
+    // A hook set by `set_hook` keeps working
+    let events2 = events.clone();
+    lua.set_hook(HookTriggers::ON_RETURNS, move |_lua, debug| {
+        events2.lock().unwrap().push(("returns", debug.event()));
+        Ok(VmState::Continue)
+    });
+
+    lua.load("local x = 1\nlocal y = 2").exec()?;
+    {
+        let events = events.lock().unwrap();
+        // Each hook receives only the events it asked for
+        assert!(events.iter().all(|(hook, event)| match *hook {
+            "calls" => *event == DebugEvent::Call,
+            "lines" => *event == DebugEvent::Line,
+            _ => *event == DebugEvent::Ret,
+        }));
+        let count = |hook| events.iter().filter(|(h, _)| *h == hook).count();
+        assert!(count("calls") >= 1);
+        assert_eq!(count("lines"), 2);
+        assert!(count("returns") >= 1);
+    }
+
+    // Removing a hook by its handle
+    calls.remove();
+    calls.remove();
+    events.lock().unwrap().clear();
+    lua.load("local x = 1").exec()?;
+    assert!(events.lock().unwrap().iter().all(|(hook, _)| *hook != "calls"));
+    lines.remove();
+    lua.remove_hook();
+    events.lock().unwrap().clear();
+    lua.load("local x = 1").exec()?;
+    assert!(events.lock().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_add_hook_results() -> Result<()> {
+    let lua = Lua::new();
+
+    // The first error aborts the execution
+    let called = Arc::new(AtomicI64::new(0));
+    let first = lua.add_hook(HookTriggers::EVERY_LINE, |_lua, _debug| {
+        Err(Error::runtime("first hook failed"))
+    });
+    let called2 = called.clone();
+    let second = lua.add_hook(HookTriggers::EVERY_LINE, move |_lua, _debug| {
+        called2.fetch_add(1, Ordering::Relaxed);
+        Ok(VmState::Continue)
+    });
+    let err = lua.load("local x = 1").exec().unwrap_err();
+    assert!(err.to_string().contains("first hook failed"), "{err}");
+    assert_eq!(called.load(Ordering::Relaxed), 0);
+    first.remove();
+    second.remove();
+
+    // Any `Yield` wins
+    let _continue = lua.add_hook(HookTriggers::EVERY_LINE, |_lua, _debug| Ok(VmState::Continue));
+    let _yield = lua.add_hook(HookTriggers::EVERY_LINE, |_lua, _debug| Ok(VmState::Yield));
+    let co = lua.create_thread(lua.load("local x = 1\nlocal y = 2").into_function()?)?;
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    {
+        co.resume::<()>(())?;
+        assert_eq!(co.status(), ThreadStatus::Resumable);
+    }
+    #[cfg(any(feature = "lua51", feature = "lua52", feature = "luajit"))]
+    {
+        assert!(
+            matches!(co.resume::<()>(()), Err(Error::RuntimeError(err)) if err.contains("attempt to yield from a hook"))
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_debugger() -> Result<()> {
+    let lua = Lua::new();
+
+    let pauses = Arc::new(Mutex::new(Vec::new()));
+    let pauses2 = pauses.clone();
+    let debugger = Debugger::new(move |_lua, ctx| {
+        let frames = ctx.frames();
+        let line = frames[0].line.unwrap();
+        let mut pauses = pauses2.lock().unwrap();
+        pauses.push((line, ctx.reason()));
+        match pauses.len() {
+            1 => Ok(DebugAction::StepIn),
+            2 => {
+                assert_eq!(frames[0].name.as_deref(), Some("add"));
+                let locals = ctx.locals(0)?;
+                let names = locals.iter().map(|v| v.name.as_str()).collect::<Vec<_>>();
+                assert_eq!(names, ["a", "b"]);
+                assert_eq!(locals[1].value, Value::Integer(5));
+                ctx.set_local(0, "b", 7)?;
+
+                let upvalues = ctx.upvalues(0)?;
+                assert_eq!(upvalues[0].name, "factor");
+                assert_eq!(upvalues[0].value, Value::Integer(1));
+                ctx.set_upvalue(0, "factor", 2)?;
+
+                assert!(ctx.set_local(0, "missing", 1).is_err());
+                assert!(ctx.locals(frames.len()).is_err());
+                Ok(DebugAction::StepOut)
+            }
+            3 => {
+                // Returned to the main chunk
+                assert!(frames[0].what == "main");
+                Ok(DebugAction::StepOver)
+            }
+            _ => Ok(DebugAction::Continue),
+        }
+    });
+    let bp = debugger.add_breakpoint("@test.lua", 7);
+    assert_eq!(debugger.breakpoints()[0].source, "test.lua");
+    debugger.attach(&lua);
+
+    let chunk = r#"local factor = 1
+local function add(a, b)
+    local sum = (a + b) * factor
+    return sum
+end
+local x = 10
+local y = add(x, 5)
+local z = y * 2
+return x, y, z"#;
+    let (x, y, z): (i64, i64, i64) = lua.load(chunk).set_name("@test.lua").eval()?;
+    assert_eq!((x, y, z), (10, 34, 68));
+
+    let pauses = pauses.lock().unwrap().clone();
+    assert_eq!(pauses.len(), 4);
+    assert_eq!(pauses[0], (7, PauseReason::Breakpoint(bp)));
+    assert_eq!(pauses[1], (3, PauseReason::Step));
+    assert_eq!(pauses[2].1, PauseReason::Step);
+    assert_eq!(pauses[3].1, PauseReason::Step);
+    assert!(pauses[3].0 > pauses[2].0);
+
+    // Pause request
+    assert!(debugger.remove_breakpoint(bp));
+    assert!(!debugger.remove_breakpoint(bp));
+    debugger.pause();
+    lua.load("local a = 1").set_name("@test.lua").exec()?;
+    debugger.clear_breakpoints();
+    assert!(debugger.breakpoints().is_empty());
+
+    Ok(())
+}
+
+#[cfg(any(feature = "lua54", feature = "lua53"))]
+#[test]
+fn test_debugger_suspend() -> Result<()> {
+    let lua = Lua::new();
+
+    let debugger = Debugger::new(|_lua, _ctx| Ok(DebugAction::Suspend));
+    debugger.add_breakpoint("suspend.lua", 3);
+
+    let func = (lua.load("local a = 1\nlocal c = 0\nlocal b = a * 2\nreturn b + c"))
+        .set_name("=suspend.lua")
+        .into_function()?;
+    let co = lua.create_thread(func)?;
+    debugger.attach_thread(&co);
+
+    co.resume::<()>(())?;
+    assert_eq!(co.status(), ThreadStatus::Resumable);
+    assert!(debugger.is_suspended());
+    assert_eq!(
+        debugger.suspend_reason(),
+        Some(PauseReason::Breakpoint(debugger.breakpoints()[0].id))
+    );
+
+    let ctx = debugger.suspended_context(&co).unwrap();
+    assert_eq!(ctx.frames()[0].line, Some(3));
+    assert_eq!(ctx.locals(0)?[0].value, Value::Integer(1));
+    ctx.set_local(0, "a", 5)?;
+
+    // Resuming the thread without resuming the debugger keeps it suspended
+    co.resume::<()>(())?;
+    assert_eq!(co.status(), ThreadStatus::Resumable);
+
+    assert!(debugger.resume(DebugAction::Continue));
+    assert!(!debugger.resume(DebugAction::Continue));
+    assert_eq!(co.resume::<i64>(())?, 10);
+    assert_eq!(co.status(), ThreadStatus::Finished);
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::time::{Duration, Instant};
+
+use mlua::{Error, ExecutionLimits, Function, Lua, Result};
+
+fn new_lua() -> Result<Lua> {
+    let lua = Lua::new();
+    // For LuaJIT disable JIT, as compiled code does not trigger hooks
+    #[cfg(feature = "luajit")]
+    lua.load("jit.off()").exec()?;
+    Ok(lua)
+}
+
+#[test]
+fn test_execution_limits_instructions() -> Result<()> {
+    let lua = new_lua()?;
+
+    lua.set_execution_limits(ExecutionLimits::new().instructions(100_000));
+    lua.load("for i = 1, 10 do end").exec()?;
+    match lua.load("while true do end").exec() {
+        Err(Error::ExecutionLimitExceeded) => {}
+        r => panic!("expected ExecutionLimitExceeded, got {r:?}"),
+    }
+
+    // The limit cannot be caught by Lua code
+    let result = lua
+        .load("pcall(function() while true do end end) return 'escaped'")
+        .eval::<String>();
+    assert!(matches!(result, Err(Error::ExecutionLimitExceeded)));
+
+    // Coroutines are limited too
+    let result = lua
+        .load("coroutine.wrap(function() while true do end end)()")
+        .exec();
+    assert!(result.is_err());
+
+    lua.remove_execution_limits();
+    lua.load("for i = 1, 1000000 do end").exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_execution_limits_timeout() -> Result<()> {
+    let lua = new_lua()?;
+
+    let start = Instant::now();
+    lua.set_execution_limits(ExecutionLimits::new().timeout(Duration::from_millis(50)));
+    match lua.load("while true do end").exec() {
+        Err(Error::ExecutionLimitExceeded) => {}
+        r => panic!("expected ExecutionLimitExceeded, got {r:?}"),
+    }
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    lua.set_execution_limits(ExecutionLimits::new().deadline(Instant::now()));
+    assert!(lua.load("for i = 1, 1000000 do end").exec().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_call_with_budget() -> Result<()> {
+    let lua = new_lua()?;
+
+    let spin: Function = lua
+        .load("function(n) local x = 0; for i = 1, n do x = x + i end; return x end")
+        .eval()?;
+    let budget = ExecutionLimits::new().instructions(100_000);
+    assert_eq!(spin.call_with_budget::<i64>(100, budget)?, 5050);
+    match spin.call_with_budget::<i64>(10_000_000, budget) {
+        Err(Error::ExecutionLimitExceeded) => {}
+        r => panic!("expected ExecutionLimitExceeded, got {r:?}"),
+    }
+
+    // Limits are removed after the call
+    assert_eq!(spin.call::<i64>(1_000_000)?, 500000500000);
+
+    // Nested budgets cannot extend the outer ones
+    let nested = lua.create_function(move |_, n: i64| {
+        spin.call_with_budget::<i64>(n, ExecutionLimits::new().instructions(u64::MAX / 2))
+    })?;
+    let outer: Function = lua.load("function(f, n) return f(n) end").eval()?;
+    assert_eq!(outer.call_with_budget::<i64>((&nested, 100), budget)?, 5050);
+    let err = outer
+        .call_with_budget::<i64>((&nested, 10_000_000), budget)
+        .unwrap_err();
+    assert!(err.to_string().contains("execution limit exceeded"), "{err}");
+
+    // The global limits still apply
+    lua.set_execution_limits(ExecutionLimits::new().instructions(100_000));
+    let result = outer.call_with_budget::<i64>((&nested, 10_000_000), ExecutionLimits::new());
+    assert!(result.is_err());
+    lua.remove_execution_limits();
+    assert_eq!(outer.call::<i64>((&nested, 100))?, 5050);
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_execution_limits_with_hook() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use mlua::{HookTriggers, VmState};
+
+    let lua = new_lua()?;
+
+    let lines = Arc::new(AtomicUsize::new(0));
+    let lines2 = lines.clone();
+    lua.set_hook(HookTriggers::EVERY_LINE, move |_, _| {
+        lines2.fetch_add(1, Ordering::Relaxed);
+        Ok(VmState::Continue)
+    });
+    lua.set_execution_limits(ExecutionLimits::new().instructions(100_000));
+    let result = lua
+        .load(
+            r#"
+            while true do
+                local x = 1
+            end
+        "#,
+        )
+        .exec();
+    assert!(matches!(result, Err(Error::ExecutionLimitExceeded)));
+    assert!(lines.load(Ordering::Relaxed) > 1000);
+
+    // Hook set while the limits are active
+    lua.set_execution_limits(ExecutionLimits::new().instructions(100_000));
+    let counts = Arc::new(AtomicUsize::new(0));
+    let counts2 = counts.clone();
+    lua.set_hook(HookTriggers::new().every_nth_instruction(7), move |_, _| {
+        counts2.fetch_add(1, Ordering::Relaxed);
+        Ok(VmState::Continue)
+    });
+    assert!(lua.load("while true do end").exec().is_err());
+    let count = counts.load(Ordering::Relaxed);
+    assert!(count > 10_000 && count <= 100_000 / 7 + 1, "{count}");
+
+    // Removing the limits keeps the hook
+    lua.remove_execution_limits();
+    counts.store(0, Ordering::Relaxed);
+    lua.load("for i = 1, 1000 do end").exec()?;
+    assert!(counts.load(Ordering::Relaxed) > 0);
+    lua.remove_hook();
+
+    Ok(())
+}
+
+#[cfg(feature = "luau")]
+#[test]
+fn test_execution_limits_with_interrupt() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use mlua::VmState;
+
+    let lua = Lua::new();
+
+    let interrupts = Arc::new(AtomicUsize::new(0));
+    let interrupts2 = interrupts.clone();
+    lua.set_interrupt(move |_| {
+        interrupts2.fetch_add(1, Ordering::Relaxed);
+        Ok(VmState::Continue)
+    });
+    lua.set_execution_limits(ExecutionLimits::new().instructions(10_000));
+    assert!(matches!(
+        lua.load("while true do end").exec(),
+        Err(Error::ExecutionLimitExceeded)
+    ));
+    assert!(interrupts.load(Ordering::Relaxed) >= 9_999);
+
+    // Interrupt keeps working after the limits are removed
+    lua.remove_execution_limits();
+    interrupts.store(0, Ordering::Relaxed);
+    lua.load("for i = 1, 100 do end").exec()?;
+    assert!(interrupts.load(Ordering::Relaxed) > 0);
+
+    Ok(())
+}
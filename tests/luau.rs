@@ -1,12 +1,16 @@
 #![cfg(feature = "luau")]
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs;
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-use mlua::{Compiler, Error, Lua, LuaOptions, Result, StdLib, Table, ThreadStatus, Value, Vector, VmState};
+use mlua::{
+    Compiler, Error, FileSystemResolver, Lua, LuaOptions, Result, StdLib, Table, ThreadStatus, Value, Vector,
+    VmState,
+};
 
 #[test]
 fn test_version() -> Result<()> {
@@ -92,6 +96,94 @@ fn test_require() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_module_resolver() -> Result<()> {
+    let lua = Lua::new();
+
+    let modules = HashMap::from([
+        (
+            ".luaurc",
+            r#"{ "aliases": { "Lib": "lib", }, // comment
+        }"#,
+        ),
+        ("main.luau", "return require('./game/player')"),
+        (
+            "game/player.luau",
+            "return { name = 'player', util = require('../lib/util') }",
+        ),
+        ("lib/init.luau", "return { version = 1 }"),
+        (
+            "lib/util.luau",
+            "counter = (counter or 0) + 1; return { counter = counter, self = require('@self/helper') }",
+        ),
+        ("lib/helper.lua", "return function() error('helper error') end"),
+        (
+            "game/.luaurc",
+            r#"{ "languageMode": "strict", "aliases": { "lib": "../lib/" } }"#,
+        ),
+        ("game/alias.luau", "return require('@lib/util')"),
+        ("cycle/a.luau", "return require('./b')"),
+        ("cycle/b.luau", "return require('./a')"),
+        ("empty.luau", "return"),
+    ]);
+    lua.set_module_resolver(modules)?;
+    lua.globals()
+        .get::<Table>("package")?
+        .get::<Table>("loaded")?
+        .set("host", "host module")?;
+
+    lua.load(
+        r#"
+        local player = require("./main")
+        assert(player.name == "player")
+        assert(player.util.counter == 1)
+        assert(require("@lib").version == 1)
+        assert(require("@lib/util") == player.util, "module must be cached")
+        assert(require("./game/alias") == player.util)
+        assert(require("host") == "host module")
+
+        local ok, err = pcall(player.util.self)
+        assert(not ok and string.find(tostring(err), "lib/helper.lua:1: helper error") ~= nil, tostring(err))
+
+        ok, err = pcall(require, "./cycle/a")
+        assert(not ok and string.find(tostring(err), "cyclic module dependency") ~= nil, tostring(err))
+        ok, err = pcall(require, "./empty")
+        assert(not ok and string.find(tostring(err), "module must return a single value") ~= nil, tostring(err))
+        ok, err = pcall(require, "../outside")
+        assert(not ok and string.find(tostring(err), "escapes the module root") ~= nil, tostring(err))
+        ok, err = pcall(require, "@unknown/module")
+        assert(not ok and string.find(tostring(err), "@unknown is not a valid alias") ~= nil, tostring(err))
+        ok, err = pcall(require, "./missing")
+        assert(not ok and string.find(tostring(err), "module './missing' not found") ~= nil, tostring(err))
+        ok, err = pcall(require, "missing")
+        assert(not ok and string.find(tostring(err), "must start with") ~= nil, tostring(err))
+    "#,
+    )
+    .set_name("@main.luau")
+    .exec()?;
+
+    if cfg!(target_arch = "wasm32") {
+        return Ok(());
+    }
+
+    // Filesystem resolver
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::create_dir(temp_dir.path().join("root"))?;
+    fs::write(temp_dir.path().join("root/module.luau"), "return 'module'")?;
+    fs::write(temp_dir.path().join("secret.luau"), "return 'secret'")?;
+    lua.set_module_resolver(FileSystemResolver::new(temp_dir.path().join("root")))?;
+    lua.load(
+        r#"
+        assert(require("./module") == "module")
+        local ok, err = pcall(require, "../secret")
+        assert(not ok and string.find(tostring(err), "escapes the module root") ~= nil, tostring(err))
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
 #[cfg(not(feature = "luau-vector4"))]
 #[test]
 fn test_vectors() -> Result<()> {
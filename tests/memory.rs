@@ -131,3 +131,128 @@ fn test_gc_error() {
         Ok(()) => panic!("__gc error did not result in error"),
     }
 }
+
+#[test]
+fn test_memory_report() -> Result<()> {
+    struct MyUserData;
+    impl UserData for MyUserData {}
+
+    let lua = Lua::new();
+
+    if cfg!(feature = "luajit") && lua.set_memory_tracking(true).is_err() {
+        // seems this luajit version does not support memory control
+        return Ok(());
+    }
+
+    // Untracked userdata are not counted
+    lua.set_memory_tracking(false)?;
+    let _untracked = lua.create_userdata(MyUserData)?;
+    let report = lua.memory_report()?;
+    assert!(report.used_memory > 0);
+    assert!(report.userdata.is_empty() && report.top_allocators.is_empty());
+
+    lua.set_memory_tracking(true)?;
+    let before = lua.memory_report()?;
+    assert!(before.userdata.is_empty());
+
+    lua.globals().set(
+        "new_ud",
+        lua.create_function(|lua, ()| lua.create_userdata(MyUserData))?,
+    )?;
+    lua.load(
+        r#"
+        cache = {}
+        local function leak(n)
+            for i = 1, n do
+                cache[#cache + 1] = {i, new_ud()}
+            end
+        end
+        leak(100)
+    "#,
+    )
+    .set_name("@leak.lua")
+    .exec()?;
+
+    let after = lua.memory_report()?;
+    assert!(after.allocations > 0);
+    assert!(after.allocated_bytes > 0);
+    assert_eq!(after.userdata.len(), 1);
+    assert_eq!(after.userdata[0].type_name, "MyUserData");
+    assert_eq!(after.userdata[0].count, 100);
+    assert!(after.userdata[0].bytes > 0);
+    let leak = (after.top_allocators.iter())
+        .find(|site| site.info.name.as_deref() == Some("leak"))
+        .unwrap();
+    assert_eq!(leak.info.line_defined, Some(3));
+    assert!(leak.bytes > 0);
+    assert!(after.to_collapsed_stacks().contains(";leak (leak.lua:3) "));
+
+    let growth = after.growth_since(&before);
+    assert!(growth.used_memory > 0);
+    assert_eq!(growth.userdata[0].type_name, "MyUserData");
+    assert_eq!(growth.userdata[0].count, 100);
+
+    // Release half of the userdata
+    lua.load("for i = 1, 50 do cache[i] = nil end").exec()?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    let growth = lua.memory_report()?.growth_since(&after);
+    assert!(growth.used_memory < 0);
+    assert_eq!(growth.userdata[0].count, -50);
+
+    // Disabling tracking discards the data
+    lua.set_memory_tracking(false)?;
+    let report = lua.memory_report()?;
+    assert!(report.userdata.is_empty() && report.top_allocators.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_memory_limit() -> Result<()> {
+    let lua = Lua::new();
+
+    let f = lua
+        .load(
+            r#"
+            local n = ...
+            local t = {}
+            for i = 1, n do t[i] = {i} end
+            coroutine.yield(#t)
+            return #t
+        "#,
+        )
+        .into_function()?;
+
+    let runaway = lua.create_thread(f.clone())?;
+    if cfg!(feature = "luajit") && runaway.set_memory_limit(0).is_err() {
+        // seems this luajit version does not support memory limit
+        return Ok(());
+    }
+    let sibling = lua.create_thread(f)?;
+    assert_eq!(sibling.set_memory_limit(1024 * 1024)?, 0);
+    assert_eq!(runaway.set_memory_limit(50 * 1024)?, 0);
+    assert_eq!(runaway.set_memory_limit(50 * 1024)?, 50 * 1024);
+
+    assert_eq!(sibling.resume::<usize>(1000)?, 1000);
+    let sibling_used = sibling.used_memory();
+    assert!(sibling_used > 1000 * 16, "sibling used {sibling_used} bytes");
+
+    match runaway.resume::<usize>(100_000) {
+        Err(Error::MemoryError(_)) => {}
+        something_else => panic!("did not trigger memory error: {:?}", something_else),
+    }
+    assert!(runaway.used_memory() <= 50 * 1024);
+
+    // Sibling is not affected
+    assert_eq!(sibling.resume::<usize>(())?, 1000);
+    let t = lua.create_table()?;
+    t.set(1, "still works")?;
+
+    // Threads without a limit are not accounted
+    let unlimited = lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?;
+    unlimited.resume::<()>(())?;
+    assert_eq!(unlimited.used_memory(), 0);
+
+    Ok(())
+}
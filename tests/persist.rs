@@ -0,0 +1,195 @@
+#![cfg(not(feature = "luau"))]
+
+use mlua::{Function, Lua, Persist, Result, Table, UserData, UserDataMethods, Value};
+
+struct Account {
+    balance: i64,
+    history: Table,
+}
+
+impl UserData for Account {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("balance", |_, this, ()| Ok(this.balance));
+        methods.add_method("history", |_, this, ()| Ok(this.history.clone()));
+    }
+}
+
+impl Persist for Account {
+    const NAME: &'static str = "Account";
+
+    fn persist(&self, lua: &Lua) -> Result<Value> {
+        let t = lua.create_table()?;
+        t.set("balance", self.balance)?;
+        t.set("history", &self.history)?;
+        Ok(Value::Table(t))
+    }
+
+    fn unpersist(lua: &Lua, value: Value) -> Result<Self> {
+        let t = lua.unpack::<Table>(value)?;
+        Ok(Account {
+            balance: t.get("balance")?,
+            history: t.get("history")?,
+        })
+    }
+}
+
+struct Socket;
+
+impl UserData for Socket {}
+
+// Lua functions usually refer to the global environment, which contains C functions
+fn permanents(lua: &Lua, persist: bool) -> Result<Table> {
+    match persist {
+        true => lua.create_table_from([(lua.globals(), "_G")]),
+        false => lua.create_table_from([("_G", lua.globals())]),
+    }
+}
+
+#[test]
+fn test_persist_tables() -> Result<()> {
+    let lua = Lua::new();
+    let value = lua
+        .load(
+            r#"
+        local shared = { "shared" }
+        local t = { a = shared, b = shared, [shared] = true, n = 1.5, [10] = "ten" }
+        t.self = t
+        return setmetatable(t, { __index = { missing = "missing" } })
+    "#,
+        )
+        .eval::<Value>()?;
+    let data = lua.persist(None, &value)?;
+
+    let lua2 = Lua::new();
+    let t = lua2.unpersist(None, &data)?;
+    lua2.globals().set("t", t)?;
+    lua2.load(
+        r#"
+        assert(t.self == t and t.a == t.b and t.a[1] == "shared" and t[t.a] == true)
+        assert(t.n == 1.5 and t[10] == "ten" and t.missing == "missing")
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_closures() -> Result<()> {
+    let lua = Lua::new();
+    let counter = lua
+        .load(
+            r#"
+        local count = 0
+        local function incr() count = count + 1; return count end
+        local function get() return count end
+        incr()
+        return { incr = incr, get = get, upper = string.upper }
+    "#,
+        )
+        .eval::<Table>()?;
+
+    // C functions must be added to the permanents table
+    let upper = lua.globals().get::<Table>("string")?.get::<Function>("upper")?;
+    let permanents = permanents(&lua, true)?;
+    match lua.persist(Some(&permanents), &counter) {
+        Err(err) => assert!(
+            err.to_string()
+                .contains("cannot persist C function at `value.upper`"),
+            "{err}"
+        ),
+        Ok(_) => panic!("expected error"),
+    }
+    permanents.set(upper, "string.upper")?;
+    let data = lua.persist(Some(&permanents), &counter)?;
+
+    let lua2 = Lua::new();
+    match lua2.unpersist(None, &data) {
+        Err(err) => assert!(err.to_string().contains("missing permanent value"), "{err}"),
+        Ok(_) => panic!("expected error"),
+    }
+    let upper = lua2.globals().get::<Table>("string")?.get::<Function>("upper")?;
+    let permanents = self::permanents(&lua2, false)?;
+    permanents.set("string.upper", upper)?;
+    let counter = lua2.unpersist(Some(&permanents), &data)?;
+    lua2.globals().set("counter", counter)?;
+    lua2.load(r#"assert(counter.incr() == 2 and counter.upper("a") == "A")"#)
+        .exec()?;
+
+    // Upvalues stay shared between closures (not supported in Lua 5.1)
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    assert_eq!(lua2.load("counter.get()").eval::<i64>()?, 2);
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    assert_eq!(lua2.load("counter.get()").eval::<i64>()?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_threads() -> Result<()> {
+    let lua = Lua::new();
+
+    // Threads cannot be persisted, unless they are permanent
+    let thread = lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?;
+    let t = lua.create_table_from([("co", &thread)])?;
+    match lua.persist(None, &t) {
+        Err(err) => assert!(
+            err.to_string()
+                .contains("cannot persist thread at `value.co`: threads cannot be persisted"),
+            "{err}"
+        ),
+        Ok(_) => panic!("expected error"),
+    }
+
+    let permanents = lua.create_table_from([(&thread, "co")])?;
+    let data = lua.persist(Some(&permanents), &t)?;
+    let permanents = lua.create_table_from([("co", &thread)])?;
+    let t2 = lua.unpersist(Some(&permanents), &data)?;
+    assert_eq!(t2.as_table().unwrap().get::<mlua::Thread>("co")?, thread);
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_userdata() -> Result<()> {
+    let lua = Lua::new();
+    lua.register_persist::<Account>();
+    let history = lua.create_sequence_from([10, -5])?;
+    let account = lua.create_userdata(Account { balance: 5, history })?;
+    let data = lua.persist(None, &account)?;
+
+    // The type must be registered to be restored
+    let lua2 = Lua::new();
+    match lua2.unpersist(None, &data) {
+        Err(err) => assert!(err.to_string().contains("`Account` is not registered"), "{err}"),
+        Ok(_) => panic!("expected error"),
+    }
+    lua2.register_persist::<Account>();
+    lua2.globals().set("account", lua2.unpersist(None, &data)?)?;
+    lua2.load("assert(account:balance() == 5 and account:history()[2] == -5)")
+        .exec()?;
+
+    // Userdata that does not implement `Persist`
+    let t = lua.create_table_from([("socket", Socket)])?;
+    match lua.persist(None, &t) {
+        Err(err) => assert!(
+            err.to_string()
+                .contains("cannot persist userdata `Socket` at `value.socket`"),
+            "{err}"
+        ),
+        Ok(_) => panic!("expected error"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_unpersist_invalid_data() -> Result<()> {
+    let lua = Lua::new();
+    assert!(lua.unpersist(None, b"invalid").is_err());
+
+    let data = lua.persist(None, lua.create_sequence_from(["a", "b"])?)?;
+    assert!(lua.unpersist(None, &data[..data.len() - 1]).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use mlua::{Error, LuaPool, Result, StdLib, Table};
+
+#[test]
+fn test_pool_reset() -> Result<()> {
+    let pool = LuaPool::builder(1)
+        .init(|lua| {
+            lua.load("config = { debug = false }").exec()?;
+            lua.set_app_data("init data");
+            Ok(())
+        })
+        .build()?;
+
+    let lua = pool.get()?;
+    lua.load(
+        r#"
+        counter = 1
+        config.debug = true
+        string.custom = function() end
+        setmetatable(_G, { __index = function() return "global" end })
+    "#,
+    )
+    .exec()?;
+    lua.set_app_data(1u32);
+    drop(lua);
+
+    let lua = pool.get()?;
+    lua.load(
+        r#"
+        assert(counter == nil and config.debug == false and string.custom == nil)
+        assert(getmetatable(_G) == nil)
+    "#,
+    )
+    .exec()?;
+    assert!(lua.app_data_ref::<u32>().is_none());
+    assert!(lua.app_data_ref::<&str>().is_none());
+    drop(lua);
+
+    let stats = pool.stats();
+    assert_eq!((stats.size, stats.idle, stats.checkouts), (1, 1, 2));
+    assert_eq!(stats.failed_resets, 0);
+    assert!(stats.peak_memory > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_pool_options() -> Result<()> {
+    let pool = LuaPool::builder(2).std_libs(StdLib::MATH).build()?;
+    let lua = pool.get()?;
+    assert!(lua.globals().get::<Option<Table>>("math")?.is_some());
+    assert!(lua.globals().get::<Option<Table>>("string")?.is_none());
+
+    assert!(LuaPool::new(0).is_err());
+    assert!(LuaPool::builder(1)
+        .init(|lua| lua.load("error('oops')").exec())
+        .build()
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_pool_memory_limit() -> Result<()> {
+    let pool = LuaPool::builder(1).memory_limit(1024 * 1024).build()?;
+
+    let lua = pool.get()?;
+    match lua.load("local t = {} for i = 1, 1e6 do t[i] = i end").exec() {
+        Err(Error::MemoryError(_)) => {}
+        res => panic!("expected memory error, got {res:?}"),
+    }
+    drop(lua);
+
+    // The limit is applied for each checkout
+    let lua = pool.get()?;
+    lua.load("local t = {} for i = 1, 1000 do t[i] = i end").exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_pool_checkout() -> Result<()> {
+    let pool = LuaPool::new(1)?;
+
+    let lua = pool.get()?;
+    assert!(pool.try_get()?.is_none());
+    assert_eq!(pool.stats().idle, 0);
+    drop(lua);
+    assert!(pool.try_get()?.is_some());
+
+    Ok(())
+}
+
+#[cfg(feature = "send")]
+#[test]
+fn test_pool_blocking() -> Result<()> {
+    use std::time::Duration;
+
+    let pool = LuaPool::new(1)?;
+    let lua = pool.get()?;
+
+    let pool2 = pool.clone();
+    let handle = std::thread::spawn(move || -> Result<i64> { pool2.get()?.load("1 + 1").eval() });
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(!handle.is_finished());
+    drop(lua);
+    assert_eq!(handle.join().unwrap()?, 2);
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_pool_async() -> Result<()> {
+    use std::time::Duration;
+
+    let pool = LuaPool::new(1)?;
+    let lua = pool.get_async().await?;
+
+    let pool2 = pool.clone();
+    let waiter = async move {
+        let lua = pool2.get_async().await?;
+        lua.load("1 + 1").eval_async::<i64>().await
+    };
+    let release = async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(lua);
+    };
+    let (res, ()) = tokio::join!(waiter, release);
+    assert_eq!(res?, 2);
+
+    Ok(())
+}
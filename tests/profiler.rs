@@ -0,0 +1,211 @@
+use mlua::{Lua, ProfilerOptions, Result};
+
+#[test]
+fn test_profiler_sampling() -> Result<()> {
+    let lua = Lua::new();
+
+    let profile = lua.start_profiler(ProfilerOptions::sampling(10))?;
+    assert!(profile.is_running());
+    lua.load(
+        r#"
+        local function hot(n)
+            local x = 0
+            for i = 1, n do
+                x = x + i % 7
+            end
+            return x
+        end
+        for _ = 1, 20 do
+            hot(1000)
+        end
+    "#,
+    )
+    .set_name("@hot.lua")
+    .exec()?;
+    profile.stop();
+    assert!(!profile.is_running());
+
+    let functions = profile.functions();
+    let hot = functions
+        .iter()
+        .find(|f| f.info.name.as_deref() == Some("hot"))
+        .expect("function `hot` is not profiled");
+    assert_eq!(hot.info.source.as_deref(), Some("@hot.lua"));
+    assert_eq!(hot.info.line_defined, Some(2));
+    assert!(hot.samples > 0);
+    assert!(hot.total_samples >= hot.samples);
+    // `hot` is the hottest function
+    assert_eq!(functions[0].info.name.as_deref(), Some("hot"));
+
+    let stacks = profile.to_collapsed_stacks();
+    let line = stacks
+        .lines()
+        .find(|line| line.contains("hot (hot.lua:2)"))
+        .unwrap();
+    #[cfg(not(feature = "luau"))]
+    assert!(line.starts_with("main chunk (hot.lua);hot (hot.lua:2) "));
+    #[cfg(feature = "luau")]
+    assert!(line.contains(";hot (hot.lua:2) "));
+    let weight: u64 = line.rsplit(' ').next().unwrap().parse().unwrap();
+    assert!(weight > 0);
+
+    let trace = profile.to_chrome_trace();
+    assert!(trace.starts_with(r#"{"traceEvents":["#));
+    assert!(trace.contains(r#""name":"hot (hot.lua:2)","cat":"lua","ph":"X","pid":1,"tid":1"#));
+    assert!(trace.contains(r#"{"name":"thread_name","ph":"M","pid":1,"tid":1,"args":{"name":"main"}}"#));
+
+    // Collection is stopped
+    let samples = hot.samples;
+    lua.load("for i = 1, 10000 do end").exec()?;
+    let functions = profile.functions();
+    let hot = functions.iter().find(|f| f.info.name.as_deref() == Some("hot"));
+    assert_eq!(hot.unwrap().samples, samples);
+
+    Ok(())
+}
+
+#[test]
+fn test_profiler_already_running() -> Result<()> {
+    let lua = Lua::new();
+
+    let profile = lua.start_profiler(ProfilerOptions::default())?;
+    assert!(lua.start_profiler(ProfilerOptions::default()).is_err());
+    drop(profile);
+    let _profile = lua.start_profiler(ProfilerOptions::default())?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_profiler_instrumenting() -> Result<()> {
+    let lua = Lua::new();
+
+    let profile = lua.start_profiler(ProfilerOptions::instrumenting())?;
+    lua.load(
+        r#"
+        local function fib(n)
+            if n < 2 then return n end
+            return fib(n - 1) + fib(n - 2)
+        end
+        local function leaf()
+            return 1
+        end
+        local function outer()
+            return leaf() + leaf()
+        end
+        fib(10)
+        for _ = 1, 5 do
+            outer()
+        end
+        pcall(function() error("boom") end)
+        outer()
+    "#,
+    )
+    .set_name("@fib.lua")
+    .exec()?;
+    profile.stop();
+
+    let functions = profile.functions();
+    let find = |name: &str| {
+        (functions.iter())
+            .find(|f| f.info.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("function `{name}` is not profiled"))
+    };
+    assert_eq!(find("fib").calls, 177);
+    assert_eq!(find("outer").calls, 6);
+    assert_eq!(find("leaf").calls, 12);
+    assert!(find("outer").total_time >= find("outer").self_time);
+    assert!(find("outer").total_time >= find("leaf").total_time);
+
+    let stacks = profile.to_collapsed_stacks();
+    assert!(stacks.contains("main chunk (fib.lua);outer (fib.lua:9);leaf (fib.lua:6) "));
+    assert!(!stacks.contains("error [C];outer"));
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_profiler_coroutines() -> Result<()> {
+    let lua = Lua::new();
+
+    let profile = lua.start_profiler(ProfilerOptions::instrumenting())?;
+    lua.load(
+        r#"
+        local function work()
+            local x = 0
+            for i = 1, 100 do x = x + i end
+            return x
+        end
+        local co = coroutine.create(function()
+            for _ = 1, 3 do
+                work()
+                coroutine.yield()
+            end
+        end)
+        for _ = 1, 3 do
+            coroutine.resume(co)
+        end
+    "#,
+    )
+    .set_name("@co.lua")
+    .exec()?;
+    profile.stop();
+
+    let functions = profile.functions();
+    let work = functions
+        .iter()
+        .find(|f| f.info.name.as_deref() == Some("work"))
+        .unwrap();
+    assert_eq!(work.calls, 3);
+
+    // `work` is attributed to the coroutine stack, not to the main thread
+    let stacks = profile.to_collapsed_stacks();
+    let work_stacks = stacks
+        .lines()
+        .filter(|l| l.contains("work (co.lua:2)"))
+        .collect::<Vec<_>>();
+    assert!(!work_stacks.is_empty());
+    for line in work_stacks {
+        assert!(
+            line.starts_with("anonymous (co.lua:7);work (co.lua:2) "),
+            "{line}"
+        );
+    }
+
+    let trace = profile.to_chrome_trace();
+    assert!(trace.contains(r#""tid":2,"args":{"name":"coroutine 1"}"#));
+    assert!(trace.contains(r#""name":"work (co.lua:2)","cat":"lua","ph":"X","pid":1,"tid":2"#));
+
+    Ok(())
+}
+
+#[test]
+fn test_profiler_with_memory_tracking() -> Result<()> {
+    let lua = Lua::new();
+    if lua.set_memory_tracking(true).is_err() {
+        return Ok(());
+    }
+
+    let script = r#"
+        local function alloc()
+            local t = {}
+            for i = 1, 1000 do t[i] = {} end
+            return t
+        end
+        alloc()
+    "#;
+    let profile = lua.start_profiler(ProfilerOptions::sampling(10))?;
+    lua.load(script).exec()?;
+    profile.stop();
+    assert!(!profile.functions().is_empty());
+
+    // Memory tracking keeps working after the profiler is stopped
+    let before = lua.memory_report()?;
+    lua.load(script).exec()?;
+    let growth = lua.memory_report()?.growth_since(&before);
+    assert_eq!(growth.top_allocators[0].info.name.as_deref(), Some("alloc"));
+
+    Ok(())
+}
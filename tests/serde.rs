@@ -540,7 +540,7 @@ fn test_from_value_enum_untagged() -> Result<(), Box<dyn StdError>> {
     let value = lua.load(r#"{b = 12}"#).eval()?;
     match lua.from_value::<Eut>(value) {
         Ok(v) => panic!("expected Error::DeserializeError, got {:?}", v),
-        Err(Error::DeserializeError(_)) => {}
+        Err(Error::DeserializeError { .. }) => {}
         Err(e) => panic!("expected Error::DeserializeError, got {}", e),
     }
 
@@ -555,7 +555,7 @@ fn test_from_value_with_options() -> Result<(), Box<dyn StdError>> {
     let value = Value::Function(lua.create_function(|_, ()| Ok(()))?);
     match lua.from_value::<Option<String>>(value) {
         Ok(v) => panic!("expected deserialization error, got {:?}", v),
-        Err(Error::DeserializeError(err)) => {
+        Err(Error::DeserializeError { message: err, .. }) => {
             assert!(err.contains("unsupported value type"))
         }
         Err(err) => panic!("expected `DeserializeError` error, got {:?}", err),
@@ -578,7 +578,7 @@ fn test_from_value_with_options() -> Result<(), Box<dyn StdError>> {
     let value = lua.load(r#"local t = {}; t.t = t; return t"#).eval()?;
     match lua.from_value::<HashMap<String, Option<String>>>(value) {
         Ok(v) => panic!("expected deserialization error, got {:?}", v),
-        Err(Error::DeserializeError(err)) => {
+        Err(Error::DeserializeError { message: err, .. }) => {
             assert!(err.contains("recursive table detected"))
         }
         Err(err) => panic!("expected `DeserializeError` error, got {:?}", err),
@@ -686,6 +686,262 @@ fn test_from_value_sorted() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_from_value_strings_and_bytes() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    struct Bytes(Vec<u8>);
+
+    impl<'de> Deserialize<'de> for Bytes {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct BytesVisitor;
+
+            impl serde::de::Visitor<'_> for BytesVisitor {
+                type Value = Bytes;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("bytes")
+                }
+
+                fn visit_bytes<E>(self, v: &[u8]) -> Result<Bytes, E> {
+                    Ok(Bytes(v.to_vec()))
+                }
+            }
+
+            deserializer.deserialize_bytes(BytesVisitor)
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Test {
+        name: String,
+        data: Bytes,
+        raw: Bytes,
+        list: Vec<String>,
+    }
+
+    let value = lua
+        .load(r#"{name = "hello", data = "\255\0\1", raw = "text", list = {"a", "b"}}"#)
+        .eval::<Value>()?;
+    let got: Test = lua.from_value(value.clone())?;
+    assert_eq!(got.name, "hello");
+    assert_eq!(got.data.0, b"\xff\x00\x01");
+    assert_eq!(got.raw.0, b"text");
+    assert_eq!(got.list, ["a", "b"]);
+
+    // `Deserializer` over `Value` gives the same result
+    let got = Test::deserialize(mlua::serde::Deserializer::new(value))?;
+    assert_eq!(got.data.0, b"\xff\x00\x01");
+    assert_eq!(got.list, ["a", "b"]);
+
+    // Non utf-8 strings cannot be deserialized to `String`
+    let value = lua.load(r#""\255""#).eval::<Value>()?;
+    assert!(lua.from_value::<String>(value).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_from_value_in_place() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        values: Vec<u32>,
+    }
+
+    let mut config = Config {
+        name: String::with_capacity(64),
+        values: Vec::with_capacity(64),
+    };
+    let value = lua.load(r#"{name = "test", values = {1, 2, 3}}"#).eval()?;
+    lua.from_value_in_place(value, &mut config)?;
+    assert_eq!(config.name, "test");
+    assert_eq!(config.values, [1, 2, 3]);
+
+    let mut values = vec![10, 20, 30, 40];
+    let value = lua.load("{1, 2}").eval()?;
+    lua.from_value_in_place(value, &mut values)?;
+    assert_eq!(values, [1, 2]);
+
+    Ok(())
+}
+
+#[test]
+fn test_from_value_other_state() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+    let lua2 = Lua::new();
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        values: Vec<u32>,
+    }
+
+    // Values of another Lua state cannot be pushed to the stack, so they are deserialized directly
+    let value = lua
+        .load(r#"{name = "test", values = {1, 2, 3}}"#)
+        .eval::<Value>()?;
+    let config: Config = lua2.from_value(value.clone())?;
+    assert_eq!(config.name, "test");
+    assert_eq!(config.values, [1, 2, 3]);
+
+    let mut values = vec![10, 20, 30];
+    lua2.from_value_in_place(lua.load("{1, 2}").eval()?, &mut values)?;
+    assert_eq!(values, [1, 2]);
+
+    let s = lua.create_string("hello")?;
+    assert_eq!(lua2.from_value::<String>(Value::String(s))?, "hello");
+
+    Ok(())
+}
+
+#[test]
+fn test_from_value_large_table() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Item {
+        id: u32,
+        name: String,
+        tags: Vec<String>,
+        props: HashMap<String, f64>,
+    }
+
+    let value = lua
+        .load(
+            r#"
+            local items = {}
+            for i = 1, 1000 do
+                items[i] = {id = i, name = "item" .. i, tags = {"a", "b"}, props = {x = i / 2}}
+            end
+            return items
+        "#,
+        )
+        .eval::<Value>()?;
+    let items: Vec<Item> = lua.from_value(value)?;
+    assert_eq!(items.len(), 1000);
+    assert_eq!(items[999].id, 1000);
+    assert_eq!(items[999].name, "item1000");
+    assert_eq!(items[999].tags, ["a", "b"]);
+    assert_eq!(items[999].props["x"], 500.0);
+
+    // Not all elements are consumed
+    let value = lua.load("{1, 2, 3}").eval::<Value>()?;
+    match lua.from_value::<(u8, u8)>(value) {
+        Err(Error::DeserializeError { message: err, .. }) => assert!(err.contains("fewer elements")),
+        r => panic!("expected `DeserializeError` error, got {r:?}"),
+    }
+    let value = lua.load("{a = 1, b = 2}").eval::<Value>()?;
+    #[derive(Debug, Deserialize)]
+    struct A {
+        #[allow(unused)]
+        a: u8,
+    }
+    assert!(lua.from_value::<A>(value).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_from_value_error_path() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    #[derive(Debug, Deserialize)]
+    #[allow(unused)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(unused)]
+    enum Mode {
+        Fixed(u8),
+        Range { min: u8, max: u8 },
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(unused)]
+    struct Config {
+        servers: Vec<Server>,
+        limits: HashMap<String, u32>,
+        mode: Mode,
+    }
+
+    let config = |s: &str| -> LuaResult<Value> {
+        let template = r#"{
+            servers = {{host = "a", port = 1}, {host = "b", port = 2}, SERVER},
+            limits = {LIMIT},
+            mode = MODE,
+        }"#;
+        let parts = s.split(';').collect::<Vec<_>>();
+        let code = template
+            .replace("SERVER", parts[0])
+            .replace("LIMIT", parts[1])
+            .replace("MODE", parts[2]);
+        lua.load(code).eval()
+    };
+
+    let check = |value: Value, options, expected_path: &str, expected_message: &str| {
+        match lua.from_value_with::<Config>(value.clone(), options) {
+            Err(Error::DeserializeError { path, message }) => {
+                assert_eq!(path, expected_path);
+                assert!(
+                    message.contains(expected_message),
+                    "unexpected message: {message}"
+                );
+            }
+            r => panic!("expected `DeserializeError` error, got {r:?}"),
+        }
+        // `Deserializer` over `Value` reports the same path
+        let deserializer = mlua::serde::Deserializer::new_with_options(value, options);
+        match Config::deserialize(deserializer) {
+            Err(Error::DeserializeError { path, .. }) => assert_eq!(path, expected_path),
+            r => panic!("expected `DeserializeError` error, got {r:?}"),
+        }
+    };
+
+    for options in [
+        DeserializeOptions::new(),
+        DeserializeOptions::new().sort_keys(true),
+    ] {
+        let value = config(r#"{host = "c", port = "x"};a = 1;{Fixed = 1}"#)?;
+        check(value, options, "servers[3].port", "invalid type");
+
+        let value = config(r#"{host = "c", port = 3};["a b"] = -1;{Fixed = 1}"#)?;
+        check(value, options, r#"limits["a b"]"#, "invalid value");
+
+        let value = config(r#"{host = "c", port = 3};a = 1;{Range = {min = 1, max = 1000}}"#)?;
+        check(value, options, "mode.Range.max", "invalid value");
+
+        let value = config(r#"{host = "c"};a = 1;{Fixed = 1}"#)?;
+        check(value, options, "servers[3]", "missing field `port`");
+
+        let value = config(r#"{host = "c", port = 3};a = 1;{Fixed = 1}"#)?;
+        assert!(lua.from_value_with::<Config>(value, options).is_ok());
+    }
+
+    // Top-level errors have an empty path
+    let err = lua.from_value::<u8>(Value::Boolean(true)).unwrap_err();
+    match err {
+        Error::DeserializeError { ref path, .. } => assert!(path.is_empty()),
+        ref err => panic!("expected `DeserializeError` error, got {err:?}"),
+    }
+    assert!(err.to_string().starts_with("deserialize error: invalid type"));
+
+    // Recursive tables
+    let value = lua.load(r#"local t = {1, {}}; t[2].x = t; return t"#).eval()?;
+    let err = lua.from_value::<serde_json::Value>(value).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "deserialize error: [2].x: recursive table detected"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_arbitrary_precision() {
     let lua = Lua::new();
@@ -745,3 +1001,93 @@ fn test_buffer_from_value() -> LuaResult<()> {
 
     Ok(())
 }
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_buffer_userdata_serialize() -> LuaResult<()> {
+    let lua = Lua::new();
+
+    let buf = lua.create_buffer(b"\x01\x02\x03\x04")?;
+    let val = serde_value::to_value(&buf).unwrap();
+    assert_eq!(val, serde_value::Value::Bytes(vec![1, 2, 3, 4]));
+
+    // Buffer userdata is serialized as bytes as well
+    let ud = buf.into_lua(&lua)?;
+    assert!(ud.is_userdata());
+    let val = serde_value::to_value(&ud).unwrap();
+    assert_eq!(val, serde_value::Value::Bytes(vec![1, 2, 3, 4]));
+
+    // Try empty buffer
+    let buf = lua.create_buffer(b"")?;
+    let val = serde_value::to_value(&buf).unwrap();
+    assert_eq!(val, serde_value::Value::Bytes(vec![]));
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_buffer_userdata_from_value() -> LuaResult<()> {
+    let lua = Lua::new();
+
+    let buf = lua.create_buffer(b"\x01\x02\x03\x04")?;
+    let val = lua.from_value::<serde_value::Value>(buf.into_lua(&lua)?).unwrap();
+    assert_eq!(val, serde_value::Value::Bytes(vec![1, 2, 3, 4]));
+
+    Ok(())
+}
+
+#[cfg(feature = "codecs")]
+#[test]
+fn test_serde_codecs() -> LuaResult<()> {
+    let lua = Lua::new();
+    lua.load_serde_codecs()?;
+
+    lua.load(
+        r#"
+        local data = json.decode('{"a": [1, 2.5, "x"], "b": null, "c": {"d": true}, "e": []}')
+        assert(data.a[1] == 1 and data.a[2] == 2.5 and data.a[3] == "x")
+        assert(data.b == json.null and data.c.d == true)
+        assert(json.encode(data.e) == "[]")
+        assert(json.encode({1, 2, 3}) == "[1,2,3]")
+        assert(json.encode({x = json.null}) == '{"x":null}')
+        assert(json.encode({[2] = "a", [10] = "b"}) == '{"2":"a","10":"b"}')
+        assert(json.decode('"\\u00e9\\ud83d\\ude00\\n"') == "\195\169\240\159\152\128\n")
+        assert(json.encode("\"\1") == '"\\"\\u0001"')
+
+        for _, codec in ipairs({json, msgpack, cbor}) do
+            local value = codec.decode(codec.encode({s = "hello", n = -1, f = 0.5, t = {1, 2}, b = false}))
+            assert(value.s == "hello" and value.n == -1 and value.f == 0.5)
+            assert(#value.t == 2 and value.t[2] == 2 and value.b == false)
+        end
+        assert(msgpack.null == json.null and cbor.array_mt == json.array_mt)
+
+        -- Errors
+        local t = {}
+        t.t = t
+        local ok, err = pcall(json.encode, t)
+        assert(not ok and tostring(err):find("recursive table detected"))
+        ok, err = pcall(msgpack.encode, {f = print})
+        assert(not ok and tostring(err):find("cannot serialize <function>"))
+        ok, err = pcall(json.decode, "{")
+        assert(not ok and tostring(err):find("json:"))
+    "#,
+    )
+    .exec()?;
+
+    // Custom options
+    let lua = Lua::new();
+    lua.load_serde_codecs_with(
+        SerializeOptions::new().set_array_metatable(false),
+        DeserializeOptions::new().sort_keys(true),
+    )?;
+    lua.load(
+        r#"
+        assert(getmetatable(json.decode("[1, 2]")) == nil)
+        assert(json.encode({c = 3, a = 1, b = 2}) == '{"a":1,"b":2,"c":3}')
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
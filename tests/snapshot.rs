@@ -0,0 +1,135 @@
+#![cfg(not(feature = "luau"))]
+
+use mlua::{AnyUserData, Lua, Result, UserData, UserDataMethods};
+
+#[derive(Clone)]
+struct Counter(i64);
+
+impl UserData for Counter {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("incr", |_, this, ()| {
+            this.0 += 1;
+            Ok(this.0)
+        });
+    }
+
+    fn clone_into(&self, lua: &Lua) -> Result<Option<AnyUserData>> {
+        lua.create_userdata(self.clone()).map(Some)
+    }
+}
+
+struct Handle;
+
+impl UserData for Handle {}
+
+#[test]
+fn test_fork() -> Result<()> {
+    let lua = Lua::new();
+    lua.load(
+        r#"
+        local count = 0
+        function incr() count = count + 1; return count end
+        function get() return count end
+
+        config = { name = "test", list = {1, 2, 3} }
+        config.self = config
+        setmetatable(config, { __index = function(_, k) return k .. "!" end })
+        upper = string.upper
+        incr()
+    "#,
+    )
+    .exec()?;
+
+    let fork = lua.fork()?;
+
+    // Upvalues stay shared between closures (not supported in Lua 5.1)
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    assert_eq!(fork.load("incr(); return get()").eval::<i64>()?, 2);
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    assert_eq!(fork.load("incr(); return get()").eval::<i64>()?, 1);
+    assert_eq!(lua.load("get()").eval::<i64>()?, 1);
+
+    // Tables are copied deeply, keeping references and metatables
+    fork.load(
+        r#"
+        assert(config.name == "test" and #config.list == 3)
+        assert(config.self == config)
+        assert(config.missing == "missing!")
+        assert(upper == string.upper and upper("a") == "A")
+        config.name = "fork"
+        string.custom = 1
+    "#,
+    )
+    .exec()?;
+    lua.load(r#"assert(config.name == "test" and string.custom == nil)"#)
+        .exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_fork_userdata() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("counter", Counter(1))?;
+    lua.load("counter:incr()").exec()?;
+
+    let fork = lua.fork()?;
+    assert_eq!(fork.load("counter:incr()").eval::<i64>()?, 3);
+    assert_eq!(fork.load("counter:incr()").eval::<i64>()?, 4);
+    assert_eq!(lua.load("counter:incr()").eval::<i64>()?, 3);
+
+    // Userdata without `clone_into` cannot be copied
+    lua.globals()
+        .set("handles", lua.create_table_from([("main", Handle)])?)?;
+    match lua.fork() {
+        Err(err) => assert!(
+            err.to_string()
+                .contains("cannot copy userdata `Handle` at `_G.handles.main`"),
+            "{err}"
+        ),
+        Ok(_) => panic!("expected error"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_fork_unsupported() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals()
+        .set("co", lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?)?;
+    match lua.fork() {
+        Err(err) => assert!(err.to_string().contains("cannot copy thread at `_G.co`"), "{err}"),
+        Ok(_) => panic!("expected error"),
+    }
+
+    lua.globals().set("co", mlua::Nil)?;
+    lua.globals().set("f", lua.create_function(|_, ()| Ok(()))?)?;
+    match lua.fork() {
+        Err(err) => assert!(
+            err.to_string().contains("cannot copy C function at `_G.f`"),
+            "{err}"
+        ),
+        Ok(_) => panic!("expected error"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshot() -> Result<()> {
+    let lua = Lua::new();
+    lua.load("state = { step = 1 }").exec()?;
+
+    let snapshot = lua.snapshot()?;
+    lua.load("state.step = 2").exec()?;
+
+    for _ in 0..2 {
+        let restored = snapshot.restore()?;
+        assert_eq!(restored.load("state.step").eval::<i64>()?, 1);
+        restored.load("state.step = 3").exec()?;
+    }
+    assert_eq!(lua.load("state.step").eval::<i64>()?, 2);
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::fs;
+
+use mlua::{Lua, Result, TestRunner, TestStatus};
+
+#[test]
+fn test_runner() -> Result<()> {
+    let lua = Lua::new();
+
+    let report = TestRunner::new()
+        .add_chunk(
+            "math_spec.lua",
+            r#"
+            describe("math", function()
+                it("adds", function()
+                    expect(1 + 1):to_be(2)
+                    expect(1 + 1).never:to_be(3)
+                end)
+
+                describe("tables", function()
+                    it("compares deeply", function()
+                        expect({1, {a = "x"}}):to_equal({1, {a = "x"}})
+                        expect({1, 2}).never:to_equal({1, 2, 3})
+                    end)
+                end)
+
+                it("fails", function()
+                    print("some", "output", nil)
+                    expect("a"):to_be("b")
+                end)
+
+                it("throws", function()
+                    expect(function() error("boom") end):to_throw("boom")
+                    expect(function() end).never:to_throw()
+                    expect(nil):to_be_nil()
+                    expect(0):to_be_truthy()
+                    expect(false):to_be_falsy()
+                end)
+            end)
+
+            it("raises errors", function()
+                error("oops")
+            end)
+        "#,
+        )
+        .add_chunk("broken_spec.lua", "describe(")
+        .run(&lua)?;
+
+    let names = (report.results.iter())
+        .map(|r| (r.file.as_str(), r.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![
+            ("math_spec.lua", "math adds"),
+            ("math_spec.lua", "math tables compares deeply"),
+            ("math_spec.lua", "math fails"),
+            ("math_spec.lua", "math throws"),
+            ("math_spec.lua", "raises errors"),
+            ("broken_spec.lua", ""),
+        ]
+    );
+    assert_eq!(report.passed(), 3);
+    assert_eq!(report.failed(), 3);
+    assert!(!report.is_success());
+
+    let failed = &report.results[2];
+    assert_eq!(
+        failed.status,
+        TestStatus::Failed(r#"math_spec.lua:17: expected "a" to be "b""#.into())
+    );
+    assert_eq!(failed.output, "some\toutput\tnil\n");
+    match &report.results[4].status {
+        TestStatus::Failed(message) => assert!(message.starts_with("math_spec.lua:30: oops"), "{message}"),
+        status => panic!("unexpected status: {status:?}"),
+    }
+
+    // Test helpers are not added to the global environment
+    assert_eq!(lua.globals().get::<Option<String>>("describe")?, None);
+
+    let junit = report.to_junit();
+    assert!(junit.contains(r#"<testsuites tests="6" failures="3""#));
+    assert!(junit.contains(r#"<testsuite name="math_spec.lua" tests="5" failures="2""#));
+    assert!(junit.contains(r#"<testcase name="math adds" classname="math_spec.lua""#));
+    assert!(
+        junit.contains(r#"<failure message="math_spec.lua:17: expected &quot;a&quot; to be &quot;b&quot;">"#)
+    );
+    assert!(junit.contains("<system-out>some\toutput\tnil\n</system-out>"));
+    assert!(junit.contains(r#"<testsuite name="broken_spec.lua" tests="1" failures="1""#));
+
+    let tap = report.to_tap();
+    assert!(tap.starts_with("TAP version 13\n1..6\nok 1 - math_spec.lua: math adds\n"));
+    assert!(tap.contains("not ok 3 - math_spec.lua: math fails\n  ---\n  message: |\n"));
+    assert!(tap.contains("# some\toutput\tnil\n"));
+    assert!(tap.contains("not ok 6 - broken_spec.lua\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_runner_isolation() -> Result<()> {
+    let lua = Lua::new();
+
+    let report = TestRunner::new()
+        .add_chunk(
+            "a_spec.lua",
+            r#"
+            counter = 1
+            it("writes to the file environment", function()
+                counter = counter + 1
+                expect(counter):to_be(2)
+            end)
+            it("runs in a fresh thread", function()
+                expect(coroutine.running()).never:to_be(nil)
+            end)
+        "#,
+        )
+        .add_chunk(
+            "b_spec.lua",
+            "it('does not see other files', function() expect(counter):to_be_nil() end)",
+        )
+        .run(&lua)?;
+    assert!(report.is_success(), "{report:?}");
+    assert_eq!(lua.globals().get::<Option<i64>>("counter")?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_runner_discover() -> Result<()> {
+    let lua = Lua::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("nested"))?;
+    fs::write(dir.path().join("a_spec.lua"), "it('a', function() end)")?;
+    fs::write(dir.path().join("nested/b_test.lua"), "it('b', function() end)")?;
+    fs::write(dir.path().join("helper.lua"), "error('not a test file')")?;
+
+    let report = TestRunner::new().add_dir(dir.path()).run(&lua)?;
+    let names = report.results.iter().map(|r| r.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["a", "b"]);
+    assert!(report.results[1].file.ends_with("b_test.lua"));
+
+    // Missing files are reported as failures
+    let report = TestRunner::new()
+        .add_file(dir.path().join("missing_spec.lua"))
+        .run(&lua)?;
+    assert_eq!(report.failed(), 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "luau")]
+#[test]
+fn test_runner_sandbox() -> Result<()> {
+    let lua = Lua::new();
+
+    let report = TestRunner::new()
+        .sandbox(true)
+        .add_chunk(
+            "sandbox_spec.lua",
+            r#"
+            it("cannot modify globals", function()
+                expect(function() string.foo = 1 end):to_throw()
+            end)
+        "#,
+        )
+        .run(&lua)?;
+    assert!(report.is_success(), "{report:?}");
+    assert_eq!(
+        lua.globals()
+            .get::<mlua::Table>("string")?
+            .raw_get::<Option<i64>>("foo")?,
+        None
+    );
+
+    Ok(())
+}
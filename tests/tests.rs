@@ -66,6 +66,79 @@ fn test_safety() -> Result<()> {
     Ok(())
 }
 
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_module_searchers() -> Result<()> {
+    use mlua::{DirectorySearcher, EmbeddedSearcher, ModuleSource};
+
+    let lua = Lua::new();
+
+    let modules = HashMap::from([
+        ("utils.math", "return { add = function(a, b) return a + b end }"),
+        (
+            "utils.error",
+            "local M = {}\nfunction M.fail() error('failed') end\nreturn M",
+        ),
+    ]);
+    lua.add_module_searcher(modules)?;
+
+    static EMBEDDED: &[(&str, &[u8])] = &[
+        ("config/init.lua", b"return { name = 'config' }"),
+        ("./config/extra.lua", b"return { name = 'extra', module = ... }"),
+    ];
+    lua.add_module_searcher(EmbeddedSearcher::new(EMBEDDED))?;
+
+    // Searchers added later take priority
+    lua.add_module_searcher(|name: &str| {
+        Ok((name == "utils.math").then(|| ModuleSource::new("=override", &b"return 'override'"[..])))
+    })?;
+
+    lua.load(
+        r#"
+        assert(require("utils.math") == "override")
+        local config = require("config")
+        assert(config.name == "config")
+        local extra = require("config.extra")
+        assert(extra.name == "extra" and extra.module == "config.extra")
+
+        local ok, err = pcall(require("utils.error").fail)
+        assert(not ok and err:find("utils/error.lua:2: failed") ~= nil, err)
+
+        ok, err = pcall(require, "missing")
+        assert(not ok and err:find("no module 'missing' in custom searcher") ~= nil, err)
+    "#,
+    )
+    .exec()?;
+
+    // Load modules from a directory
+    if cfg!(target_arch = "wasm32") {
+        return Ok(());
+    }
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path().join("root");
+    std::fs::create_dir_all(root.join("game"))?;
+    std::fs::write(root.join("game/player.lua"), "return { name = 'player' }")?;
+    std::fs::write(temp_dir.path().join("secret.lua"), "return 'secret'")?;
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(temp_dir.path().join("secret.lua"), root.join("link.lua"))?;
+
+    let lua = Lua::new();
+    lua.add_module_searcher(DirectorySearcher::new(&root))?;
+    lua.load(
+        r#"
+        assert(require("game.player").name == "player")
+        package.path = ""
+        for _, name in ipairs({"..secret", "../secret", "link", "game/player"}) do
+            local ok, err = pcall(require, name)
+            assert(not ok and err:find("no module '" .. name .. "'", 1, true) ~= nil, err)
+        end
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
 #[test]
 fn test_load() -> Result<()> {
     let lua = Lua::new();
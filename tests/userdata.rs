@@ -892,6 +892,102 @@ fn test_userdata_derive() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "macros")]
+#[test]
+fn test_userdata_derive_methods() -> Result<()> {
+    let lua = Lua::new();
+
+    #[derive(Clone, Default, mlua::FromLua, mlua::UserData)]
+    struct Counter {
+        pub value: i64,
+        #[lua(rename = "label")]
+        pub name: StdString,
+        #[lua(skip)]
+        pub hidden: i64,
+        private: i64,
+    }
+
+    #[mlua::methods]
+    impl Counter {
+        fn new(name: StdString) -> Self {
+            Counter {
+                name,
+                ..Default::default()
+            }
+        }
+
+        fn get(&self) -> i64 {
+            self.value
+        }
+
+        fn inc(&mut self, by: Option<i64>) {
+            self.value += by.unwrap_or(1);
+            self.private += 1;
+        }
+
+        #[lua(rename = "calls")]
+        fn private_calls(&self) -> i64 {
+            self.private + self.hidden
+        }
+
+        fn checked_sub(&mut self, lua: &Lua, by: i64) -> Result<i64> {
+            let _ = lua.globals();
+            if by > self.value {
+                return Err(Error::runtime("underflow"));
+            }
+            self.value -= by;
+            Ok(self.value)
+        }
+
+        #[lua(meta = "Add")]
+        fn add(&self, other: Self) -> Self {
+            Counter::new(self.name.clone() + &other.name)
+        }
+
+        #[lua(meta = "__tostring")]
+        fn show(&self) -> StdString {
+            format!("{}={}", self.name, self.value)
+        }
+
+        #[lua(skip)]
+        #[allow(unused)]
+        fn skipped(&self) {}
+    }
+
+    lua.globals().set("Counter", lua.create_proxy::<Counter>()?)?;
+    lua.load(
+        r#"
+        local c = Counter.new("a")
+        assert(c.value == 0 and c.label == "a")
+        assert(c.name == nil and c.hidden == nil and c.private == nil)
+        c:inc()
+        c:inc(5)
+        assert(c:get() == 6 and c:calls() == 2)
+        c.value = 10
+        c.label = "b"
+        assert(c:checked_sub(3) == 7)
+        local ok, err = pcall(c.checked_sub, c, 100)
+        assert(not ok and tostring(err):find("underflow"))
+        assert(tostring(c) == "b=7")
+        assert(tostring(c + Counter.new("c")) == "bc=0")
+        assert(c.skipped == nil)
+    "#,
+    )
+    .exec()?;
+
+    // Without methods
+    #[derive(mlua::UserData)]
+    struct Point {
+        pub x: f64,
+        pub y: f64,
+    }
+
+    lua.globals().set("p", Point { x: 1.0, y: 2.0 })?;
+    lua.load("p.x = p.x + p.y; assert(p.x == 3)").exec()?;
+
+    Ok(())
+}
+
 #[test]
 fn test_nested_userdata_gc() -> Result<()> {
     let lua = Lua::new();
@@ -1066,3 +1162,78 @@ fn test_userdata_wrappers() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_userdata_type_definitions() -> Result<()> {
+    #[derive(Clone, Copy)]
+    struct Vec2(f64, f64);
+
+    impl UserData for Vec2 {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field("dims", 2);
+            fields.add_field_method_get("x", |_, this| Ok(this.0));
+            fields.add_field_method_set("x", |_, this, x: f64| {
+                this.0 = x;
+                Ok(())
+            });
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_function("new", |_, (x, y): (f64, f64)| Ok(Vec2(x, y)));
+            methods.add_method("scale", |_, this, (k, round): (f64, Option<bool>)| {
+                let v = Vec2(this.0 * k, this.1 * k);
+                Ok(if round == Some(true) {
+                    Vec2(v.0.round(), v.1.round())
+                } else {
+                    v
+                })
+            });
+            methods.add_method("unpack", |_, this, ()| Ok((this.0, this.1)));
+            methods.add_method("sum", |_, _, values: Variadic<i64>| {
+                Ok(values.iter().sum::<i64>())
+            });
+            methods.add_meta_method(MetaMethod::Add, |_, this, other: UserDataRef<Vec2>| {
+                Ok(Vec2(this.0 + other.0, this.1 + other.1))
+            });
+        }
+    }
+
+    struct Handle;
+
+    let lua = Lua::new();
+    lua.globals().set("origin", Vec2(0.0, 0.0))?;
+    lua.register_userdata_type::<Handle>(|reg| {
+        reg.add_method("name", |_, _, ()| Ok("handle"));
+    })?;
+
+    let defs = lua.type_definitions();
+    assert_eq!(defs.class_names().collect::<Vec<_>>(), ["Handle", "Vec2"]);
+
+    let luals = defs.to_luals();
+    assert!(luals.starts_with("---@meta\n"));
+    for expected in [
+        "---@class Vec2\n---@field dims integer\n---@field x number\n---@operator add(Vec2): Vec2\nlocal Vec2 = {}\n",
+        "---@param arg1 number\n---@param arg2 number\n---@return Vec2\nfunction Vec2.new(arg1, arg2) end\n",
+        "---@param arg1 number\n---@param arg2 boolean?\n---@return Vec2\nfunction Vec2:scale(arg1, arg2) end\n",
+        "---@return number\n---@return number\nfunction Vec2:unpack() end\n",
+        "---@param ... integer\n---@return integer\nfunction Vec2:sum(...) end\n",
+        "---@return string\nfunction Handle:name() end\n",
+    ] {
+        assert!(luals.contains(expected), "`{expected}` not found in:\n{luals}");
+    }
+
+    let luau = defs.to_luau();
+    for expected in [
+        "declare class Vec2\n\tdims: number\n\tx: number\n",
+        "\tnew: (arg1: number, arg2: number) -> Vec2\n",
+        "\tfunction scale(self, arg1: number, arg2: boolean?): Vec2\n",
+        "\tfunction unpack(self): (number, number)\n",
+        "\tfunction sum(self, ...: number): number\n",
+        "\tfunction __add(self, arg1: Vec2): Vec2\nend\n",
+        "declare class Handle\n\tfunction name(self): string\nend\n",
+    ] {
+        assert!(luau.contains(expected), "`{expected}` not found in:\n{luau}");
+    }
+
+    Ok(())
+}